@@ -126,19 +126,7 @@ impl PgpuScene {
     }
 
     fn encoded_scene<'a>(&'a self) -> EncodedSceneRef<'a, piet_scene::geometry::Affine> {
-        let d = self.scene.data();
-        EncodedSceneRef {
-            transform_stream: &d.transform_stream,
-            tag_stream: &d.tag_stream,
-            pathseg_stream: &d.pathseg_stream,
-            linewidth_stream: &d.linewidth_stream,
-            drawtag_stream: &d.drawtag_stream,
-            drawdata_stream: &d.drawdata_stream,
-            n_path: d.n_path,
-            n_pathseg: d.n_pathseg,
-            n_clip: d.n_clip,
-            ramp_data: self.rcx.ramp_data(),
-        }
+        EncodedSceneRef::from_scene(&self.scene, &self.rcx)
     }
 }
 
@@ -153,7 +141,8 @@ impl<'a> PgpuSceneBuilder<'a> {
     }
 
     pub fn finish(self) {
-        self.0.finish();
+        // Glyphs are added with balanced transforms, so this cannot fail.
+        let _ = self.0.finish();
     }
 }
 
@@ -221,15 +210,8 @@ pub struct PgpuGlyph {
 
 impl PgpuGlyph {
     pub fn bbox(&self, transform: Option<Affine>) -> Rect {
-        if let Some(transform) = &transform {
-            Rect::from_points(
-                self.fragment
-                    .points()
-                    .iter()
-                    .map(|p| p.transform(transform)),
-            )
-        } else {
-            Rect::from_points(self.fragment.points())
-        }
+        self.fragment
+            .bounds(&transform.unwrap_or_default())
+            .unwrap_or_default()
     }
 }
@@ -17,7 +17,7 @@ pub fn gen_glsl(module: &LayoutModule) -> String {
     // Note: GLSL needs definitions before uses. We could do a topological sort here,
     // but easiest for now to just require that in spec.
     for name in &module.def_names {
-        gen_refdef(&mut r, &name);
+        gen_refdef(&mut r, name);
     }
 
     for name in &module.def_names {
@@ -39,15 +39,15 @@ pub fn gen_glsl(module: &LayoutModule) -> String {
         let is_mem = !module.name.eq(&"state") && !module.name.eq(&"scene");
         match def {
             (_size, LayoutTypeDef::Struct(fields)) => {
-                gen_struct_read(&mut r, &module.name, &name, is_mem, fields);
+                gen_struct_read(&mut r, &module.name, name, is_mem, fields);
                 if module.gpu_write {
-                    gen_struct_write(&mut r, &module.name, &name, is_mem, fields);
+                    gen_struct_write(&mut r, &module.name, name, is_mem, fields);
                 }
             }
             (_size, LayoutTypeDef::Enum(en)) => {
-                gen_enum_read(&mut r, &module.name, &name, is_mem, en);
+                gen_enum_read(&mut r, &module.name, name, is_mem, en);
                 if module.gpu_write {
-                    gen_enum_write(&mut r, &module.name, &name, is_mem, en);
+                    gen_enum_write(&mut r, &module.name, name, is_mem, en);
                 }
             }
         }
@@ -230,14 +230,11 @@ fn gen_extract(offset: usize, ty: &GpuType, preload: bool) -> (String, String) {
             (setup, gen_extract_scalar(offset, scalar))
         }
         GpuType::Vector(scalar, size) => {
-            let is_f16 = match scalar {
-                GpuScalar::F16 => true,
-                _ => false,
-            };
+            let is_f16 = matches!(scalar, GpuScalar::F16);
 
             let mut setup = String::new();
             let mut extract = glsl_type(ty);
-            extract.push_str("(");
+            extract.push('(');
             for i in 0..*size {
                 if i != 0 {
                     extract.push_str(", ");
@@ -252,7 +249,7 @@ fn gen_extract(offset: usize, ty: &GpuType, preload: bool) -> (String, String) {
                 let el_offset = offset + i * scalar.size();
                 extract.push_str(&gen_extract_scalar(el_offset, scalar));
             }
-            extract.push_str(")");
+            extract.push(')');
             (setup, extract)
         }
         GpuType::InlineStruct(name) => (
@@ -286,7 +283,7 @@ fn gen_extract_scalar(offset: usize, ty: &GpuScalar) -> String {
         GpuScalar::F16 | GpuScalar::F32 => extract_fbits(offset, ty.size()),
         GpuScalar::U8 | GpuScalar::U16 | GpuScalar::U32 => extract_ubits(offset, ty.size()),
         GpuScalar::I8 | GpuScalar::I16 | GpuScalar::I32 => extract_ibits(offset, ty.size()),
-        GpuScalar::TagFlags => format!("0 /* TODO */"),
+        GpuScalar::TagFlags => "0 /* TODO */".to_string(),
     }
 }
 
@@ -295,7 +292,7 @@ fn extract_ubits(offset: usize, nbytes: usize) -> String {
         return format!("raw{}", offset / 4);
     }
     let mask = (1 << (nbytes * 8)) - 1;
-    if offset % 4 == 0 {
+    if offset.is_multiple_of(4) {
         format!("raw{} & 0x{:x}", offset / 4, mask)
     } else if offset % 4 + nbytes == 4 {
         format!("raw{} >> {}", offset / 4, (offset % 4) * 8)
@@ -340,11 +337,10 @@ fn extract_fbits(offset: usize, nbytes: usize) -> String {
 // Writing
 
 fn is_f16(ty: &GpuType) -> bool {
-    match ty {
-        GpuType::Scalar(GpuScalar::F16) => true,
-        GpuType::Vector(GpuScalar::F16, _) => true,
-        _ => false,
-    }
+    matches!(
+        ty,
+        GpuType::Scalar(GpuScalar::F16) | GpuType::Vector(GpuScalar::F16, _)
+    )
 }
 
 fn is_f16_pair(field_ixs: &[usize], fields: &[(String, usize, LayoutType)]) -> bool {
@@ -485,7 +481,7 @@ fn gen_pack_bits_scalar(ty: &GpuScalar, offset: usize, inner: &str) -> String {
             }
         }
         GpuScalar::I32 => format!("uint({})", inner),
-        GpuScalar::TagFlags => format!("0"),
+        GpuScalar::TagFlags => "0".to_string(),
     };
     if shift == 0 {
         bits
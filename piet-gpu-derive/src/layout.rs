@@ -140,11 +140,11 @@ impl LayoutModule {
 }
 
 impl<'a> LayoutSession<'a> {
-    fn new(module: &GpuModule) -> LayoutSession {
+    fn new(module: &GpuModule) -> LayoutSession<'_> {
         let mut orig_defs = HashMap::new();
         let mut enum_variants = HashSet::new();
         for def in &module.defs {
-            orig_defs.insert(def.name().to_owned(), def.clone());
+            orig_defs.insert(def.name().to_owned(), def);
             if let GpuTypeDef::Enum(en) = def {
                 for variant in &en.variants {
                     if let Some(GpuType::InlineStruct(name)) = variant.1.first() {
@@ -213,7 +213,7 @@ pub fn struct_coverage(
             }
         };
         if size > 0 {
-            for ix in (offset / 4)..(offset + size + 3) / 4 {
+            for ix in (offset / 4)..(offset + size).div_ceil(4) {
                 if ix >= result.len() {
                     result.resize_with(ix + 1, Default::default);
                 }
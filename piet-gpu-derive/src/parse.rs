@@ -110,7 +110,7 @@ impl GpuType {
                 Err("unknown path case".into())
             }
             syn::Type::Array(TypeArray { elem, len, .. }) => {
-                if let Some(elem) = GpuScalar::from_syn(&elem) {
+                if let Some(elem) = GpuScalar::from_syn(elem) {
                     if let Some(len) = expr_int_lit(len) {
                         // maybe sanity-check length here
                         Ok(GpuType::Vector(elem, len))
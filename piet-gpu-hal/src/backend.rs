@@ -258,6 +258,15 @@ pub trait CmdBuf<D: Device> {
 
     unsafe fn copy_buffer(&mut self, src: &D::Buffer, dst: &D::Buffer);
 
+    unsafe fn copy_buffer_range(
+        &mut self,
+        src: &D::Buffer,
+        src_offset: u64,
+        dst: &D::Buffer,
+        dst_offset: u64,
+        size: u64,
+    );
+
     unsafe fn copy_image_to_buffer(&mut self, src: &D::Image, dst: &D::Buffer);
 
     unsafe fn copy_buffer_to_image(&mut self, src: &D::Buffer, dst: &D::Image);
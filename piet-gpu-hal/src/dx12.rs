@@ -734,6 +734,18 @@ impl crate::backend::CmdBuf<Dx12Device> for CmdBuf {
         self.c.copy_buffer(&dst.resource, 0, &src.resource, 0, size);
     }
 
+    unsafe fn copy_buffer_range(
+        &mut self,
+        src: &Buffer,
+        src_offset: u64,
+        dst: &Buffer,
+        dst_offset: u64,
+        size: u64,
+    ) {
+        self.c
+            .copy_buffer(&dst.resource, dst_offset, &src.resource, src_offset, size);
+    }
+
     unsafe fn copy_image_to_buffer(&mut self, src: &Image, dst: &Buffer) {
         self.c
             .copy_texture_to_buffer(&src.resource, &dst.resource, src.size.0, src.size.1);
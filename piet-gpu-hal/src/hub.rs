@@ -288,12 +288,12 @@ impl Session {
             let mapped =
                 self.0
                     .device
-                    .map_buffer(&create_buf.mux_buffer(), 0, size, MapMode::Write)?;
+                    .map_buffer(create_buf.mux_buffer(), 0, size, MapMode::Write)?;
             let mut buf_write = BufWrite::new(mapped, 0, size as usize);
             f(&mut buf_write);
             self.0
                 .device
-                .unmap_buffer(&create_buf.mux_buffer(), 0, size, MapMode::Write)?;
+                .unmap_buffer(create_buf.mux_buffer(), 0, size, MapMode::Write)?;
             if use_staging_buffer {
                 let buf = self.create_buffer(size, usage | BufferUsage::COPY_DST)?;
                 let mut staging_cmd_buf = self.0.staging_cmd_buf.lock().unwrap();
@@ -406,10 +406,7 @@ impl Session {
     pub unsafe fn fetch_query_pool(&self, pool: &QueryPool) -> Result<Vec<f64>, Error> {
         let result = self.0.device.fetch_query_pool(pool)?;
         // Subtract off first timestamp.
-        Ok(result[1..]
-            .iter()
-            .map(|ts| *ts as f64 - result[0])
-            .collect())
+        Ok(result[1..].iter().map(|ts| *ts - result[0]).collect())
     }
 
     #[doc(hidden)]
@@ -510,7 +507,7 @@ impl CmdBuf {
     }
 
     /// Begin a compute pass.
-    pub unsafe fn begin_compute_pass(&mut self, desc: &ComputePassDescriptor) -> ComputePass {
+    pub unsafe fn begin_compute_pass(&mut self, desc: &ComputePassDescriptor) -> ComputePass<'_> {
         self.cmd_buf().begin_compute_pass(desc);
         ComputePass { cmd_buf: self }
     }
@@ -567,6 +564,24 @@ impl CmdBuf {
             .copy_buffer(src.mux_buffer(), dst.mux_buffer());
     }
 
+    /// Copy a range of bytes from one buffer to another.
+    pub unsafe fn copy_buffer_range(
+        &mut self,
+        src: &Buffer,
+        src_offset: u64,
+        dst: &Buffer,
+        dst_offset: u64,
+        size: u64,
+    ) {
+        self.cmd_buf().copy_buffer_range(
+            src.mux_buffer(),
+            src_offset,
+            dst.mux_buffer(),
+            dst_offset,
+            size,
+        );
+    }
+
     /// Copy an image to a buffer.
     ///
     /// The size of the image and buffer must match.
@@ -1005,21 +1020,21 @@ pub trait IntoRefs<'a, T: 'a> {
 impl<'a, T> IntoRefs<'a, T> for &'a [T] {
     type Iterator = std::slice::Iter<'a, T>;
     fn into_refs(self) -> Self::Iterator {
-        self.into_iter()
+        self.iter()
     }
 }
 
 impl<'a, T> IntoRefs<'a, T> for &'a [&'a T] {
     type Iterator = std::iter::Copied<std::slice::Iter<'a, &'a T>>;
     fn into_refs(self) -> Self::Iterator {
-        self.into_iter().copied()
+        self.iter().copied()
     }
 }
 
 impl<'a, T, const N: usize> IntoRefs<'a, T> for &'a [&'a T; N] {
     type Iterator = std::iter::Copied<std::slice::Iter<'a, &'a T>>;
     fn into_refs(self) -> Self::Iterator {
-        self.into_iter().copied()
+        self.iter().copied()
     }
 }
 
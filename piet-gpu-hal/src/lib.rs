@@ -3,6 +3,10 @@
 //! This abstraction is inspired by gfx-hal, but is specialized to the needs of piet-gpu.
 //! In time, it may go away and be replaced by either gfx-hal or wgpu.
 
+// Unsafe functions here wrap the underlying GPU APIs, whose safety
+// requirements are documented by those APIs rather than repeated per function.
+#![allow(clippy::missing_safety_doc)]
+
 use bitflags::bitflags;
 
 mod backend;
@@ -202,7 +206,11 @@ pub struct ComputePassDescriptor<'a> {
 }
 
 impl<'a> ComputePassDescriptor<'a> {
-    pub fn timer(pool: &'a QueryPool, start_query: u32, end_query: u32) -> ComputePassDescriptor {
+    pub fn timer(
+        pool: &'a QueryPool,
+        start_query: u32,
+        end_query: u32,
+    ) -> ComputePassDescriptor<'a> {
         ComputePassDescriptor {
             timer_queries: Some((pool, start_query, end_query)),
         }
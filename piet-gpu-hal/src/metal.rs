@@ -719,6 +719,18 @@ impl crate::backend::CmdBuf<MtlDevice> for CmdBuf {
         encoder.copy_from_buffer(&src.buffer, 0, &dst.buffer, 0, size);
     }
 
+    unsafe fn copy_buffer_range(
+        &mut self,
+        src: &Buffer,
+        src_offset: u64,
+        dst: &Buffer,
+        dst_offset: u64,
+        size: u64,
+    ) {
+        let encoder = self.blit_command_encoder();
+        encoder.copy_from_buffer(&src.buffer, src_offset, &dst.buffer, dst_offset, size);
+    }
+
     unsafe fn copy_image_to_buffer(&mut self, src: &Image, dst: &Buffer) {
         let encoder = self.blit_command_encoder();
         assert_eq!(dst.size, (src.width as u64) * (src.height as u64) * 4);
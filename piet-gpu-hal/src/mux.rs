@@ -768,6 +768,21 @@ impl CmdBuf {
         }
     }
 
+    pub unsafe fn copy_buffer_range(
+        &mut self,
+        src: &Buffer,
+        src_offset: u64,
+        dst: &Buffer,
+        dst_offset: u64,
+        size: u64,
+    ) {
+        mux_match! { self;
+            CmdBuf::Vk(c) => c.copy_buffer_range(src.vk(), src_offset, dst.vk(), dst_offset, size),
+            CmdBuf::Dx12(c) => c.copy_buffer_range(src.dx12(), src_offset, dst.dx12(), dst_offset, size),
+            CmdBuf::Mtl(c) => c.copy_buffer_range(src.mtl(), src_offset, dst.mtl(), dst_offset, size),
+        }
+    }
+
     pub unsafe fn copy_image_to_buffer(&mut self, src: &Image, dst: &Buffer) {
         mux_match! { self;
             CmdBuf::Vk(c) => c.copy_image_to_buffer(src.vk(), dst.vk()),
@@ -100,9 +100,6 @@ pub struct QueryPool {
     n_queries: u32,
 }
 
-#[derive(Clone, Copy)]
-pub struct MemFlags(vk::MemoryPropertyFlags);
-
 pub struct DescriptorSetBuilder {
     buffers: Vec<vk::Buffer>,
     images: Vec<vk::ImageView>,
@@ -127,7 +124,7 @@ unsafe extern "system" fn vulkan_debug_callback(
     _user_data: *mut std::os::raw::c_void,
 ) -> vk::Bool32 {
     let callback_data = &*p_callback_data;
-    let message_id_number: i32 = callback_data.message_id_number as i32;
+    let message_id_number: i32 = callback_data.message_id_number;
 
     let message_id_name = if callback_data.p_message_id_name.is_null() {
         Cow::from("")
@@ -952,12 +949,9 @@ impl crate::backend::CmdBuf<VkDevice> for CmdBuf {
     }
 
     unsafe fn begin_compute_pass(&mut self, desc: &ComputePassDescriptor) {
-        if let Some((pool, start, end)) = &desc.timer_queries {
-            #[allow(irrefutable_let_patterns)]
-            if let crate::hub::QueryPool::Vk(pool) = pool {
-                self.write_timestamp_raw(pool.pool, *start);
-                self.end_query = Some((pool.pool, *end));
-            }
+        if let Some((crate::hub::QueryPool::Vk(pool), start, end)) = &desc.timer_queries {
+            self.write_timestamp_raw(pool.pool, *start);
+            self.end_query = Some((pool.pool, *end));
         }
     }
 
@@ -1077,6 +1071,26 @@ impl crate::backend::CmdBuf<VkDevice> for CmdBuf {
         );
     }
 
+    unsafe fn copy_buffer_range(
+        &mut self,
+        src: &Buffer,
+        src_offset: u64,
+        dst: &Buffer,
+        dst_offset: u64,
+        size: u64,
+    ) {
+        let device = &self.device.device;
+        device.cmd_copy_buffer(
+            self.cmd_buf,
+            src.buffer,
+            dst.buffer,
+            &[vk::BufferCopy::default()
+                .src_offset(src_offset)
+                .dst_offset(dst_offset)
+                .size(size)],
+        );
+    }
+
     unsafe fn copy_image_to_buffer(&mut self, src: &Image, dst: &Buffer) {
         let device = &self.device.device;
         device.cmd_copy_image_to_buffer(
@@ -1463,16 +1477,12 @@ fn find_memory_type(
     property_flags: vk::MemoryPropertyFlags,
     props: &vk::PhysicalDeviceMemoryProperties,
 ) -> Option<u32> {
-    for i in 0..props.memory_type_count {
-        if (memory_type_bits & (1 << i)) != 0
+    (0..props.memory_type_count).find(|&i| {
+        (memory_type_bits & (1 << i)) != 0
             && props.memory_types[i as usize]
                 .property_flags
                 .contains(property_flags)
-        {
-            return Some(i);
-        }
-    }
-    None
+    })
 }
 
 fn convert_u32_vec(src: &[u8]) -> Vec<u32> {
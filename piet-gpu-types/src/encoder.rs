@@ -54,6 +54,12 @@ impl<T> Ref<T> {
     }
 }
 
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Encoder {
     pub fn new() -> Encoder {
         Encoder { buf: Vec::new() }
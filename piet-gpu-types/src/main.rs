@@ -1,8 +1,5 @@
 fn main() {
-    let mod_name = std::env::args()
-        .skip(1)
-        .next()
-        .expect("provide a module name");
+    let mod_name = std::env::args().nth(1).expect("provide a module name");
     match mod_name.as_str() {
         "scene" => print!("{}", piet_gpu_types::scene::gen_gpu_scene()),
         "state" => print!("{}", piet_gpu_types::state::gen_gpu_state()),
@@ -45,9 +45,16 @@ piet_gpu! {
         struct CmdJump {
             new_ref: u32,
         }
+        struct CmdBlurRect {
+            rgba_color: u32,
+            // Rectangle in device space, as [x0, y0, x1, y1].
+            rect: [f32; 4],
+            radius: f32,
+            std_dev: f32,
+        }
         enum Cmd {
             End,
-            Fill(CmdFill),
+            Fill(TagFlags, CmdFill),
             Stroke(CmdStroke),
             Solid,
             Alpha(CmdAlpha),
@@ -58,6 +65,20 @@ piet_gpu! {
             BeginClip,
             EndClip(CmdEndClip),
             Jump(CmdJump),
+            BlurRect(CmdBlurRect),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn shader_header_is_up_to_date() {
+        // Regenerate with `cargo run -p piet-gpu-types -- ptcl > ../piet-gpu/shader/ptcl.h`.
+        let header = include_str!("../../piet-gpu/shader/ptcl.h");
+        assert!(
+            super::gen_gpu_ptcl() == header,
+            "piet-gpu/shader/ptcl.h does not match piet-gpu-types/src/ptcl.rs"
+        );
+    }
+}
@@ -20,7 +20,9 @@ use piet_gpu_hal::{
 use piet::kurbo::Point;
 use piet::{RenderContext, Text, TextAttribute, TextLayoutBuilder};
 
-use piet_gpu::{test_scenes, PietGpuRenderContext, Renderer};
+use piet_gpu::{test_scenes, EncodedSceneRef, PietGpuRenderContext, Renderer};
+use piet_scene::resource::ResourceContext;
+use piet_scene::scene::Scene;
 
 #[cfg_attr(target_os = "android", ndk_glue::main(backtrace = "on"))]
 fn main() {
@@ -41,6 +43,8 @@ struct GfxState {
     cmd_bufs: [Option<CmdBuf>; NUM_FRAMES],
     query_pools: Vec<QueryPool>,
     present_semaphores: Vec<Semaphore>,
+    scene: Scene,
+    rcx: ResourceContext,
 }
 
 const NUM_FRAMES: usize = 2;
@@ -127,6 +131,8 @@ impl GfxState {
                 cmd_bufs,
                 query_pools,
                 present_semaphores,
+                scene: Scene::default(),
+                rcx: ResourceContext::new(),
             })
         }
     }
@@ -146,11 +152,12 @@ impl GfxState {
                 info_string = format!("{:.1}ms", ts.last().unwrap() * 1e3);
                 println!("render time: {:?}", ts);
             }
-            let mut ctx = PietGpuRenderContext::new();
+            let mut ctx = PietGpuRenderContext::new(&mut self.scene, &mut self.rcx);
             test_scenes::render_anim_frame(&mut ctx, self.current_frame);
             //test_scenes::render_tiger(&mut ctx);
             render_info_string(&mut ctx, &info_string);
-            if let Err(e) = self.renderer.upload_render_ctx(&mut ctx, frame_idx) {
+            let encoded = EncodedSceneRef::from_scene(&self.scene, &self.rcx);
+            if let Err(e) = self.renderer.upload_scene(&encoded, frame_idx) {
                 println!("error in uploading: {}", e);
             }
             let (image_idx, acquisition_semaphore) = self.swapchain.next().unwrap();
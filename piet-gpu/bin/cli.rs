@@ -1,15 +1,18 @@
 use std::fs::File;
-use std::io::BufWriter;
-use std::path::Path;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches, SubCommand};
 
-use piet_gpu_hal::{BufferUsage, Error, Instance, InstanceFlags, Session};
+use piet::kurbo::Rect;
+use piet::{Color, RenderContext};
+use piet_gpu_hal::{
+    BackendType, Buffer, BufferUsage, Error, Instance, InstanceFlags, QueryPool, Session,
+};
 
-use piet_gpu::{test_scenes, PicoSvg, PietGpuRenderContext, Renderer};
-
-const WIDTH: usize = 2048;
-const HEIGHT: usize = 1536;
+use piet_gpu::{test_scenes, EncodedSceneRef, PicoSvg, PietGpuRenderContext, Renderer};
+use piet_scene::resource::ResourceContext;
+use piet_scene::scene::Scene;
 
 #[allow(unused)]
 fn dump_scene(buf: &[u8]) {
@@ -215,95 +218,668 @@ fn trace_ptcl(buf: &[u32]) {
     }
 }
 
-fn main() -> Result<(), Error> {
-    let matches = App::new("piet-gpu test")
-        .arg(Arg::with_name("INPUT").index(1))
-        .arg(Arg::with_name("flip").short("f").long("flip"))
-        .arg(
-            Arg::with_name("scale")
-                .short("s")
-                .long("scale")
-                .takes_value(true),
-        )
-        .get_matches();
-    let (instance, _) = Instance::new(None, InstanceFlags::default())?;
-    unsafe {
-        let device = instance.device(None)?;
-        let session = Session::new(device);
+/// The format of an output image.
+#[derive(Clone, Copy)]
+enum Format {
+    Png,
+    /// Binary PPM, which has no alpha channel.
+    Ppm,
+    /// Bare RGBA pixels, row by row.
+    Raw,
+}
 
-        let mut cmd_buf = session.cmd_buf()?;
-        let query_pool = session.create_query_pool(Renderer::QUERY_POOL_SIZE)?;
+impl Format {
+    const NAMES: &'static [&'static str] = &["png", "ppm", "raw"];
 
-        let mut ctx = PietGpuRenderContext::new();
-        if let Some(input) = matches.value_of("INPUT") {
-            let mut scale = matches
-                .value_of("scale")
-                .map(|scale| scale.parse().unwrap())
-                .unwrap_or(8.0);
-            if matches.is_present("flip") {
-                scale = -scale;
-            }
-            let xml_str = std::fs::read_to_string(input).unwrap();
-            let start = std::time::Instant::now();
-            let svg = PicoSvg::load(&xml_str, scale).unwrap();
-            println!("parsing time: {:?}", start.elapsed());
-            test_scenes::render_svg(&mut ctx, &svg);
-        } else {
-            //test_scenes::render_scene(&mut ctx);
-            test_scenes::render_blend_grid(&mut ctx);
+    fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png),
+            "ppm" => Some(Format::Ppm),
+            "raw" | "rgba" => Some(Format::Raw),
+            _ => None,
         }
+    }
 
-        let mut renderer = Renderer::new(&session, WIDTH, HEIGHT, 1)?;
-        renderer.upload_render_ctx(&mut ctx, 0)?;
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Ppm => "ppm",
+            Format::Raw => "raw",
+        }
+    }
+}
+
+/// What to render into an image.
+enum Content {
+    Svg(PicoSvg),
+    Scene(fn(&mut PietGpuRenderContext)),
+}
+
+struct Options {
+    width: usize,
+    height: usize,
+    background: Option<Color>,
+    /// The scale of SVG documents, or `None` to fit them to the image.
+    scale: Option<f64>,
+    flip: bool,
+}
+
+/// The GPU stages of the renderer, in the order of their timestamps.
+///
+/// Each stage is one timed compute pass of `Renderer::record`.
+const STAGES: &[&str] = &[
+    "Element",
+    "Clip, binning and tile allocation",
+    "Path flattening",
+    "Backdrop",
+    "Coarse raster",
+    "Fine raster",
+];
+
+/// A renderer for images of a fixed size.
+struct Headless {
+    renderer: Renderer,
+    query_pool: QueryPool,
+    image_buf: Buffer,
+    /// The size of the renderer's image, which is rounded up to whole tiles.
+    padded_width: usize,
+    padded_height: usize,
+    // Resources only hold weak references to the session, so it is dropped
+    // last.
+    session: Session,
+}
+
+impl Headless {
+    unsafe fn new(session: Session, width: usize, height: usize) -> Result<Headless, Error> {
+        let padded_width = (width + 15) & !15;
+        let padded_height = (height + 15) & !15;
+        let renderer = Renderer::new(&session, width, height, 1)?;
+        let query_pool = session.create_query_pool(Renderer::QUERY_POOL_SIZE)?;
         let image_usage = BufferUsage::MAP_READ | BufferUsage::COPY_DST;
-        let image_buf = session.create_buffer((WIDTH * HEIGHT * 4) as u64, image_usage)?;
+        let image_buf =
+            session.create_buffer((padded_width * padded_height * 4) as u64, image_usage)?;
+        Ok(Headless {
+            renderer,
+            query_pool,
+            image_buf,
+            padded_width,
+            padded_height,
+            session,
+        })
+    }
+
+    fn upload(&mut self, scene: &Scene, rcx: &ResourceContext) -> Result<(), Error> {
+        self.renderer
+            .upload_scene(&EncodedSceneRef::from_scene(scene, rcx), 0)
+    }
 
+    /// Render the uploaded scene, returning the time at the end of each
+    /// stage in seconds, or nothing if timestamps are unsupported.
+    unsafe fn run(&self) -> Result<Vec<f64>, Error> {
+        let mut cmd_buf = self.session.cmd_buf()?;
         cmd_buf.begin();
-        renderer.record(&mut cmd_buf, &query_pool, 0);
-        cmd_buf.copy_image_to_buffer(&renderer.image_dev, &image_buf);
-        cmd_buf.finish_timestamps(&query_pool);
+        self.renderer.record(&mut cmd_buf, &self.query_pool, 0);
+        cmd_buf.copy_image_to_buffer(&self.renderer.image_dev, &self.image_buf);
+        cmd_buf.finish_timestamps(&self.query_pool);
         cmd_buf.host_barrier();
         cmd_buf.finish();
-        let start = std::time::Instant::now();
-        let submitted = session.run_cmd_buf(cmd_buf, &[], &[])?;
+        let submitted = self.session.run_cmd_buf(cmd_buf, &[], &[])?;
         submitted.wait()?;
-        println!("elapsed = {:?}", start.elapsed());
-        let ts = session.fetch_query_pool(&query_pool).unwrap();
-        if !ts.is_empty() {
-            println!("Element kernel time: {:.3}ms", ts[0] * 1e3);
+        self.session.fetch_query_pool(&self.query_pool)
+    }
+
+    /// Read the RGBA pixels of the top left `width` by `height` region of
+    /// the last rendered image.
+    unsafe fn read_image(&self, width: usize, height: usize) -> Result<Vec<u8>, Error> {
+        let mut img_data: Vec<u8> = Default::default();
+        self.image_buf.read(&mut img_data)?;
+        if width == self.padded_width && height == self.padded_height {
+            return Ok(img_data);
+        }
+        let stride = self.padded_width * 4;
+        Ok(img_data
+            .chunks_exact(stride)
+            .take(height)
+            .flat_map(|row| &row[..width * 4])
+            .copied()
+            .collect())
+    }
+}
+
+/// The durations of the stages from their timestamps.
+///
+/// Stage `k` runs from timestamp `2k` to `2k + 1`. The query pool results
+/// are relative to the first timestamp, and omit it.
+fn stage_times(ts: &[f64]) -> Vec<f64> {
+    (0..STAGES.len())
+        .take_while(|k| 2 * k < ts.len())
+        .map(|k| ts[2 * k] - if k == 0 { 0.0 } else { ts[2 * k - 1] })
+        .collect()
+}
+
+/// Build the scene for some content.
+fn encode_scene(options: &Options, content: &Content) -> Result<(Scene, ResourceContext), Error> {
+    let mut scene = Scene::default();
+    let mut rcx = ResourceContext::new();
+    {
+        let mut ctx = PietGpuRenderContext::new(&mut scene, &mut rcx);
+        if let Some(background) = &options.background {
+            let rect = Rect::new(0.0, 0.0, options.width as f64, options.height as f64);
+            ctx.fill(rect, background);
+        }
+        match content {
+            Content::Svg(svg) => svg.render_layers(&mut ctx),
+            Content::Scene(render) => render(&mut ctx),
+        }
+        ctx.validate()?;
+    }
+    Ok((scene, rcx))
+}
+
+/// Render some content to a file.
+unsafe fn render_to_file(
+    headless: &mut Headless,
+    options: &Options,
+    content: &Content,
+    path: &Path,
+    format: Format,
+) -> Result<(), Error> {
+    let start = std::time::Instant::now();
+    let (scene, rcx) = encode_scene(options, content)?;
+    println!("flattening and encoding time: {:?}", start.elapsed());
+    headless.upload(&scene, &rcx)?;
+    let start = std::time::Instant::now();
+    let ts = headless.run()?;
+    println!("elapsed = {:?}", start.elapsed());
+    for (stage, time) in STAGES.iter().zip(stage_times(&ts)) {
+        println!("{} kernel time: {:.3}ms", stage, time * 1e3);
+    }
+
+    /*
+    let mut data: Vec<u32> = Default::default();
+    renderer.memory_buf_dev.read(&mut data).unwrap();
+    piet_gpu::dump_k1_data(&data[2..]);
+    */
+
+    let img_data = headless.read_image(options.width, options.height)?;
+    write_image(path, format, options.width, options.height, &img_data)
+}
+
+/// Timing statistics of a benchmark, in seconds.
+struct Stats {
+    min: f64,
+    median: f64,
+    p95: f64,
+}
+
+impl Stats {
+    /// Compute the statistics of a non-empty set of samples.
+    fn new(mut samples: Vec<f64>) -> Stats {
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let n = samples.len();
+        // Nearest-rank percentiles.
+        let percentile = |p: f64| samples[((p * n as f64).ceil() as usize).clamp(1, n) - 1];
+        Stats {
+            min: samples[0],
+            median: percentile(0.5),
+            p95: percentile(0.95),
+        }
+    }
+}
+
+/// The results of a benchmark.
+struct Bench {
+    /// Building the scene, on the CPU.
+    encode: Stats,
+    /// Uploading the scene, on the CPU.
+    upload: Stats,
+    /// From submitting the commands to their completion.
+    wall: Stats,
+    /// The GPU stages, if timestamps are supported.
+    stages: Vec<(&'static str, Stats)>,
+}
+
+/// Render some content repeatedly, discarding the first `warmup` runs.
+unsafe fn bench(
+    headless: &mut Headless,
+    options: &Options,
+    content: &Content,
+    warmup: usize,
+    reps: usize,
+) -> Result<Bench, Error> {
+    let mut encode = Vec::with_capacity(reps);
+    let mut upload = Vec::with_capacity(reps);
+    let mut wall = Vec::with_capacity(reps);
+    let mut stages = vec![Vec::with_capacity(reps); STAGES.len()];
+    for i in 0..warmup + reps {
+        let start = std::time::Instant::now();
+        let (scene, rcx) = encode_scene(options, content)?;
+        let encode_time = start.elapsed().as_secs_f64();
+        let start = std::time::Instant::now();
+        headless.upload(&scene, &rcx)?;
+        let upload_time = start.elapsed().as_secs_f64();
+        let start = std::time::Instant::now();
+        let ts = headless.run()?;
+        let wall_time = start.elapsed().as_secs_f64();
+        if i < warmup {
+            continue;
+        }
+        encode.push(encode_time);
+        upload.push(upload_time);
+        wall.push(wall_time);
+        for (samples, time) in stages.iter_mut().zip(stage_times(&ts)) {
+            samples.push(time);
+        }
+    }
+    Ok(Bench {
+        encode: Stats::new(encode),
+        upload: Stats::new(upload),
+        wall: Stats::new(wall),
+        stages: STAGES
+            .iter()
+            .zip(stages)
+            .filter(|(_, samples)| samples.len() == reps)
+            .map(|(stage, samples)| (*stage, Stats::new(samples)))
+            .collect(),
+    })
+}
+
+impl Bench {
+    fn print(&self) {
+        println!("{:24}{:>10}{:>10}{:>10}", "(ms)", "min", "median", "p95");
+        let row = |name: &str, stats: &Stats| {
             println!(
-                "Tile allocation kernel time: {:.3}ms",
-                (ts[1] - ts[0]) * 1e3
+                "{:24}{:10.3}{:10.3}{:10.3}",
+                name,
+                stats.min * 1e3,
+                stats.median * 1e3,
+                stats.p95 * 1e3
             );
-            println!("Coarse path kernel time: {:.3}ms", (ts[2] - ts[1]) * 1e3);
-            println!("Backdrop kernel time: {:.3}ms", (ts[3] - ts[2]) * 1e3);
-            println!("Binning kernel time: {:.3}ms", (ts[4] - ts[3]) * 1e3);
-            println!("Coarse raster kernel time: {:.3}ms", (ts[5] - ts[4]) * 1e3);
-            println!("Render kernel time: {:.3}ms", (ts[6] - ts[5]) * 1e3);
+        };
+        row("CPU encode", &self.encode);
+        row("CPU upload", &self.upload);
+        row("Submit to completion", &self.wall);
+        for (stage, stats) in &self.stages {
+            row(stage, stats);
         }
+    }
 
-        /*
-        let mut data: Vec<u32> = Default::default();
-        renderer.memory_buf_dev.read(&mut data).unwrap();
-        piet_gpu::dump_k1_data(&data[2..]);
-        */
+    /// Print the results as a JSON object, with times in milliseconds.
+    fn print_json(
+        &self,
+        input: &str,
+        backend: BackendType,
+        options: &Options,
+        warmup: usize,
+        reps: usize,
+    ) {
+        let json_stats = |stats: &Stats| {
+            format!(
+                "{{\"min\": {:.6}, \"median\": {:.6}, \"p95\": {:.6}}}",
+                stats.min * 1e3,
+                stats.median * 1e3,
+                stats.p95 * 1e3
+            )
+        };
+        let stages = self
+            .stages
+            .iter()
+            .map(|(stage, stats)| {
+                let key = stage
+                    .split(|c: char| !c.is_alphanumeric())
+                    .filter(|word| !word.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("_")
+                    .to_lowercase();
+                format!("\n    \"{}\": {}", key, json_stats(stats))
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        println!("{{");
+        println!("  \"input\": {},", json_string(input));
+        println!("  \"backend\": \"{:?}\",", backend);
+        println!("  \"width\": {},", options.width);
+        println!("  \"height\": {},", options.height);
+        println!("  \"warmup\": {},", warmup);
+        println!("  \"repetitions\": {},", reps);
+        println!("  \"cpu\": {{");
+        println!("    \"encode\": {},", json_stats(&self.encode));
+        println!("    \"upload\": {},", json_stats(&self.upload));
+        println!("    \"submit_to_completion\": {}", json_stats(&self.wall));
+        println!("  }},");
+        println!("  \"gpu\": {{{}\n  }}", stages);
+        println!("}}");
+    }
+}
 
-        let mut img_data: Vec<u8> = Default::default();
-        // Note: because png can use a `&[u8]` slice, we could avoid an extra copy
-        // (probably passing a slice into a closure). But for now: keep it simple.
-        image_buf.read(&mut img_data).unwrap();
+/// Quote a string for JSON.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
 
-        // Write image as PNG file.
-        let path = Path::new("image.png");
-        let file = File::create(path).unwrap();
-        let ref mut w = BufWriter::new(file);
+fn load_svg(input: &Path, options: &Options) -> Result<PicoSvg, Error> {
+    let xml_str = std::fs::read_to_string(input)?;
+    let start = std::time::Instant::now();
+    let scale = match options.scale {
+        Some(scale) => scale,
+        None => {
+            // Scale the document to fit the image, keeping its aspect ratio.
+            let size = PicoSvg::load_lenient(&xml_str, 1.0)?.size();
+            let scale =
+                (options.width as f64 / size.width).min(options.height as f64 / size.height);
+            if scale.is_finite() && scale > 0.0 {
+                scale
+            } else {
+                1.0
+            }
+        }
+    };
+    let scale = if options.flip { -scale } else { scale };
+    let svg = PicoSvg::load_lenient(&xml_str, scale)?;
+    println!("parsing time: {:?}", start.elapsed());
+    for warning in svg.warnings() {
+        eprintln!("warning: {}", warning);
+    }
+    Ok(svg)
+}
 
-        let mut encoder = png::Encoder::new(w, WIDTH as u32, HEIGHT as u32);
-        encoder.set_color(png::ColorType::RGBA);
-        encoder.set_depth(png::BitDepth::Eight);
-        let mut writer = encoder.write_header().unwrap();
+fn write_image(
+    path: &Path,
+    format: Format,
+    width: usize,
+    height: usize,
+    img_data: &[u8],
+) -> Result<(), Error> {
+    let file = File::create(path)?;
+    let mut w = BufWriter::new(file);
+    match format {
+        Format::Png => {
+            let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+            encoder.set_color(png::ColorType::RGBA);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(img_data)?;
+        }
+        Format::Ppm => {
+            write!(w, "P6\n{} {}\n255\n", width, height)?;
+            for pixel in img_data.chunks_exact(4) {
+                w.write_all(&pixel[..3])?;
+            }
+            w.flush()?;
+        }
+        Format::Raw => {
+            w.write_all(img_data)?;
+            w.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse a size such as `800x600`.
+fn parse_size(size: &str) -> Option<(usize, usize)> {
+    let (width, height) = size.split_once('x')?;
+    let width = width.trim().parse().ok()?;
+    let height = height.trim().parse().ok()?;
+    if width > 0 && height > 0 {
+        Some((width, height))
+    } else {
+        None
+    }
+}
+
+/// Parse a color such as `#ffffff` or `#00000080`.
+fn parse_color(color: &str) -> Option<Color> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    match hex.len() {
+        6 => Some(Color::from_rgba32_u32((value << 8) | 0xff)),
+        8 => Some(Color::from_rgba32_u32(value)),
+        _ => None,
+    }
+}
+
+/// The SVG files of a directory, in order.
+fn svg_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_svg = path
+            .extension()
+            .map_or(false, |ext| ext.eq_ignore_ascii_case("svg"));
+        if is_svg && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
 
-        writer.write_image_data(&img_data).unwrap();
+impl Options {
+    fn from_matches(matches: &ArgMatches) -> Result<Options, Error> {
+        let size = matches.value_of("size").unwrap();
+        let (width, height) = parse_size(size).ok_or_else(|| format!("invalid size '{}'", size))?;
+        let background = match matches.value_of("background") {
+            Some(color) => {
+                Some(parse_color(color).ok_or_else(|| format!("invalid background '{}'", color))?)
+            }
+            None => None,
+        };
+        let scale = match matches.value_of("scale") {
+            Some(scale) => Some(
+                scale
+                    .parse()
+                    .map_err(|_| format!("invalid scale '{}'", scale))?,
+            ),
+            None if matches.is_present("fit") => None,
+            None => Some(8.0),
+        };
+        Ok(Options {
+            width,
+            height,
+            background,
+            scale,
+            flip: matches.is_present("flip"),
+        })
+    }
+}
+
+/// The arguments selecting and placing the content, shared by subcommands.
+fn content_args(scene_names: &[&'static str]) -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("INPUT")
+            .index(1)
+            .help("An SVG file, or a directory of SVG files to render in batch"),
+        Arg::with_name("flip").short("f").long("flip"),
+        Arg::with_name("scale")
+            .short("s")
+            .long("scale")
+            .takes_value(true)
+            .help("The scale of SVG documents [default: 8]"),
+        Arg::with_name("fit")
+            .long("fit")
+            .conflicts_with("scale")
+            .help("Scale SVG documents to fit the image"),
+        Arg::with_name("size")
+            .long("size")
+            .takes_value(true)
+            .default_value("2048x1536")
+            .help("The size of the image, as WIDTHxHEIGHT"),
+        Arg::with_name("background")
+            .short("b")
+            .long("background")
+            .takes_value(true)
+            .help("A background color, as #RRGGBB or #RRGGBBAA [default: transparent]"),
+        Arg::with_name("scene")
+            .long("scene")
+            .takes_value(true)
+            .possible_values(scene_names)
+            .conflicts_with("INPUT")
+            .help("A test scene to render instead of an SVG document"),
+    ]
+}
+
+/// The content of a single image, and a name for it.
+fn single_content(matches: &ArgMatches, options: &Options) -> Result<(Content, String), Error> {
+    Ok(
+        match (matches.value_of("INPUT"), matches.value_of("scene")) {
+            (Some(input), _) => (
+                Content::Svg(load_svg(Path::new(input), options)?),
+                input.into(),
+            ),
+            (None, Some(name)) => (
+                Content::Scene(test_scenes::find_scene(name).unwrap()),
+                name.into(),
+            ),
+            (None, None) => (
+                Content::Scene(test_scenes::render_blend_grid),
+                "blend-grid".into(),
+            ),
+        },
+    )
+}
+
+fn main() -> Result<(), Error> {
+    let scene_names = test_scenes::SCENES
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    let matches = App::new("piet-gpu test")
+        .args(&content_args(&scene_names))
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .help("The output file, or directory in batch mode [default: image.png, or .]"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(Format::NAMES)
+                .help("The output format [default: from the output file extension, or png]"),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("Render a scene repeatedly and report timing statistics")
+                .args(&content_args(&scene_names))
+                .arg(
+                    Arg::with_name("warmup")
+                        .long("warmup")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help("The number of untimed runs first"),
+                )
+                .arg(
+                    Arg::with_name("reps")
+                        .short("n")
+                        .long("reps")
+                        .takes_value(true)
+                        .default_value("100")
+                        .help("The number of timed runs"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print the results as JSON, with times in milliseconds"),
+                ),
+        )
+        .get_matches();
+
+    let (instance, _) = Instance::new(None, InstanceFlags::default())?;
+    unsafe {
+        let device = instance.device(None)?;
+        let session = Session::new(device);
+        let backend = session.backend_type();
+
+        if let Some(matches) = matches.subcommand_matches("bench") {
+            let options = Options::from_matches(matches)?;
+            let count = |name: &str| {
+                let value = matches.value_of(name).unwrap();
+                value
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid {} '{}'", name, value))
+            };
+            let warmup = count("warmup")?;
+            let reps = count("reps")?;
+            if reps == 0 {
+                return Err("at least one repetition is needed".into());
+            }
+            if matches
+                .value_of("INPUT")
+                .map_or(false, |input| Path::new(input).is_dir())
+            {
+                return Err("benchmarks need a single SVG file".into());
+            }
+            let (content, name) = single_content(matches, &options)?;
+            let mut headless = Headless::new(session, options.width, options.height)?;
+            let results = bench(&mut headless, &options, &content, warmup, reps)?;
+            if matches.is_present("json") {
+                results.print_json(&name, backend, &options, warmup, reps);
+            } else {
+                println!(
+                    "{}, {}x{}, {:?}, {} runs after {} warmup runs",
+                    name, options.width, options.height, backend, reps, warmup
+                );
+                results.print();
+            }
+            return Ok(());
+        }
+
+        let options = Options::from_matches(&matches)?;
+        let format = matches.value_of("format").and_then(Format::from_name);
+        let output = matches.value_of("output").map(Path::new);
+        let mut headless = Headless::new(session, options.width, options.height)?;
+
+        let input = matches.value_of("INPUT").map(Path::new);
+        if let Some(dir) = input.filter(|input| input.is_dir()) {
+            let format = format.unwrap_or(Format::Png);
+            let out_dir = output.unwrap_or_else(|| Path::new("."));
+            std::fs::create_dir_all(out_dir)?;
+            let files = svg_files(dir)?;
+            let mut n_failed = 0;
+            for file in &files {
+                let mut name = file.file_stem().unwrap_or_default().to_os_string();
+                name.push(".");
+                name.push(format.extension());
+                let path = out_dir.join(name);
+                println!("{} -> {}", file.display(), path.display());
+                let rendered = load_svg(file, &options).and_then(|svg| {
+                    let content = Content::Svg(svg);
+                    render_to_file(&mut headless, &options, &content, &path, format)
+                });
+                if let Err(err) = rendered {
+                    eprintln!("error: {}: {}", file.display(), err);
+                    n_failed += 1;
+                }
+            }
+            if n_failed > 0 {
+                return Err(format!("{} of {} files failed", n_failed, files.len()).into());
+            }
+        } else {
+            let (content, _) = single_content(&matches, &options)?;
+            // Without an explicit format, follow the extension of the output.
+            let format = format
+                .or_else(|| {
+                    let ext = output?.extension()?.to_str()?;
+                    Format::from_name(ext)
+                })
+                .unwrap_or(Format::Png);
+            let default_output = PathBuf::from(format!("image.{}", format.extension()));
+            let path = output.unwrap_or(&default_output);
+            render_to_file(&mut headless, &options, &content, path, format)?;
+        }
     }
 
     Ok(())
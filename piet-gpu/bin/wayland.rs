@@ -126,11 +126,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let session = piet_gpu_hal::Session::new(device);
     let present_semaphore = unsafe{session.create_semaphore()}?;
     let mut renderer = unsafe{piet_gpu::Renderer::new(&session, width, height, 1)}?;
-    let mut cx = piet_gpu::PietGpuRenderContext::new();
+    let mut scene = piet_scene::scene::Scene::default();
+    let mut rcx = piet_scene::resource::ResourceContext::new();
+    let mut cx = piet_gpu::PietGpuRenderContext::new(&mut scene, &mut rcx);
     use piet::{RenderContext, Text, TextLayoutBuilder};
     let layout = cx.text().new_text_layout("Hello World!").default_attribute(piet::TextAttribute::FontSize(height as _)).build().unwrap();
     cx.draw_text(&layout, piet::kurbo::Point{x: 0., y: height as _});
-    renderer.upload_render_ctx(&mut cx, 0)?;
+    renderer.upload_scene(&piet_gpu::EncodedSceneRef::from_scene(&scene, &rcx), 0)?;
 	let (image_idx, acquisition_semaphore) = unsafe{swapchain.next()}?;
     let image = unsafe{swapchain.image(image_idx)};
 	let ref query_pool = session.create_query_pool(12)?;
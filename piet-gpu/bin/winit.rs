@@ -2,7 +2,9 @@ use piet::kurbo::Point;
 use piet::{RenderContext, Text, TextAttribute, TextLayoutBuilder};
 use piet_gpu_hal::{CmdBuf, Error, ImageLayout, Instance, Session, SubmittedCmdBuf};
 
-use piet_gpu::{test_scenes, PicoSvg, PietGpuRenderContext, Renderer};
+use piet_gpu::{test_scenes, EncodedSceneRef, PicoSvg, PietGpuRenderContext, Renderer};
+use piet_scene::resource::ResourceContext;
+use piet_scene::scene::Scene;
 
 use clap::{App, Arg};
 
@@ -39,10 +41,13 @@ fn main() -> Result<(), Error> {
             if matches.is_present("flip") {
                 scale = -scale;
             }
-            let xml_str = std::fs::read_to_string(file).unwrap();
+            let xml_str = std::fs::read_to_string(file)?;
             let start = std::time::Instant::now();
-            let svg = PicoSvg::load(&xml_str, scale).unwrap();
+            let svg = PicoSvg::load_lenient(&xml_str, scale)?;
             println!("parsing time: {:?}", start.elapsed());
+            for warning in svg.warnings() {
+                eprintln!("warning: {}", warning);
+            }
             Some(svg)
         }
         None => None,
@@ -76,6 +81,9 @@ fn main() -> Result<(), Error> {
         let mut submitted: [Option<SubmittedCmdBuf>; NUM_FRAMES] = Default::default();
 
         let mut renderer = Renderer::new(&session, WIDTH, HEIGHT, NUM_FRAMES)?;
+        let mut scene = Scene::default();
+        // Retained across frames so that gradient ramps are reused.
+        let mut rcx = ResourceContext::new();
         let mut mode = 0usize;
 
         event_loop.run(move |event, _, control_flow| {
@@ -124,7 +132,7 @@ fn main() -> Result<(), Error> {
                         }
                     }
 
-                    let mut ctx = PietGpuRenderContext::new();
+                    let mut ctx = PietGpuRenderContext::new(&mut scene, &mut rcx);
                     let test_blend = true;
                     if let Some(svg) = &svg {
                         test_scenes::render_svg(&mut ctx, svg);
@@ -168,7 +176,11 @@ fn main() -> Result<(), Error> {
                         test_scenes::render_anim_frame(&mut ctx, current_frame);
                     }
                     render_info_string(&mut ctx, &info_string);
-                    if let Err(e) = renderer.upload_render_ctx(&mut ctx, frame_idx) {
+                    if let Err(e) = ctx.validate() {
+                        println!("error in scene: {}", e);
+                    }
+                    let encoded = EncodedSceneRef::from_scene(&scene, &rcx);
+                    if let Err(e) = renderer.upload_scene(&encoded, frame_idx) {
                         println!("error in uploading: {}", e);
                     }
 
@@ -19,6 +19,10 @@
 #define Blend_Color 14
 #define Blend_Luminosity 15
 #define Blend_Clip 128
+// Mask modes don't blend: the source is multiplied by a value derived from
+// the backdrop, which holds the content of the mask.
+#define Blend_MaskAlpha 129
+#define Blend_MaskLuminance 130
 
 vec3 screen(vec3 cb, vec3 cs) {
 	return cb + cs - (cb * cs);
@@ -263,6 +267,10 @@ vec4 mix_compose(vec3 cb, vec3 cs, float ab, float as, uint mode) {
 #define BlendComp_default (Blend_Normal << 8 | Comp_SrcOver)
 #define BlendComp_clip (Blend_Clip << 8 | Comp_SrcOver)
 
+// The upper 16 bits of the blend word of a layer hold its transparency
+// (1 - alpha) in 0.16 fixed point, so that zero means fully opaque.
+#define BLEND_MODE_MASK 0xffff
+
 // This is added to alpha to prevent divide-by-zero
 #define EPSILON 1e-15
 
@@ -273,6 +281,12 @@ vec4 mix_blend_compose(vec4 backdrop, vec4 src, uint mode) {
 		// Both normal+src_over blend and clip case
 		return backdrop * (1.0 - src.a) + src;
 	}
+	if ((mode >> 8) == Blend_MaskAlpha) {
+		return src * backdrop.a;
+	} else if ((mode >> 8) == Blend_MaskLuminance) {
+		// Luminance of the premultiplied mask, which includes its alpha.
+		return src * dot(backdrop.rgb, vec3(0.2125, 0.7154, 0.0721));
+	}
 	// Un-premultiply colors for blending
 	float inv_src_a = 1.0 / (src.a + EPSILON);
 	vec3 cs = src.rgb * inv_src_a;
@@ -11,9 +11,17 @@
 #define Drawtag_FillLinGradient 0x114
 #define Drawtag_FillRadGradient 0x2dc
 #define Drawtag_FillImage 0x48
+#define Drawtag_FillGlyph 0x4a
+#define Drawtag_FillBlurRect 0x19c
 #define Drawtag_BeginClip 0x05
 #define Drawtag_EndClip 0x25
 
+// Flags in the scene data of Drawtag_FillGlyph, passed through to the
+// fine rasterizer as the flags of Cmd_Fill.
+#define GLYPH_FLAG_LCD 1
+#define GLYPH_FLAG_BGR 2
+#define GLYPH_FLAG_GAMMA 4
+
 struct DrawMonoid {
     uint path_ix;
     uint clip_ix;
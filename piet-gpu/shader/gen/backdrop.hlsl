@@ -48,6 +48,10 @@ struct Config
     uint pathseg_offset;
     uint drawtag_offset;
     uint drawdata_offset;
+    uint damage_x0;
+    uint damage_y0;
+    uint damage_x1;
+    uint damage_y1;
 };
 
 static const uint3 gl_WorkGroupSize = uint3(256u, 1u, 1u);
@@ -56,6 +56,10 @@ struct Config
     uint pathseg_offset;
     uint drawtag_offset;
     uint drawdata_offset;
+    uint damage_x0;
+    uint damage_y0;
+    uint damage_x1;
+    uint damage_y1;
 };
 
 static const uint3 gl_WorkGroupSize = uint3(256u, 1u, 1u);
@@ -212,11 +216,8 @@ void comp_main()
         float4 param_3 = path_bbox;
         float4 param_4 = clip_bbox;
         float4 bbox = bbox_intersect(param_3, param_4);
-        float4 _417 = bbox;
-        float4 _419 = bbox;
-        float2 _421 = max(_417.xy, _419.zw);
-        bbox.z = _421.x;
-        bbox.w = _421.y;
+        float2 _421 = max(bbox.xy, bbox.zw);
+        bbox = float4(bbox.x, bbox.y, _421.x, _421.y);
         uint param_5 = element_ix;
         float4 param_6 = bbox;
         store_draw_bbox(param_5, param_6);
@@ -226,11 +227,14 @@ void comp_main()
         y1 = int(ceil(bbox.w * 0.00390625f));
     }
     uint width_in_bins = ((_156.Load(8) + 16u) - 1u) / 16u;
-    uint height_in_bins = ((_156.Load(12) + 16u) - 1u) / 16u;
-    x0 = clamp(x0, 0, int(width_in_bins));
-    x1 = clamp(x1, x0, int(width_in_bins));
-    y0 = clamp(y0, 0, int(height_in_bins));
-    y1 = clamp(y1, y0, int(height_in_bins));
+    int damage_x0 = int(_156.Load(108) / 16u);
+    int damage_y0 = int(_156.Load(112) / 16u);
+    int damage_x1 = int(((_156.Load(116) + 16u) - 1u) / 16u);
+    int damage_y1 = int(((_156.Load(120) + 16u) - 1u) / 16u);
+    x0 = clamp(x0, damage_x0, damage_x1);
+    x1 = clamp(x1, x0, damage_x1);
+    y0 = clamp(y0, damage_y0, damage_y1);
+    y1 = clamp(y1, y0, damage_y1);
     if (x0 == x1)
     {
         y1 = y0;
@@ -241,8 +245,8 @@ void comp_main()
     uint my_mask = 1u << (gl_LocalInvocationID.x & 31u);
     while (y < y1)
     {
-        uint _523;
-        InterlockedOr(bitmaps[my_slice][(uint(y) * width_in_bins) + uint(x)], my_mask, _523);
+        uint _540;
+        InterlockedOr(bitmaps[my_slice][(uint(y) * width_in_bins) + uint(x)], my_mask, _540);
         x++;
         if (x == x1)
         {
@@ -264,8 +268,8 @@ void comp_main()
     if (element_count != 0u)
     {
         uint param_10 = element_count * 4u;
-        MallocResult _573 = malloc(param_10);
-        MallocResult chunk = _573;
+        MallocResult _590 = malloc(param_10);
+        MallocResult chunk = _590;
         chunk_alloc = chunk.alloc;
         sh_chunk_alloc[gl_LocalInvocationID.x] = chunk_alloc;
         if (chunk.failed)
@@ -274,31 +278,31 @@ void comp_main()
         }
     }
     uint out_ix = (_156.Load(20) >> uint(2)) + (((my_partition * 256u) + gl_LocalInvocationID.x) * 2u);
-    Alloc _603;
-    _603.offset = _156.Load(20);
+    Alloc _620;
+    _620.offset = _156.Load(20);
     Alloc param_11;
-    param_11.offset = _603.offset;
+    param_11.offset = _620.offset;
     uint param_12 = out_ix;
     uint param_13 = element_count;
     write_mem(param_11, param_12, param_13);
-    Alloc _615;
-    _615.offset = _156.Load(20);
+    Alloc _632;
+    _632.offset = _156.Load(20);
     Alloc param_14;
-    param_14.offset = _615.offset;
+    param_14.offset = _632.offset;
     uint param_15 = out_ix + 1u;
     uint param_16 = chunk_alloc.offset;
     write_mem(param_14, param_15, param_16);
     GroupMemoryBarrierWithGroupSync();
-    bool _630;
+    bool _647;
     if (!sh_alloc_failed)
     {
-        _630 = _81.Load(4) != 0u;
+        _647 = _81.Load(4) != 0u;
     }
     else
     {
-        _630 = sh_alloc_failed;
+        _647 = sh_alloc_failed;
     }
-    if (_630)
+    if (_647)
     {
         return;
     }
@@ -317,11 +321,11 @@ void comp_main()
             }
             Alloc out_alloc = sh_chunk_alloc[bin_ix];
             uint out_offset = out_alloc.offset + (idx * 4u);
-            BinInstanceRef _692 = { out_offset };
-            BinInstance _694 = { element_ix };
+            BinInstanceRef _709 = { out_offset };
+            BinInstance _711 = { element_ix };
             Alloc param_17 = out_alloc;
-            BinInstanceRef param_18 = _692;
-            BinInstance param_19 = _694;
+            BinInstanceRef param_18 = _709;
+            BinInstance param_19 = _711;
             BinInstance_write(param_17, param_18, param_19);
         }
         x++;
@@ -117,6 +117,16 @@ struct CmdImage
     int2 offset;
 };
 
+struct CmdAlphaRef
+{
+    uint offset;
+};
+
+struct CmdAlpha
+{
+    float alpha;
+};
+
 struct CmdEndClipRef
 {
     uint offset;
@@ -137,6 +147,19 @@ struct CmdJump
     uint new_ref;
 };
 
+struct CmdBlurRectRef
+{
+    uint offset;
+};
+
+struct CmdBlurRect
+{
+    uint rgba_color;
+    float4 rect;
+    float radius;
+    float std_dev;
+};
+
 struct CmdRef
 {
     uint offset;
@@ -171,13 +194,17 @@ struct Config
     uint pathseg_offset;
     uint drawtag_offset;
     uint drawdata_offset;
+    uint damage_x0;
+    uint damage_y0;
+    uint damage_x1;
+    uint damage_y1;
 };
 
 static const uint3 gl_WorkGroupSize = uint3(256u, 1u, 1u);
 
-RWByteAddressBuffer _266 : register(u0, space0);
-ByteAddressBuffer _1020 : register(t1, space0);
-ByteAddressBuffer _1399 : register(t2, space0);
+RWByteAddressBuffer _299 : register(u0, space0);
+ByteAddressBuffer _1180 : register(t1, space0);
+ByteAddressBuffer _1571 : register(t2, space0);
 
 static uint3 gl_WorkGroupID;
 static uint3 gl_LocalInvocationID;
@@ -200,8 +227,8 @@ groupshared uint sh_tile_count[256];
 
 Alloc slice_mem(Alloc a, uint offset, uint size)
 {
-    Alloc _343 = { a.offset + offset };
-    return _343;
+    Alloc _376 = { a.offset + offset };
+    return _376;
 }
 
 bool touch_mem(Alloc alloc, uint offset)
@@ -217,7 +244,7 @@ uint read_mem(Alloc alloc, uint offset)
     {
         return 0u;
     }
-    uint v = _266.Load(offset * 4 + 8);
+    uint v = _299.Load(offset * 4 + 8);
     return v;
 }
 
@@ -230,8 +257,8 @@ Alloc new_alloc(uint offset, uint size, bool mem_ok)
 
 BinInstanceRef BinInstance_index(BinInstanceRef ref, uint index)
 {
-    BinInstanceRef _361 = { ref.offset + (index * 4u) };
-    return _361;
+    BinInstanceRef _394 = { ref.offset + (index * 4u) };
+    return _394;
 }
 
 BinInstance BinInstance_read(Alloc a, BinInstanceRef ref)
@@ -259,8 +286,8 @@ Path Path_read(Alloc a, PathRef ref)
     uint raw2 = read_mem(param_4, param_5);
     Path s;
     s.bbox = uint4(raw0 & 65535u, raw0 >> uint(16), raw1 & 65535u, raw1 >> uint(16));
-    TileRef _424 = { raw2 };
-    s.tiles = _424;
+    TileRef _457 = { raw2 };
+    s.tiles = _457;
     return s;
 }
 
@@ -270,11 +297,11 @@ void write_tile_alloc(uint el_ix, Alloc a)
 
 Alloc read_tile_alloc(uint el_ix, bool mem_ok)
 {
-    uint _907;
-    _266.GetDimensions(_907);
-    _907 = (_907 - 8) / 4;
+    uint _1067;
+    _299.GetDimensions(_1067);
+    _1067 = (_1067 - 8) / 4;
     uint param = 0u;
-    uint param_1 = uint(int(_907) * 4);
+    uint param_1 = uint(int(_1067) * 4);
     bool param_2 = mem_ok;
     return new_alloc(param, param_1, param_2);
 }
@@ -288,31 +315,31 @@ Tile Tile_read(Alloc a, TileRef ref)
     Alloc param_2 = a;
     uint param_3 = ix + 1u;
     uint raw1 = read_mem(param_2, param_3);
-    TileSegRef _449 = { raw0 };
+    TileSegRef _482 = { raw0 };
     Tile s;
-    s.tile = _449;
+    s.tile = _482;
     s.backdrop = int(raw1);
     return s;
 }
 
 MallocResult malloc(uint size)
 {
-    uint _272;
-    _266.InterlockedAdd(0, size, _272);
-    uint offset = _272;
-    uint _279;
-    _266.GetDimensions(_279);
-    _279 = (_279 - 8) / 4;
+    uint _305;
+    _299.InterlockedAdd(0, size, _305);
+    uint offset = _305;
+    uint _312;
+    _299.GetDimensions(_312);
+    _312 = (_312 - 8) / 4;
     MallocResult r;
-    r.failed = (offset + size) > uint(int(_279) * 4);
+    r.failed = (offset + size) > uint(int(_312) * 4);
     uint param = offset;
     uint param_1 = size;
     bool param_2 = !r.failed;
     r.alloc = new_alloc(param, param_1, param_2);
     if (r.failed)
     {
-        uint _301;
-        _266.InterlockedMax(4, 1u, _301);
+        uint _334;
+        _299.InterlockedMax(4, 1u, _334);
         return r;
     }
     return r;
@@ -326,7 +353,7 @@ void write_mem(Alloc alloc, uint offset, uint val)
     {
         return;
     }
-    _266.Store(offset * 4 + 8, val);
+    _299.Store(offset * 4 + 8, val);
 }
 
 void CmdJump_write(Alloc a, CmdJumpRef ref, CmdJump s)
@@ -344,9 +371,9 @@ void Cmd_Jump_write(Alloc a, CmdRef ref, CmdJump s)
     uint param_1 = ref.offset >> uint(2);
     uint param_2 = 11u;
     write_mem(param, param_1, param_2);
-    CmdJumpRef _900 = { ref.offset + 4u };
+    CmdJumpRef _1041 = { ref.offset + 4u };
     Alloc param_3 = a;
-    CmdJumpRef param_4 = _900;
+    CmdJumpRef param_4 = _1041;
     CmdJump param_5 = s;
     CmdJump_write(param_3, param_4, param_5);
 }
@@ -358,22 +385,22 @@ bool alloc_cmd(inout Alloc cmd_alloc, inout CmdRef cmd_ref, inout uint cmd_limit
         return true;
     }
     uint param = 1024u;
-    MallocResult _928 = malloc(param);
-    MallocResult new_cmd = _928;
+    MallocResult _1088 = malloc(param);
+    MallocResult new_cmd = _1088;
     if (new_cmd.failed)
     {
         return false;
     }
-    CmdJump _938 = { new_cmd.alloc.offset };
-    CmdJump jump = _938;
+    CmdJump _1098 = { new_cmd.alloc.offset };
+    CmdJump jump = _1098;
     Alloc param_1 = cmd_alloc;
     CmdRef param_2 = cmd_ref;
     CmdJump param_3 = jump;
     Cmd_Jump_write(param_1, param_2, param_3);
     cmd_alloc = new_cmd.alloc;
-    CmdRef _950 = { cmd_alloc.offset };
-    cmd_ref = _950;
-    cmd_limit = (cmd_alloc.offset + 1024u) - 144u;
+    CmdRef _1110 = { cmd_alloc.offset };
+    cmd_ref = _1110;
+    cmd_limit = (cmd_alloc.offset + 1024u) - 192u;
     return true;
 }
 
@@ -390,15 +417,15 @@ void CmdFill_write(Alloc a, CmdFillRef ref, CmdFill s)
     write_mem(param_3, param_4, param_5);
 }
 
-void Cmd_Fill_write(Alloc a, CmdRef ref, CmdFill s)
+void Cmd_Fill_write(Alloc a, CmdRef ref, uint flags, CmdFill s)
 {
     Alloc param = a;
     uint param_1 = ref.offset >> uint(2);
-    uint param_2 = 1u;
+    uint param_2 = (flags << uint(16)) | 1u;
     write_mem(param, param_1, param_2);
-    CmdFillRef _757 = { ref.offset + 4u };
+    CmdFillRef _880 = { ref.offset + 4u };
     Alloc param_3 = a;
-    CmdFillRef param_4 = _757;
+    CmdFillRef param_4 = _880;
     CmdFill param_5 = s;
     CmdFill_write(param_3, param_4, param_5);
 }
@@ -430,9 +457,9 @@ void Cmd_Stroke_write(Alloc a, CmdRef ref, CmdStroke s)
     uint param_1 = ref.offset >> uint(2);
     uint param_2 = 2u;
     write_mem(param, param_1, param_2);
-    CmdStrokeRef _775 = { ref.offset + 4u };
+    CmdStrokeRef _898 = { ref.offset + 4u };
     Alloc param_3 = a;
-    CmdStrokeRef param_4 = _775;
+    CmdStrokeRef param_4 = _898;
     CmdStroke param_5 = s;
     CmdStroke_write(param_3, param_4, param_5);
 }
@@ -443,30 +470,31 @@ void write_fill(Alloc alloc, inout CmdRef cmd_ref, Tile tile, float linewidth)
     {
         if (tile.tile.offset != 0u)
         {
-            CmdFill _973 = { tile.tile.offset, tile.backdrop };
-            CmdFill cmd_fill = _973;
+            CmdFill _1133 = { tile.tile.offset, tile.backdrop };
+            CmdFill cmd_fill = _1133;
             Alloc param = alloc;
             CmdRef param_1 = cmd_ref;
-            CmdFill param_2 = cmd_fill;
-            Cmd_Fill_write(param, param_1, param_2);
+            uint param_2 = 0u;
+            CmdFill param_3 = cmd_fill;
+            Cmd_Fill_write(param, param_1, param_2, param_3);
             cmd_ref.offset += 12u;
         }
         else
         {
-            Alloc param_3 = alloc;
-            CmdRef param_4 = cmd_ref;
-            Cmd_Solid_write(param_3, param_4);
+            Alloc param_4 = alloc;
+            CmdRef param_5 = cmd_ref;
+            Cmd_Solid_write(param_4, param_5);
             cmd_ref.offset += 4u;
         }
     }
     else
     {
-        CmdStroke _1003 = { tile.tile.offset, 0.5f * linewidth };
-        CmdStroke cmd_stroke = _1003;
-        Alloc param_5 = alloc;
-        CmdRef param_6 = cmd_ref;
-        CmdStroke param_7 = cmd_stroke;
-        Cmd_Stroke_write(param_5, param_6, param_7);
+        CmdStroke _1163 = { tile.tile.offset, 0.5f * linewidth };
+        CmdStroke cmd_stroke = _1163;
+        Alloc param_6 = alloc;
+        CmdRef param_7 = cmd_ref;
+        CmdStroke param_8 = cmd_stroke;
+        Cmd_Stroke_write(param_6, param_7, param_8);
         cmd_ref.offset += 12u;
     }
 }
@@ -486,13 +514,59 @@ void Cmd_Color_write(Alloc a, CmdRef ref, CmdColor s)
     uint param_1 = ref.offset >> uint(2);
     uint param_2 = 5u;
     write_mem(param, param_1, param_2);
-    CmdColorRef _801 = { ref.offset + 4u };
+    CmdColorRef _942 = { ref.offset + 4u };
     Alloc param_3 = a;
-    CmdColorRef param_4 = _801;
+    CmdColorRef param_4 = _942;
     CmdColor param_5 = s;
     CmdColor_write(param_3, param_4, param_5);
 }
 
+void CmdBlurRect_write(Alloc a, CmdBlurRectRef ref, CmdBlurRect s)
+{
+    uint ix = ref.offset >> uint(2);
+    Alloc param = a;
+    uint param_1 = ix + 0u;
+    uint param_2 = s.rgba_color;
+    write_mem(param, param_1, param_2);
+    Alloc param_3 = a;
+    uint param_4 = ix + 1u;
+    uint param_5 = asuint(s.rect.x);
+    write_mem(param_3, param_4, param_5);
+    Alloc param_6 = a;
+    uint param_7 = ix + 2u;
+    uint param_8 = asuint(s.rect.y);
+    write_mem(param_6, param_7, param_8);
+    Alloc param_9 = a;
+    uint param_10 = ix + 3u;
+    uint param_11 = asuint(s.rect.z);
+    write_mem(param_9, param_10, param_11);
+    Alloc param_12 = a;
+    uint param_13 = ix + 4u;
+    uint param_14 = asuint(s.rect.w);
+    write_mem(param_12, param_13, param_14);
+    Alloc param_15 = a;
+    uint param_16 = ix + 5u;
+    uint param_17 = asuint(s.radius);
+    write_mem(param_15, param_16, param_17);
+    Alloc param_18 = a;
+    uint param_19 = ix + 6u;
+    uint param_20 = asuint(s.std_dev);
+    write_mem(param_18, param_19, param_20);
+}
+
+void Cmd_BlurRect_write(Alloc a, CmdRef ref, CmdBlurRect s)
+{
+    Alloc param = a;
+    uint param_1 = ref.offset >> uint(2);
+    uint param_2 = 12u;
+    write_mem(param, param_1, param_2);
+    CmdBlurRectRef _1060 = { ref.offset + 4u };
+    Alloc param_3 = a;
+    CmdBlurRectRef param_4 = _1060;
+    CmdBlurRect param_5 = s;
+    CmdBlurRect_write(param_3, param_4, param_5);
+}
+
 void CmdLinGrad_write(Alloc a, CmdLinGradRef ref, CmdLinGrad s)
 {
     uint ix = ref.offset >> uint(2);
@@ -520,9 +594,9 @@ void Cmd_LinGrad_write(Alloc a, CmdRef ref, CmdLinGrad s)
     uint param_1 = ref.offset >> uint(2);
     uint param_2 = 6u;
     write_mem(param, param_1, param_2);
-    CmdLinGradRef _819 = { ref.offset + 4u };
+    CmdLinGradRef _960 = { ref.offset + 4u };
     Alloc param_3 = a;
-    CmdLinGradRef param_4 = _819;
+    CmdLinGradRef param_4 = _960;
     CmdLinGrad param_5 = s;
     CmdLinGrad_write(param_3, param_4, param_5);
 }
@@ -582,9 +656,9 @@ void Cmd_RadGrad_write(Alloc a, CmdRef ref, CmdRadGrad s)
     uint param_1 = ref.offset >> uint(2);
     uint param_2 = 7u;
     write_mem(param, param_1, param_2);
-    CmdRadGradRef _837 = { ref.offset + 4u };
+    CmdRadGradRef _978 = { ref.offset + 4u };
     Alloc param_3 = a;
-    CmdRadGradRef param_4 = _837;
+    CmdRadGradRef param_4 = _978;
     CmdRadGrad param_5 = s;
     CmdRadGrad_write(param_3, param_4, param_5);
 }
@@ -608,9 +682,9 @@ void Cmd_Image_write(Alloc a, CmdRef ref, CmdImage s)
     uint param_1 = ref.offset >> uint(2);
     uint param_2 = 8u;
     write_mem(param, param_1, param_2);
-    CmdImageRef _855 = { ref.offset + 4u };
+    CmdImageRef _996 = { ref.offset + 4u };
     Alloc param_3 = a;
-    CmdImageRef param_4 = _855;
+    CmdImageRef param_4 = _996;
     CmdImage param_5 = s;
     CmdImage_write(param_3, param_4, param_5);
 }
@@ -623,6 +697,28 @@ void Cmd_BeginClip_write(Alloc a, CmdRef ref)
     write_mem(param, param_1, param_2);
 }
 
+void CmdAlpha_write(Alloc a, CmdAlphaRef ref, CmdAlpha s)
+{
+    uint ix = ref.offset >> uint(2);
+    Alloc param = a;
+    uint param_1 = ix + 0u;
+    uint param_2 = asuint(s.alpha);
+    write_mem(param, param_1, param_2);
+}
+
+void Cmd_Alpha_write(Alloc a, CmdRef ref, CmdAlpha s)
+{
+    Alloc param = a;
+    uint param_1 = ref.offset >> uint(2);
+    uint param_2 = 4u;
+    write_mem(param, param_1, param_2);
+    CmdAlphaRef _924 = { ref.offset + 4u };
+    Alloc param_3 = a;
+    CmdAlphaRef param_4 = _924;
+    CmdAlpha param_5 = s;
+    CmdAlpha_write(param_3, param_4, param_5);
+}
+
 void CmdEndClip_write(Alloc a, CmdEndClipRef ref, CmdEndClip s)
 {
     uint ix = ref.offset >> uint(2);
@@ -638,9 +734,9 @@ void Cmd_EndClip_write(Alloc a, CmdRef ref, CmdEndClip s)
     uint param_1 = ref.offset >> uint(2);
     uint param_2 = 10u;
     write_mem(param, param_1, param_2);
-    CmdEndClipRef _881 = { ref.offset + 4u };
+    CmdEndClipRef _1022 = { ref.offset + 4u };
     Alloc param_3 = a;
-    CmdEndClipRef param_4 = _881;
+    CmdEndClipRef param_4 = _1022;
     CmdEndClip param_5 = s;
     CmdEndClip_write(param_3, param_4, param_5);
 }
@@ -663,26 +759,28 @@ void alloc_write(Alloc a, uint offset, Alloc alloc)
 
 void comp_main()
 {
-    uint width_in_bins = ((_1020.Load(8) + 16u) - 1u) / 16u;
-    uint bin_ix = (width_in_bins * gl_WorkGroupID.y) + gl_WorkGroupID.x;
+    uint width_in_bins = ((_1180.Load(8) + 16u) - 1u) / 16u;
+    uint bin_x = gl_WorkGroupID.x + (_1180.Load(108) / 16u);
+    uint bin_y = gl_WorkGroupID.y + (_1180.Load(112) / 16u);
+    uint bin_ix = (width_in_bins * bin_y) + bin_x;
     uint partition_ix = 0u;
-    uint n_partitions = ((_1020.Load(0) + 256u) - 1u) / 256u;
+    uint n_partitions = ((_1180.Load(0) + 256u) - 1u) / 256u;
     uint th_ix = gl_LocalInvocationID.x;
-    uint bin_tile_x = 16u * gl_WorkGroupID.x;
-    uint bin_tile_y = 16u * gl_WorkGroupID.y;
+    uint bin_tile_x = 16u * bin_x;
+    uint bin_tile_y = 16u * bin_y;
     uint tile_x = gl_LocalInvocationID.x % 16u;
     uint tile_y = gl_LocalInvocationID.x / 16u;
-    uint this_tile_ix = (((bin_tile_y + tile_y) * _1020.Load(8)) + bin_tile_x) + tile_x;
-    Alloc _1085;
-    _1085.offset = _1020.Load(24);
+    uint this_tile_ix = (((bin_tile_y + tile_y) * _1180.Load(8)) + bin_tile_x) + tile_x;
+    Alloc _1257;
+    _1257.offset = _1180.Load(24);
     Alloc param;
-    param.offset = _1085.offset;
+    param.offset = _1257.offset;
     uint param_1 = this_tile_ix * 1024u;
     uint param_2 = 1024u;
     Alloc cmd_alloc = slice_mem(param, param_1, param_2);
-    CmdRef _1094 = { cmd_alloc.offset };
-    CmdRef cmd_ref = _1094;
-    uint cmd_limit = (cmd_ref.offset + 1024u) - 144u;
+    CmdRef _1266 = { cmd_alloc.offset };
+    CmdRef cmd_ref = _1266;
+    uint cmd_limit = (cmd_ref.offset + 1024u) - 192u;
     uint clip_depth = 0u;
     uint clip_zero_depth = 0u;
     uint rd_ix = 0u;
@@ -696,19 +794,21 @@ void comp_main()
     cmd_ref.offset += 4u;
     uint render_blend_depth = 0u;
     uint max_blend_depth = 0u;
-    uint drawmonoid_start = _1020.Load(44) >> uint(2);
-    uint drawtag_start = _1020.Load(100) >> uint(2);
-    uint drawdata_start = _1020.Load(104) >> uint(2);
-    uint drawinfo_start = _1020.Load(68) >> uint(2);
-    bool mem_ok = _266.Load(4) == 0u;
+    uint drawmonoid_start = _1180.Load(44) >> uint(2);
+    uint drawtag_start = _1180.Load(100) >> uint(2);
+    uint drawdata_start = _1180.Load(104) >> uint(2);
+    uint drawinfo_start = _1180.Load(68) >> uint(2);
+    bool mem_ok = _299.Load(4) == 0u;
     Alloc param_6;
     Alloc param_8;
-    uint _1331;
+    uint _1503;
     uint element_ix;
     Alloc param_17;
     uint tile_count;
-    uint _1632;
+    uint _1804;
     float linewidth;
+    uint rgba;
+    CmdBlurRect cmd_blur;
     CmdLinGrad cmd_lin;
     CmdRadGrad cmd_rad;
     while (true)
@@ -717,34 +817,34 @@ void comp_main()
         {
             sh_bitmaps[i][th_ix] = 0u;
         }
-        bool _1383;
+        bool _1555;
         for (;;)
         {
             if ((ready_ix == wr_ix) && (partition_ix < n_partitions))
             {
                 part_start_ix = ready_ix;
                 uint count = 0u;
-                bool _1181 = th_ix < 256u;
-                bool _1189;
-                if (_1181)
+                bool _1353 = th_ix < 256u;
+                bool _1361;
+                if (_1353)
                 {
-                    _1189 = (partition_ix + th_ix) < n_partitions;
+                    _1361 = (partition_ix + th_ix) < n_partitions;
                 }
                 else
                 {
-                    _1189 = _1181;
+                    _1361 = _1353;
                 }
-                if (_1189)
+                if (_1361)
                 {
-                    uint in_ix = (_1020.Load(20) >> uint(2)) + ((((partition_ix + th_ix) * 256u) + bin_ix) * 2u);
-                    Alloc _1206;
-                    _1206.offset = _1020.Load(20);
-                    param_6.offset = _1206.offset;
+                    uint in_ix = (_1180.Load(20) >> uint(2)) + ((((partition_ix + th_ix) * 256u) + bin_ix) * 2u);
+                    Alloc _1378;
+                    _1378.offset = _1180.Load(20);
+                    param_6.offset = _1378.offset;
                     uint param_7 = in_ix;
                     count = read_mem(param_6, param_7);
-                    Alloc _1217;
-                    _1217.offset = _1020.Load(20);
-                    param_8.offset = _1217.offset;
+                    Alloc _1389;
+                    _1389.offset = _1180.Load(20);
+                    param_8.offset = _1389.offset;
                     uint param_9 = in_ix + 1u;
                     uint offset = read_mem(param_8, param_9);
                     uint param_10 = offset;
@@ -790,16 +890,16 @@ void comp_main()
                 }
                 if (part_ix > 0u)
                 {
-                    _1331 = sh_part_count[part_ix - 1u];
+                    _1503 = sh_part_count[part_ix - 1u];
                 }
                 else
                 {
-                    _1331 = part_start_ix;
+                    _1503 = part_start_ix;
                 }
-                ix -= _1331;
+                ix -= _1503;
                 Alloc bin_alloc = sh_part_elements[part_ix];
-                BinInstanceRef _1350 = { bin_alloc.offset };
-                BinInstanceRef inst_ref = _1350;
+                BinInstanceRef _1522 = { bin_alloc.offset };
+                BinInstanceRef inst_ref = _1522;
                 BinInstanceRef param_13 = inst_ref;
                 uint param_14 = ix;
                 Alloc param_15 = bin_alloc;
@@ -809,16 +909,16 @@ void comp_main()
             }
             GroupMemoryBarrierWithGroupSync();
             wr_ix = min((rd_ix + 256u), ready_ix);
-            bool _1373 = (wr_ix - rd_ix) < 256u;
-            if (_1373)
+            bool _1545 = (wr_ix - rd_ix) < 256u;
+            if (_1545)
             {
-                _1383 = (wr_ix < ready_ix) || (partition_ix < n_partitions);
+                _1555 = (wr_ix < ready_ix) || (partition_ix < n_partitions);
             }
             else
             {
-                _1383 = _1373;
+                _1555 = _1545;
             }
-            if (_1383)
+            if (_1555)
             {
                 continue;
             }
@@ -831,11 +931,13 @@ void comp_main()
         if ((th_ix + rd_ix) < wr_ix)
         {
             element_ix = sh_elements[th_ix];
-            tag = _1399.Load((drawtag_start + element_ix) * 4 + 0);
+            tag = _1571.Load((drawtag_start + element_ix) * 4 + 0);
         }
         switch (tag)
         {
             case 68u:
+            case 74u:
+            case 412u:
             case 72u:
             case 276u:
             case 732u:
@@ -843,12 +945,12 @@ void comp_main()
             case 37u:
             {
                 uint drawmonoid_base = drawmonoid_start + (4u * element_ix);
-                uint path_ix = _266.Load(drawmonoid_base * 4 + 8);
-                PathRef _1424 = { _1020.Load(16) + (path_ix * 12u) };
-                Alloc _1427;
-                _1427.offset = _1020.Load(16);
-                param_17.offset = _1427.offset;
-                PathRef param_18 = _1424;
+                uint path_ix = _299.Load(drawmonoid_base * 4 + 8);
+                PathRef _1596 = { _1180.Load(16) + (path_ix * 12u) };
+                Alloc _1599;
+                _1599.offset = _1180.Load(16);
+                param_17.offset = _1599.offset;
+                PathRef param_18 = _1596;
                 Path path = Path_read(param_17, param_18);
                 uint stride = path.bbox.z - path.bbox.x;
                 sh_tile_stride[th_ix] = stride;
@@ -904,16 +1006,16 @@ void comp_main()
                 }
             }
             uint element_ix_1 = sh_elements[el_ix];
-            uint tag_1 = _1399.Load((drawtag_start + element_ix_1) * 4 + 0);
+            uint tag_1 = _1571.Load((drawtag_start + element_ix_1) * 4 + 0);
             if (el_ix > 0u)
             {
-                _1632 = sh_tile_count[el_ix - 1u];
+                _1804 = sh_tile_count[el_ix - 1u];
             }
             else
             {
-                _1632 = 0u;
+                _1804 = 0u;
             }
-            uint seq_ix = ix_1 - _1632;
+            uint seq_ix = ix_1 - _1804;
             uint width = sh_tile_width[el_ix];
             uint x = sh_tile_x0[el_ix] + (seq_ix % width);
             uint y = sh_tile_y0[el_ix] + (seq_ix / width);
@@ -922,38 +1024,38 @@ void comp_main()
             {
                 uint param_24 = el_ix;
                 bool param_25 = mem_ok;
-                TileRef _1684 = { sh_tile_base[el_ix] + (((sh_tile_stride[el_ix] * y) + x) * 8u) };
+                TileRef _1856 = { sh_tile_base[el_ix] + (((sh_tile_stride[el_ix] * y) + x) * 8u) };
                 Alloc param_26 = read_tile_alloc(param_24, param_25);
-                TileRef param_27 = _1684;
+                TileRef param_27 = _1856;
                 Tile tile = Tile_read(param_26, param_27);
                 bool is_clip = (tag_1 & 1u) != 0u;
                 bool is_blend = false;
                 if (is_clip)
                 {
                     uint drawmonoid_base_1 = drawmonoid_start + (4u * element_ix_1);
-                    uint scene_offset = _266.Load((drawmonoid_base_1 + 2u) * 4 + 8);
+                    uint scene_offset = _299.Load((drawmonoid_base_1 + 2u) * 4 + 8);
                     uint dd = drawdata_start + (scene_offset >> uint(2));
-                    uint blend = _1399.Load(dd * 4 + 0);
+                    uint blend = _1571.Load(dd * 4 + 0);
                     is_blend = blend != 32771u;
                 }
-                bool _1720 = tile.tile.offset != 0u;
-                bool _1729;
-                if (!_1720)
+                bool _1892 = tile.tile.offset != 0u;
+                bool _1901;
+                if (!_1892)
                 {
-                    _1729 = (tile.backdrop == 0) == is_clip;
+                    _1901 = (tile.backdrop == 0) == is_clip;
                 }
                 else
                 {
-                    _1729 = _1720;
+                    _1901 = _1892;
                 }
-                include_tile = _1729 || is_blend;
+                include_tile = _1901 || is_blend;
             }
             if (include_tile)
             {
                 uint el_slice = el_ix / 32u;
                 uint el_mask = 1u << (el_ix & 31u);
-                uint _1751;
-                InterlockedOr(sh_bitmaps[el_slice][(y * 16u) + x], el_mask, _1751);
+                uint _1923;
+                InterlockedOr(sh_bitmaps[el_slice][(y * 16u) + x], el_mask, _1923);
             }
         }
         GroupMemoryBarrierWithGroupSync();
@@ -977,33 +1079,33 @@ void comp_main()
             uint element_ref_ix = (slice_ix * 32u) + uint(int(firstbitlow(bitmap)));
             uint element_ix_2 = sh_elements[element_ref_ix];
             bitmap &= (bitmap - 1u);
-            uint drawtag = _1399.Load((drawtag_start + element_ix_2) * 4 + 0);
+            uint drawtag = _1571.Load((drawtag_start + element_ix_2) * 4 + 0);
             if (clip_zero_depth == 0u)
             {
                 uint param_28 = element_ref_ix;
                 bool param_29 = mem_ok;
-                TileRef _1828 = { sh_tile_base[element_ref_ix] + (((sh_tile_stride[element_ref_ix] * tile_y) + tile_x) * 8u) };
+                TileRef _2000 = { sh_tile_base[element_ref_ix] + (((sh_tile_stride[element_ref_ix] * tile_y) + tile_x) * 8u) };
                 Alloc param_30 = read_tile_alloc(param_28, param_29);
-                TileRef param_31 = _1828;
+                TileRef param_31 = _2000;
                 Tile tile_1 = Tile_read(param_30, param_31);
                 uint drawmonoid_base_2 = drawmonoid_start + (4u * element_ix_2);
-                uint scene_offset_1 = _266.Load((drawmonoid_base_2 + 2u) * 4 + 8);
-                uint info_offset = _266.Load((drawmonoid_base_2 + 3u) * 4 + 8);
+                uint scene_offset_1 = _299.Load((drawmonoid_base_2 + 2u) * 4 + 8);
+                uint info_offset = _299.Load((drawmonoid_base_2 + 3u) * 4 + 8);
                 uint dd_1 = drawdata_start + (scene_offset_1 >> uint(2));
                 uint di = drawinfo_start + (info_offset >> uint(2));
                 switch (drawtag)
                 {
                     case 68u:
                     {
-                        linewidth = asfloat(_266.Load(di * 4 + 8));
+                        linewidth = asfloat(_299.Load(di * 4 + 8));
                         Alloc param_32 = cmd_alloc;
                         CmdRef param_33 = cmd_ref;
                         uint param_34 = cmd_limit;
-                        bool _1876 = alloc_cmd(param_32, param_33, param_34);
+                        bool _2050 = alloc_cmd(param_32, param_33, param_34);
                         cmd_alloc = param_32;
                         cmd_ref = param_33;
                         cmd_limit = param_34;
-                        if (!_1876)
+                        if (!_2050)
                         {
                             break;
                         }
@@ -1013,142 +1115,220 @@ void comp_main()
                         float param_38 = linewidth;
                         write_fill(param_35, param_36, param_37, param_38);
                         cmd_ref = param_36;
-                        uint rgba = _1399.Load(dd_1 * 4 + 0);
-                        CmdColor _1899 = { rgba };
+                        rgba = _1571.Load(dd_1 * 4 + 0);
+                        CmdColor _2073 = { rgba };
                         Alloc param_39 = cmd_alloc;
                         CmdRef param_40 = cmd_ref;
-                        CmdColor param_41 = _1899;
+                        CmdColor param_41 = _2073;
                         Cmd_Color_write(param_39, param_40, param_41);
                         cmd_ref.offset += 8u;
                         break;
                     }
-                    case 276u:
+                    case 74u:
                     {
+                        linewidth = asfloat(_299.Load(di * 4 + 8));
                         Alloc param_42 = cmd_alloc;
                         CmdRef param_43 = cmd_ref;
                         uint param_44 = cmd_limit;
-                        bool _1917 = alloc_cmd(param_42, param_43, param_44);
+                        bool _2095 = alloc_cmd(param_42, param_43, param_44);
                         cmd_alloc = param_42;
                         cmd_ref = param_43;
                         cmd_limit = param_44;
-                        if (!_1917)
+                        if (!_2095)
                         {
                             break;
                         }
-                        linewidth = asfloat(_266.Load(di * 4 + 8));
-                        Alloc param_45 = cmd_alloc;
-                        CmdRef param_46 = cmd_ref;
-                        Tile param_47 = tile_1;
-                        float param_48 = linewidth;
-                        write_fill(param_45, param_46, param_47, param_48);
-                        cmd_ref = param_46;
-                        cmd_lin.index = _1399.Load(dd_1 * 4 + 0);
-                        cmd_lin.line_x = asfloat(_266.Load((di + 1u) * 4 + 8));
-                        cmd_lin.line_y = asfloat(_266.Load((di + 2u) * 4 + 8));
-                        cmd_lin.line_c = asfloat(_266.Load((di + 3u) * 4 + 8));
-                        Alloc param_49 = cmd_alloc;
-                        CmdRef param_50 = cmd_ref;
-                        CmdLinGrad param_51 = cmd_lin;
-                        Cmd_LinGrad_write(param_49, param_50, param_51);
-                        cmd_ref.offset += 20u;
+                        uint glyph_flags = _1571.Load((dd_1 + 1u) * 4 + 0);
+                        bool _2109 = linewidth < 0.0f;
+                        bool _2115;
+                        if (_2109)
+                        {
+                            _2115 = tile_1.tile.offset != 0u;
+                        }
+                        else
+                        {
+                            _2115 = _2109;
+                        }
+                        if (_2115 && (glyph_flags != 0u))
+                        {
+                            CmdFill _2126 = { tile_1.tile.offset, tile_1.backdrop };
+                            CmdFill cmd_fill = _2126;
+                            Alloc param_45 = cmd_alloc;
+                            CmdRef param_46 = cmd_ref;
+                            uint param_47 = glyph_flags;
+                            CmdFill param_48 = cmd_fill;
+                            Cmd_Fill_write(param_45, param_46, param_47, param_48);
+                            cmd_ref.offset += 12u;
+                        }
+                        else
+                        {
+                            Alloc param_49 = cmd_alloc;
+                            CmdRef param_50 = cmd_ref;
+                            Tile param_51 = tile_1;
+                            float param_52 = linewidth;
+                            write_fill(param_49, param_50, param_51, param_52);
+                            cmd_ref = param_50;
+                        }
+                        rgba = _1571.Load(dd_1 * 4 + 0);
+                        CmdColor _2155 = { rgba };
+                        Alloc param_53 = cmd_alloc;
+                        CmdRef param_54 = cmd_ref;
+                        CmdColor param_55 = _2155;
+                        Cmd_Color_write(param_53, param_54, param_55);
+                        cmd_ref.offset += 8u;
                         break;
                     }
-                    case 732u:
+                    case 412u:
                     {
-                        Alloc param_52 = cmd_alloc;
-                        CmdRef param_53 = cmd_ref;
-                        uint param_54 = cmd_limit;
-                        bool _1981 = alloc_cmd(param_52, param_53, param_54);
-                        cmd_alloc = param_52;
-                        cmd_ref = param_53;
-                        cmd_limit = param_54;
-                        if (!_1981)
+                        Alloc param_56 = cmd_alloc;
+                        CmdRef param_57 = cmd_ref;
+                        uint param_58 = cmd_limit;
+                        bool _2173 = alloc_cmd(param_56, param_57, param_58);
+                        cmd_alloc = param_56;
+                        cmd_ref = param_57;
+                        cmd_limit = param_58;
+                        if (!_2173)
                         {
                             break;
                         }
-                        linewidth = asfloat(_266.Load(di * 4 + 8));
-                        Alloc param_55 = cmd_alloc;
-                        CmdRef param_56 = cmd_ref;
-                        Tile param_57 = tile_1;
-                        float param_58 = linewidth;
-                        write_fill(param_55, param_56, param_57, param_58);
-                        cmd_ref = param_56;
-                        cmd_rad.index = _1399.Load(dd_1 * 4 + 0);
-                        cmd_rad.mat = asfloat(uint4(_266.Load((di + 1u) * 4 + 8), _266.Load((di + 2u) * 4 + 8), _266.Load((di + 3u) * 4 + 8), _266.Load((di + 4u) * 4 + 8)));
-                        cmd_rad.xlat = asfloat(uint2(_266.Load((di + 5u) * 4 + 8), _266.Load((di + 6u) * 4 + 8)));
-                        cmd_rad.c1 = asfloat(uint2(_266.Load((di + 7u) * 4 + 8), _266.Load((di + 8u) * 4 + 8)));
-                        cmd_rad.ra = asfloat(_266.Load((di + 9u) * 4 + 8));
-                        cmd_rad.roff = asfloat(_266.Load((di + 10u) * 4 + 8));
+                        cmd_blur.rgba_color = _1571.Load(dd_1 * 4 + 0);
+                        cmd_blur.rect = asfloat(uint4(_299.Load(di * 4 + 8), _299.Load((di + 1u) * 4 + 8), _299.Load((di + 2u) * 4 + 8), _299.Load((di + 3u) * 4 + 8)));
+                        cmd_blur.radius = asfloat(_299.Load((di + 4u) * 4 + 8));
+                        cmd_blur.std_dev = asfloat(_299.Load((di + 5u) * 4 + 8));
                         Alloc param_59 = cmd_alloc;
                         CmdRef param_60 = cmd_ref;
-                        CmdRadGrad param_61 = cmd_rad;
-                        Cmd_RadGrad_write(param_59, param_60, param_61);
-                        cmd_ref.offset += 48u;
+                        CmdBlurRect param_61 = cmd_blur;
+                        Cmd_BlurRect_write(param_59, param_60, param_61);
+                        cmd_ref.offset += 32u;
                         break;
                     }
-                    case 72u:
+                    case 276u:
                     {
-                        linewidth = asfloat(_266.Load(di * 4 + 8));
                         Alloc param_62 = cmd_alloc;
                         CmdRef param_63 = cmd_ref;
                         uint param_64 = cmd_limit;
-                        bool _2087 = alloc_cmd(param_62, param_63, param_64);
+                        bool _2235 = alloc_cmd(param_62, param_63, param_64);
                         cmd_alloc = param_62;
                         cmd_ref = param_63;
                         cmd_limit = param_64;
-                        if (!_2087)
+                        if (!_2235)
                         {
                             break;
                         }
+                        linewidth = asfloat(_299.Load(di * 4 + 8));
                         Alloc param_65 = cmd_alloc;
                         CmdRef param_66 = cmd_ref;
                         Tile param_67 = tile_1;
                         float param_68 = linewidth;
                         write_fill(param_65, param_66, param_67, param_68);
                         cmd_ref = param_66;
-                        uint index = _1399.Load(dd_1 * 4 + 0);
-                        uint raw1 = _1399.Load((dd_1 + 1u) * 4 + 0);
-                        int2 offset_1 = int2(int(raw1 << uint(16)) >> 16, int(raw1) >> 16);
-                        CmdImage _2126 = { index, offset_1 };
+                        cmd_lin.index = _1571.Load(dd_1 * 4 + 0);
+                        cmd_lin.line_x = asfloat(_299.Load((di + 1u) * 4 + 8));
+                        cmd_lin.line_y = asfloat(_299.Load((di + 2u) * 4 + 8));
+                        cmd_lin.line_c = asfloat(_299.Load((di + 3u) * 4 + 8));
                         Alloc param_69 = cmd_alloc;
                         CmdRef param_70 = cmd_ref;
-                        CmdImage param_71 = _2126;
-                        Cmd_Image_write(param_69, param_70, param_71);
+                        CmdLinGrad param_71 = cmd_lin;
+                        Cmd_LinGrad_write(param_69, param_70, param_71);
+                        cmd_ref.offset += 20u;
+                        break;
+                    }
+                    case 732u:
+                    {
+                        Alloc param_72 = cmd_alloc;
+                        CmdRef param_73 = cmd_ref;
+                        uint param_74 = cmd_limit;
+                        bool _2299 = alloc_cmd(param_72, param_73, param_74);
+                        cmd_alloc = param_72;
+                        cmd_ref = param_73;
+                        cmd_limit = param_74;
+                        if (!_2299)
+                        {
+                            break;
+                        }
+                        linewidth = asfloat(_299.Load(di * 4 + 8));
+                        Alloc param_75 = cmd_alloc;
+                        CmdRef param_76 = cmd_ref;
+                        Tile param_77 = tile_1;
+                        float param_78 = linewidth;
+                        write_fill(param_75, param_76, param_77, param_78);
+                        cmd_ref = param_76;
+                        cmd_rad.index = _1571.Load(dd_1 * 4 + 0);
+                        cmd_rad.mat = asfloat(uint4(_299.Load((di + 1u) * 4 + 8), _299.Load((di + 2u) * 4 + 8), _299.Load((di + 3u) * 4 + 8), _299.Load((di + 4u) * 4 + 8)));
+                        cmd_rad.xlat = asfloat(uint2(_299.Load((di + 5u) * 4 + 8), _299.Load((di + 6u) * 4 + 8)));
+                        cmd_rad.c1 = asfloat(uint2(_299.Load((di + 7u) * 4 + 8), _299.Load((di + 8u) * 4 + 8)));
+                        cmd_rad.ra = asfloat(_299.Load((di + 9u) * 4 + 8));
+                        cmd_rad.roff = asfloat(_299.Load((di + 10u) * 4 + 8));
+                        Alloc param_79 = cmd_alloc;
+                        CmdRef param_80 = cmd_ref;
+                        CmdRadGrad param_81 = cmd_rad;
+                        Cmd_RadGrad_write(param_79, param_80, param_81);
+                        cmd_ref.offset += 48u;
+                        break;
+                    }
+                    case 72u:
+                    {
+                        linewidth = asfloat(_299.Load(di * 4 + 8));
+                        Alloc param_82 = cmd_alloc;
+                        CmdRef param_83 = cmd_ref;
+                        uint param_84 = cmd_limit;
+                        bool _2404 = alloc_cmd(param_82, param_83, param_84);
+                        cmd_alloc = param_82;
+                        cmd_ref = param_83;
+                        cmd_limit = param_84;
+                        if (!_2404)
+                        {
+                            break;
+                        }
+                        Alloc param_85 = cmd_alloc;
+                        CmdRef param_86 = cmd_ref;
+                        Tile param_87 = tile_1;
+                        float param_88 = linewidth;
+                        write_fill(param_85, param_86, param_87, param_88);
+                        cmd_ref = param_86;
+                        uint index = _1571.Load(dd_1 * 4 + 0);
+                        uint raw1 = _1571.Load((dd_1 + 1u) * 4 + 0);
+                        int2 offset_1 = int2(int(raw1 << uint(16)) >> 16, int(raw1) >> 16);
+                        CmdImage _2443 = { index, offset_1 };
+                        Alloc param_89 = cmd_alloc;
+                        CmdRef param_90 = cmd_ref;
+                        CmdImage param_91 = _2443;
+                        Cmd_Image_write(param_89, param_90, param_91);
                         cmd_ref.offset += 12u;
                         break;
                     }
                     case 5u:
                     {
-                        bool _2140 = tile_1.tile.offset == 0u;
-                        bool _2146;
-                        if (_2140)
+                        bool _2457 = tile_1.tile.offset == 0u;
+                        bool _2463;
+                        if (_2457)
                         {
-                            _2146 = tile_1.backdrop == 0;
+                            _2463 = tile_1.backdrop == 0;
                         }
                         else
                         {
-                            _2146 = _2140;
+                            _2463 = _2457;
                         }
-                        if (_2146)
+                        if (_2463)
                         {
                             clip_zero_depth = clip_depth + 1u;
                         }
                         else
                         {
-                            Alloc param_72 = cmd_alloc;
-                            CmdRef param_73 = cmd_ref;
-                            uint param_74 = cmd_limit;
-                            bool _2158 = alloc_cmd(param_72, param_73, param_74);
-                            cmd_alloc = param_72;
-                            cmd_ref = param_73;
-                            cmd_limit = param_74;
-                            if (!_2158)
+                            Alloc param_92 = cmd_alloc;
+                            CmdRef param_93 = cmd_ref;
+                            uint param_94 = cmd_limit;
+                            bool _2475 = alloc_cmd(param_92, param_93, param_94);
+                            cmd_alloc = param_92;
+                            cmd_ref = param_93;
+                            cmd_limit = param_94;
+                            if (!_2475)
                             {
                                 break;
                             }
-                            Alloc param_75 = cmd_alloc;
-                            CmdRef param_76 = cmd_ref;
-                            Cmd_BeginClip_write(param_75, param_76);
+                            Alloc param_95 = cmd_alloc;
+                            CmdRef param_96 = cmd_ref;
+                            Cmd_BeginClip_write(param_95, param_96);
                             cmd_ref.offset += 4u;
                             render_blend_depth++;
                             max_blend_depth = max(max_blend_depth, render_blend_depth);
@@ -1159,29 +1339,40 @@ void comp_main()
                     case 37u:
                     {
                         clip_depth--;
-                        Alloc param_77 = cmd_alloc;
-                        CmdRef param_78 = cmd_ref;
-                        uint param_79 = cmd_limit;
-                        bool _2191 = alloc_cmd(param_77, param_78, param_79);
-                        cmd_alloc = param_77;
-                        cmd_ref = param_78;
-                        cmd_limit = param_79;
-                        if (!_2191)
+                        Alloc param_97 = cmd_alloc;
+                        CmdRef param_98 = cmd_ref;
+                        uint param_99 = cmd_limit;
+                        bool _2508 = alloc_cmd(param_97, param_98, param_99);
+                        cmd_alloc = param_97;
+                        cmd_ref = param_98;
+                        cmd_limit = param_99;
+                        if (!_2508)
                         {
                             break;
                         }
-                        Alloc param_80 = cmd_alloc;
-                        CmdRef param_81 = cmd_ref;
-                        Tile param_82 = tile_1;
-                        float param_83 = -1.0f;
-                        write_fill(param_80, param_81, param_82, param_83);
-                        cmd_ref = param_81;
-                        uint blend_1 = _1399.Load(dd_1 * 4 + 0);
-                        CmdEndClip _2214 = { blend_1 };
-                        Alloc param_84 = cmd_alloc;
-                        CmdRef param_85 = cmd_ref;
-                        CmdEndClip param_86 = _2214;
-                        Cmd_EndClip_write(param_84, param_85, param_86);
+                        Alloc param_100 = cmd_alloc;
+                        CmdRef param_101 = cmd_ref;
+                        Tile param_102 = tile_1;
+                        float param_103 = -1.0f;
+                        write_fill(param_100, param_101, param_102, param_103);
+                        cmd_ref = param_101;
+                        uint blend_1 = _1571.Load(dd_1 * 4 + 0);
+                        uint transparency = blend_1 >> uint(16);
+                        if (transparency != 0u)
+                        {
+                            float alpha = 1.0f - (float(transparency) * 1.525902189314365386962890625e-05f);
+                            CmdAlpha _2545 = { alpha };
+                            Alloc param_104 = cmd_alloc;
+                            CmdRef param_105 = cmd_ref;
+                            CmdAlpha param_106 = _2545;
+                            Cmd_Alpha_write(param_104, param_105, param_106);
+                            cmd_ref.offset += 8u;
+                        }
+                        CmdEndClip _2558 = { blend_1 & 65535u };
+                        Alloc param_107 = cmd_alloc;
+                        CmdRef param_108 = cmd_ref;
+                        CmdEndClip param_109 = _2558;
+                        Cmd_EndClip_write(param_107, param_108, param_109);
                         cmd_ref.offset += 8u;
                         render_blend_depth--;
                         break;
@@ -1216,31 +1407,31 @@ void comp_main()
             break;
         }
     }
-    bool _2263 = (bin_tile_x + tile_x) < _1020.Load(8);
-    bool _2272;
-    if (_2263)
+    bool _2607 = (bin_tile_x + tile_x) < _1180.Load(8);
+    bool _2616;
+    if (_2607)
     {
-        _2272 = (bin_tile_y + tile_y) < _1020.Load(12);
+        _2616 = (bin_tile_y + tile_y) < _1180.Load(12);
     }
     else
     {
-        _2272 = _2263;
+        _2616 = _2607;
     }
-    if (_2272)
+    if (_2616)
     {
-        Alloc param_87 = cmd_alloc;
-        CmdRef param_88 = cmd_ref;
-        Cmd_End_write(param_87, param_88);
+        Alloc param_110 = cmd_alloc;
+        CmdRef param_111 = cmd_ref;
+        Cmd_End_write(param_110, param_111);
         if (max_blend_depth > 4u)
         {
             uint scratch_size = (((max_blend_depth * 16u) * 16u) * 1u) * 4u;
-            uint param_89 = scratch_size;
-            MallocResult _2293 = malloc(param_89);
-            MallocResult scratch = _2293;
-            Alloc param_90 = scratch_alloc;
-            uint param_91 = scratch_alloc.offset;
-            Alloc param_92 = scratch.alloc;
-            alloc_write(param_90, param_91, param_92);
+            uint param_112 = scratch_size;
+            MallocResult _2637 = malloc(param_112);
+            MallocResult scratch = _2637;
+            Alloc param_113 = scratch_alloc;
+            uint param_114 = scratch_alloc.offset;
+            Alloc param_115 = scratch.alloc;
+            alloc_write(param_113, param_114, param_115);
         }
     }
 }
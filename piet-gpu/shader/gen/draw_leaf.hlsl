@@ -40,6 +40,10 @@ struct Config
     uint pathseg_offset;
     uint drawtag_offset;
     uint drawdata_offset;
+    uint damage_x0;
+    uint damage_y0;
+    uint damage_x1;
+    uint damage_y1;
 };
 
 static const uint3 gl_WorkGroupSize = uint3(256u, 1u, 1u);
@@ -162,7 +166,7 @@ void comp_main()
         uint dd = drawdata_base + (m.scene_offset >> uint(2));
         uint di = drawinfo_base + (m.info_offset >> uint(2));
         tag_word = _103.Load(((drawtag_base + ix) + i_2) * 4 + 0);
-        if (((((tag_word == 68u) || (tag_word == 276u)) || (tag_word == 732u)) || (tag_word == 72u)) || (tag_word == 5u))
+        if (((((((tag_word == 68u) || (tag_word == 276u)) || (tag_word == 732u)) || (tag_word == 72u)) || (tag_word == 74u)) || (tag_word == 412u)) || (tag_word == 5u))
         {
             uint bbox_offset = (_93.Load(40) >> uint(2)) + (6u * m.path_ix);
             float bbox_l = float(_285.Load(bbox_offset * 4 + 8)) - 32768.0f;
@@ -172,12 +176,13 @@ void comp_main()
             float4 bbox = float4(bbox_l, bbox_t, bbox_r, bbox_b);
             float linewidth = asfloat(_285.Load((bbox_offset + 4u) * 4 + 8));
             uint fill_mode = uint(linewidth >= 0.0f);
-            if (((linewidth >= 0.0f) || (tag_word == 276u)) || (tag_word == 732u))
+            bool need_translate = ((tag_word == 276u) || (tag_word == 732u)) || (tag_word == 412u);
+            if ((linewidth >= 0.0f) || need_translate)
             {
                 uint trans_ix = _285.Load((bbox_offset + 5u) * 4 + 8);
                 uint t = (_93.Load(36) >> uint(2)) + (6u * trans_ix);
                 mat = asfloat(uint4(_285.Load(t * 4 + 8), _285.Load((t + 1u) * 4 + 8), _285.Load((t + 2u) * 4 + 8), _285.Load((t + 3u) * 4 + 8)));
-                if ((tag_word == 276u) || (tag_word == 732u))
+                if (need_translate)
                 {
                     translate = asfloat(uint2(_285.Load((t + 4u) * 4 + 8), _285.Load((t + 5u) * 4 + 8)));
                 }
@@ -190,6 +195,7 @@ void comp_main()
             {
                 case 68u:
                 case 72u:
+                case 74u:
                 {
                     _285.Store(di * 4 + 8, asuint(linewidth));
                     break;
@@ -240,6 +246,25 @@ void comp_main()
                     _285.Store((di + 10u) * 4 + 8, asuint(roff));
                     break;
                 }
+                case 412u:
+                {
+                    float2 rect_min = asfloat(uint2(_103.Load((dd + 1u) * 4 + 0), _103.Load((dd + 2u) * 4 + 0)));
+                    float2 rect_max = asfloat(uint2(_103.Load((dd + 3u) * 4 + 0), _103.Load((dd + 4u) * 4 + 0)));
+                    float2 corner0 = ((mat.xy * rect_min.x) + (mat.zw * rect_min.y)) + translate;
+                    float2 corner1 = ((mat.xy * rect_max.x) + (mat.zw * rect_min.y)) + translate;
+                    float2 corner2 = ((mat.xy * rect_min.x) + (mat.zw * rect_max.y)) + translate;
+                    float2 corner3 = ((mat.xy * rect_max.x) + (mat.zw * rect_max.y)) + translate;
+                    rect_min = min(min(corner0, corner1), min(corner2, corner3));
+                    rect_max = max(max(corner0, corner1), max(corner2, corner3));
+                    float blur_scale = sqrt(abs((mat.x * mat.w) - (mat.y * mat.z)));
+                    _285.Store(di * 4 + 8, asuint(rect_min.x));
+                    _285.Store((di + 1u) * 4 + 8, asuint(rect_min.y));
+                    _285.Store((di + 2u) * 4 + 8, asuint(rect_max.x));
+                    _285.Store((di + 3u) * 4 + 8, asuint(rect_max.y));
+                    _285.Store((di + 4u) * 4 + 8, asuint(asfloat(_103.Load((dd + 5u) * 4 + 0)) * blur_scale));
+                    _285.Store((di + 5u) * 4 + 8, asuint(asfloat(_103.Load((dd + 6u) * 4 + 0)) * blur_scale));
+                    break;
+                }
                 case 5u:
                 {
                     break;
@@ -0,0 +1,129 @@
+struct FilterParams
+{
+    uint kind;
+    float std_dev;
+    float2 offset;
+    uint rgba_color;
+    uint atlas_y;
+    float4 _matrix[5];
+};
+
+static const uint3 gl_WorkGroupSize = uint3(16u, 16u, 1u);
+
+ByteAddressBuffer _27 : register(t0, space0);
+RWTexture2D<unorm float4> source : register(u3, space0);
+RWTexture2D<unorm float4> scratch : register(u1, space0);
+RWTexture2D<unorm float4> atlas : register(u2, space0);
+
+static uint3 gl_GlobalInvocationID;
+struct SPIRV_Cross_Input
+{
+    uint3 gl_GlobalInvocationID : SV_DispatchThreadID;
+};
+
+uint2 spvImageSize(RWTexture2D<unorm float4> Tex, out uint Param)
+{
+    uint2 ret;
+    Tex.GetDimensions(ret.x, ret.y);
+    Param = 0u;
+    return ret;
+}
+
+float4 load_input(inout int2 xy, int2 size)
+{
+    if (_27.Load(0) == 1u)
+    {
+        xy -= int2(round(asfloat(_27.Load2(8))));
+    }
+    bool _49 = any(bool2(xy.x < int2(0, 0).x, xy.y < int2(0, 0).y));
+    bool _57;
+    if (!_49)
+    {
+        _57 = any(bool2(xy.x >= size.x, xy.y >= size.y));
+    }
+    else
+    {
+        _57 = _49;
+    }
+    if (_57)
+    {
+        return 0.0f.xxxx;
+    }
+    return source[xy];
+}
+
+float4 blur(int2 xy, int2 size)
+{
+    int2 dir = int2(1, 0);
+    float sigma = asfloat(_27.Load(4));
+    int radius = min(int(ceil(3.0f * sigma)), 96);
+    int2 param = xy;
+    int2 param_1 = size;
+    float4 _94 = load_input(param, param_1);
+    float4 sum = _94;
+    if (radius == 0)
+    {
+        return sum;
+    }
+    float k = (-0.5f) / (sigma * sigma);
+    float weight_sum = 1.0f;
+    for (int i = 1; i <= radius; i++)
+    {
+        float w = exp(k * float(i * i));
+        int2 param_2 = xy + (i.xx * dir);
+        int2 param_3 = size;
+        float4 _136 = load_input(param_2, param_3);
+        int2 param_4 = xy - (i.xx * dir);
+        int2 param_5 = size;
+        float4 _146 = load_input(param_4, param_5);
+        sum += ((_136 + _146) * w);
+        weight_sum += (2.0f * w);
+    }
+    return sum / weight_sum.xxxx;
+}
+
+void comp_main()
+{
+    int2 xy = int2(gl_GlobalInvocationID.xy);
+    uint _174_dummy_parameter;
+    int2 size = int2(spvImageSize(source, _174_dummy_parameter));
+    bool _180 = xy.x >= size.x;
+    bool _189;
+    if (!_180)
+    {
+        _189 = xy.y >= size.y;
+    }
+    else
+    {
+        _189 = _180;
+    }
+    if (_189)
+    {
+        return;
+    }
+    float4 rgba;
+    switch (_27.Load(0))
+    {
+        case 0u:
+        case 1u:
+        {
+            int2 param = xy;
+            int2 param_1 = size;
+            rgba = blur(param, param_1);
+            break;
+        }
+        default:
+        {
+            rgba = source[xy];
+            break;
+        }
+    }
+    scratch[xy] = rgba;
+}
+
+[numthreads(16, 16, 1)]
+void main(SPIRV_Cross_Input stage_input)
+{
+    gl_GlobalInvocationID = stage_input.gl_GlobalInvocationID;
+    comp_main();
+}
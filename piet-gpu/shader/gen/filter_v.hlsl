@@ -0,0 +1,148 @@
+struct FilterParams
+{
+    uint kind;
+    float std_dev;
+    float2 offset;
+    uint rgba_color;
+    uint atlas_y;
+    float4 _matrix[5];
+};
+
+static const uint3 gl_WorkGroupSize = uint3(16u, 16u, 1u);
+
+ByteAddressBuffer _60 : register(t0, space0);
+RWTexture2D<unorm float4> scratch : register(u1, space0);
+RWTexture2D<unorm float4> source : register(u3, space0);
+RWTexture2D<unorm float4> atlas : register(u2, space0);
+
+static uint3 gl_GlobalInvocationID;
+struct SPIRV_Cross_Input
+{
+    uint3 gl_GlobalInvocationID : SV_DispatchThreadID;
+};
+
+uint2 spvImageSize(RWTexture2D<unorm float4> Tex, out uint Param)
+{
+    uint2 ret;
+    Tex.GetDimensions(ret.x, ret.y);
+    Param = 0u;
+    return ret;
+}
+
+uint spvPackUnorm4x8(float4 value)
+{
+    uint4 Packed = uint4(round(saturate(value) * 255.0));
+    return Packed.x | (Packed.y << 8) | (Packed.z << 16) | (Packed.w << 24);
+}
+
+float4 spvUnpackUnorm4x8(uint value)
+{
+    uint4 Packed = uint4(value & 0xff, (value >> 8) & 0xff, (value >> 16) & 0xff, value >> 24);
+    return float4(Packed) / 255.0;
+}
+
+float4 load_input(int2 xy, int2 size)
+{
+    bool _26 = any(bool2(xy.x < int2(0, 0).x, xy.y < int2(0, 0).y));
+    bool _34;
+    if (!_26)
+    {
+        _34 = any(bool2(xy.x >= size.x, xy.y >= size.y));
+    }
+    else
+    {
+        _34 = _26;
+    }
+    if (_34)
+    {
+        return 0.0f.xxxx;
+    }
+    return scratch[xy];
+}
+
+float4 blur(int2 xy, int2 size)
+{
+    int2 dir = int2(0, 1);
+    float sigma = asfloat(_60.Load(4));
+    int radius = min(int(ceil(3.0f * sigma)), 96);
+    int2 param = xy;
+    int2 param_1 = size;
+    float4 sum = load_input(param, param_1);
+    if (radius == 0)
+    {
+        return sum;
+    }
+    float k = (-0.5f) / (sigma * sigma);
+    float weight_sum = 1.0f;
+    for (int i = 1; i <= radius; i++)
+    {
+        float w = exp(k * float(i * i));
+        int2 param_2 = xy + (i.xx * dir);
+        int2 param_3 = size;
+        int2 param_4 = xy - (i.xx * dir);
+        int2 param_5 = size;
+        sum += ((load_input(param_2, param_3) + load_input(param_4, param_5)) * w);
+        weight_sum += (2.0f * w);
+    }
+    return sum / weight_sum.xxxx;
+}
+
+void comp_main()
+{
+    int2 xy = int2(gl_GlobalInvocationID.xy);
+    uint _160_dummy_parameter;
+    int2 size = int2(spvImageSize(source, _160_dummy_parameter));
+    bool _166 = xy.x >= size.x;
+    bool _176;
+    if (!_166)
+    {
+        _176 = xy.y >= size.y;
+    }
+    else
+    {
+        _176 = _166;
+    }
+    if (_176)
+    {
+        return;
+    }
+    float4 rgba;
+    switch (_60.Load(0))
+    {
+        case 0u:
+        {
+            int2 param = xy;
+            int2 param_1 = size;
+            rgba = blur(param, param_1);
+            break;
+        }
+        case 1u:
+        {
+            int2 param_2 = xy;
+            int2 param_3 = size;
+            float shadow_alpha = blur(param_2, param_3).w;
+            float4 shadow = spvUnpackUnorm4x8(_60.Load(16)).wzyx * shadow_alpha;
+            float4 fg = source[xy];
+            rgba = fg + (shadow * (1.0f - fg.w));
+            break;
+        }
+        case 2u:
+        {
+            float4 c = scratch[xy];
+            float3 _234 = c.xyz * (1.0f / max(c.w, 9.9999999747524270787835121154785e-07f));
+            c = float4(_234.x, _234.y, _234.z, c.w);
+            float4x4 m = float4x4(float4(asfloat(_60.Load4(32))), float4(asfloat(_60.Load4(48))), float4(asfloat(_60.Load4(64))), float4(asfloat(_60.Load4(80))));
+            c = clamp(mul(c, m) + asfloat(_60.Load4(96)), 0.0f.xxxx, 1.0f.xxxx);
+            rgba = float4(c.xyz * c.w, c.w);
+            break;
+        }
+    }
+    atlas[xy + int2(0, int(_60.Load(20)))] = rgba;
+}
+
+[numthreads(16, 16, 1)]
+void main(SPIRV_Cross_Input stage_input)
+{
+    gl_GlobalInvocationID = stage_input.gl_GlobalInvocationID;
+    comp_main();
+}
@@ -104,6 +104,19 @@ struct CmdJump
     uint new_ref;
 };
 
+struct CmdBlurRectRef
+{
+    uint offset;
+};
+
+struct CmdBlurRect
+{
+    uint rgba_color;
+    float4 rect;
+    float radius;
+    float std_dev;
+};
+
 struct CmdRef
 {
     uint offset;
@@ -157,12 +170,16 @@ struct Config
     uint pathseg_offset;
     uint drawtag_offset;
     uint drawdata_offset;
+    uint damage_x0;
+    uint damage_y0;
+    uint damage_x1;
+    uint damage_y1;
 };
 
 static const uint3 gl_WorkGroupSize = uint3(8u, 4u, 1u);
 
-RWByteAddressBuffer _297 : register(u0, space0);
-ByteAddressBuffer _1681 : register(t1, space0);
+RWByteAddressBuffer _330 : register(u0, space0);
+ByteAddressBuffer _2263 : register(t1, space0);
 RWTexture2D<unorm float4> image_atlas : register(u3, space0);
 RWTexture2D<unorm float4> gradients : register(u4, space0);
 RWTexture2D<unorm float4> image : register(u2, space0);
@@ -189,8 +206,8 @@ float4 spvUnpackUnorm4x8(uint value)
 
 Alloc slice_mem(Alloc a, uint offset, uint size)
 {
-    Alloc _310 = { a.offset + offset };
-    return _310;
+    Alloc _343 = { a.offset + offset };
+    return _343;
 }
 
 bool touch_mem(Alloc alloc, uint offset)
@@ -206,7 +223,7 @@ uint read_mem(Alloc alloc, uint offset)
     {
         return 0u;
     }
-    uint v = _297.Load(offset * 4 + 8);
+    uint v = _330.Load(offset * 4 + 8);
     return v;
 }
 
@@ -215,8 +232,8 @@ CmdTag Cmd_tag(Alloc a, CmdRef ref)
     Alloc param = a;
     uint param_1 = ref.offset >> uint(2);
     uint tag_and_flags = read_mem(param, param_1);
-    CmdTag _669 = { tag_and_flags & 65535u, tag_and_flags >> uint(16) };
-    return _669;
+    CmdTag _775 = { tag_and_flags & 65535u, tag_and_flags >> uint(16) };
+    return _775;
 }
 
 CmdStroke CmdStroke_read(Alloc a, CmdStrokeRef ref)
@@ -236,9 +253,9 @@ CmdStroke CmdStroke_read(Alloc a, CmdStrokeRef ref)
 
 CmdStroke Cmd_Stroke_read(Alloc a, CmdRef ref)
 {
-    CmdStrokeRef _685 = { ref.offset + 4u };
+    CmdStrokeRef _791 = { ref.offset + 4u };
     Alloc param = a;
-    CmdStrokeRef param_1 = _685;
+    CmdStrokeRef param_1 = _791;
     return CmdStroke_read(param, param_1);
 }
 
@@ -274,8 +291,8 @@ TileSeg TileSeg_read(Alloc a, TileSegRef ref)
     s.origin = float2(asfloat(raw0), asfloat(raw1));
     s._vector = float2(asfloat(raw2), asfloat(raw3));
     s.y_edge = asfloat(raw4);
-    TileSegRef _826 = { raw5 };
-    s.next = _826;
+    TileSegRef _942 = { raw5 };
+    s.next = _942;
     return s;
 }
 
@@ -301,12 +318,64 @@ CmdFill CmdFill_read(Alloc a, CmdFillRef ref)
 
 CmdFill Cmd_Fill_read(Alloc a, CmdRef ref)
 {
-    CmdFillRef _675 = { ref.offset + 4u };
+    CmdFillRef _781 = { ref.offset + 4u };
     Alloc param = a;
-    CmdFillRef param_1 = _675;
+    CmdFillRef param_1 = _781;
     return CmdFill_read(param, param_1);
 }
 
+void fill_area(out float spvReturnValue[8], float2 xy, CmdFill fill, float x_offset, bool mem_ok)
+{
+    float area[8];
+    float2 sample_xy[8];
+    for (uint k = 0u; k < 8u; k++)
+    {
+        area[k] = float(fill.backdrop);
+        uint param = k;
+        float tile_x = float(gl_LocalInvocationID.x + chunk_offset(param).x);
+        float offset = clamp(x_offset, -tile_x, 15.0f - tile_x);
+        uint param_1 = k;
+        sample_xy[k] = (xy + float2(chunk_offset(param_1))) + float2(offset, 0.0f);
+    }
+    TileSegRef _1865 = { fill.tile_ref };
+    TileSegRef tile_seg_ref = _1865;
+    do
+    {
+        uint param_2 = tile_seg_ref.offset;
+        uint param_3 = 24u;
+        bool param_4 = mem_ok;
+        Alloc param_5 = new_alloc(param_2, param_3, param_4);
+        TileSegRef param_6 = tile_seg_ref;
+        TileSeg seg = TileSeg_read(param_5, param_6);
+        for (uint k_1 = 0u; k_1 < 8u; k_1++)
+        {
+            float2 my_xy = sample_xy[k_1];
+            float2 start = seg.origin - my_xy;
+            float2 end = start + seg._vector;
+            float2 window = clamp(float2(start.y, end.y), 0.0f.xx, 1.0f.xx);
+            if (window.x != window.y)
+            {
+                float2 t = (window - start.y.xx) / seg._vector.y.xx;
+                float2 xs = float2(lerp(start.x, end.x, t.x), lerp(start.x, end.x, t.y));
+                float xmin = min(min(xs.x, xs.y), 1.0f) - 9.9999999747524270787835121154785e-07f;
+                float xmax = max(xs.x, xs.y);
+                float b = min(xmax, 1.0f);
+                float c = max(b, 0.0f);
+                float d = max(xmin, 0.0f);
+                float a = ((b + (0.5f * ((d * d) - (c * c)))) - xmin) / (xmax - xmin);
+                area[k_1] += (a * (window.x - window.y));
+            }
+            area[k_1] += (sign(seg._vector.x) * clamp((my_xy.y - seg.y_edge) + 1.0f, 0.0f, 1.0f));
+        }
+        tile_seg_ref = seg.next;
+    } while (tile_seg_ref.offset != 0u);
+    for (uint k_2 = 0u; k_2 < 8u; k_2++)
+    {
+        area[k_2] = min(abs(area[k_2]), 1.0f);
+    }
+    spvReturnValue = area;
+}
+
 CmdAlpha CmdAlpha_read(Alloc a, CmdAlphaRef ref)
 {
     uint ix = ref.offset >> uint(2);
@@ -320,9 +389,9 @@ CmdAlpha CmdAlpha_read(Alloc a, CmdAlphaRef ref)
 
 CmdAlpha Cmd_Alpha_read(Alloc a, CmdRef ref)
 {
-    CmdAlphaRef _695 = { ref.offset + 4u };
+    CmdAlphaRef _801 = { ref.offset + 4u };
     Alloc param = a;
-    CmdAlphaRef param_1 = _695;
+    CmdAlphaRef param_1 = _801;
     return CmdAlpha_read(param, param_1);
 }
 
@@ -339,9 +408,9 @@ CmdColor CmdColor_read(Alloc a, CmdColorRef ref)
 
 CmdColor Cmd_Color_read(Alloc a, CmdRef ref)
 {
-    CmdColorRef _705 = { ref.offset + 4u };
+    CmdColorRef _811 = { ref.offset + 4u };
     Alloc param = a;
-    CmdColorRef param_1 = _705;
+    CmdColorRef param_1 = _811;
     return CmdColor_read(param, param_1);
 }
 
@@ -382,9 +451,9 @@ CmdLinGrad CmdLinGrad_read(Alloc a, CmdLinGradRef ref)
 
 CmdLinGrad Cmd_LinGrad_read(Alloc a, CmdRef ref)
 {
-    CmdLinGradRef _715 = { ref.offset + 4u };
+    CmdLinGradRef _821 = { ref.offset + 4u };
     Alloc param = a;
-    CmdLinGradRef param_1 = _715;
+    CmdLinGradRef param_1 = _821;
     return CmdLinGrad_read(param, param_1);
 }
 
@@ -436,9 +505,9 @@ CmdRadGrad CmdRadGrad_read(Alloc a, CmdRadGradRef ref)
 
 CmdRadGrad Cmd_RadGrad_read(Alloc a, CmdRef ref)
 {
-    CmdRadGradRef _725 = { ref.offset + 4u };
+    CmdRadGradRef _831 = { ref.offset + 4u };
     Alloc param = a;
-    CmdRadGradRef param_1 = _725;
+    CmdRadGradRef param_1 = _831;
     return CmdRadGrad_read(param, param_1);
 }
 
@@ -459,9 +528,9 @@ CmdImage CmdImage_read(Alloc a, CmdImageRef ref)
 
 CmdImage Cmd_Image_read(Alloc a, CmdRef ref)
 {
-    CmdImageRef _735 = { ref.offset + 4u };
+    CmdImageRef _841 = { ref.offset + 4u };
     Alloc param = a;
-    CmdImageRef param_1 = _735;
+    CmdImageRef param_1 = _841;
     return CmdImage_read(param, param_1);
 }
 
@@ -474,15 +543,91 @@ void fillImage(out float4 spvReturnValue[8], uint2 xy, CmdImage cmd_img)
         int2 uv = int2(xy + chunk_offset(param)) + cmd_img.offset;
         float4 fg_rgba = image_atlas[uv];
         float3 param_1 = fg_rgba.xyz;
-        float3 _1653 = fromsRGB(param_1);
-        fg_rgba.x = _1653.x;
-        fg_rgba.y = _1653.y;
-        fg_rgba.z = _1653.z;
+        float3 _1797 = fromsRGB(param_1);
+        fg_rgba = float4(_1797.x, _1797.y, _1797.z, fg_rgba.w);
         rgba[i] = fg_rgba;
     }
     spvReturnValue = rgba;
 }
 
+CmdBlurRect CmdBlurRect_read(Alloc a, CmdBlurRectRef ref)
+{
+    uint ix = ref.offset >> uint(2);
+    Alloc param = a;
+    uint param_1 = ix + 0u;
+    uint raw0 = read_mem(param, param_1);
+    Alloc param_2 = a;
+    uint param_3 = ix + 1u;
+    uint raw1 = read_mem(param_2, param_3);
+    Alloc param_4 = a;
+    uint param_5 = ix + 2u;
+    uint raw2 = read_mem(param_4, param_5);
+    Alloc param_6 = a;
+    uint param_7 = ix + 3u;
+    uint raw3 = read_mem(param_6, param_7);
+    Alloc param_8 = a;
+    uint param_9 = ix + 4u;
+    uint raw4 = read_mem(param_8, param_9);
+    Alloc param_10 = a;
+    uint param_11 = ix + 5u;
+    uint raw5 = read_mem(param_10, param_11);
+    Alloc param_12 = a;
+    uint param_13 = ix + 6u;
+    uint raw6 = read_mem(param_12, param_13);
+    CmdBlurRect s;
+    s.rgba_color = raw0;
+    s.rect = float4(asfloat(raw1), asfloat(raw2), asfloat(raw3), asfloat(raw4));
+    s.radius = asfloat(raw5);
+    s.std_dev = asfloat(raw6);
+    return s;
+}
+
+CmdBlurRect Cmd_BlurRect_read(Alloc a, CmdRef ref)
+{
+    CmdBlurRectRef _871 = { ref.offset + 4u };
+    Alloc param = a;
+    CmdBlurRectRef param_1 = _871;
+    return CmdBlurRect_read(param, param_1);
+}
+
+float erf7(inout float x)
+{
+    x *= 1.12837922573089599609375f;
+    float xx = x * x;
+    x += ((0.24295000731945037841796875f + ((0.0339500010013580322265625f + (0.010400000028312206268310546875f * xx)) * xx)) * (x * xx));
+    return x / sqrt(1.0f + (x * x));
+}
+
+float blur_rect_coverage(float2 p, CmdBlurRect blur)
+{
+    float2 size = blur.rect.zw - blur.rect.xy;
+    float2 center = (blur.rect.xy + blur.rect.zw) * 0.5f;
+    float std_dev = max(blur.std_dev, 0.001000000047497451305389404296875f);
+    float s_inv = 1.0f / (std_dev * 1.41421353816986083984375f);
+    float min_edge = min(size.x, size.y);
+    float rmax = 0.5f * min_edge;
+    float radius = min(blur.radius, rmax);
+    float r0 = min(sqrt((radius * radius) + ((1.3224999904632568359375f * std_dev) * std_dev)), rmax);
+    float r1 = min(sqrt((radius * radius) + ((4.0f * std_dev) * std_dev)), rmax);
+    float exponent = (2.0f * r1) / max(r0, 9.9999999747524270787835121154785e-07f);
+    float delta = (1.25f * std_dev) * (exp(-pow((0.5f * s_inv) * size.x, 2.0f)) - exp(-pow((0.5f * s_inv) * size.y, 2.0f)));
+    float w = size.x + min(delta, 0.0f);
+    float h = size.y - max(delta, 0.0f);
+    float param = (s_inv * 0.5f) * (max(w, h) - (0.5f * radius));
+    float _2191 = erf7(param);
+    float scale = 0.5f * _2191;
+    float2 pos = abs(p - center) - float2((0.5f * w) - r0, (0.5f * h) - r0);
+    float2 pos_pos = max(pos, 0.0f.xx);
+    float d_pos = pow(pow(pos_pos.x, exponent) + pow(pos_pos.y, exponent), 1.0f / exponent);
+    float d_neg = min(max(pos.x, pos.y), 0.0f);
+    float d = (d_pos + d_neg) - r0;
+    float param_1 = s_inv * (min_edge + d);
+    float _2245 = erf7(param_1);
+    float param_2 = s_inv * d;
+    float _2250 = erf7(param_2);
+    return scale * (_2245 - _2250);
+}
+
 float3 tosRGB(float3 rgb)
 {
     return rgb;
@@ -508,9 +653,9 @@ CmdEndClip CmdEndClip_read(Alloc a, CmdEndClipRef ref)
 
 CmdEndClip Cmd_EndClip_read(Alloc a, CmdRef ref)
 {
-    CmdEndClipRef _745 = { ref.offset + 4u };
+    CmdEndClipRef _851 = { ref.offset + 4u };
     Alloc param = a;
-    CmdEndClipRef param_1 = _745;
+    CmdEndClipRef param_1 = _851;
     return CmdEndClip_read(param, param_1);
 }
 
@@ -523,10 +668,10 @@ float3 hard_light(float3 cb, float3 cs)
 {
     float3 param = cb;
     float3 param_1 = (cs * 2.0f) - 1.0f.xxx;
-    float3 _889 = screen(param, param_1);
-    float3 _893 = (cb * 2.0f) * cs;
-    bool3 _898 = bool3(cs.x <= 0.5f.xxx.x, cs.y <= 0.5f.xxx.y, cs.z <= 0.5f.xxx.z);
-    return float3(_898.x ? _893.x : _889.x, _898.y ? _893.y : _889.y, _898.z ? _893.z : _889.z);
+    float3 _1005 = screen(param, param_1);
+    float3 _1009 = (cb * 2.0f) * cs;
+    bool3 _1014 = bool3(cs.x <= 0.5f.xxx.x, cs.y <= 0.5f.xxx.y, cs.z <= 0.5f.xxx.z);
+    return float3(_1014.x ? _1009.x : _1005.x, _1014.y ? _1009.y : _1005.y, _1014.z ? _1009.z : _1005.z);
 }
 
 float color_dodge(float cb, float cs)
@@ -569,14 +714,14 @@ float color_burn(float cb, float cs)
 
 float3 soft_light(float3 cb, float3 cs)
 {
-    float3 _904 = sqrt(cb);
-    float3 _917 = ((((cb * 16.0f) - 12.0f.xxx) * cb) + 4.0f.xxx) * cb;
-    bool3 _921 = bool3(cb.x <= 0.25f.xxx.x, cb.y <= 0.25f.xxx.y, cb.z <= 0.25f.xxx.z);
-    float3 d = float3(_921.x ? _917.x : _904.x, _921.y ? _917.y : _904.y, _921.z ? _917.z : _904.z);
-    float3 _932 = cb + (((cs * 2.0f) - 1.0f.xxx) * (d - cb));
-    float3 _942 = cb - (((1.0f.xxx - (cs * 2.0f)) * cb) * (1.0f.xxx - cb));
-    bool3 _944 = bool3(cs.x <= 0.5f.xxx.x, cs.y <= 0.5f.xxx.y, cs.z <= 0.5f.xxx.z);
-    return float3(_944.x ? _942.x : _932.x, _944.y ? _942.y : _932.y, _944.z ? _942.z : _932.z);
+    float3 _1020 = sqrt(cb);
+    float3 _1033 = ((((cb * 16.0f) - 12.0f.xxx) * cb) + 4.0f.xxx) * cb;
+    bool3 _1037 = bool3(cb.x <= 0.25f.xxx.x, cb.y <= 0.25f.xxx.y, cb.z <= 0.25f.xxx.z);
+    float3 d = float3(_1037.x ? _1033.x : _1020.x, _1037.y ? _1033.y : _1020.y, _1037.z ? _1033.z : _1020.z);
+    float3 _1048 = cb + (((cs * 2.0f) - 1.0f.xxx) * (d - cb));
+    float3 _1058 = cb - (((1.0f.xxx - (cs * 2.0f)) * cb) * (1.0f.xxx - cb));
+    bool3 _1060 = bool3(cs.x <= 0.5f.xxx.x, cs.y <= 0.5f.xxx.y, cs.z <= 0.5f.xxx.z);
+    return float3(_1060.x ? _1058.x : _1048.x, _1060.y ? _1058.y : _1048.y, _1060.z ? _1058.z : _1048.z);
 }
 
 float sat(float3 c)
@@ -709,8 +854,8 @@ float3 set_lum(float3 c, float l)
 {
     float3 param = c;
     float3 param_1 = c + (l - lum(param)).xxx;
-    float3 _1048 = clip_color(param_1);
-    return _1048;
+    float3 _1164 = clip_color(param_1);
+    return _1164;
 }
 
 float3 mix_blend(float3 cb, float3 cs, uint mode)
@@ -798,9 +943,9 @@ float3 mix_blend(float3 cb, float3 cs, uint mode)
             float3 param_20 = cb;
             float3 param_21 = cs;
             float param_22 = sat(param_20);
-            float3 _1340 = set_sat(param_21, param_22);
+            float3 _1456 = set_sat(param_21, param_22);
             float3 param_23 = cb;
-            float3 param_24 = _1340;
+            float3 param_24 = _1456;
             float param_25 = lum(param_23);
             b = set_lum(param_24, param_25);
             break;
@@ -810,9 +955,9 @@ float3 mix_blend(float3 cb, float3 cs, uint mode)
             float3 param_26 = cs;
             float3 param_27 = cb;
             float param_28 = sat(param_26);
-            float3 _1354 = set_sat(param_27, param_28);
+            float3 _1470 = set_sat(param_27, param_28);
             float3 param_29 = cb;
-            float3 param_30 = _1354;
+            float3 param_30 = _1470;
             float param_31 = lum(param_29);
             b = set_lum(param_30, param_31);
             break;
@@ -941,6 +1086,17 @@ float4 mix_blend_compose(float4 backdrop, float4 src, uint mode)
     {
         return (backdrop * (1.0f - src.w)) + src;
     }
+    if ((mode >> uint(8)) == 129u)
+    {
+        return src * backdrop.w;
+    }
+    else
+    {
+        if ((mode >> uint(8)) == 130u)
+        {
+            return src * dot(backdrop.xyz, float3(0.2125000059604644775390625f, 0.7153999805450439453125f, 0.07209999859333038330078125f));
+        }
+    }
     float inv_src_a = 1.0f / (src.w + 1.0000000036274937255387218471014e-15f);
     float3 cs = src.xyz * inv_src_a;
     float inv_backdrop_a = 1.0f / (backdrop.w + 1.0000000036274937255387218471014e-15f);
@@ -981,38 +1137,42 @@ CmdJump CmdJump_read(Alloc a, CmdJumpRef ref)
 
 CmdJump Cmd_Jump_read(Alloc a, CmdRef ref)
 {
-    CmdJumpRef _755 = { ref.offset + 4u };
+    CmdJumpRef _861 = { ref.offset + 4u };
     Alloc param = a;
-    CmdJumpRef param_1 = _755;
+    CmdJumpRef param_1 = _861;
     return CmdJump_read(param, param_1);
 }
 
 void comp_main()
 {
-    uint tile_ix = (gl_WorkGroupID.y * _1681.Load(8)) + gl_WorkGroupID.x;
-    Alloc _1696;
-    _1696.offset = _1681.Load(24);
+    uint2 tile_xy = gl_WorkGroupID.xy + uint2(_2263.Load(108), _2263.Load(112));
+    uint tile_ix = (tile_xy.y * _2263.Load(8)) + tile_xy.x;
+    Alloc _2289;
+    _2289.offset = _2263.Load(24);
     Alloc param;
-    param.offset = _1696.offset;
+    param.offset = _2289.offset;
     uint param_1 = tile_ix * 1024u;
     uint param_2 = 1024u;
     Alloc cmd_alloc = slice_mem(param, param_1, param_2);
-    CmdRef _1705 = { cmd_alloc.offset };
-    CmdRef cmd_ref = _1705;
-    uint blend_offset = _297.Load((cmd_ref.offset >> uint(2)) * 4 + 8);
+    CmdRef _2298 = { cmd_alloc.offset };
+    CmdRef cmd_ref = _2298;
+    uint blend_offset = _330.Load((cmd_ref.offset >> uint(2)) * 4 + 8);
     cmd_ref.offset += 4u;
-    uint2 xy_uint = uint2(gl_LocalInvocationID.x + (16u * gl_WorkGroupID.x), gl_LocalInvocationID.y + (16u * gl_WorkGroupID.y));
+    uint2 xy_uint = uint2(gl_LocalInvocationID.x + (16u * tile_xy.x), gl_LocalInvocationID.y + (16u * tile_xy.y));
     float2 xy = float2(xy_uint);
     float4 rgba[8];
     for (uint i = 0u; i < 8u; i++)
     {
         rgba[i] = 0.0f.xxxx;
     }
+    bool lcd = false;
     uint clip_depth = 0u;
-    bool mem_ok = _297.Load(4) == 0u;
+    bool mem_ok = _330.Load(4) == 0u;
     float df[8];
-    TileSegRef tile_seg_ref;
     float area[8];
+    float area_r[8];
+    float area_b[8];
+    float4 fg;
     uint blend_stack[4][8];
     uint base_ix_1;
     uint bg_rgba;
@@ -1020,7 +1180,8 @@ void comp_main()
     {
         Alloc param_3 = cmd_alloc;
         CmdRef param_4 = cmd_ref;
-        uint tag = Cmd_tag(param_3, param_4).tag;
+        CmdTag cmd_tag = Cmd_tag(param_3, param_4);
+        uint tag = cmd_tag.tag;
         if (tag == 0u)
         {
             break;
@@ -1036,8 +1197,8 @@ void comp_main()
                 {
                     df[k] = 1000000000.0f;
                 }
-                TileSegRef _1810 = { stroke.tile_ref };
-                tile_seg_ref = _1810;
+                TileSegRef _2406 = { stroke.tile_ref };
+                TileSegRef tile_seg_ref = _2406;
                 do
                 {
                     uint param_7 = tile_seg_ref.offset;
@@ -1061,6 +1222,7 @@ void comp_main()
                 {
                     area[k_2] = clamp((stroke.half_width + 0.5f) - df[k_2], 0.0f, 1.0f);
                 }
+                lcd = false;
                 cmd_ref.offset += 12u;
                 break;
             }
@@ -1069,104 +1231,117 @@ void comp_main()
                 Alloc param_13 = cmd_alloc;
                 CmdRef param_14 = cmd_ref;
                 CmdFill fill = Cmd_Fill_read(param_13, param_14);
-                for (uint k_3 = 0u; k_3 < 8u; k_3++)
+                float2 param_15 = xy;
+                CmdFill param_16 = fill;
+                float param_17 = 0.0f;
+                bool param_18 = mem_ok;
+                float _2515[8];
+                fill_area(_2515, param_15, param_16, param_17, param_18);
+                area = _2515;
+                lcd = (cmd_tag.flags & 1u) != 0u;
+                if (lcd)
                 {
-                    area[k_3] = float(fill.backdrop);
+                    float r_offset = ((cmd_tag.flags & 2u) != 0u) ? 0.3333333432674407958984375f : (-0.3333333432674407958984375f);
+                    float2 param_19 = xy;
+                    CmdFill param_20 = fill;
+                    float param_21 = r_offset;
+                    bool param_22 = mem_ok;
+                    float _2540[8];
+                    fill_area(_2540, param_19, param_20, param_21, param_22);
+                    area_r = _2540;
+                    float2 param_23 = xy;
+                    CmdFill param_24 = fill;
+                    float param_25 = -r_offset;
+                    bool param_26 = mem_ok;
+                    float _2551[8];
+                    fill_area(_2551, param_23, param_24, param_25, param_26);
+                    area_b = _2551;
                 }
-                TileSegRef _1930 = { fill.tile_ref };
-                tile_seg_ref = _1930;
-                do
+                if ((cmd_tag.flags & 4u) != 0u)
                 {
-                    uint param_15 = tile_seg_ref.offset;
-                    uint param_16 = 24u;
-                    bool param_17 = mem_ok;
-                    Alloc param_18 = new_alloc(param_15, param_16, param_17);
-                    TileSegRef param_19 = tile_seg_ref;
-                    TileSeg seg_1 = TileSeg_read(param_18, param_19);
-                    for (uint k_4 = 0u; k_4 < 8u; k_4++)
+                    for (uint k_3 = 0u; k_3 < 8u; k_3++)
                     {
-                        uint param_20 = k_4;
-                        float2 my_xy = xy + float2(chunk_offset(param_20));
-                        float2 start = seg_1.origin - my_xy;
-                        float2 end = start + seg_1._vector;
-                        float2 window = clamp(float2(start.y, end.y), 0.0f.xx, 1.0f.xx);
-                        if (window.x != window.y)
+                        area[k_3] = pow(area[k_3], 0.68965518474578857421875f);
+                    }
+                    if (lcd)
+                    {
+                        for (uint k_4 = 0u; k_4 < 8u; k_4++)
                         {
-                            float2 t_1 = (window - start.y.xx) / seg_1._vector.y.xx;
-                            float2 xs = float2(lerp(start.x, end.x, t_1.x), lerp(start.x, end.x, t_1.y));
-                            float xmin = min(min(xs.x, xs.y), 1.0f) - 9.9999999747524270787835121154785e-07f;
-                            float xmax = max(xs.x, xs.y);
-                            float b = min(xmax, 1.0f);
-                            float c = max(b, 0.0f);
-                            float d = max(xmin, 0.0f);
-                            float a = ((b + (0.5f * ((d * d) - (c * c)))) - xmin) / (xmax - xmin);
-                            area[k_4] += (a * (window.x - window.y));
+                            area_r[k_4] = pow(area_r[k_4], 0.68965518474578857421875f);
+                            area_b[k_4] = pow(area_b[k_4], 0.68965518474578857421875f);
                         }
-                        area[k_4] += (sign(seg_1._vector.x) * clamp((my_xy.y - seg_1.y_edge) + 1.0f, 0.0f, 1.0f));
                     }
-                    tile_seg_ref = seg_1.next;
-                } while (tile_seg_ref.offset != 0u);
-                for (uint k_5 = 0u; k_5 < 8u; k_5++)
-                {
-                    area[k_5] = min(abs(area[k_5]), 1.0f);
                 }
                 cmd_ref.offset += 12u;
                 break;
             }
             case 3u:
             {
-                for (uint k_6 = 0u; k_6 < 8u; k_6++)
+                for (uint k_5 = 0u; k_5 < 8u; k_5++)
                 {
-                    area[k_6] = 1.0f;
+                    area[k_5] = 1.0f;
                 }
+                lcd = false;
                 cmd_ref.offset += 4u;
                 break;
             }
             case 4u:
             {
-                Alloc param_21 = cmd_alloc;
-                CmdRef param_22 = cmd_ref;
-                CmdAlpha alpha = Cmd_Alpha_read(param_21, param_22);
-                for (uint k_7 = 0u; k_7 < 8u; k_7++)
+                Alloc param_27 = cmd_alloc;
+                CmdRef param_28 = cmd_ref;
+                CmdAlpha alpha = Cmd_Alpha_read(param_27, param_28);
+                for (uint k_6 = 0u; k_6 < 8u; k_6++)
                 {
-                    area[k_7] = alpha.alpha;
+                    area[k_6] *= alpha.alpha;
                 }
+                lcd = false;
                 cmd_ref.offset += 8u;
                 break;
             }
             case 5u:
             {
-                Alloc param_23 = cmd_alloc;
-                CmdRef param_24 = cmd_ref;
-                CmdColor color = Cmd_Color_read(param_23, param_24);
-                uint param_25 = color.rgba_color;
-                float4 fg = unpacksRGB(param_25);
-                for (uint k_8 = 0u; k_8 < 8u; k_8++)
+                Alloc param_29 = cmd_alloc;
+                CmdRef param_30 = cmd_ref;
+                CmdColor color = Cmd_Color_read(param_29, param_30);
+                uint param_31 = color.rgba_color;
+                fg = unpacksRGB(param_31);
+                if (lcd)
+                {
+                    for (uint k_7 = 0u; k_7 < 8u; k_7++)
+                    {
+                        float3 cov = float3(area_r[k_7], area[k_7], area_b[k_7]);
+                        float3 _2699 = (rgba[k_7].xyz * (1.0f.xxx - (cov * fg.w))) + (fg.xyz * cov);
+                        rgba[k_7] = float4(_2699.x, _2699.y, _2699.z, rgba[k_7].w);
+                        rgba[k_7].w = (rgba[k_7].w * (1.0f - (fg.w * area[k_7]))) + (fg.w * area[k_7]);
+                    }
+                }
+                else
                 {
-                    float4 fg_k = fg * area[k_8];
-                    rgba[k_8] = (rgba[k_8] * (1.0f - fg_k.w)) + fg_k;
+                    for (uint k_8 = 0u; k_8 < 8u; k_8++)
+                    {
+                        float4 fg_k = fg * area[k_8];
+                        rgba[k_8] = (rgba[k_8] * (1.0f - fg_k.w)) + fg_k;
+                    }
                 }
                 cmd_ref.offset += 8u;
                 break;
             }
             case 6u:
             {
-                Alloc param_26 = cmd_alloc;
-                CmdRef param_27 = cmd_ref;
-                CmdLinGrad lin = Cmd_LinGrad_read(param_26, param_27);
-                float d_1 = ((lin.line_x * xy.x) + (lin.line_y * xy.y)) + lin.line_c;
+                Alloc param_32 = cmd_alloc;
+                CmdRef param_33 = cmd_ref;
+                CmdLinGrad lin = Cmd_LinGrad_read(param_32, param_33);
+                float d = ((lin.line_x * xy.x) + (lin.line_y * xy.y)) + lin.line_c;
                 for (uint k_9 = 0u; k_9 < 8u; k_9++)
                 {
-                    uint param_28 = k_9;
-                    float2 chunk_xy = float2(chunk_offset(param_28));
-                    float my_d = (d_1 + (lin.line_x * chunk_xy.x)) + (lin.line_y * chunk_xy.y);
+                    uint param_34 = k_9;
+                    float2 chunk_xy = float2(chunk_offset(param_34));
+                    float my_d = (d + (lin.line_x * chunk_xy.x)) + (lin.line_y * chunk_xy.y);
                     int x = int(round(clamp(my_d, 0.0f, 1.0f) * 511.0f));
                     float4 fg_rgba = gradients[int2(x, int(lin.index))];
-                    float3 param_29 = fg_rgba.xyz;
-                    float3 _2264 = fromsRGB(param_29);
-                    fg_rgba.x = _2264.x;
-                    fg_rgba.y = _2264.y;
-                    fg_rgba.z = _2264.z;
+                    float3 param_35 = fg_rgba.xyz;
+                    float3 _2825 = fromsRGB(param_35);
+                    fg_rgba = float4(_2825.x, _2825.y, _2825.z, fg_rgba.w);
                     float4 fg_k_1 = fg_rgba * area[k_9];
                     rgba[k_9] = (rgba[k_9] * (1.0f - fg_k_1.w)) + fg_k_1;
                 }
@@ -1175,24 +1350,22 @@ void comp_main()
             }
             case 7u:
             {
-                Alloc param_30 = cmd_alloc;
-                CmdRef param_31 = cmd_ref;
-                CmdRadGrad rad = Cmd_RadGrad_read(param_30, param_31);
+                Alloc param_36 = cmd_alloc;
+                CmdRef param_37 = cmd_ref;
+                CmdRadGrad rad = Cmd_RadGrad_read(param_36, param_37);
                 for (uint k_10 = 0u; k_10 < 8u; k_10++)
                 {
-                    uint param_32 = k_10;
-                    float2 my_xy_1 = xy + float2(chunk_offset(param_32));
-                    my_xy_1 = ((rad.mat.xz * my_xy_1.x) + (rad.mat.yw * my_xy_1.y)) - rad.xlat;
-                    float ba = dot(my_xy_1, rad.c1);
-                    float ca = rad.ra * dot(my_xy_1, my_xy_1);
-                    float t_2 = (sqrt((ba * ba) + ca) - ba) - rad.roff;
-                    int x_1 = int(round(clamp(t_2, 0.0f, 1.0f) * 511.0f));
+                    uint param_38 = k_10;
+                    float2 my_xy = xy + float2(chunk_offset(param_38));
+                    my_xy = ((rad.mat.xz * my_xy.x) + (rad.mat.yw * my_xy.y)) - rad.xlat;
+                    float ba = dot(my_xy, rad.c1);
+                    float ca = rad.ra * dot(my_xy, my_xy);
+                    float t_1 = (sqrt((ba * ba) + ca) - ba) - rad.roff;
+                    int x_1 = int(round(clamp(t_1, 0.0f, 1.0f) * 511.0f));
                     float4 fg_rgba_1 = gradients[int2(x_1, int(rad.index))];
-                    float3 param_33 = fg_rgba_1.xyz;
-                    float3 _2374 = fromsRGB(param_33);
-                    fg_rgba_1.x = _2374.x;
-                    fg_rgba_1.y = _2374.y;
-                    fg_rgba_1.z = _2374.z;
+                    float3 param_39 = fg_rgba_1.xyz;
+                    float3 _2931 = fromsRGB(param_39);
+                    fg_rgba_1 = float4(_2931.x, _2931.y, _2931.z, fg_rgba_1.w);
                     float4 fg_k_2 = fg_rgba_1 * area[k_10];
                     rgba[k_10] = (rgba[k_10] * (1.0f - fg_k_2.w)) + fg_k_2;
                 }
@@ -1201,14 +1374,14 @@ void comp_main()
             }
             case 8u:
             {
-                Alloc param_34 = cmd_alloc;
-                CmdRef param_35 = cmd_ref;
-                CmdImage fill_img = Cmd_Image_read(param_34, param_35);
-                uint2 param_36 = xy_uint;
-                CmdImage param_37 = fill_img;
-                float4 _2417[8];
-                fillImage(_2417, param_36, param_37);
-                float4 img[8] = _2417;
+                Alloc param_40 = cmd_alloc;
+                CmdRef param_41 = cmd_ref;
+                CmdImage fill_img = Cmd_Image_read(param_40, param_41);
+                uint2 param_42 = xy_uint;
+                CmdImage param_43 = fill_img;
+                float4 _2970[8];
+                fillImage(_2970, param_42, param_43);
+                float4 img[8] = _2970;
                 for (uint k_11 = 0u; k_11 < 8u; k_11++)
                 {
                     float4 fg_k_3 = img[k_11] * area[k_11];
@@ -1217,27 +1390,46 @@ void comp_main()
                 cmd_ref.offset += 12u;
                 break;
             }
+            case 12u:
+            {
+                Alloc param_44 = cmd_alloc;
+                CmdRef param_45 = cmd_ref;
+                CmdBlurRect blur = Cmd_BlurRect_read(param_44, param_45);
+                uint param_46 = blur.rgba_color;
+                fg = unpacksRGB(param_46);
+                for (uint k_12 = 0u; k_12 < 8u; k_12++)
+                {
+                    uint param_47 = k_12;
+                    float2 my_xy_1 = (xy + float2(chunk_offset(param_47))) + 0.5f.xx;
+                    float2 param_48 = my_xy_1;
+                    CmdBlurRect param_49 = blur;
+                    float4 fg_k_4 = fg * blur_rect_coverage(param_48, param_49);
+                    rgba[k_12] = (rgba[k_12] * (1.0f - fg_k_4.w)) + fg_k_4;
+                }
+                cmd_ref.offset += 32u;
+                break;
+            }
             case 9u:
             {
                 if (clip_depth < 4u)
                 {
-                    for (uint k_12 = 0u; k_12 < 8u; k_12++)
+                    for (uint k_13 = 0u; k_13 < 8u; k_13++)
                     {
-                        float4 param_38 = float4(rgba[k_12]);
-                        uint _2479 = packsRGB(param_38);
-                        blend_stack[clip_depth][k_12] = _2479;
-                        rgba[k_12] = 0.0f.xxxx;
+                        float4 param_50 = float4(rgba[k_13]);
+                        uint _3085 = packsRGB(param_50);
+                        blend_stack[clip_depth][k_13] = _3085;
+                        rgba[k_13] = 0.0f.xxxx;
                     }
                 }
                 else
                 {
                     uint base_ix = ((blend_offset >> uint(2)) + (((clip_depth - 4u) * 16u) * 16u)) + (8u * (gl_LocalInvocationID.x + (8u * gl_LocalInvocationID.y)));
-                    for (uint k_13 = 0u; k_13 < 8u; k_13++)
+                    for (uint k_14 = 0u; k_14 < 8u; k_14++)
                     {
-                        float4 param_39 = float4(rgba[k_13]);
-                        uint _2522 = packsRGB(param_39);
-                        _297.Store((base_ix + k_13) * 4 + 8, _2522);
-                        rgba[k_13] = 0.0f.xxxx;
+                        float4 param_51 = float4(rgba[k_14]);
+                        uint _3128 = packsRGB(param_51);
+                        _330.Store((base_ix + k_14) * 4 + 8, _3128);
+                        rgba[k_14] = 0.0f.xxxx;
                     }
                 }
                 clip_depth++;
@@ -1246,41 +1438,41 @@ void comp_main()
             }
             case 10u:
             {
-                Alloc param_40 = cmd_alloc;
-                CmdRef param_41 = cmd_ref;
-                CmdEndClip end_clip = Cmd_EndClip_read(param_40, param_41);
+                Alloc param_52 = cmd_alloc;
+                CmdRef param_53 = cmd_ref;
+                CmdEndClip end_clip = Cmd_EndClip_read(param_52, param_53);
                 clip_depth--;
                 if (clip_depth >= 4u)
                 {
                     base_ix_1 = ((blend_offset >> uint(2)) + (((clip_depth - 4u) * 16u) * 16u)) + (8u * (gl_LocalInvocationID.x + (8u * gl_LocalInvocationID.y)));
                 }
-                for (uint k_14 = 0u; k_14 < 8u; k_14++)
+                for (uint k_15 = 0u; k_15 < 8u; k_15++)
                 {
                     if (clip_depth < 4u)
                     {
-                        bg_rgba = blend_stack[clip_depth][k_14];
+                        bg_rgba = blend_stack[clip_depth][k_15];
                     }
                     else
                     {
-                        bg_rgba = _297.Load((base_ix_1 + k_14) * 4 + 8);
+                        bg_rgba = _330.Load((base_ix_1 + k_15) * 4 + 8);
                     }
-                    uint param_42 = bg_rgba;
-                    float4 bg = unpacksRGB(param_42);
-                    float4 fg_1 = rgba[k_14] * area[k_14];
-                    float4 param_43 = bg;
-                    float4 param_44 = fg_1;
-                    uint param_45 = end_clip.blend;
-                    rgba[k_14] = mix_blend_compose(param_43, param_44, param_45);
+                    uint param_54 = bg_rgba;
+                    float4 bg = unpacksRGB(param_54);
+                    float4 fg_1 = rgba[k_15] * area[k_15];
+                    float4 param_55 = bg;
+                    float4 param_56 = fg_1;
+                    uint param_57 = end_clip.blend;
+                    rgba[k_15] = mix_blend_compose(param_55, param_56, param_57);
                 }
                 cmd_ref.offset += 8u;
                 break;
             }
             case 11u:
             {
-                Alloc param_46 = cmd_alloc;
-                CmdRef param_47 = cmd_ref;
-                CmdRef _2621 = { Cmd_Jump_read(param_46, param_47).new_ref };
-                cmd_ref = _2621;
+                Alloc param_58 = cmd_alloc;
+                CmdRef param_59 = cmd_ref;
+                CmdRef _3227 = { Cmd_Jump_read(param_58, param_59).new_ref };
+                cmd_ref = _3227;
                 cmd_alloc.offset = cmd_ref.offset;
                 break;
             }
@@ -1288,9 +1480,9 @@ void comp_main()
     }
     for (uint i_1 = 0u; i_1 < 8u; i_1++)
     {
-        uint param_48 = i_1;
-        float3 param_49 = rgba[i_1].xyz;
-        image[int2(xy_uint + chunk_offset(param_48))] = float4(tosRGB(param_49), rgba[i_1].w);
+        uint param_60 = i_1;
+        float3 param_61 = rgba[i_1].xyz;
+        image[int2(xy_uint + chunk_offset(param_60))] = float4(tosRGB(param_61), rgba[i_1].w);
     }
 }
 
@@ -79,17 +79,21 @@ struct Config
     uint pathseg_offset;
     uint drawtag_offset;
     uint drawdata_offset;
+    uint damage_x0;
+    uint damage_y0;
+    uint damage_x1;
+    uint damage_y1;
 };
 
 static const uint3 gl_WorkGroupSize = uint3(256u, 1u, 1u);
 
 static const TagMonoid _135 = { 0u, 0u, 0u, 0u, 0u };
-static const Monoid _567 = { 0.0f.xxxx, 0u };
+static const Monoid _565 = { 0.0f.xxxx, 0u };
 
 RWByteAddressBuffer _111 : register(u0, space0);
-ByteAddressBuffer _574 : register(t2, space0);
-ByteAddressBuffer _639 : register(t1, space0);
-ByteAddressBuffer _710 : register(t3, space0);
+ByteAddressBuffer _572 : register(t2, space0);
+ByteAddressBuffer _637 : register(t1, space0);
+ByteAddressBuffer _708 : register(t3, space0);
 
 static uint3 gl_WorkGroupID;
 static uint3 gl_LocalInvocationID;
@@ -138,14 +142,14 @@ TagMonoid tag_monoid_identity()
 
 float2 read_f32_point(uint ix)
 {
-    float x = asfloat(_574.Load(ix * 4 + 0));
-    float y = asfloat(_574.Load((ix + 1u) * 4 + 0));
+    float x = asfloat(_572.Load(ix * 4 + 0));
+    float y = asfloat(_572.Load((ix + 1u) * 4 + 0));
     return float2(x, y);
 }
 
 float2 read_i16_point(uint ix)
 {
-    uint raw = _574.Load(ix * 4 + 0);
+    uint raw = _572.Load(ix * 4 + 0);
     float x = float(int(raw << uint(16)) >> 16);
     float y = float(int(raw) >> 16);
     return float2(x, y);
@@ -332,14 +336,10 @@ Monoid combine_monoid(Monoid a, Monoid b)
         }
         if (_522)
         {
-            float4 _529 = c.bbox;
-            float2 _531 = min(a.bbox.xy, _529.xy);
-            c.bbox.x = _531.x;
-            c.bbox.y = _531.y;
-            float4 _540 = c.bbox;
-            float2 _542 = max(a.bbox.zw, _540.zw);
-            c.bbox.z = _542.x;
-            c.bbox.w = _542.y;
+            float2 _531 = min(a.bbox.xy, c.bbox.xy);
+            c.bbox = float4(_531.x, _531.y, c.bbox.z, c.bbox.w);
+            float2 _541 = max(a.bbox.zw, c.bbox.zw);
+            c.bbox = float4(c.bbox.x, c.bbox.y, _541.x, _541.y);
         }
     }
     c.flags = (a.flags & 2u) | b.flags;
@@ -349,7 +349,7 @@ Monoid combine_monoid(Monoid a, Monoid b)
 
 Monoid monoid_identity()
 {
-    return _567;
+    return _565;
 }
 
 uint round_down(float x)
@@ -365,7 +365,7 @@ uint round_up(float x)
 void comp_main()
 {
     uint ix = gl_GlobalInvocationID.x * 4u;
-    uint tag_word = _574.Load(((_639.Load(92) >> uint(2)) + (ix >> uint(2))) * 4 + 0);
+    uint tag_word = _572.Load(((_637.Load(92) >> uint(2)) + (ix >> uint(2))) * 4 + 0);
     uint param = tag_word;
     TagMonoid local_tm = reduce_tag(param);
     sh_tag[gl_LocalInvocationID.x] = local_tm;
@@ -386,17 +386,17 @@ void comp_main()
     TagMonoid tm = tag_monoid_identity();
     if (gl_WorkGroupID.x > 0u)
     {
-        TagMonoid _716;
-        _716.trans_ix = _710.Load((gl_WorkGroupID.x - 1u) * 20 + 0);
-        _716.linewidth_ix = _710.Load((gl_WorkGroupID.x - 1u) * 20 + 4);
-        _716.pathseg_ix = _710.Load((gl_WorkGroupID.x - 1u) * 20 + 8);
-        _716.path_ix = _710.Load((gl_WorkGroupID.x - 1u) * 20 + 12);
-        _716.pathseg_offset = _710.Load((gl_WorkGroupID.x - 1u) * 20 + 16);
-        tm.trans_ix = _716.trans_ix;
-        tm.linewidth_ix = _716.linewidth_ix;
-        tm.pathseg_ix = _716.pathseg_ix;
-        tm.path_ix = _716.path_ix;
-        tm.pathseg_offset = _716.pathseg_offset;
+        TagMonoid _714;
+        _714.trans_ix = _708.Load((gl_WorkGroupID.x - 1u) * 20 + 0);
+        _714.linewidth_ix = _708.Load((gl_WorkGroupID.x - 1u) * 20 + 4);
+        _714.pathseg_ix = _708.Load((gl_WorkGroupID.x - 1u) * 20 + 8);
+        _714.path_ix = _708.Load((gl_WorkGroupID.x - 1u) * 20 + 12);
+        _714.pathseg_offset = _708.Load((gl_WorkGroupID.x - 1u) * 20 + 16);
+        tm.trans_ix = _714.trans_ix;
+        tm.linewidth_ix = _714.linewidth_ix;
+        tm.pathseg_ix = _714.pathseg_ix;
+        tm.path_ix = _714.path_ix;
+        tm.pathseg_offset = _714.pathseg_offset;
     }
     if (gl_LocalInvocationID.x > 0u)
     {
@@ -404,14 +404,14 @@ void comp_main()
         TagMonoid param_4 = sh_tag[gl_LocalInvocationID.x - 1u];
         tm = combine_tag_monoid(param_3, param_4);
     }
-    uint ps_ix = (_639.Load(96) >> uint(2)) + tm.pathseg_offset;
-    uint lw_ix = (_639.Load(88) >> uint(2)) + tm.linewidth_ix;
+    uint ps_ix = (_637.Load(96) >> uint(2)) + tm.pathseg_offset;
+    uint lw_ix = (_637.Load(88) >> uint(2)) + tm.linewidth_ix;
     uint save_path_ix = tm.path_ix;
     uint trans_ix = tm.trans_ix;
-    TransformSegRef _771 = { _639.Load(36) + (trans_ix * 24u) };
-    TransformSegRef trans_ref = _771;
-    PathSegRef _781 = { _639.Load(28) + (tm.pathseg_ix * 52u) };
-    PathSegRef ps_ref = _781;
+    TransformSegRef _769 = { _637.Load(36) + (trans_ix * 24u) };
+    TransformSegRef trans_ref = _769;
+    PathSegRef _779 = { _637.Load(28) + (tm.pathseg_ix * 52u) };
+    PathSegRef ps_ref = _779;
     float linewidth[4];
     uint save_trans_ix[4];
     float2 p0;
@@ -424,7 +424,7 @@ void comp_main()
     Alloc param_15;
     for (uint i_1 = 0u; i_1 < 4u; i_1++)
     {
-        linewidth[i_1] = asfloat(_574.Load(lw_ix * 4 + 0));
+        linewidth[i_1] = asfloat(_572.Load(lw_ix * 4 + 0));
         save_trans_ix[i_1] = trans_ix;
         uint tag_byte = tag_word >> (i_1 * 8u);
         uint seg_type = tag_byte & 3u;
@@ -464,9 +464,9 @@ void comp_main()
                     }
                 }
             }
-            Alloc _877;
-            _877.offset = _639.Load(36);
-            param_13.offset = _877.offset;
+            Alloc _875;
+            _875.offset = _637.Load(36);
+            param_13.offset = _875.offset;
             TransformSegRef param_14 = trans_ref;
             TransformSeg transform = TransformSeg_read(param_13, param_14);
             p0 = ((transform.mat.xy * p0.x) + (transform.mat.zw * p0.y)) + transform.translate;
@@ -475,25 +475,17 @@ void comp_main()
             if (seg_type >= 2u)
             {
                 p2 = ((transform.mat.xy * p2.x) + (transform.mat.zw * p2.y)) + transform.translate;
-                float4 _947 = bbox;
-                float2 _950 = min(_947.xy, p2);
-                bbox.x = _950.x;
-                bbox.y = _950.y;
-                float4 _955 = bbox;
-                float2 _958 = max(_955.zw, p2);
-                bbox.z = _958.x;
-                bbox.w = _958.y;
+                float2 _948 = min(bbox.xy, p2);
+                bbox = float4(_948.x, _948.y, bbox.z, bbox.w);
+                float2 _954 = max(bbox.zw, p2);
+                bbox = float4(bbox.x, bbox.y, _954.x, _954.y);
                 if (seg_type == 3u)
                 {
                     p3 = ((transform.mat.xy * p3.x) + (transform.mat.zw * p3.y)) + transform.translate;
-                    float4 _983 = bbox;
-                    float2 _986 = min(_983.xy, p3);
-                    bbox.x = _986.x;
-                    bbox.y = _986.y;
-                    float4 _991 = bbox;
-                    float2 _994 = max(_991.zw, p3);
-                    bbox.z = _994.x;
-                    bbox.w = _994.y;
+                    float2 _980 = min(bbox.xy, p3);
+                    bbox = float4(_980.x, _980.y, bbox.z, bbox.w);
+                    float2 _986 = max(bbox.zw, p3);
+                    bbox = float4(bbox.x, bbox.y, _986.x, _986.y);
                 }
                 else
                 {
@@ -524,9 +516,9 @@ void comp_main()
             cubic.trans_ix = (gl_GlobalInvocationID.x * 4u) + i_1;
             cubic.stroke = stroke;
             uint fill_mode = uint(linewidth[i_1] >= 0.0f);
-            Alloc _1089;
-            _1089.offset = _639.Load(28);
-            param_15.offset = _1089.offset;
+            Alloc _1079;
+            _1079.offset = _637.Load(28);
+            param_15.offset = _1079.offset;
             PathSegRef param_16 = ps_ref;
             uint param_17 = fill_mode;
             PathCubic param_18 = cubic;
@@ -571,7 +563,7 @@ void comp_main()
     }
     GroupMemoryBarrierWithGroupSync();
     uint path_ix = save_path_ix;
-    uint bbox_out_ix = (_639.Load(40) >> uint(2)) + (path_ix * 6u);
+    uint bbox_out_ix = (_637.Load(40) >> uint(2)) + (path_ix * 6u);
     Monoid row = monoid_identity();
     if (gl_LocalInvocationID.x > 0u)
     {
@@ -583,17 +575,17 @@ void comp_main()
         Monoid param_24 = local[i_4];
         Monoid m = combine_monoid(param_23, param_24);
         bool do_atomic = false;
-        bool _1264 = i_4 == 3u;
-        bool _1270;
-        if (_1264)
+        bool _1254 = i_4 == 3u;
+        bool _1260;
+        if (_1254)
         {
-            _1270 = gl_LocalInvocationID.x == 255u;
+            _1260 = gl_LocalInvocationID.x == 255u;
         }
         else
         {
-            _1270 = _1264;
+            _1260 = _1254;
         }
-        if (_1270)
+        if (_1260)
         {
             do_atomic = true;
         }
@@ -621,30 +613,30 @@ void comp_main()
         }
         if (do_atomic)
         {
-            bool _1335 = m.bbox.z > m.bbox.x;
-            bool _1344;
-            if (!_1335)
+            bool _1325 = m.bbox.z > m.bbox.x;
+            bool _1334;
+            if (!_1325)
             {
-                _1344 = m.bbox.w > m.bbox.y;
+                _1334 = m.bbox.w > m.bbox.y;
             }
             else
             {
-                _1344 = _1335;
+                _1334 = _1325;
             }
-            if (_1344)
+            if (_1334)
             {
                 float param_29 = m.bbox.x;
-                uint _1353;
-                _111.InterlockedMin(bbox_out_ix * 4 + 8, round_down(param_29), _1353);
+                uint _1343;
+                _111.InterlockedMin(bbox_out_ix * 4 + 8, round_down(param_29), _1343);
                 float param_30 = m.bbox.y;
-                uint _1361;
-                _111.InterlockedMin((bbox_out_ix + 1u) * 4 + 8, round_down(param_30), _1361);
+                uint _1351;
+                _111.InterlockedMin((bbox_out_ix + 1u) * 4 + 8, round_down(param_30), _1351);
                 float param_31 = m.bbox.z;
-                uint _1369;
-                _111.InterlockedMax((bbox_out_ix + 2u) * 4 + 8, round_up(param_31), _1369);
+                uint _1359;
+                _111.InterlockedMax((bbox_out_ix + 2u) * 4 + 8, round_up(param_31), _1359);
                 float param_32 = m.bbox.w;
-                uint _1377;
-                _111.InterlockedMax((bbox_out_ix + 3u) * 4 + 8, round_up(param_32), _1377);
+                uint _1367;
+                _111.InterlockedMax((bbox_out_ix + 3u) * 4 + 8, round_up(param_32), _1367);
             }
             bbox_out_ix += 6u;
         }
@@ -54,6 +54,10 @@ struct Config
     uint pathseg_offset;
     uint drawtag_offset;
     uint drawdata_offset;
+    uint damage_x0;
+    uint damage_y0;
+    uint damage_x1;
+    uint damage_y1;
 };
 
 static const uint3 gl_WorkGroupSize = uint3(256u, 1u, 1u);
@@ -178,10 +182,10 @@ void comp_main()
         x1 = int(ceil(bbox.z * 0.0625f));
         y1 = int(ceil(bbox.w * 0.0625f));
     }
-    x0 = clamp(x0, 0, int(_181.Load(8)));
-    y0 = clamp(y0, 0, int(_181.Load(12)));
-    x1 = clamp(x1, 0, int(_181.Load(8)));
-    y1 = clamp(y1, 0, int(_181.Load(12)));
+    x0 = clamp(x0, int(_181.Load(108)), int(_181.Load(116)));
+    y0 = clamp(y0, int(_181.Load(112)), int(_181.Load(120)));
+    x1 = clamp(x1, int(_181.Load(108)), int(_181.Load(116)));
+    y1 = clamp(y1, int(_181.Load(112)), int(_181.Load(120)));
     Path path;
     path.bbox = uint4(uint(x0), uint(y0), uint(x1), uint(y1));
     uint tile_count = uint((x1 - x0) * (y1 - y0));
@@ -200,46 +204,46 @@ void comp_main()
     if (th_ix == 255u)
     {
         uint param_1 = total_tile_count * 8u;
-        MallocResult _392 = malloc(param_1);
-        sh_tile_alloc = _392;
+        MallocResult _407 = malloc(param_1);
+        sh_tile_alloc = _407;
     }
     GroupMemoryBarrierWithGroupSync();
     MallocResult alloc_start = sh_tile_alloc;
-    bool _403;
+    bool _418;
     if (!alloc_start.failed)
     {
-        _403 = _70.Load(4) != 0u;
+        _418 = _70.Load(4) != 0u;
     }
     else
     {
-        _403 = alloc_start.failed;
+        _418 = alloc_start.failed;
     }
-    if (_403)
+    if (_418)
     {
         return;
     }
     if (element_ix < _181.Load(0))
     {
-        uint _416;
+        uint _431;
         if (th_ix > 0u)
         {
-            _416 = sh_tile_count[th_ix - 1u];
+            _431 = sh_tile_count[th_ix - 1u];
         }
         else
         {
-            _416 = 0u;
+            _431 = 0u;
         }
-        uint tile_subix = _416;
+        uint tile_subix = _431;
         Alloc param_2 = alloc_start.alloc;
         uint param_3 = 8u * tile_subix;
         uint param_4 = 8u * tile_count;
         Alloc tiles_alloc = slice_mem(param_2, param_3, param_4);
-        TileRef _438 = { tiles_alloc.offset };
-        path.tiles = _438;
-        Alloc _444;
-        _444.offset = _181.Load(16);
+        TileRef _453 = { tiles_alloc.offset };
+        path.tiles = _453;
+        Alloc _459;
+        _459.offset = _181.Load(16);
         Alloc param_5;
-        param_5.offset = _444.offset;
+        param_5.offset = _459.offset;
         PathRef param_6 = path_ref;
         Path param_7 = path;
         Path_write(param_5, param_6, param_7);
@@ -38,6 +38,10 @@ struct CmdJumpRef {
     uint offset;
 };
 
+struct CmdBlurRectRef {
+    uint offset;
+};
+
 struct CmdRef {
     uint offset;
 };
@@ -143,6 +147,19 @@ CmdJumpRef CmdJump_index(CmdJumpRef ref, uint index) {
     return CmdJumpRef(ref.offset + index * CmdJump_size);
 }
 
+struct CmdBlurRect {
+    uint rgba_color;
+    vec4 rect;
+    float radius;
+    float std_dev;
+};
+
+#define CmdBlurRect_size 28
+
+CmdBlurRectRef CmdBlurRect_index(CmdBlurRectRef ref, uint index) {
+    return CmdBlurRectRef(ref.offset + index * CmdBlurRect_size);
+}
+
 #define Cmd_End 0
 #define Cmd_Fill 1
 #define Cmd_Stroke 2
@@ -155,6 +172,7 @@ CmdJumpRef CmdJump_index(CmdJumpRef ref, uint index) {
 #define Cmd_BeginClip 9
 #define Cmd_EndClip 10
 #define Cmd_Jump 11
+#define Cmd_BlurRect 12
 #define Cmd_size 48
 
 CmdRef Cmd_index(CmdRef ref, uint index) {
@@ -326,6 +344,34 @@ void CmdJump_write(Alloc a, CmdJumpRef ref, CmdJump s) {
     write_mem(a, ix + 0, s.new_ref);
 }
 
+CmdBlurRect CmdBlurRect_read(Alloc a, CmdBlurRectRef ref) {
+    uint ix = ref.offset >> 2;
+    uint raw0 = read_mem(a, ix + 0);
+    uint raw1 = read_mem(a, ix + 1);
+    uint raw2 = read_mem(a, ix + 2);
+    uint raw3 = read_mem(a, ix + 3);
+    uint raw4 = read_mem(a, ix + 4);
+    uint raw5 = read_mem(a, ix + 5);
+    uint raw6 = read_mem(a, ix + 6);
+    CmdBlurRect s;
+    s.rgba_color = raw0;
+    s.rect = vec4(uintBitsToFloat(raw1), uintBitsToFloat(raw2), uintBitsToFloat(raw3), uintBitsToFloat(raw4));
+    s.radius = uintBitsToFloat(raw5);
+    s.std_dev = uintBitsToFloat(raw6);
+    return s;
+}
+
+void CmdBlurRect_write(Alloc a, CmdBlurRectRef ref, CmdBlurRect s) {
+    uint ix = ref.offset >> 2;
+    write_mem(a, ix + 0, s.rgba_color);
+    write_mem(a, ix + 1, floatBitsToUint(s.rect.x));
+    write_mem(a, ix + 2, floatBitsToUint(s.rect.y));
+    write_mem(a, ix + 3, floatBitsToUint(s.rect.z));
+    write_mem(a, ix + 4, floatBitsToUint(s.rect.w));
+    write_mem(a, ix + 5, floatBitsToUint(s.radius));
+    write_mem(a, ix + 6, floatBitsToUint(s.std_dev));
+}
+
 CmdTag Cmd_tag(Alloc a, CmdRef ref) {
     uint tag_and_flags = read_mem(a, ref.offset >> 2);
     return CmdTag(tag_and_flags & 0xffff, tag_and_flags >> 16);
@@ -367,12 +413,16 @@ CmdJump Cmd_Jump_read(Alloc a, CmdRef ref) {
     return CmdJump_read(a, CmdJumpRef(ref.offset + 4));
 }
 
+CmdBlurRect Cmd_BlurRect_read(Alloc a, CmdRef ref) {
+    return CmdBlurRect_read(a, CmdBlurRectRef(ref.offset + 4));
+}
+
 void Cmd_End_write(Alloc a, CmdRef ref) {
     write_mem(a, ref.offset >> 2, Cmd_End);
 }
 
-void Cmd_Fill_write(Alloc a, CmdRef ref, CmdFill s) {
-    write_mem(a, ref.offset >> 2, Cmd_Fill);
+void Cmd_Fill_write(Alloc a, CmdRef ref, uint flags, CmdFill s) {
+    write_mem(a, ref.offset >> 2, (flags << 16) | Cmd_Fill);
     CmdFill_write(a, CmdFillRef(ref.offset + 4), s);
 }
 
@@ -424,3 +474,8 @@ void Cmd_Jump_write(Alloc a, CmdRef ref, CmdJump s) {
     CmdJump_write(a, CmdJumpRef(ref.offset + 4), s);
 }
 
+void Cmd_BlurRect_write(Alloc a, CmdRef ref, CmdBlurRect s) {
+    write_mem(a, ref.offset >> 2, Cmd_BlurRect);
+    CmdBlurRect_write(a, CmdBlurRectRef(ref.offset + 4), s);
+}
+
@@ -87,6 +87,13 @@ struct Config {
     uint drawtag_offset;
     // Offset (in bytes) of draw payload stream in scene
     uint drawdata_offset;
+
+    // Region of tiles to render, for partial updates of the target. Tiles
+    // outside of it are left untouched.
+    uint damage_x0;
+    uint damage_y0;
+    uint damage_x1;
+    uint damage_y1;
 };
 #endif
 
@@ -14,6 +14,8 @@
 //
 // Also licensed under MIT license, at your choice.
 
+use piet_scene::scene::{self, Compose, Mix};
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub enum BlendMode {
@@ -69,10 +71,6 @@ impl Blend {
             composition_mode,
         }
     }
-
-    pub(crate) fn pack(&self) -> u32 {
-        (self.mode as u32) << 8 | self.composition_mode as u32
-    }
 }
 
 impl Default for Blend {
@@ -101,3 +99,92 @@ impl From<CompositionMode> for Blend {
         }
     }
 }
+
+/// How the content of a mask layer attenuates the content it masks.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Mask {
+    /// Use the alpha channel of the mask.
+    Alpha,
+    /// Use the luminance of the mask, multiplied by its alpha.
+    Luminance,
+}
+
+impl From<BlendMode> for Mix {
+    fn from(mode: BlendMode) -> Self {
+        match mode {
+            BlendMode::Normal => Mix::Normal,
+            BlendMode::Multiply => Mix::Multiply,
+            BlendMode::Screen => Mix::Screen,
+            BlendMode::Overlay => Mix::Overlay,
+            BlendMode::Darken => Mix::Darken,
+            BlendMode::Lighten => Mix::Lighten,
+            BlendMode::ColorDodge => Mix::ColorDodge,
+            BlendMode::ColorBurn => Mix::ColorBurn,
+            BlendMode::HardLight => Mix::HardLight,
+            BlendMode::SoftLight => Mix::SoftLight,
+            BlendMode::Difference => Mix::Difference,
+            BlendMode::Exclusion => Mix::Exclusion,
+            BlendMode::Hue => Mix::Hue,
+            BlendMode::Saturation => Mix::Saturation,
+            BlendMode::Color => Mix::Color,
+            BlendMode::Luminosity => Mix::Luminosity,
+            BlendMode::Clip => Mix::Clip,
+        }
+    }
+}
+
+impl From<CompositionMode> for Compose {
+    fn from(mode: CompositionMode) -> Self {
+        match mode {
+            CompositionMode::Clear => Compose::Clear,
+            CompositionMode::Copy => Compose::Copy,
+            CompositionMode::Dest => Compose::Dest,
+            CompositionMode::SrcOver => Compose::SrcOver,
+            CompositionMode::DestOver => Compose::DestOver,
+            CompositionMode::SrcIn => Compose::SrcIn,
+            CompositionMode::DestIn => Compose::DestIn,
+            CompositionMode::SrcOut => Compose::SrcOut,
+            CompositionMode::DestOut => Compose::DestOut,
+            CompositionMode::SrcAtop => Compose::SrcAtop,
+            CompositionMode::DestAtop => Compose::DestAtop,
+            CompositionMode::Xor => Compose::Xor,
+            CompositionMode::Plus => Compose::Plus,
+            CompositionMode::PlusLighter => Compose::PlusLighter,
+        }
+    }
+}
+
+impl From<Blend> for scene::Blend {
+    fn from(blend: Blend) -> Self {
+        scene::Blend::new(blend.mode.into(), blend.composition_mode.into())
+    }
+}
+
+impl From<Mask> for scene::Mask {
+    fn from(mask: Mask) -> Self {
+        match mask {
+            Mask::Alpha => scene::Mask::Alpha,
+            Mask::Luminance => scene::Mask::Luminance,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Mask;
+    use crate::shader_define;
+    use piet_scene::scene;
+
+    #[test]
+    fn masks_match_shader() {
+        let header = include_str!("../shader/blend.h");
+        let masks = [
+            (Mask::Alpha, "Blend_MaskAlpha"),
+            (Mask::Luminance, "Blend_MaskLuminance"),
+        ];
+        for (mask, name) in masks {
+            let mix = scene::Mask::from(mask).pack() >> 8;
+            assert_eq!(mix, shader_define(header, name), "{}", name);
+        }
+    }
+}
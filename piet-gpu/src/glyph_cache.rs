@@ -0,0 +1,241 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! A bounded cache of encoded glyph outlines.
+
+use std::collections::{BTreeMap, HashMap};
+
+use piet_scene::path::Element;
+use piet_scene::scene::Fragment;
+
+/// The default maximum number of outlines retained by a cache.
+pub const DEFAULT_MAX_ENTRIES: usize = 2048;
+
+/// Identifies a scaled glyph outline.
+///
+/// Two outlines are only interchangeable if they come from the same font
+/// and were scaled with the same size, hinting and variation settings.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct GlyphKey {
+    /// Unique identifier of the font (usually a swash `CacheKey` value).
+    pub font_id: u64,
+    pub glyph_id: u16,
+    /// Bits of the size in pixels per em, as an `f32`.
+    size_bits: u32,
+    pub hint: bool,
+    /// Normalized variation coordinates, in 2.14 fixed point.
+    pub coords: Vec<i16>,
+    /// Palette index used for color outlines.
+    pub palette: u16,
+    /// Foreground color substituted into color outlines.
+    pub foreground: u32,
+    /// Horizontal subpixel offset bucket of hinted outlines.
+    pub subpixel: u8,
+    /// Bits of the emboldening strength in outline units, as an `f32`.
+    embolden_bits: u32,
+}
+
+impl GlyphKey {
+    pub fn new(font_id: u64, glyph_id: u16, size: f32, hint: bool, coords: &[i16]) -> GlyphKey {
+        GlyphKey {
+            font_id,
+            glyph_id,
+            size_bits: size.to_bits(),
+            hint,
+            coords: coords.to_owned(),
+            palette: 0,
+            foreground: 0,
+            subpixel: 0,
+            embolden_bits: 0,
+        }
+    }
+
+    /// Distinguish outlines offset by a fraction of a pixel.
+    pub fn with_subpixel(mut self, subpixel: u8) -> GlyphKey {
+        self.subpixel = subpixel;
+        self
+    }
+
+    /// Distinguish outlines emboldened by stem darkening.
+    pub fn with_embolden(mut self, strength: f32) -> GlyphKey {
+        self.embolden_bits = strength.to_bits();
+        self
+    }
+
+    /// Distinguish color outlines encoded with different palettes or
+    /// foreground colors.
+    ///
+    /// This is only needed for fonts with color outlines; the fill of
+    /// monochrome outlines is applied when they are drawn.
+    pub fn with_colors(mut self, palette: u16, foreground: u32) -> GlyphKey {
+        self.palette = palette;
+        self.foreground = foreground;
+        self
+    }
+
+    /// The size in pixels per em.
+    pub fn size(&self) -> f32 {
+        f32::from_bits(self.size_bits)
+    }
+}
+
+/// A scaled glyph outline.
+pub enum GlyphOutline {
+    /// A monochrome outline, filled with the text color when it is drawn.
+    Mono(Vec<Element>),
+    /// A color outline, encoded together with its fills.
+    Color(Box<Fragment>),
+}
+
+/// Counters describing the effectiveness of a glyph cache.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct GlyphCacheStats {
+    /// Number of lookups satisfied from the cache.
+    pub hits: u64,
+    /// Number of lookups that required scaling a new outline.
+    pub misses: u64,
+    /// Number of outlines dropped to stay within the size bound.
+    pub evictions: u64,
+}
+
+struct Entry {
+    outline: GlyphOutline,
+    last_used: u64,
+}
+
+/// Least recently used cache of encoded glyph outlines.
+pub struct GlyphCache {
+    max_entries: usize,
+    map: HashMap<GlyphKey, Entry>,
+    // Keys ordered by time of last use; the first entry is evicted first.
+    lru: BTreeMap<u64, GlyphKey>,
+    tick: u64,
+    stats: GlyphCacheStats,
+}
+
+impl Default for GlyphCache {
+    fn default() -> Self {
+        GlyphCache::new(DEFAULT_MAX_ENTRIES)
+    }
+}
+
+impl GlyphCache {
+    /// Create a cache retaining at most `max_entries` outlines.
+    pub fn new(max_entries: usize) -> GlyphCache {
+        GlyphCache {
+            max_entries: max_entries.max(1),
+            map: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            stats: GlyphCacheStats::default(),
+        }
+    }
+
+    /// Get the outline for `key`, encoding it with `f` if it is not present.
+    pub fn get_or_insert_with(
+        &mut self,
+        key: GlyphKey,
+        f: impl FnOnce() -> GlyphOutline,
+    ) -> &GlyphOutline {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.map.get_mut(&key) {
+            self.stats.hits += 1;
+            self.lru.remove(&entry.last_used);
+            entry.last_used = tick;
+            self.lru.insert(tick, key.clone());
+        } else {
+            self.stats.misses += 1;
+            while self.map.len() >= self.max_entries {
+                self.evict_one();
+            }
+            let outline = f();
+            self.lru.insert(tick, key.clone());
+            self.map.insert(
+                key.clone(),
+                Entry {
+                    outline,
+                    last_used: tick,
+                },
+            );
+        }
+        &self.map[&key].outline
+    }
+
+    fn evict_one(&mut self) {
+        let oldest = self.lru.keys().next().copied();
+        if let Some(tick) = oldest {
+            if let Some(key) = self.lru.remove(&tick) {
+                self.map.remove(&key);
+                self.stats.evictions += 1;
+            }
+        }
+    }
+
+    /// The number of outlines currently retained.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Change the size bound, evicting outlines if necessary.
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries.max(1);
+        while self.map.len() > self.max_entries {
+            self.evict_one();
+        }
+    }
+
+    pub fn stats(&self) -> GlyphCacheStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = GlyphCacheStats::default();
+    }
+
+    /// Drop all outlines. Statistics are retained.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.lru.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GlyphCache, GlyphKey, GlyphOutline};
+
+    #[test]
+    fn lru_eviction() {
+        let mut cache = GlyphCache::new(2);
+        let key = |gid| GlyphKey::new(1, gid, 12.0, false, &[]);
+        let outline = || GlyphOutline::Mono(Vec::new());
+        cache.get_or_insert_with(key(1), outline);
+        cache.get_or_insert_with(key(2), outline);
+        // Touch 1 so that 2 becomes the least recently used.
+        cache.get_or_insert_with(key(1), || panic!("should be cached"));
+        cache.get_or_insert_with(key(3), outline);
+        cache.get_or_insert_with(key(1), || panic!("should be cached"));
+        let stats = cache.stats();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 3);
+        assert_eq!(stats.evictions, 1);
+    }
+}
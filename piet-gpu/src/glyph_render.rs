@@ -16,27 +16,64 @@
 
 //! An experimental API for glyph rendering.
 
-use piet::{kurbo::Affine, RenderContext};
-use swash::{scale::ScaleContext, CacheKey, FontDataRef};
+use piet::Color;
+use piet_scene::brush;
+use piet_scene::geometry::Affine;
+use piet_scene::resource::ResourceContext;
+use piet_scene::scene::{build_scene, extend_scene, Scene};
+use swash::{scale::ScaleContext, CacheKey, ColorPalette, FontDataRef, FontRef};
 
-use crate::{encoder::GlyphEncoder, PietGpuRenderContext};
+use crate::encoder::EncodedSceneRef;
+use crate::glyph_cache::{GlyphCache, GlyphCacheStats, GlyphKey, GlyphOutline};
+use crate::render_ctx::to_scene_color;
+use crate::text::{make_color_outline, make_path_elements, select_palette, PaletteSelection};
 
 pub struct GlyphRenderer {
-    pub render_ctx: PietGpuRenderContext,
+    scene: Scene,
+    rcx: ResourceContext,
     scale_context: ScaleContext,
+    // Retained across `reset` so that outlines are only scaled once.
+    glyph_cache: GlyphCache,
+    palette: PaletteSelection,
+    foreground: brush::Color,
 }
 
 #[repr(transparent)]
 pub struct FontId(CacheKey);
 
+const DEFAULT_UPEM: u16 = 2048;
+
+impl Default for GlyphRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GlyphRenderer {
     pub fn new() -> GlyphRenderer {
-        let render_ctx = PietGpuRenderContext::new();
-        let scale_context = ScaleContext::new();
-        GlyphRenderer {
-            render_ctx,
-            scale_context,
-        }
+        let mut renderer = GlyphRenderer {
+            scene: Scene::default(),
+            rcx: ResourceContext::new(),
+            scale_context: ScaleContext::new(),
+            glyph_cache: GlyphCache::default(),
+            palette: PaletteSelection::default(),
+            foreground: brush::Color::rgb8(255, 255, 255),
+        };
+        renderer.reset();
+        renderer
+    }
+
+    /// Set the palette used for fonts with color outlines.
+    pub fn set_palette(&mut self, palette: PaletteSelection) {
+        self.palette = palette;
+    }
+
+    /// Set the color of monochrome glyphs.
+    ///
+    /// This is also substituted for the foreground entry (0xFFFF) of color
+    /// outlines.
+    pub fn set_foreground(&mut self, color: Color) {
+        self.foreground = to_scene_color(&color);
     }
 
     pub unsafe fn add_glyph(
@@ -49,39 +86,76 @@ impl GlyphRenderer {
         // This transmute is dodgy because the definition in swash isn't repr(transparent).
         // I think the best solution is to have a from_u64 method, but we'll work that out
         // later.
-        let font_id = FontId(std::mem::transmute(font_id));
-        let encoder = self.make_glyph(font_data, font_id, glyph_id);
-        const DEFAULT_UPEM: u16 = 2048;
-        let affine = Affine::new([
-            transform[0] as f64,
-            transform[1] as f64,
-            transform[2] as f64,
-            transform[3] as f64,
-            transform[4] as f64,
-            transform[5] as f64,
-        ]) * Affine::scale(1.0 / DEFAULT_UPEM as f64);
-        self.render_ctx.transform(affine);
-        self.render_ctx.encode_glyph(&encoder);
-        // TODO: don't fill glyph if RGBA
-        self.render_ctx.fill_glyph(0xff_ff_ff_ff);
-        self.render_ctx.transform(affine.inverse());
+        let mut key = GlyphKey::new(font_id, glyph_id, DEFAULT_UPEM as f32, false, &[]);
+        let font_id = FontId(std::mem::transmute::<u64, CacheKey>(font_id));
+        let font_data = FontDataRef::new(font_data).expect("invalid font");
+        let mut font_ref = font_data.get(0).expect("invalid font index");
+        font_ref.key = font_id.0;
+        // Only color outlines depend on the palette and foreground.
+        let palette = select_palette(&font_ref, self.palette);
+        if let Some(palette) = &palette {
+            key = key.with_colors(palette.index(), self.foreground.to_premul_u32());
+        }
+        let scale_context = &mut self.scale_context;
+        let foreground = self.foreground;
+        let outline = self.glyph_cache.get_or_insert_with(key, || {
+            make_glyph(scale_context, font_ref, glyph_id, palette, foreground)
+        });
+        // Glyphs without an outline, such as spaces, draw nothing.
+        if matches!(outline, GlyphOutline::Mono(path) if path.is_empty()) {
+            return;
+        }
+        let scale = 1.0 / DEFAULT_UPEM as f32;
+        let affine = Affine::new(&transform) * Affine::scale(scale, scale);
+        let mut builder = extend_scene(&mut self.scene, &mut self.rcx);
+        builder.push_transform(affine);
+        match outline {
+            GlyphOutline::Mono(path) => builder.fill_glyph(self.foreground, 0, path),
+            GlyphOutline::Color(fragment) => builder.append(fragment),
+        }
+        builder.pop_transform();
     }
 
     pub fn reset(&mut self) {
-        self.render_ctx = PietGpuRenderContext::new();
+        self.rcx.advance();
+        // The builder only adds the root transform, which cannot fail.
+        let _ = build_scene(&mut self.scene, &mut self.rcx).finish();
     }
 
-    fn make_glyph(&mut self, font_data: &[u8], font_id: FontId, glyph_id: u16) -> GlyphEncoder {
-        let mut encoder = GlyphEncoder::default();
-        let font_data = FontDataRef::new(font_data).expect("invalid font");
-        let mut font_ref = font_data.get(0).expect("invalid font index");
-        font_ref.key = font_id.0;
-        let mut scaler = self.scale_context.builder(font_ref).size(2048.).build();
-        if let Some(outline) = scaler.scale_outline(glyph_id) {
-            crate::text::append_outline(&mut encoder, outline.verbs(), outline.points());
-        } else {
-            println!("failed to scale");
+    /// The glyphs added since the last `reset`, ready for upload.
+    pub fn encoded_scene(&self) -> EncodedSceneRef<'_, Affine> {
+        EncodedSceneRef::from_scene(&self.scene, &self.rcx)
+    }
+
+    /// Statistics for the glyph outline cache.
+    pub fn glyph_cache_stats(&self) -> GlyphCacheStats {
+        self.glyph_cache.stats()
+    }
+}
+
+fn make_glyph(
+    scale_context: &mut ScaleContext,
+    font_ref: FontRef,
+    glyph_id: u16,
+    palette: Option<ColorPalette>,
+    foreground: brush::Color,
+) -> GlyphOutline {
+    let mut scaler = scale_context
+        .builder(font_ref)
+        .size(DEFAULT_UPEM as f32)
+        .build();
+    if scaler.has_color_outlines() {
+        if let Some(outline) = scaler.scale_color_outline(glyph_id) {
+            return GlyphOutline::Color(Box::new(make_color_outline(
+                &outline, palette, foreground,
+            )));
         }
-        encoder
     }
+    // A glyph without an outline, such as a space, is cached as an empty
+    // path so it is skipped quietly.
+    let path = scaler
+        .scale_outline(glyph_id)
+        .map(|outline| make_path_elements(outline.verbs(), outline.points()))
+        .unwrap_or_default();
+    GlyphOutline::Mono(path)
 }
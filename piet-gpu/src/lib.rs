@@ -1,38 +1,107 @@
+//! A GPU renderer for 2D graphics.
+//!
+//! Scenes are encoded with piet-scene, either directly with its builder or
+//! through the piet `RenderContext` implementation [`PietGpuRenderContext`],
+//! and uploaded with [`Renderer::upload_scene`].
+//!
+//! # Migrating from `upload_render_ctx`
+//!
+//! `PietGpuRenderContext` used to own its encoding, which was uploaded with
+//! `Renderer::upload_render_ctx`. It now encodes into a borrowed piet-scene
+//! `Scene`, which is uploaded once the context is dropped:
+//!
+//! ```ignore
+//! // Keep the resource context across frames, so gradient ramps are reused.
+//! let mut ctx = PietGpuRenderContext::new(&mut scene, &mut rcx);
+//! draw(&mut ctx);
+//! ctx.finish()?;
+//! drop(ctx);
+//! renderer.upload_scene(&EncodedSceneRef::from_scene(&scene, &rcx), buf_ix)?;
+//! ```
+//!
+//! The encoder types of the old context, `encoder::Encoder` and
+//! `encoder::GlyphEncoder`, are replaced by the piet-scene builder, and its
+//! brush type `PietGpuBrush` by `piet_scene::brush::Brush`.
+
+#![allow(clippy::missing_safety_doc)]
+
 mod blend;
 pub mod encoder;
+pub mod glyph_cache;
+#[cfg(feature = "swash")] pub mod glyph_render;
 mod gradient;
 #[cfg(feature = "roxmltree")] mod pico_svg;
 mod render_ctx;
 pub mod stages;
 #[cfg(feature = "roxmltree")] pub mod test_scenes;
 #[cfg(feature = "swash")] mod text;
+mod validate;
 
 use bytemuck::Pod;
 use std::convert::TryInto;
 
-pub use blend::{Blend, BlendMode, CompositionMode};
-pub use encoder::EncodedSceneRef;
+pub use blend::{Blend, BlendMode, CompositionMode, Mask};
+pub use encoder::{EncodedSceneRef, Filter, FilterLayerRef};
+pub use glyph_cache::{GlyphCache, GlyphCacheStats, GlyphKey, GlyphOutline};
 pub use gradient::Colrv1RadialGradient;
 pub use render_ctx::PietGpuRenderContext;
+pub use validate::SceneError;
+#[cfg(feature = "swash")] pub use text::{PaletteSelection, SubpixelOrder, TextRasterOptions};
 
 //#[cfg(feature = "swash")] use piet::kurbo::Vec2;
 use piet::ImageFormat;
 //#[cfg(feature = "swash")]  use piet::RenderContext;
 
+use piet_scene::geometry::Rect;
+
 use piet_gpu_hal::{
-    include_shader, BindType, Buffer, BufferUsage, CmdBuf, ComputePassDescriptor, DescriptorSet,
-    Error, Image, ImageLayout, Pipeline, QueryPool, Session,
+    include_shader, BackendType, BindType, Buffer, BufferUsage, CmdBuf, ComputePassDescriptor,
+    DescriptorSet, Error, Image, ImageLayout, Pipeline, QueryPool, Session,
 };
 
-use stages::{ClipBinding, ElementBinding, ElementCode, ClipCode, Config, ElementStage};
+use stages::{
+    ClipBinding, ClipCode, Config, ElementBinding, ElementCode, ElementStage, FilterBinding,
+    FilterCode, FilterParams,
+};
 
-#[cfg(feature = "roxmltree")] pub use pico_svg::PicoSvg;
+#[cfg(feature = "roxmltree")] pub use pico_svg::{PicoSvg, SvgError, SvgErrorKind, SvgLocation, SvgWarning};
 
 const TILE_W: usize = 16;
 const TILE_H: usize = 16;
 
+// Size of a bin in the coarse rasterizer, in tiles.
+const BIN_W: u32 = 16;
+const BIN_H: u32 = 16;
+
 const PTCL_INITIAL_ALLOC: usize = 1024;
 
+/// Shaders whose DXIL in `shader/gen` is missing or older than their GLSL,
+/// with the change that it lacks.
+///
+/// Building DXIL needs dxc, so `ninja` in the shader directory may leave it
+/// behind the SPIR-V, HLSL and MSL. While this list is not empty, the
+/// renderer refuses to run on DX12 rather than run outdated shaders. Remove
+/// the entries once the DXIL has been rebuilt.
+const STALE_DXIL: &[(&str, &str)] = &[
+    ("coarse", "glyph flags in Cmd_Fill"),
+    ("kernel4", "LCD coverage and text gamma"),
+    ("kernel4_gray", "LCD coverage and text gamma"),
+    ("coarse", "Cmd_BlurRect and the blurred rectangle draw tag"),
+    ("draw_leaf", "the blurred rectangle draw tag"),
+    ("kernel4", "Cmd_BlurRect and Cmd_Alpha"),
+    ("kernel4_gray", "Cmd_BlurRect and Cmd_Alpha"),
+    ("coarse", "layer alpha and mask modes"),
+    ("kernel4", "mask modes 129 and 130 and transparent EndClip"),
+    ("kernel4_gray", "mask modes 129 and 130 and transparent EndClip"),
+    ("binning", "damage regions"),
+    ("tile_alloc", "damage regions"),
+    ("coarse", "damage regions"),
+    ("kernel4", "damage regions"),
+    ("kernel4_gray", "damage regions"),
+    ("filter_h", "filter layers, no DXIL built yet"),
+    ("filter_v", "filter layers, no DXIL built yet"),
+];
+
 #[allow(unused)]
 fn dump_scene(buf: &[u8]) {
     for i in 0..(buf.len() / 4) {
@@ -44,9 +113,9 @@ fn dump_scene(buf: &[u8]) {
 
 #[allow(unused)]
 pub fn dump_k1_data(k1_buf: &[u32]) {
-    for i in 0..k1_buf.len() {
-        if k1_buf[i] != 0 {
-            println!("{:4x}: {:8x}", i * 4, k1_buf[i]);
+    for (i, &val) in k1_buf.iter().enumerate() {
+        if val != 0 {
+            println!("{:4x}: {:8x}", i * 4, val);
         }
     }
 }
@@ -55,6 +124,7 @@ pub struct RenderConfig {
     width: usize,
     height: usize,
     format: PixelFormat,
+    max_filter_layers: usize,
 }
 
 // Should we just use the enum from piet-gpu-hal?
@@ -110,18 +180,67 @@ pub struct Renderer {
     k4_pipeline: Pipeline,
     k4_ds: DescriptorSet,
 
+    counts: SceneCounts,
+    // Offset of the draw object bounding boxes in device memory.
+    draw_bbox_alloc: u32,
+    // Tile region set by `set_damage`, as x0, y0, x1, y1.
+    damage: Option<[u32; 4]>,
+    // Tile region rendered for the uploaded scene.
+    render_tiles: [u32; 4],
+    // Whether the uploaded scene preserves the image outside `render_tiles`.
+    preserve_image: bool,
+
+    filter: Option<FilterResources>,
+
+    // Keep a reference to the image so that it is not destroyed.
+    _bg_image: Image,
+
+    gradient_bufs: Vec<Buffer>,
+    gradients: Image,
+}
+
+/// Element counts of an uploaded scene, which determine dispatch sizes.
+#[derive(Clone, Copy, Default)]
+struct SceneCounts {
     n_transform: usize,
     n_drawobj: usize,
     n_paths: usize,
     n_pathseg: usize,
     n_pathtag: usize,
     n_clip: u32,
+}
 
-    // Keep a reference to the image so that it is not destroyed.
-    _bg_image: Image,
+/// Resources for rendering filter layers.
+///
+/// Each layer is rendered to the output image and then filtered into its
+/// slot in the atlas, where slots are stacked vertically.
+struct FilterResources {
+    code: FilterCode,
+    atlas: Image,
+    scratch: Image,
+    max_layers: usize,
 
-    gradient_bufs: Vec<Buffer>,
-    gradients: Image,
+    // These are indexed by buffer, then by atlas slot.
+    params_bufs: Vec<Vec<Buffer>>,
+    config_bufs: Vec<Vec<Buffer>>,
+    memory_bufs: Vec<Vec<Buffer>>,
+    bindings: Vec<Vec<FilterBinding>>,
+
+    // Counts of the uploaded layers, in rendering order.
+    layers: Vec<SceneCounts>,
+}
+
+impl SceneCounts {
+    fn new<T: Copy + Pod>(scene: &EncodedSceneRef<T>) -> SceneCounts {
+        SceneCounts {
+            n_transform: scene.n_transform(),
+            n_drawobj: scene.n_drawobj(),
+            n_paths: scene.n_path() as usize,
+            n_pathseg: scene.n_pathseg() as usize,
+            n_pathtag: scene.n_pathtag(),
+            n_clip: scene.n_clip(),
+        }
+    }
 }
 
 impl RenderConfig {
@@ -130,6 +249,7 @@ impl RenderConfig {
             width,
             height,
             format: PixelFormat::Rgba8,
+            max_filter_layers: 0,
         }
     }
 
@@ -137,6 +257,17 @@ impl RenderConfig {
         self.format = format;
         self
     }
+
+    /// Set the maximum number of filter layers in a scene.
+    ///
+    /// Each layer reserves an image the size of the output in the image
+    /// atlas, which then replaces the test image. Filter layers are only
+    /// supported with the `Rgba8` pixel format, and not yet on DX12, where
+    /// creating the renderer fails.
+    pub fn filter_layers(mut self, max_filter_layers: usize) -> Self {
+        self.max_filter_layers = max_filter_layers;
+        self
+    }
 }
 
 impl Renderer {
@@ -159,6 +290,16 @@ impl Renderer {
         config: RenderConfig,
         n_bufs: usize,
     ) -> Result<Self, Error> {
+        if session.backend_type() == BackendType::Dx12 && !STALE_DXIL.is_empty() {
+            let mut shaders = STALE_DXIL.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            shaders.sort_unstable();
+            shaders.dedup();
+            return Err(format!(
+                "the DXIL of {} is out of date, rebuild it with dxc to render on DX12",
+                shaders.join(", ")
+            )
+            .into());
+        }
         // For now, round up to tile alignment
         let width = config.width;
         let height = config.height;
@@ -192,7 +333,9 @@ impl Renderer {
         let memory_buf_host = (0..n_bufs)
             .map(|_| session.create_buffer(2 * 4, host_upload).unwrap())
             .collect();
-        let memory_buf_dev = session.create_buffer(128 * 1024 * 1024, dev)?;
+        // Also a copy source, for reading back draw object bounding boxes.
+        let memory_buf_dev =
+            session.create_buffer(128 * 1024 * 1024, dev | BufferUsage::COPY_SRC)?;
 
         let element_code = ElementCode::new(session);
         let element_stage = ElementStage::new(session, &element_code);
@@ -274,7 +417,7 @@ impl Renderer {
                 )
             })
             .collect::<Result<Vec<_>, _>>()?;
-        let bg_image = Self::make_test_bg_image(&session);
+        let bg_image = Self::make_test_bg_image(session);
 
         const GRADIENT_BUF_SIZE: usize =
             crate::gradient::N_GRADIENTS * crate::gradient::N_SAMPLES * 4;
@@ -285,7 +428,62 @@ impl Renderer {
                     .unwrap()
             })
             .collect();
-        let gradients = Self::make_gradient_image(&session);
+        let gradients = Self::make_gradient_image(session);
+
+        let filter = match config.format {
+            PixelFormat::Rgba8 if config.max_filter_layers > 0 => {
+                let max_layers = config.max_filter_layers;
+                // Atlas offsets are encoded as 16 bit signed integers.
+                if height * max_layers > i16::MAX as usize {
+                    return Err("too many filter layers for image size".into());
+                }
+                let atlas = session.create_image2d(
+                    width as u32,
+                    (height * max_layers) as u32,
+                    image_format,
+                )?;
+                let scratch = session.create_image2d(width as u32, height as u32, image_format)?;
+                let code = FilterCode::new(session)?;
+                const PARAMS_SIZE: u64 = std::mem::size_of::<FilterParams>() as u64;
+                let per_slot = |size, usage| {
+                    (0..n_bufs)
+                        .map(|_| {
+                            (0..max_layers)
+                                .map(|_| session.create_buffer(size, usage))
+                                .collect::<Result<Vec<_>, _>>()
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                };
+                let params_usage = BufferUsage::MAP_WRITE | BufferUsage::STORAGE;
+                let params_bufs = per_slot(PARAMS_SIZE, params_usage)?;
+                let config_bufs = per_slot(CONFIG_BUFFER_SIZE, host_upload)?;
+                let memory_bufs = per_slot(2 * 4, host_upload)?;
+                let bindings = params_bufs
+                    .iter()
+                    .map(|bufs| {
+                        bufs.iter()
+                            .map(|params| {
+                                FilterBinding::new(
+                                    session, &code, params, &image_dev, &scratch, &atlas,
+                                )
+                            })
+                            .collect()
+                    })
+                    .collect();
+                Some(FilterResources {
+                    code,
+                    atlas,
+                    scratch,
+                    max_layers,
+                    params_bufs,
+                    config_bufs,
+                    memory_bufs,
+                    bindings,
+                    layers: Vec::new(),
+                })
+            }
+            _ => None,
+        };
 
         let k4_code = match config.format {
             PixelFormat::A8 => include_shader!(session, "../shader/gen/kernel4_gray"),
@@ -305,8 +503,11 @@ impl Renderer {
             .descriptor_set_builder()
             .add_buffers(&[&memory_buf_dev, &config_buf])
             .add_images(&[&image_dev])
-            .add_textures(&[&bg_image, &gradients])
-            .build(&session, &k4_pipeline)?;
+            .add_textures(&[
+                filter.as_ref().map(|f| &f.atlas).unwrap_or(&bg_image),
+                &gradients,
+            ])
+            .build(session, &k4_pipeline)?;
 
         Ok(Renderer {
             width,
@@ -335,95 +536,138 @@ impl Renderer {
             coarse_ds,
             k4_pipeline,
             k4_ds,
-            n_transform: 0,
-            n_drawobj: 0,
-            n_paths: 0,
-            n_pathseg: 0,
-            n_pathtag: 0,
-            n_clip: 0,
+            counts: SceneCounts::default(),
+            draw_bbox_alloc: 0,
+            damage: None,
+            render_tiles: [0; 4],
+            preserve_image: false,
+            filter,
             _bg_image: bg_image,
             gradient_bufs,
             gradients,
         })
     }
 
-    /// Convert the scene in the render context to GPU resources.
+    /// Restrict rendering to the tiles covering the damage rectangles, in
+    /// pixels, preserving the rest of the image from the previous frame.
+    ///
+    /// This applies to scenes uploaded afterwards, until it is reset with
+    /// `None`. The previous frame must have rendered the whole image, and
+    /// scenes with filter layers are always rendered completely.
+    pub fn set_damage(&mut self, damage: Option<&[Rect]>) {
+        self.damage =
+            damage.map(|rects| damage_tiles(rects, self.width / TILE_W, self.height / TILE_H));
+    }
+
+    /// Convert an encoded scene and its filter layers to GPU resources.
     ///
     /// At present, this requires that any command buffer submission has completed.
     /// A future evolution will handle staging of the next frame's scene while the
     /// rendering of the current frame is in flight.
-    pub fn upload_render_ctx(
+    ///
+    /// Returns an error if the scene is structurally invalid.
+    pub fn upload_scene<T: Copy + Pod>(
         &mut self,
-        render_ctx: &mut PietGpuRenderContext,
+        scene: &EncodedSceneRef<T>,
         buf_ix: usize,
     ) -> Result<(), Error> {
-        let (mut config, mut alloc) = render_ctx.stage_config();
-        let n_drawobj = render_ctx.n_drawobj();
-        // TODO: be more consistent in size types
-        let n_path = render_ctx.n_path() as usize;
-        self.n_paths = n_path;
-        self.n_transform = render_ctx.n_transform();
-        self.n_drawobj = render_ctx.n_drawobj();
-        self.n_pathseg = render_ctx.n_pathseg() as usize;
-        self.n_pathtag = render_ctx.n_pathtag();
-        self.n_clip = render_ctx.n_clip();
-
-        // These constants depend on encoding and may need to be updated.
-        // Perhaps we can plumb these from piet-gpu-derive?
-        const PATH_SIZE: usize = 12;
-        const BIN_SIZE: usize = 8;
-        let width_in_tiles = self.width / TILE_W;
-        let height_in_tiles = self.height / TILE_H;
-        let tile_base = alloc;
-        alloc += ((n_path + 3) & !3) * PATH_SIZE;
-        let bin_base = alloc;
-        alloc += ((n_drawobj + 255) & !255) * BIN_SIZE;
-        let ptcl_base = alloc;
-        alloc += width_in_tiles * height_in_tiles * PTCL_INITIAL_ALLOC;
-
-        config.width_in_tiles = width_in_tiles as u32;
-        config.height_in_tiles = height_in_tiles as u32;
-        config.tile_alloc = tile_base as u32;
-        config.bin_alloc = bin_base as u32;
-        config.ptcl_alloc = ptcl_base as u32;
+        scene.validate()?;
+        // Filter layers are rendered before the scenes that composite them,
+        // so the main scene comes last.
+        let mut uploads = Vec::new();
+        flatten_filter_layers(scene, None, self.height, &mut uploads);
+        let n_layers = uploads.len() - 1;
+        if n_layers > 0 {
+            match &self.filter {
+                None => return Err("filter layers are not enabled".into()),
+                Some(filter) if n_layers > filter.max_layers => {
+                    return Err("too many filter layers in scene".into())
+                }
+                _ => (),
+            }
+        }
+        // Each scene is placed in the scene buffer after the previous one,
+        // with its stream offsets adjusted accordingly.
+        let mut layouts = Vec::with_capacity(uploads.len());
+        let mut scene_end = 0;
+        for upload in &uploads {
+            let base = (scene_end + 255) & !255;
+            let (mut config, alloc) = upload.scene.stage_config();
+            scene_end = base + config.pathseg_offset as usize + upload.scene.pathseg_stream.len();
+            let offset = base as u32;
+            config.trans_offset += offset;
+            config.linewidth_offset += offset;
+            config.pathtag_offset += offset;
+            config.pathseg_offset += offset;
+            config.drawtag_offset += offset;
+            config.drawdata_offset += offset;
+            let counts = SceneCounts::new(upload.scene);
+            let alloc = self.layout_memory(&mut config, alloc, &counts);
+            layouts.push((base..scene_end, config, alloc, counts));
+        }
         unsafe {
             // TODO: reallocate scene buffer if size is inadequate
             {
                 let mut mapped_scene = self.scene_bufs[buf_ix].map_write(..)?;
-                render_ctx.write_scene(&mut mapped_scene);
+                let mut scene_end = 0;
+                for (upload, (range, ..)) in uploads.iter().zip(&layouts) {
+                    mapped_scene.fill_zero(range.start - scene_end);
+                    upload
+                        .scene
+                        .write_scene_patched(&mut mapped_scene, &upload.patches);
+                    scene_end = range.end;
+                }
+            }
+            let mut layer_counts = Vec::with_capacity(n_layers);
+            for (slot, (upload, (_, mut config, alloc, counts))) in
+                uploads.iter().zip(layouts).enumerate()
+            {
+                let memory_header = [alloc as u32, 0 /* Overflow flag */];
+                match upload.filter {
+                    Some(filter_kind) => {
+                        let filter = self.filter.as_mut().unwrap();
+                        let params = FilterParams::new(filter_kind, (slot * self.height) as u32);
+                        filter.params_bufs[buf_ix][slot].write(&[params])?;
+                        filter.config_bufs[buf_ix][slot].write(&[config])?;
+                        filter.memory_bufs[buf_ix][slot].write(&memory_header)?;
+                        layer_counts.push(counts);
+                    }
+                    None => {
+                        self.preserve_image = apply_damage(&mut config, self.damage, n_layers);
+                        self.render_tiles = [
+                            config.damage_x0,
+                            config.damage_y0,
+                            config.damage_x1,
+                            config.damage_y1,
+                        ];
+                        self.config_bufs[buf_ix].write(&[config])?;
+                        self.memory_buf_host[buf_ix].write(&memory_header)?;
+                        self.counts = counts;
+                        self.draw_bbox_alloc = config.draw_bbox_alloc;
+                    }
+                }
+            }
+            if let Some(filter) = &mut self.filter {
+                filter.layers = layer_counts;
             }
-            self.config_bufs[buf_ix].write(&[config])?;
-            self.memory_buf_host[buf_ix].write(&[alloc as u32, 0 /* Overflow flag */])?;
 
             // Upload gradient data.
-            let ramp_data = render_ctx.get_ramp_data();
-            if !ramp_data.is_empty() {
+            if !scene.ramp_data.is_empty() {
                 assert!(
                     self.gradient_bufs[buf_ix].size() as usize
-                        >= std::mem::size_of_val(&*ramp_data)
+                        >= std::mem::size_of_val(scene.ramp_data)
                 );
-                self.gradient_bufs[buf_ix].write(&ramp_data)?;
+                self.gradient_bufs[buf_ix].write(scene.ramp_data)?;
             }
         }
         Ok(())
     }
 
-    pub fn upload_scene<T: Copy + Pod>(
-        &mut self,
-        scene: &EncodedSceneRef<T>,
-        buf_ix: usize,
-    ) -> Result<(), Error> {
-        let (mut config, mut alloc) = scene.stage_config();
-        let n_drawobj = scene.n_drawobj();
-        // TODO: be more consistent in size types
-        let n_path = scene.n_path() as usize;
-        self.n_paths = n_path;
-        self.n_transform = scene.n_transform();
-        self.n_drawobj = scene.n_drawobj();
-        self.n_pathseg = scene.n_pathseg() as usize;
-        self.n_pathtag = scene.n_pathtag();
-        self.n_clip = scene.n_clip();
-
+    /// Allocate memory for the coarse pipeline stages after the memory used
+    /// by the element stages, setting the offsets in the config.
+    ///
+    /// Returns the beginning of free memory.
+    fn layout_memory(&self, config: &mut Config, mut alloc: usize, counts: &SceneCounts) -> usize {
         // These constants depend on encoding and may need to be updated.
         // Perhaps we can plumb these from piet-gpu-derive?
         const PATH_SIZE: usize = 12;
@@ -431,47 +675,30 @@ impl Renderer {
         let width_in_tiles = self.width / TILE_W;
         let height_in_tiles = self.height / TILE_H;
         let tile_base = alloc;
-        alloc += ((n_path + 3) & !3) * PATH_SIZE;
+        alloc += ((counts.n_paths + 3) & !3) * PATH_SIZE;
         let bin_base = alloc;
-        alloc += ((n_drawobj + 255) & !255) * BIN_SIZE;
+        alloc += ((counts.n_drawobj + 255) & !255) * BIN_SIZE;
         let ptcl_base = alloc;
         alloc += width_in_tiles * height_in_tiles * PTCL_INITIAL_ALLOC;
 
         config.width_in_tiles = width_in_tiles as u32;
         config.height_in_tiles = height_in_tiles as u32;
+        config.damage_x1 = width_in_tiles as u32;
+        config.damage_y1 = height_in_tiles as u32;
         config.tile_alloc = tile_base as u32;
         config.bin_alloc = bin_base as u32;
         config.ptcl_alloc = ptcl_base as u32;
-        unsafe {
-            // TODO: reallocate scene buffer if size is inadequate
-            {
-                let mut mapped_scene = self.scene_bufs[buf_ix].map_write(..)?;
-                scene.write_scene(&mut mapped_scene);
-            }
-            self.config_bufs[buf_ix].write(&[config])?;
-            self.memory_buf_host[buf_ix].write(&[alloc as u32, 0 /* Overflow flag */])?;
-
-            // Upload gradient data.
-            if !scene.ramp_data.is_empty() {
-                assert!(
-                    self.gradient_bufs[buf_ix].size() as usize
-                        >= std::mem::size_of_val(&*scene.ramp_data)
-                );
-                self.gradient_bufs[buf_ix].write(scene.ramp_data)?;
-            }
-        }
-        Ok(())
+        alloc
     }
 
     pub unsafe fn record(&self, cmd_buf: &mut CmdBuf, query_pool: &QueryPool, buf_ix: usize) {
-        cmd_buf.copy_buffer(&self.config_bufs[buf_ix], &self.config_buf);
-        cmd_buf.copy_buffer(&self.memory_buf_host[buf_ix], &self.memory_buf_dev);
-        cmd_buf.memory_barrier();
-        cmd_buf.image_barrier(
-            &self.image_dev,
-            ImageLayout::Undefined,
-            ImageLayout::General,
-        );
+        // The image is left ready to blit from by the previous frame.
+        let image_layout = if self.preserve_image {
+            ImageLayout::BlitSrc
+        } else {
+            ImageLayout::Undefined
+        };
+        cmd_buf.image_barrier(&self.image_dev, image_layout, ImageLayout::General);
         // TODO: make gradient upload optional, only if it's changed
         cmd_buf.image_barrier(
             &self.gradients,
@@ -480,31 +707,126 @@ impl Renderer {
         );
         cmd_buf.copy_buffer_to_image(&self.gradient_bufs[buf_ix], &self.gradients);
         cmd_buf.image_barrier(&self.gradients, ImageLayout::BlitDst, ImageLayout::General);
-        cmd_buf.reset_query_pool(&query_pool);
+        cmd_buf.reset_query_pool(query_pool);
+        if let Some(filter) = &self.filter {
+            cmd_buf.image_barrier(
+                &filter.scratch,
+                ImageLayout::Undefined,
+                ImageLayout::General,
+            );
+            cmd_buf.image_barrier(&filter.atlas, ImageLayout::Undefined, ImageLayout::General);
+            // Each layer is rendered with the full pipeline, then filtered
+            // into its slot in the atlas.
+            for (slot, counts) in filter.layers.iter().enumerate() {
+                cmd_buf.begin_debug_label("Filter layer");
+                cmd_buf.copy_buffer(&filter.config_bufs[buf_ix][slot], &self.config_buf);
+                cmd_buf.copy_buffer(&filter.memory_bufs[buf_ix][slot], &self.memory_buf_dev);
+                cmd_buf.memory_barrier();
+                self.record_pipeline(cmd_buf, None, buf_ix, counts, self.full_tiles());
+                let mut pass = cmd_buf.begin_compute_pass(&ComputePassDescriptor::default());
+                filter.bindings[buf_ix][slot].record(
+                    &mut pass,
+                    &filter.code,
+                    self.width as u32,
+                    self.height as u32,
+                );
+                pass.end();
+                cmd_buf.memory_barrier();
+                cmd_buf.end_debug_label();
+            }
+        }
+        cmd_buf.copy_buffer(&self.config_bufs[buf_ix], &self.config_buf);
+        cmd_buf.copy_buffer(&self.memory_buf_host[buf_ix], &self.memory_buf_dev);
+        cmd_buf.memory_barrier();
+        self.record_pipeline(
+            cmd_buf,
+            Some(query_pool),
+            buf_ix,
+            &self.counts,
+            self.render_tiles,
+        );
+        cmd_buf.image_barrier(&self.image_dev, ImageLayout::General, ImageLayout::BlitSrc);
+    }
+
+    /// The size in bytes of the draw object bounding boxes of the uploaded
+    /// scene, for allocating the buffer passed to `record_draw_bbox_readback`.
+    pub fn draw_bbox_size(&self) -> u64 {
+        (self.counts.n_drawobj * encoder::DRAW_BBOX_SIZE) as u64
+    }
+
+    /// Record a copy of the draw object bounding boxes of the uploaded scene
+    /// to `dst`, which must be at least `draw_bbox_size` bytes.
+    ///
+    /// This must be recorded after `record`. Once the command buffer has
+    /// completed, the buffer can be read as `[f32; 4]` boxes in pixels, one
+    /// per draw object and intersected with enclosing clips.
+    pub unsafe fn record_draw_bbox_readback(&self, cmd_buf: &mut CmdBuf, dst: &Buffer) {
+        let size = self.draw_bbox_size();
+        if size == 0 {
+            return;
+        }
+        cmd_buf.memory_barrier();
+        cmd_buf.copy_buffer_range(
+            &self.memory_buf_dev,
+            self.draw_bbox_alloc as u64,
+            dst,
+            0,
+            size,
+        );
+        cmd_buf.host_barrier();
+    }
+
+    /// The tile region covering the whole output image.
+    fn full_tiles(&self) -> [u32; 4] {
+        [
+            0,
+            0,
+            (self.width / TILE_W) as u32,
+            (self.height / TILE_H) as u32,
+        ]
+    }
+
+    /// Record the pipeline stages rendering a scene to the output image.
+    ///
+    /// Timer queries are recorded if a query pool is given. Coarse and fine
+    /// rasterization are dispatched over the tile region given as x0, y0,
+    /// x1, y1, which must match the damage region in the config.
+    unsafe fn record_pipeline(
+        &self,
+        cmd_buf: &mut CmdBuf,
+        query_pool: Option<&QueryPool>,
+        buf_ix: usize,
+        counts: &SceneCounts,
+        [x0, y0, x1, y1]: [u32; 4],
+    ) {
+        let timer = |start, end| match query_pool {
+            Some(pool) => ComputePassDescriptor::timer(pool, start, end),
+            None => ComputePassDescriptor::default(),
+        };
         cmd_buf.begin_debug_label("Element bounding box calculation");
-        let mut pass = cmd_buf.begin_compute_pass(&ComputePassDescriptor::timer(&query_pool, 0, 1));
+        let mut pass = cmd_buf.begin_compute_pass(&timer(0, 1));
         self.element_stage.record(
             &mut pass,
             &self.element_code,
             &self.element_bindings[buf_ix],
-            self.n_transform as u64,
-            self.n_paths as u32,
-            self.n_pathtag as u32,
-            self.n_drawobj as u64,
+            counts.n_transform as u64,
+            counts.n_paths as u32,
+            counts.n_pathtag as u32,
+            counts.n_drawobj as u64,
         );
         pass.end();
         cmd_buf.end_debug_label();
         cmd_buf.memory_barrier();
-        let mut pass = cmd_buf.begin_compute_pass(&ComputePassDescriptor::timer(&query_pool, 2, 3));
+        let mut pass = cmd_buf.begin_compute_pass(&timer(2, 3));
         pass.begin_debug_label("Clip bounding box calculation");
         self.clip_binding
-            .record(&mut pass, &self.clip_code, self.n_clip as u32);
+            .record(&mut pass, &self.clip_code, counts.n_clip);
         pass.end_debug_label();
         pass.begin_debug_label("Element binning");
         pass.dispatch(
             &self.bin_pipeline,
             &self.bin_ds,
-            (((self.n_paths + 255) / 256) as u32, 1, 1),
+            (counts.n_paths.div_ceil(256) as u32, 1, 1),
             (256, 1, 1),
         );
         pass.end_debug_label();
@@ -513,29 +835,29 @@ impl Renderer {
         pass.dispatch(
             &self.tile_pipeline,
             &self.tile_ds[buf_ix],
-            (((self.n_paths + 255) / 256) as u32, 1, 1),
+            (counts.n_paths.div_ceil(256) as u32, 1, 1),
             (256, 1, 1),
         );
         pass.end_debug_label();
         pass.end();
         cmd_buf.begin_debug_label("Path flattening");
         cmd_buf.memory_barrier();
-        let mut pass = cmd_buf.begin_compute_pass(&ComputePassDescriptor::timer(&query_pool, 4, 5));
+        let mut pass = cmd_buf.begin_compute_pass(&timer(4, 5));
         pass.dispatch(
             &self.path_pipeline,
             &self.path_ds,
-            (((self.n_pathseg + 31) / 32) as u32, 1, 1),
+            (counts.n_pathseg.div_ceil(32) as u32, 1, 1),
             (32, 1, 1),
         );
         pass.end();
         cmd_buf.end_debug_label();
         cmd_buf.memory_barrier();
         cmd_buf.begin_debug_label("Backdrop propagation");
-        let mut pass = cmd_buf.begin_compute_pass(&ComputePassDescriptor::timer(&query_pool, 6, 7));
+        let mut pass = cmd_buf.begin_compute_pass(&timer(6, 7));
         pass.dispatch(
             &self.backdrop_pipeline,
             &self.backdrop_ds,
-            (((self.n_paths + 255) / 256) as u32, 1, 1),
+            (counts.n_paths.div_ceil(256) as u32, 1, 1),
             (256, self.backdrop_y, 1),
         );
         pass.end();
@@ -543,13 +865,13 @@ impl Renderer {
         // TODO: redo query accounting
         cmd_buf.memory_barrier();
         cmd_buf.begin_debug_label("Coarse raster");
-        let mut pass = cmd_buf.begin_compute_pass(&ComputePassDescriptor::timer(&query_pool, 8, 9));
+        let mut pass = cmd_buf.begin_compute_pass(&timer(8, 9));
         pass.dispatch(
             &self.coarse_pipeline,
             &self.coarse_ds[buf_ix],
             (
-                (self.width as u32 + 255) / 256,
-                (self.height as u32 + 255) / 256,
+                x1.div_ceil(BIN_W) - x0 / BIN_W,
+                y1.div_ceil(BIN_H) - y0 / BIN_H,
                 1,
             ),
             (256, 1, 1),
@@ -558,22 +880,16 @@ impl Renderer {
         cmd_buf.end_debug_label();
         cmd_buf.memory_barrier();
         cmd_buf.begin_debug_label("Fine raster");
-        let mut pass =
-            cmd_buf.begin_compute_pass(&ComputePassDescriptor::timer(&query_pool, 10, 11));
+        let mut pass = cmd_buf.begin_compute_pass(&timer(10, 11));
         pass.dispatch(
             &self.k4_pipeline,
             &self.k4_ds,
-            (
-                (self.width / TILE_W) as u32,
-                (self.height / TILE_H) as u32,
-                1,
-            ),
+            (x1 - x0, y1 - y0, 1),
             (8, 4, 1),
         );
         pass.end();
         cmd_buf.end_debug_label();
         cmd_buf.memory_barrier();
-        cmd_buf.image_barrier(&self.image_dev, ImageLayout::General, ImageLayout::BlitSrc);
     }
 
     pub fn make_image(
@@ -587,7 +903,7 @@ impl Renderer {
             if format != ImageFormat::RgbaPremul {
                 return Err("unsupported image format".into());
             }
-            let buffer = session.create_buffer_init(&buf, BufferUsage::COPY_SRC)?;
+            let buffer = session.create_buffer_init(buf, BufferUsage::COPY_SRC)?;
             const RGBA: piet_gpu_hal::ImageFormat = piet_gpu_hal::ImageFormat::Rgba8;
             let image = session.create_image2d(width.try_into()?, height.try_into()?, RGBA)?;
             let mut cmd_buf = session.cmd_buf()?;
@@ -636,3 +952,193 @@ impl Renderer {
         }
     }
 }
+
+/// A scene to be uploaded, with the filter applied to it if it is the
+/// content of a filter layer.
+struct SceneUpload<'s, 'a, T: Copy + Pod> {
+    scene: &'s EncodedSceneRef<'a, T>,
+    filter: Option<&'s Filter>,
+    // Atlas offsets of the image draws compositing filter layers.
+    patches: Vec<(usize, u32)>,
+}
+
+/// Flatten a tree of filter layers so that each layer comes after the layers
+/// it composites, assigning atlas slots in that order.
+fn flatten_filter_layers<'s, 'a, T: Copy + Pod>(
+    scene: &'s EncodedSceneRef<'a, T>,
+    filter: Option<&'s Filter>,
+    height: usize,
+    uploads: &mut Vec<SceneUpload<'s, 'a, T>>,
+) {
+    let mut patches = Vec::with_capacity(scene.filter_layers.len());
+    for layer in &scene.filter_layers {
+        flatten_filter_layers(&layer.scene, Some(&layer.filter), height, uploads);
+        let slot = uploads.len() - 1;
+        // The offset is the second word of the image draw data, with the
+        // vertical component in the upper 16 bits.
+        patches.push((layer.drawdata_offset + 4, ((slot * height) as u32) << 16));
+    }
+    patches.sort_unstable_by_key(|patch| patch.0);
+    uploads.push(SceneUpload {
+        scene,
+        filter,
+        patches,
+    });
+}
+
+/// The tiles covering the damage rectangles, in pixels, as x0, y0, x1, y1.
+///
+/// The region is clamped to the image, and is empty when no rectangle
+/// overlaps it.
+fn damage_tiles(rects: &[Rect], width_in_tiles: usize, height_in_tiles: usize) -> [u32; 4] {
+    let width_in_tiles = width_in_tiles as f32;
+    let height_in_tiles = height_in_tiles as f32;
+    let mut tiles = [u32::MAX, u32::MAX, 0, 0];
+    for rect in rects {
+        if rect.width() <= 0.0 || rect.height() <= 0.0 {
+            continue;
+        }
+        let x0 = (rect.min.x / TILE_W as f32)
+            .floor()
+            .clamp(0.0, width_in_tiles);
+        let y0 = (rect.min.y / TILE_H as f32)
+            .floor()
+            .clamp(0.0, height_in_tiles);
+        let x1 = (rect.max.x / TILE_W as f32)
+            .ceil()
+            .clamp(0.0, width_in_tiles);
+        let y1 = (rect.max.y / TILE_H as f32)
+            .ceil()
+            .clamp(0.0, height_in_tiles);
+        tiles = [
+            tiles[0].min(x0 as u32),
+            tiles[1].min(y0 as u32),
+            tiles[2].max(x1 as u32),
+            tiles[3].max(y1 as u32),
+        ];
+    }
+    if tiles[0] >= tiles[2] || tiles[1] >= tiles[3] {
+        // Nothing to render.
+        tiles = [0; 4];
+    }
+    tiles
+}
+
+/// Restrict the tiles rendered by `config` to the damage region, returning
+/// whether the rest of the image is preserved.
+///
+/// Filter layers are rendered through the output image, so it can't be
+/// preserved when there are any, and the whole image is rendered.
+fn apply_damage(config: &mut Config, damage: Option<[u32; 4]>, n_layers: usize) -> bool {
+    match damage.filter(|_| n_layers == 0) {
+        Some([x0, y0, x1, y1]) => {
+            config.damage_x0 = x0;
+            config.damage_y0 = y0;
+            config.damage_x1 = x1;
+            config.damage_y1 = y1;
+            true
+        }
+        None => false,
+    }
+}
+
+/// The value of a `#define` in a shader header, for tests of the constants
+/// shared with the shaders.
+#[cfg(test)]
+fn shader_define(header: &str, name: &str) -> u32 {
+    header
+        .lines()
+        .find_map(|line| {
+            let mut words = line.strip_prefix("#define ")?.split_whitespace();
+            (words.next()? == name).then(|| words.next()).flatten()
+        })
+        .and_then(|value| match value.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        })
+        .unwrap_or_else(|| panic!("no #define {} in shader header", name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply_damage, damage_tiles};
+    use crate::stages::Config;
+    use piet_scene::geometry::{Point, Rect};
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Rect {
+        Rect {
+            min: Point::new(x0, y0),
+            max: Point::new(x1, y1),
+        }
+    }
+
+    #[test]
+    fn damage_rounds_out_to_tiles() {
+        // Tiles are 16 pixels square, and partially covered tiles are
+        // rendered.
+        assert_eq!(
+            damage_tiles(&[rect(17.0, 31.0, 33.0, 32.0)], 8, 8),
+            [1, 1, 3, 2]
+        );
+        assert_eq!(
+            damage_tiles(&[rect(16.0, 16.0, 32.0, 48.0)], 8, 8),
+            [1, 1, 2, 3]
+        );
+        assert_eq!(
+            damage_tiles(&[rect(0.5, 0.5, 1.0, 1.0)], 8, 8),
+            [0, 0, 1, 1]
+        );
+    }
+
+    #[test]
+    fn damage_clamped_to_image() {
+        assert_eq!(
+            damage_tiles(&[rect(-40.0, -1.0, 200.0, 20.0)], 8, 4),
+            [0, 0, 8, 2]
+        );
+        assert_eq!(
+            damage_tiles(&[rect(100.0, 60.0, 1e6, 1e6)], 8, 4),
+            [6, 3, 8, 4]
+        );
+        // Outside the image, so nothing is rendered.
+        assert_eq!(damage_tiles(&[rect(200.0, 0.0, 300.0, 10.0)], 8, 4), [0; 4]);
+        assert_eq!(
+            damage_tiles(&[rect(-20.0, -20.0, -1.0, -1.0)], 8, 4),
+            [0; 4]
+        );
+    }
+
+    #[test]
+    fn damage_union() {
+        let rects = [
+            rect(20.0, 100.0, 30.0, 110.0),
+            // Empty rectangles are ignored.
+            rect(0.0, 0.0, 0.0, 120.0),
+            rect(60.0, 40.0, 70.0, 50.0),
+        ];
+        assert_eq!(damage_tiles(&rects, 8, 8), [1, 2, 5, 7]);
+        assert_eq!(damage_tiles(&[], 8, 8), [0; 4]);
+    }
+
+    #[test]
+    fn damage_falls_back_with_filter_layers() {
+        let full = Config {
+            damage_x1: 8,
+            damage_y1: 8,
+            ..Default::default()
+        };
+        let mut config = full;
+        assert!(apply_damage(&mut config, Some([1, 2, 5, 7]), 0));
+        let region = |c: &Config| [c.damage_x0, c.damage_y0, c.damage_x1, c.damage_y1];
+        assert_eq!(region(&config), [1, 2, 5, 7]);
+
+        // Scenes with filter layers are rendered completely.
+        let mut config = full;
+        assert!(!apply_damage(&mut config, Some([1, 2, 5, 7]), 1));
+        assert_eq!(region(&config), [0, 0, 8, 8]);
+
+        let mut config = full;
+        assert!(!apply_damage(&mut config, None, 0));
+        assert_eq!(region(&config), [0, 0, 8, 8]);
+    }
+}
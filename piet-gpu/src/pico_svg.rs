@@ -1,146 +1,2859 @@
 //! A loader for a tiny fragment of SVG
 
+use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 
 use roxmltree::{Document, Node};
 
-use piet::kurbo::{Affine, BezPath};
+use piet::kurbo::{Affine, BezPath, Point, Rect, Shape, Size, SvgParseError};
 
-use piet::{Color, RenderContext};
+use piet::{
+    Color, FixedGradient, FixedLinearGradient, FixedRadialGradient, GradientStop, LineCap,
+    LineJoin, RenderContext, StrokeStyle,
+};
+
+use piet_scene::brush::{self, Brush, Extend};
+use piet_scene::geometry;
+use piet_scene::scene::{build_fragment, Fill, Fragment};
+
+use crate::render_ctx::{
+    to_scene_affine, to_scene_color, to_scene_elements, to_scene_point, to_scene_stops,
+    to_scene_stroke,
+};
+use crate::{Blend, BlendMode, Mask, PietGpuRenderContext};
 
 pub struct PicoSvg {
     items: Vec<Item>,
+    size: Size,
+    warnings: Vec<SvgWarning>,
 }
 
 pub enum Item {
     Fill(FillItem),
     Stroke(StrokeItem),
+    /// Begin a group, ended by `PopLayer`.
+    PushLayer(LayerItem),
+    PopLayer,
+    /// Begin the content of a mask, followed by `BeginMaskedContent`, the
+    /// masked content and `PopMask`.
+    PushMask(MaskItem),
+    BeginMaskedContent,
+    PopMask,
+}
+
+/// A group clipped to `path`, composited with `blend` and `alpha`.
+pub struct LayerItem {
+    path: BezPath,
+    /// The blend mode, or `None` for a clip.
+    blend: Option<Blend>,
+    alpha: f32,
+}
+
+pub struct MaskItem {
+    path: BezPath,
+    mask: Mask,
+}
+
+/// An error loading an SVG document.
+#[derive(Debug)]
+pub struct SvgError {
+    kind: SvgErrorKind,
+    location: Option<SvgLocation>,
+}
+
+#[derive(Debug)]
+pub enum SvgErrorKind {
+    /// The document is not well-formed XML.
+    Xml(roxmltree::Error),
+    /// A required attribute is missing.
+    MissingAttribute(&'static str),
+    /// The value of an attribute or style property can't be parsed.
+    InvalidAttribute { name: String, value: String },
+    /// The data of a `path` element can't be parsed.
+    InvalidPath(SvgParseError),
+}
+
+/// The element of a document where an error or warning occurred.
+#[derive(Clone, Debug)]
+pub struct SvgLocation {
+    /// The path of the element from the root, such as `svg/g[2]/path#leaf`.
+    pub element: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// A problem that didn't prevent loading a document.
+#[derive(Debug)]
+pub enum SvgWarning {
+    /// An error tolerated in lenient mode, where the element with the error
+    /// is skipped.
+    Error(SvgError),
+    /// An unsupported element or attribute was ignored.
+    Unsupported {
+        feature: String,
+        location: SvgLocation,
+    },
 }
 
 pub struct StrokeItem {
     width: f64,
-    color: Color,
+    style: StrokeStyle,
+    paint: Paint,
     path: BezPath,
 }
 
 pub struct FillItem {
-    color: Color,
+    paint: Paint,
     path: BezPath,
 }
 
+#[derive(Clone)]
+enum Paint {
+    Color(Color),
+    LinearGradient(LinearGradient),
+    RadialGradient(RadialGradient),
+}
+
+/// A linear gradient in its own space, which `transform` maps to the image.
+#[derive(Clone)]
+struct LinearGradient {
+    start: Point,
+    end: Point,
+    stops: Vec<GradientStop>,
+    spread: SpreadMethod,
+    transform: Affine,
+}
+
+/// A radial gradient in its own space, which `transform` maps to the image.
+#[derive(Clone)]
+struct RadialGradient {
+    center: Point,
+    radius: f64,
+    focus: Point,
+    focus_radius: f64,
+    stops: Vec<GradientStop>,
+    spread: SpreadMethod,
+    transform: Affine,
+}
+
+#[derive(Clone, Copy)]
+enum SpreadMethod {
+    Pad,
+    Reflect,
+    Repeat,
+}
+
+impl From<SpreadMethod> for Extend {
+    fn from(spread: SpreadMethod) -> Extend {
+        match spread {
+            SpreadMethod::Pad => Extend::Pad,
+            SpreadMethod::Reflect => Extend::Reflect,
+            SpreadMethod::Repeat => Extend::Repeat,
+        }
+    }
+}
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+
+impl SvgError {
+    pub fn kind(&self) -> &SvgErrorKind {
+        &self.kind
+    }
+
+    /// The element where the error occurred, if any.
+    pub fn location(&self) -> Option<&SvgLocation> {
+        self.location.as_ref()
+    }
+
+    /// Attribute the error to an element, unless it already is.
+    fn locate(mut self, node: Node) -> SvgError {
+        if self.location.is_none() {
+            self.location = Some(SvgLocation::of(node));
+        }
+        self
+    }
+}
+
+impl From<SvgErrorKind> for SvgError {
+    fn from(kind: SvgErrorKind) -> SvgError {
+        SvgError {
+            kind,
+            location: None,
+        }
+    }
+}
+
+impl fmt::Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        match &self.kind {
+            SvgErrorKind::Xml(err) => write!(f, "{}", err),
+            SvgErrorKind::MissingAttribute(name) => write!(f, "missing '{}' attribute", name),
+            SvgErrorKind::InvalidAttribute { name, value } => {
+                write!(f, "invalid {} '{}'", name, value)
+            }
+            SvgErrorKind::InvalidPath(err) => write!(f, "invalid path data: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SvgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            SvgErrorKind::Xml(err) => Some(err),
+            SvgErrorKind::InvalidPath(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl SvgLocation {
+    fn of(node: Node) -> SvgLocation {
+        let pos = node.document().text_pos_at(node.range().start);
+        let mut path = node
+            .ancestors()
+            .filter(|node| node.is_element())
+            .map(element_name)
+            .collect::<Vec<_>>();
+        path.reverse();
+        SvgLocation {
+            element: path.join("/"),
+            line: pos.row,
+            column: pos.col,
+        }
+    }
+}
+
+impl fmt::Display for SvgLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{} in {}", self.line, self.column, self.element)
+    }
+}
+
+impl fmt::Display for SvgWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SvgWarning::Error(err) => write!(f, "skipped element, {}", err),
+            SvgWarning::Unsupported { feature, location } => {
+                write!(f, "{}: unsupported {}", location, feature)
+            }
+        }
+    }
+}
+
 struct Parser<'a> {
-    scale: f64,
-    items: &'a mut Vec<Item>,
+    transform: Affine,
+    /// The size of the viewport in user units, for percentage lengths.
+    viewport: Size,
+    style: Style,
+    /// Elements by id, for references.
+    ids: HashMap<&'a str, Node<'a, 'a>>,
+    /// The elements instanced by the enclosing `use` and `mask` elements.
+    use_stack: Vec<Node<'a, 'a>>,
+    /// The bounds of the document in output space, for unclipped layers.
+    bounds: Rect,
+    /// Whether to skip elements with errors rather than fail.
+    lenient: bool,
+    items: Vec<Item>,
+    warnings: Vec<SvgWarning>,
+}
+
+/// The layers opened by an element, to be closed after its content.
+struct Group {
+    layer: bool,
+    mask: bool,
+}
+
+/// Presentation attributes inherited from ancestors.
+#[derive(Clone)]
+struct Style {
+    /// The color used for `currentColor`.
+    color: Color,
+    /// The fill paint, or `None` for no fill.
+    fill: Option<StylePaint>,
+    fill_opacity: f64,
+    stroke: Option<StylePaint>,
+    stroke_opacity: f64,
+    stroke_width: f64,
+    stroke_linejoin: LineJoin,
+    stroke_linecap: LineCap,
+    stroke_miterlimit: f64,
+    /// The dash lengths, or empty for a solid stroke.
+    stroke_dasharray: Vec<f64>,
+    stroke_dashoffset: f64,
+}
+
+/// A paint as specified, before references are resolved.
+#[derive(Clone)]
+enum StylePaint {
+    Color(Color),
+    /// A reference to a paint server by id, with an optional fallback.
+    Url(String, Option<Color>),
 }
 
 impl PicoSvg {
-    pub fn load(xml_string: &str, scale: f64) -> Result<PicoSvg, Box<dyn std::error::Error>> {
-        let doc = Document::parse(xml_string)?;
+    /// Load an SVG document, scaling it from its size in pixels.
+    ///
+    /// A negative scale flips the document vertically. Any invalid element
+    /// is an error, see `load_lenient`.
+    pub fn load(xml_string: &str, scale: f64) -> Result<PicoSvg, SvgError> {
+        Self::load_with(xml_string, scale, false)
+    }
+
+    /// Load an SVG document like `load`, but skip the elements with errors,
+    /// which are reported as warnings.
+    ///
+    /// Only malformed XML and an invalid size of the document are errors.
+    pub fn load_lenient(xml_string: &str, scale: f64) -> Result<PicoSvg, SvgError> {
+        Self::load_with(xml_string, scale, true)
+    }
+
+    fn load_with(xml_string: &str, scale: f64, lenient: bool) -> Result<PicoSvg, SvgError> {
+        let doc = Document::parse(xml_string).map_err(SvgErrorKind::Xml)?;
         let root = doc.root_element();
-        let mut items = Vec::new();
-        let mut parser = Parser::new(&mut items, scale);
+        let (size, view_box) = parse_viewport(root).map_err(|err| err.locate(root))?;
+        let view_box_transform = match view_box {
+            Some(view_box) => view_box_transform(root, view_box, size),
+            None => Affine::default(),
+        };
+        let viewport = view_box.map_or(size, |view_box| view_box.size());
+        let s = scale.abs();
+        let size = Size::new(s * size.width, s * size.height);
+        let transform = if scale >= 0.0 {
+            Affine::scale(s)
+        } else {
+            Affine::new([s, 0.0, 0.0, -s, 0.0, size.height])
+        };
+        let bounds = size.to_rect();
+        let mut parser = Parser::new(&doc, transform * view_box_transform, viewport, bounds);
+        parser.lenient = lenient;
+        let result = parser.style.apply(root);
+        parser.tolerate(root, result)?;
+        parser.warn_unsupported_attributes(root);
         for node in root.children() {
             parser.rec_parse(node)?;
         }
-        Ok(PicoSvg { items })
+        Ok(PicoSvg {
+            items: parser.items,
+            size,
+            warnings: parser.warnings,
+        })
+    }
+
+    /// The problems found while loading, such as unsupported features.
+    pub fn warnings(&self) -> &[SvgWarning] {
+        &self.warnings
+    }
+
+    /// The size of the document in pixels, including the scale.
+    pub fn size(&self) -> Size {
+        self.size
     }
 
+    /// Render with any piet context.
+    ///
+    /// Layers are approximated with clips and by applying their alpha to
+    /// each item; blend modes and masks are ignored.
     pub fn render(&self, rc: &mut impl RenderContext) {
+        let mut alpha_stack = vec![1.0];
+        // Nesting depth of mask content, which is skipped.
+        let mut mask_depth = 0;
+        for item in &self.items {
+            match item {
+                Item::PushMask(_) => mask_depth += 1,
+                Item::BeginMaskedContent => mask_depth -= 1,
+                _ if mask_depth > 0 => {}
+                Item::PushLayer(layer) => {
+                    let _ = rc.save();
+                    rc.clip(&layer.path);
+                    let alpha = alpha_stack.last().unwrap() * layer.alpha as f64;
+                    alpha_stack.push(alpha);
+                }
+                Item::PopLayer => {
+                    let _ = rc.restore();
+                    alpha_stack.pop();
+                }
+                Item::PopMask => {}
+                _ => draw_item(rc, item, *alpha_stack.last().unwrap()),
+            }
+        }
+    }
+
+    /// Render with the layers of a piet-gpu context, which support blend
+    /// modes and masks.
+    pub fn render_layers(&self, rc: &mut PietGpuRenderContext) {
+        for item in &self.items {
+            match item {
+                Item::PushLayer(layer) => rc.push_layer(&layer.path, layer.blend, layer.alpha),
+                Item::PopLayer => rc.pop_layer(),
+                Item::PushMask(mask) => rc.push_mask(&mask.path, mask.mask),
+                Item::BeginMaskedContent => rc.begin_masked_content(),
+                Item::PopMask => rc.pop_mask(),
+                _ => draw_item(rc, item, 1.0),
+            }
+        }
+    }
+
+    /// Encode into a scene fragment, with gradients as fragment resources.
+    ///
+    /// The fragment can be appended to scenes many times, under different
+    /// transforms. Unlike piet brushes, gradients keep their transform.
+    pub fn to_fragment(&self) -> Fragment {
+        let mut fragment = Fragment::default();
+        let mut builder = build_fragment(&mut fragment);
         for item in &self.items {
             match item {
                 Item::Fill(fill_item) => {
-                    rc.fill(&fill_item.path, &fill_item.color);
-                    //rc.stroke(&fill_item.path, &fill_item.color, 1.0);
+                    let (brush, brush_transform) = fill_item.paint.to_scene_brush();
+                    let path = to_scene_elements(&fill_item.path);
+                    builder.fill(Fill::NonZero, &brush, brush_transform, &path);
                 }
                 Item::Stroke(stroke_item) => {
-                    rc.stroke(&stroke_item.path, &stroke_item.color, stroke_item.width);
+                    let (brush, brush_transform) = stroke_item.paint.to_scene_brush();
+                    let style = to_scene_stroke(stroke_item.width, Some(&stroke_item.style));
+                    let path = to_scene_elements(&stroke_item.path);
+                    builder.stroke(&style, &brush, brush_transform, &path);
+                }
+                Item::PushLayer(layer) => builder.push_layer_with_alpha(
+                    layer.blend.unwrap_or_default().into(),
+                    layer.alpha,
+                    to_scene_elements(&layer.path),
+                ),
+                Item::PopLayer => builder.pop_layer(),
+                Item::PushMask(mask) => {
+                    builder.push_mask(mask.mask.into(), to_scene_elements(&mask.path))
                 }
+                Item::BeginMaskedContent => builder.begin_masked_content(),
+                Item::PopMask => builder.pop_mask(),
+            }
+        }
+        // Layers are pushed and popped in pairs, so this cannot fail.
+        let _ = builder.finish();
+        fragment
+    }
+}
+
+/// Draw a fill or stroke item, with an opacity applied to its paint.
+fn draw_item(rc: &mut impl RenderContext, item: &Item, opacity: f64) {
+    match item {
+        Item::Fill(fill_item) => {
+            if let Some(brush) = fill_item.paint.with_opacity(opacity).make_brush(rc) {
+                rc.fill(&fill_item.path, &brush);
+            }
+        }
+        Item::Stroke(stroke_item) => {
+            if let Some(brush) = stroke_item.paint.with_opacity(opacity).make_brush(rc) {
+                rc.stroke_styled(
+                    &stroke_item.path,
+                    &brush,
+                    stroke_item.width,
+                    &stroke_item.style,
+                );
             }
         }
+        _ => {}
+    }
+}
+
+impl Paint {
+    fn with_opacity(&self, opacity: f64) -> Paint {
+        let with_stops = |stops: &[GradientStop]| {
+            stops
+                .iter()
+                .map(|stop| GradientStop {
+                    pos: stop.pos,
+                    color: with_opacity(stop.color.clone(), opacity),
+                })
+                .collect()
+        };
+        match self {
+            Paint::Color(color) => Paint::Color(with_opacity(color.clone(), opacity)),
+            Paint::LinearGradient(gradient) => Paint::LinearGradient(LinearGradient {
+                stops: with_stops(&gradient.stops),
+                ..gradient.clone()
+            }),
+            Paint::RadialGradient(gradient) => Paint::RadialGradient(RadialGradient {
+                stops: with_stops(&gradient.stops),
+                ..gradient.clone()
+            }),
+        }
+    }
+
+    /// Make a scene brush in the space of the paint, with the transform from
+    /// that space to the image.
+    fn to_scene_brush(&self) -> (Brush, Option<geometry::Affine>) {
+        match self {
+            Paint::Color(color) => (Brush::Solid(to_scene_color(color)), None),
+            Paint::LinearGradient(gradient) => (
+                Brush::LinearGradient(brush::LinearGradient {
+                    start: to_scene_point(gradient.start),
+                    end: to_scene_point(gradient.end),
+                    stops: to_scene_stops(&gradient.stops),
+                    extend: gradient.spread.into(),
+                }),
+                Some(to_scene_affine(gradient.transform)),
+            ),
+            Paint::RadialGradient(gradient) => (
+                Brush::RadialGradient(brush::RadialGradient {
+                    center0: to_scene_point(gradient.focus),
+                    radius0: gradient.focus_radius as f32,
+                    center1: to_scene_point(gradient.center),
+                    radius1: gradient.radius as f32,
+                    stops: to_scene_stops(&gradient.stops),
+                    extend: gradient.spread.into(),
+                }),
+                Some(to_scene_affine(gradient.transform)),
+            ),
+        }
+    }
+
+    /// Make a brush for the paint.
+    ///
+    /// Piet gradients can't be transformed or repeated, so gradients are
+    /// approximated by transforming their geometry, and padded.
+    fn make_brush<R: RenderContext>(&self, rc: &mut R) -> Option<R::Brush> {
+        let gradient = match self {
+            Paint::Color(color) => return Some(rc.solid_brush(color.clone())),
+            Paint::LinearGradient(gradient) => FixedGradient::Linear(FixedLinearGradient {
+                start: gradient.transform * gradient.start,
+                end: gradient.transform * gradient.end,
+                stops: gradient.stops.clone(),
+            }),
+            Paint::RadialGradient(gradient) => {
+                let center = gradient.transform * gradient.center;
+                let scale = gradient.transform.determinant().abs().sqrt();
+                FixedGradient::Radial(FixedRadialGradient {
+                    center,
+                    origin_offset: gradient.transform * gradient.focus - center,
+                    radius: scale * gradient.radius,
+                    stops: gradient.stops.clone(),
+                })
+            }
+        };
+        rc.gradient(gradient).ok()
     }
 }
 
 impl<'a> Parser<'a> {
-    fn new(items: &'a mut Vec<Item>, scale: f64) -> Parser<'a> {
-        Parser { scale, items }
+    fn new(doc: &'a Document, transform: Affine, viewport: Size, bounds: Rect) -> Parser<'a> {
+        let ids = doc
+            .descendants()
+            .filter_map(|node| Some((node.attribute("id")?, node)))
+            .collect();
+        Parser {
+            transform,
+            viewport,
+            style: Style::default(),
+            ids,
+            use_stack: Vec::new(),
+            bounds,
+            lenient: false,
+            items: Vec::new(),
+            warnings: Vec::new(),
+        }
     }
 
-    fn rec_parse(&mut self, node: Node) -> Result<(), Box<dyn std::error::Error>> {
-        let transform = if self.scale >= 0.0 {
-            Affine::scale(self.scale)
-        } else {
-            Affine::new([-self.scale, 0.0, 0.0, self.scale, 0.0, 1536.0])
+    fn rec_parse(&mut self, node: Node<'a, 'a>) -> Result<(), SvgError> {
+        if !node.is_element() {
+            return Ok(());
+        }
+        let saved_transform = self.transform;
+        let saved_style = self.style.clone();
+        let n_items = self.items.len();
+        let n_uses = self.use_stack.len();
+        let result = self.parse_element(node);
+        self.transform = saved_transform;
+        self.style = saved_style;
+        if result.is_err() {
+            // Drop the partial output of the element, whose layers may be
+            // unbalanced.
+            self.items.truncate(n_items);
+            self.use_stack.truncate(n_uses);
+        }
+        self.tolerate(node, result)
+    }
+
+    fn parse_element(&mut self, node: Node<'a, 'a>) -> Result<(), SvgError> {
+        if let Some(transform) = transform_attr(node, "transform")? {
+            self.transform *= transform;
+        }
+        self.style.apply(node)?;
+        self.warn_unsupported_attributes(node);
+        if let Some(group) = self.begin_group(node)? {
+            self.parse_content(node)?;
+            if group.mask {
+                self.items.push(Item::PopMask);
+            }
+            if group.layer {
+                self.items.push(Item::PopLayer);
+            }
+        }
+        Ok(())
+    }
+
+    /// Attribute an error to an element, unless it is located already, and
+    /// report it as a warning in lenient mode.
+    fn tolerate(&mut self, node: Node, result: Result<(), SvgError>) -> Result<(), SvgError> {
+        let err = match result {
+            Ok(()) => return Ok(()),
+            Err(err) => err.locate(node),
         };
-        if node.is_element() {
-            match node.tag_name().name() {
-                "g" => {
-                    for child in node.children() {
-                        self.rec_parse(child)?;
-                    }
+        if self.lenient {
+            self.warnings.push(SvgWarning::Error(err));
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    fn warn_unsupported(&mut self, node: Node, feature: String) {
+        self.warnings.push(SvgWarning::Unsupported {
+            feature,
+            location: SvgLocation::of(node),
+        });
+    }
+
+    fn warn_unsupported_attributes(&mut self, node: Node) {
+        let declarations = node
+            .attribute("style")
+            .map(parse_declarations)
+            .unwrap_or_default();
+        for name in ["filter", "marker-start", "marker-mid", "marker-end"] {
+            if let Some(value) = presentation_attr(node, &declarations, name) {
+                if value != "none" {
+                    self.warn_unsupported(node, format!("'{}' attribute", name));
                 }
-                "path" => {
-                    let d = node.attribute("d").ok_or("missing 'd' attribute")?;
-                    let bp = BezPath::from_svg(d)?;
-                    let path = transform * bp;
-                    // TODO: default fill color is black, but this is overridden in tiger to this logic.
-                    if let Some(fill_color) = node.attribute("fill") {
-                        if fill_color != "none" {
-                            let color = parse_color(fill_color);
-                            let color = modify_opacity(color, "fill-opacity", node);
-                            self.items.push(Item::Fill(FillItem {
-                                color,
-                                path: path.clone(),
-                            }));
-                        }
-                    }
-                    if let Some(stroke_color) = node.attribute("stroke") {
-                        if stroke_color != "none" {
-                            let width = self.scale.abs()
-                                * f64::from_str(
-                                    node.attribute("stroke-width").ok_or("missing width")?,
-                                )?;
-                            let color = parse_color(stroke_color);
-                            let color = modify_opacity(color, "stroke-opacity", node);
-                            self.items
-                                .push(Item::Stroke(StrokeItem { width, color, path }));
+            }
+        }
+        // Everything is filled with the non-zero rule.
+        for name in ["fill-rule", "clip-rule"] {
+            if presentation_attr(node, &declarations, name) == Some("evenodd") {
+                self.warn_unsupported(node, format!("'{}' of 'evenodd'", name));
+            }
+        }
+    }
+
+    fn parse_content(&mut self, node: Node<'a, 'a>) -> Result<(), SvgError> {
+        let viewport = self.viewport;
+        match node.tag_name().name() {
+            "g" => {
+                for child in node.children() {
+                    self.rec_parse(child)?;
+                }
+            }
+            "use" => {
+                let target = href(node).and_then(|id| self.ids.get(id)).copied();
+                // References to an enclosing instance would recurse forever.
+                if let Some(target) = target.filter(|target| !self.use_stack.contains(target)) {
+                    let x = length_attr(node, "x", viewport)?.unwrap_or(0.0);
+                    let y = length_attr(node, "y", viewport)?.unwrap_or(0.0);
+                    self.transform *= Affine::translate((x, y));
+                    self.use_stack.push(target);
+                    if target.tag_name().name() == "symbol" {
+                        self.style.apply(target)?;
+                        for child in target.children() {
+                            self.rec_parse(child)?;
                         }
+                    } else {
+                        self.rec_parse(target)?;
                     }
+                    self.use_stack.pop();
+                }
+            }
+            "path" | "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" => {
+                if let Some(path) = self.shape_path(node)? {
+                    self.add_shape(node, path)?;
+                }
+            }
+            // These are only rendered when referenced, or not at all.
+            "defs" | "symbol" | "clipPath" | "mask" | "linearGradient" | "radialGradient"
+            | "title" | "desc" | "metadata" => {}
+            name => {
+                // Elements of other namespaces, such as those of editors,
+                // are ignored silently.
+                if matches!(node.tag_name().namespace(), None | Some(SVG_NAMESPACE)) {
+                    self.warn_unsupported(node, format!("<{}> element", name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The path of a basic shape in user space, if the element is one.
+    fn shape_path(&self, node: Node) -> Result<Option<BezPath>, SvgError> {
+        let viewport = self.viewport;
+        let path = match node.tag_name().name() {
+            "path" => {
+                let d = node
+                    .attribute("d")
+                    .ok_or(SvgErrorKind::MissingAttribute("d"))?;
+                Some(BezPath::from_svg(d).map_err(SvgErrorKind::InvalidPath)?)
+            }
+            "rect" => rect_path(node, viewport)?,
+            "circle" => {
+                let r = length_attr(node, "r", viewport)?.unwrap_or(0.0);
+                ellipse_path(node, viewport, r, r)?
+            }
+            "ellipse" => {
+                let rx = length_attr(node, "rx", viewport)?;
+                let ry = length_attr(node, "ry", viewport)?;
+                // A missing radius is the same as the other one.
+                match rx.or(ry) {
+                    Some(r) => ellipse_path(node, viewport, rx.unwrap_or(r), ry.unwrap_or(r))?,
+                    None => None,
+                }
+            }
+            "line" => {
+                let mut path = BezPath::new();
+                path.move_to((
+                    length_attr(node, "x1", viewport)?.unwrap_or(0.0),
+                    length_attr(node, "y1", viewport)?.unwrap_or(0.0),
+                ));
+                path.line_to((
+                    length_attr(node, "x2", viewport)?.unwrap_or(0.0),
+                    length_attr(node, "y2", viewport)?.unwrap_or(0.0),
+                ));
+                Some(path)
+            }
+            "polyline" => poly_path(node, false)?,
+            "polygon" => poly_path(node, true)?,
+            _ => None,
+        };
+        Ok(path)
+    }
+
+    /// Open the layers for the clip path, mask, opacity and blend mode of an
+    /// element, or return `None` if they hide it entirely.
+    fn begin_group(&mut self, node: Node<'a, 'a>) -> Result<Option<Group>, SvgError> {
+        let declarations = node
+            .attribute("style")
+            .map(parse_declarations)
+            .unwrap_or_default();
+        let attr = |name| presentation_attr(node, &declarations, name);
+        let alpha = attr("opacity").map_or(1.0, parse_opacity);
+        let blend_mode = attr("mix-blend-mode").and_then(parse_blend_mode);
+        let clip = match attr("clip-path").and_then(|value| self.reference(value, "clipPath")) {
+            Some(clip) => {
+                let path = self.clip_path(node, clip)?;
+                if path.elements().is_empty() {
+                    return Ok(None);
+                }
+                Some(path)
+            }
+            None => None,
+        };
+        let mask = attr("mask")
+            .and_then(|value| self.reference(value, "mask"))
+            // A mask used by its own content is ignored there.
+            .filter(|mask| !self.use_stack.contains(mask));
+        let mask = match mask {
+            Some(mask) => match self.mask_region(node, mask)? {
+                Some((region, bbox)) => Some((mask, region, bbox)),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+        let group = Group {
+            layer: clip.is_some() || alpha < 1.0 || blend_mode.is_some(),
+            mask: mask.is_some(),
+        };
+        if group.layer {
+            // Without a clip path, the layer covers the whole document.
+            let path = clip.unwrap_or_else(|| self.bounds.to_path(0.1));
+            self.items.push(Item::PushLayer(LayerItem {
+                path,
+                blend: blend_mode.map(Blend::from),
+                alpha: alpha as f32,
+            }));
+        }
+        if let Some((mask, region, bbox)) = mask {
+            self.push_mask(mask, region, bbox)?;
+        }
+        Ok(Some(group))
+    }
+
+    /// Resolve a `url(#id)` reference to an element with the given tag name.
+    fn reference(&self, value: &str, tag: &str) -> Option<Node<'a, 'a>> {
+        let id = value
+            .trim()
+            .strip_prefix("url(")?
+            .strip_suffix(')')?
+            .trim()
+            .trim_matches(|c| c == '\'' || c == '"');
+        let node = *self.ids.get(id.strip_prefix('#')?)?;
+        Some(node).filter(|node| node.tag_name().name() == tag)
+    }
+
+    /// The path of a `clipPath` element applied to an element, in output
+    /// space.
+    ///
+    /// The shapes are combined into one path, so overlapping shapes of
+    /// opposite direction cancel out.
+    fn clip_path(&self, node: Node<'a, 'a>, clip: Node<'a, 'a>) -> Result<BezPath, SvgError> {
+        let mut transform = self.transform;
+        if clip.attribute("clipPathUnits") == Some("objectBoundingBox") {
+            match self
+                .object_bbox(node, Affine::default())?
+                .and_then(bbox_transform)
+            {
+                Some(bbox_transform) => transform *= bbox_transform,
+                None => return Ok(BezPath::new()),
+            }
+        }
+        if let Some(clip_transform) = transform_attr(clip, "transform")? {
+            transform *= clip_transform;
+        }
+        let mut path = BezPath::new();
+        for child in clip.children().filter(|child| child.is_element()) {
+            let mut child_transform = transform;
+            if let Some(transform) = transform_attr(child, "transform")? {
+                child_transform *= transform;
+            }
+            if let Some(shape) = self.shape_path(child)? {
+                for el in (child_transform * shape).elements() {
+                    path.push(*el);
                 }
-                _ => (),
             }
         }
+        Ok(path)
+    }
+
+    /// The region of a `mask` element applied to an element in its user
+    /// space, and the bounding box of the element, or `None` if the region
+    /// is empty.
+    fn mask_region(
+        &self,
+        node: Node<'a, 'a>,
+        mask: Node<'a, 'a>,
+    ) -> Result<Option<(Rect, Option<Rect>)>, SvgError> {
+        let bbox = self.object_bbox(node, Affine::default())?;
+        let user_space = mask.attribute("maskUnits") == Some("userSpaceOnUse");
+        let reference = if user_space {
+            self.viewport
+        } else {
+            Size::new(1.0, 1.0)
+        };
+        let coord = |name, default, reference| {
+            let value = mask.attribute(name).unwrap_or(default);
+            parse_coordinate(value, reference).ok_or_else(|| invalid_attribute(name, value))
+        };
+        let x = coord("x", "-10%", reference.width)?;
+        let y = coord("y", "-10%", reference.height)?;
+        let width = coord("width", "120%", reference.width)?;
+        let height = coord("height", "120%", reference.height)?;
+        let region = Rect::new(x, y, x + width, y + height);
+        let region = if user_space {
+            region
+        } else {
+            match bbox.and_then(bbox_transform) {
+                Some(transform) => (transform * region.to_path(0.1)).bounding_box(),
+                None => return Ok(None),
+            }
+        };
+        if region.width() <= 0.0 || region.height() <= 0.0 {
+            return Ok(None);
+        }
+        Ok(Some((region, bbox)))
+    }
+
+    /// Add a mask layer over a region with the content of a `mask` element.
+    fn push_mask(
+        &mut self,
+        mask: Node<'a, 'a>,
+        region: Rect,
+        bbox: Option<Rect>,
+    ) -> Result<(), SvgError> {
+        let declarations = mask
+            .attribute("style")
+            .map(parse_declarations)
+            .unwrap_or_default();
+        let mask_type = match presentation_attr(mask, &declarations, "mask-type") {
+            Some("alpha") => Mask::Alpha,
+            _ => Mask::Luminance,
+        };
+        self.items.push(Item::PushMask(MaskItem {
+            path: self.transform * region.to_path(0.1),
+            mask: mask_type,
+        }));
+        let content_transform = if mask.attribute("maskContentUnits") == Some("objectBoundingBox") {
+            bbox.and_then(bbox_transform)
+                .map(|bbox_transform| self.transform * bbox_transform)
+        } else {
+            Some(self.transform)
+        };
+        if let Some(transform) = content_transform {
+            let saved_transform = std::mem::replace(&mut self.transform, transform);
+            // The content inherits from the mask element, not the masked one.
+            let saved_style = std::mem::take(&mut self.style);
+            self.style.apply(mask)?;
+            self.use_stack.push(mask);
+            for child in mask.children() {
+                self.rec_parse(child)?;
+            }
+            self.use_stack.pop();
+            self.transform = saved_transform;
+            self.style = saved_style;
+        }
+        self.items.push(Item::BeginMaskedContent);
         Ok(())
     }
+
+    /// The bounding box of an element in its user space, mapped by
+    /// `transform`.
+    fn object_bbox(&self, node: Node<'a, 'a>, transform: Affine) -> Result<Option<Rect>, SvgError> {
+        if node.tag_name().name() != "g" {
+            return Ok(self
+                .shape_path(node)?
+                .map(|path| (transform * path).bounding_box()));
+        }
+        let mut bbox: Option<Rect> = None;
+        for child in node.children().filter(|child| child.is_element()) {
+            let mut child_transform = transform;
+            if let Some(transform) = transform_attr(child, "transform")? {
+                child_transform *= transform;
+            }
+            if let Some(child_bbox) = self.object_bbox(child, child_transform)? {
+                bbox = Some(bbox.map_or(child_bbox, |bbox| bbox.union(child_bbox)));
+            }
+        }
+        Ok(bbox)
+    }
+
+    /// Add the fill and stroke of a shape in user space.
+    fn add_shape(&mut self, node: Node, path: BezPath) -> Result<(), SvgError> {
+        let bbox = path.bounding_box();
+        let path = self.transform * path;
+        let style = &self.style;
+        let fill = match &style.fill {
+            Some(paint) => self.resolve_paint(paint, style.fill_opacity, bbox),
+            None => None,
+        };
+        let stroke = match &style.stroke {
+            Some(paint) => self.resolve_paint(paint, style.stroke_opacity, bbox),
+            None => None,
+        };
+        if let Some(paint) = fill {
+            self.items.push(Item::Fill(FillItem {
+                paint,
+                path: path.clone(),
+            }));
+        }
+        if let Some(paint) = stroke {
+            let declarations = node
+                .attribute("style")
+                .map(parse_declarations)
+                .unwrap_or_default();
+            // Strokes are widened in the transformed space, so lengths are
+            // scaled like the area of the path, unless they are in pixels.
+            let scale = match presentation_attr(node, &declarations, "vector-effect") {
+                Some("non-scaling-stroke") => 1.0,
+                _ => self.transform.determinant().abs().sqrt(),
+            };
+            let style = &self.style;
+            let stroke_style = StrokeStyle {
+                line_join: Some(style.stroke_linejoin),
+                line_cap: Some(style.stroke_linecap),
+                dash: if style.stroke_dasharray.is_empty() {
+                    None
+                } else {
+                    let dashes = style.stroke_dasharray.iter().map(|d| scale * d);
+                    Some((dashes.collect(), scale * style.stroke_dashoffset))
+                },
+                miter_limit: Some(style.stroke_miterlimit),
+            };
+            self.items.push(Item::Stroke(StrokeItem {
+                width: scale * style.stroke_width,
+                style: stroke_style,
+                paint,
+                path,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Resolve a paint for a shape with the given bounding box in user space.
+    fn resolve_paint(&self, paint: &StylePaint, opacity: f64, bbox: Rect) -> Option<Paint> {
+        match paint {
+            StylePaint::Color(color) => Some(Paint::Color(with_opacity(color.clone(), opacity))),
+            StylePaint::Url(id, fallback) => match self.ids.get(id.as_str()) {
+                Some(&node)
+                    if matches!(node.tag_name().name(), "linearGradient" | "radialGradient") =>
+                {
+                    self.gradient(node, opacity, bbox)
+                }
+                _ => fallback
+                    .clone()
+                    .map(|color| Paint::Color(with_opacity(color, opacity))),
+            },
+        }
+    }
+
+    /// Resolve a gradient element for a shape with the given bounding box.
+    fn gradient(&self, node: Node<'a, 'a>, opacity: f64, bbox: Rect) -> Option<Paint> {
+        // Attributes and stops that are not specified are taken from the
+        // referenced gradients.
+        let mut chain = vec![node];
+        while let Some(&next) = href(*chain.last().unwrap()).and_then(|id| self.ids.get(id)) {
+            let is_gradient = matches!(next.tag_name().name(), "linearGradient" | "radialGradient");
+            if !is_gradient || chain.contains(&next) {
+                break;
+            }
+            chain.push(next);
+        }
+        let attr = |name: &str| chain.iter().find_map(|node| node.attribute(name));
+        let mut transform = self.transform;
+        // Coordinates are relative to the bounding box unless specified
+        // otherwise, in which case percentages are relative to the viewport.
+        let reference = if attr("gradientUnits") == Some("userSpaceOnUse") {
+            self.viewport
+        } else {
+            transform *= bbox_transform(bbox)?;
+            Size::new(1.0, 1.0)
+        };
+        if let Some(gradient_transform) = attr("gradientTransform") {
+            transform *= parse_transform(gradient_transform)?;
+        }
+        let diagonal = (0.5 * (reference.width.powi(2) + reference.height.powi(2))).sqrt();
+        let coord = |name: &str, default: &str, reference: f64| {
+            parse_coordinate(attr(name).unwrap_or(default), reference)
+        };
+        let spread = match attr("spreadMethod") {
+            Some("reflect") => SpreadMethod::Reflect,
+            Some("repeat") => SpreadMethod::Repeat,
+            _ => SpreadMethod::Pad,
+        };
+        let stop_parent = chain
+            .iter()
+            .find(|node| node.children().any(|child| child.has_tag_name("stop")))?;
+        let mut stops: Vec<GradientStop> = Vec::new();
+        for stop in stop_parent
+            .children()
+            .filter(|child| child.has_tag_name("stop"))
+        {
+            let declarations = stop
+                .attribute("style")
+                .map(parse_declarations)
+                .unwrap_or_default();
+            let attr = |name| presentation_attr(stop, &declarations, name);
+            // Offsets are clamped to be increasing.
+            let offset = attr("offset")
+                .and_then(|offset| parse_fraction(offset, 1.0))
+                .unwrap_or(0.0);
+            let offset = stops
+                .last()
+                .map_or(offset, |last| offset.max(last.pos as f64));
+            let color = match attr("stop-color") {
+                Some("currentColor") => self.style.color.clone(),
+                Some(color) => parse_color(color)?,
+                None => Color::BLACK,
+            };
+            let stop_opacity = attr("stop-opacity").map_or(1.0, parse_opacity);
+            stops.push(GradientStop {
+                pos: offset as f32,
+                color: with_opacity(color, stop_opacity * opacity),
+            });
+        }
+        // A gradient with a single stop is a solid color.
+        if stops.len() == 1 {
+            return stops.pop().map(|stop| Paint::Color(stop.color));
+        }
+        if node.tag_name().name() == "linearGradient" {
+            Some(Paint::LinearGradient(LinearGradient {
+                start: Point::new(
+                    coord("x1", "0%", reference.width)?,
+                    coord("y1", "0%", reference.height)?,
+                ),
+                end: Point::new(
+                    coord("x2", "100%", reference.width)?,
+                    coord("y2", "0%", reference.height)?,
+                ),
+                stops,
+                spread,
+                transform,
+            }))
+        } else {
+            let cx = attr("cx").unwrap_or("50%");
+            let cy = attr("cy").unwrap_or("50%");
+            Some(Paint::RadialGradient(RadialGradient {
+                center: Point::new(
+                    parse_coordinate(cx, reference.width)?,
+                    parse_coordinate(cy, reference.height)?,
+                ),
+                radius: coord("r", "50%", diagonal)?,
+                // The focus defaults to the center.
+                focus: Point::new(
+                    coord("fx", cx, reference.width)?,
+                    coord("fy", cy, reference.height)?,
+                ),
+                focus_radius: coord("fr", "0%", diagonal)?,
+                stops,
+                spread,
+                transform,
+            }))
+        }
+    }
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            color: Color::BLACK,
+            fill: Some(StylePaint::Color(Color::BLACK)),
+            fill_opacity: 1.0,
+            stroke: None,
+            stroke_opacity: 1.0,
+            stroke_width: 1.0,
+            stroke_linejoin: LineJoin::Miter,
+            stroke_linecap: LineCap::Butt,
+            stroke_miterlimit: 4.0,
+            stroke_dasharray: Vec::new(),
+            stroke_dashoffset: 0.0,
+        }
+    }
 }
 
-fn parse_color(color: &str) -> Color {
-    if color.as_bytes()[0] == b'#' {
-        let mut hex = u32::from_str_radix(&color[1..], 16).unwrap();
-        if color.len() == 4 {
-            hex = (hex >> 8) * 0x110000 + ((hex >> 4) & 0xf) * 0x1100 + (hex & 0xf) * 0x11;
+impl Style {
+    /// Update the style with the presentation attributes of an element.
+    ///
+    /// Declarations in the `style` attribute take precedence over the
+    /// attributes of the same name.
+    fn apply(&mut self, node: Node) -> Result<(), SvgError> {
+        let declarations = node
+            .attribute("style")
+            .map(parse_declarations)
+            .unwrap_or_default();
+        let attr = |name| presentation_attr(node, &declarations, name);
+        if let Some(color) = attr("color") {
+            self.color = parse_color(color).ok_or_else(|| invalid_attribute("color", color))?;
+        }
+        if let Some(fill) = attr("fill") {
+            self.fill = self.paint("fill", fill)?;
+        }
+        if let Some(opacity) = attr("fill-opacity") {
+            self.fill_opacity = parse_opacity(opacity);
+        }
+        if let Some(stroke) = attr("stroke") {
+            self.stroke = self.paint("stroke", stroke)?;
+        }
+        if let Some(opacity) = attr("stroke-opacity") {
+            self.stroke_opacity = parse_opacity(opacity);
+        }
+        if let Some(width) = attr("stroke-width") {
+            self.stroke_width =
+                parse_length(width).ok_or_else(|| invalid_attribute("stroke-width", width))?;
+        }
+        // Invalid values of the remaining stroke properties are ignored.
+        match attr("stroke-linejoin") {
+            Some("miter" | "miter-clip" | "arcs") => self.stroke_linejoin = LineJoin::Miter,
+            Some("round") => self.stroke_linejoin = LineJoin::Round,
+            Some("bevel") => self.stroke_linejoin = LineJoin::Bevel,
+            _ => {}
+        }
+        match attr("stroke-linecap") {
+            Some("butt") => self.stroke_linecap = LineCap::Butt,
+            Some("round") => self.stroke_linecap = LineCap::Round,
+            Some("square") => self.stroke_linecap = LineCap::Square,
+            _ => {}
+        }
+        if let Some(limit) = attr("stroke-miterlimit").and_then(|limit| limit.parse().ok()) {
+            if limit >= 1.0 {
+                self.stroke_miterlimit = limit;
+            }
+        }
+        if let Some(dasharray) = attr("stroke-dasharray") {
+            self.stroke_dasharray = parse_dasharray(dasharray).unwrap_or_default();
         }
-        Color::from_rgba32_u32((hex << 8) + 0xff)
-    } else if color.starts_with("rgb(") {
-        let mut iter = color[4..color.len() - 1].split(',');
-        let r = u8::from_str(iter.next().unwrap()).unwrap();
-        let g = u8::from_str(iter.next().unwrap()).unwrap();
-        let b = u8::from_str(iter.next().unwrap()).unwrap();
-        Color::rgb8(r, g, b)
+        if let Some(offset) = attr("stroke-dashoffset").and_then(parse_length) {
+            self.stroke_dashoffset = offset;
+        }
+        Ok(())
+    }
+
+    /// Parse the paint of a property, which is `None` for no paint.
+    fn paint(&self, name: &str, paint: &str) -> Result<Option<StylePaint>, SvgError> {
+        let invalid = || invalid_attribute(name, paint);
+        let paint = paint.trim();
+        if let Some(url) = paint.strip_prefix("url(") {
+            let (id, fallback) = url.split_once(')').ok_or_else(invalid)?;
+            let id = id.trim().trim_matches(|c| c == '\'' || c == '"');
+            let fallback = match self.paint(name, fallback)? {
+                Some(StylePaint::Color(color)) => Some(color),
+                _ => None,
+            };
+            return Ok(Some(StylePaint::Url(
+                id.strip_prefix('#').unwrap_or(id).to_string(),
+                fallback,
+            )));
+        }
+        match paint {
+            "" | "none" => Ok(None),
+            "currentColor" => Ok(Some(StylePaint::Color(self.color.clone()))),
+            _ => match parse_color(paint) {
+                Some(color) => Ok(Some(StylePaint::Color(color))),
+                None => Err(invalid()),
+            },
+        }
+    }
+}
+
+/// The transform from bounding box units to user space, or `None` if the
+/// bounding box is empty.
+fn bbox_transform(bbox: Rect) -> Option<Affine> {
+    if bbox.width() <= 0.0 || bbox.height() <= 0.0 {
+        return None;
+    }
+    Some(Affine::new([
+        bbox.width(),
+        0.0,
+        0.0,
+        bbox.height(),
+        bbox.x0,
+        bbox.y0,
+    ]))
+}
+
+/// Parse a `mix-blend-mode`, where `normal` is `None`.
+fn parse_blend_mode(mode: &str) -> Option<BlendMode> {
+    Some(match mode.trim() {
+        "multiply" => BlendMode::Multiply,
+        "screen" => BlendMode::Screen,
+        "overlay" => BlendMode::Overlay,
+        "darken" => BlendMode::Darken,
+        "lighten" => BlendMode::Lighten,
+        "color-dodge" => BlendMode::ColorDodge,
+        "color-burn" => BlendMode::ColorBurn,
+        "hard-light" => BlendMode::HardLight,
+        "soft-light" => BlendMode::SoftLight,
+        "difference" => BlendMode::Difference,
+        "exclusion" => BlendMode::Exclusion,
+        "hue" => BlendMode::Hue,
+        "saturation" => BlendMode::Saturation,
+        "color" => BlendMode::Color,
+        "luminosity" => BlendMode::Luminosity,
+        _ => return None,
+    })
+}
+
+/// Look up a presentation attribute, where declarations of the `style`
+/// attribute take precedence.
+fn presentation_attr<'a>(
+    node: Node<'a, '_>,
+    declarations: &[(&'a str, &'a str)],
+    name: &str,
+) -> Option<&'a str> {
+    declarations
+        .iter()
+        .rev()
+        .find(|(property, _)| *property == name)
+        .map(|(_, value)| *value)
+        .or_else(|| node.attribute(name))
+        .filter(|value| *value != "inherit")
+}
+
+/// The name of an element in an element path: its tag name, with its id
+/// or its position among its siblings of the same name.
+fn element_name(node: Node) -> String {
+    let name = node.tag_name().name();
+    if let Some(id) = node.attribute("id") {
+        return format!("{}#{}", name, id);
+    }
+    let siblings = node
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.children())
+        .filter(|sibling| sibling.tag_name().name() == name);
+    if siblings.clone().count() > 1 {
+        let index = siblings.take_while(|sibling| *sibling != node).count();
+        format!("{}[{}]", name, index + 1)
     } else {
-        Color::from_rgba32_u32(0xff00ff80)
+        name.to_string()
+    }
+}
+
+fn invalid_attribute(name: &str, value: &str) -> SvgError {
+    SvgErrorKind::InvalidAttribute {
+        name: name.to_string(),
+        value: value.to_string(),
     }
+    .into()
+}
+
+/// The id referenced by the `href` of an element.
+fn href<'a>(node: Node<'a, '_>) -> Option<&'a str> {
+    node.attribute(("http://www.w3.org/1999/xlink", "href"))
+        .or_else(|| node.attribute("href"))?
+        .strip_prefix('#')
 }
 
-fn modify_opacity(color: Color, attr_name: &str, node: Node) -> Color {
-    if let Some(opacity) = node.attribute(attr_name) {
-        let alpha = if opacity.ends_with("%") {
-            let pctg = opacity[..opacity.len() - 1].parse().unwrap_or(100.0);
-            pctg * 0.01
+/// Determine the size of the document and its view box, if any.
+fn parse_viewport(root: Node) -> Result<(Size, Option<Rect>), SvgError> {
+    let view_box = match root.attribute("viewBox") {
+        Some(view_box) => match parse_numbers(view_box).as_deref() {
+            Some(&[x, y, width, height]) if width > 0.0 && height > 0.0 => {
+                Some(Rect::new(x, y, x + width, y + height))
+            }
+            _ => return Err(invalid_attribute("viewBox", view_box)),
+        },
+        None => None,
+    };
+    let width = root.attribute("width").and_then(parse_length);
+    let height = root.attribute("height").and_then(parse_length);
+    let size = match (width, height, view_box) {
+        (Some(width), Some(height), _) => Size::new(width, height),
+        // A missing dimension follows the aspect ratio of the view box.
+        (Some(width), None, Some(vb)) => Size::new(width, width * vb.height() / vb.width()),
+        (None, Some(height), Some(vb)) => Size::new(height * vb.width() / vb.height(), height),
+        (None, None, Some(vb)) => vb.size(),
+        (width, height, None) => Size::new(width.unwrap_or(0.0), height.unwrap_or(0.0)),
+    };
+    Ok((size, view_box))
+}
+
+/// Determine the transform from the view box to a document of the given size.
+fn view_box_transform(root: Node, view_box: Rect, size: Size) -> Affine {
+    let mut sx = size.width / view_box.width();
+    let mut sy = size.height / view_box.height();
+    let mut aspect = root
+        .attribute("preserveAspectRatio")
+        .unwrap_or("xMidYMid meet")
+        .split_whitespace();
+    let align = aspect.next().unwrap_or("xMidYMid");
+    if align != "none" {
+        let scale = if aspect.next() == Some("slice") {
+            sx.max(sy)
         } else {
-            opacity.parse().unwrap_or(1.0)
+            sx.min(sy)
+        };
+        sx = scale;
+        sy = scale;
+    }
+    // Position of the view box within the viewport along each axis.
+    let fraction = |min: &str, max: &str| {
+        if align.contains(max) {
+            1.0
+        } else if align.contains(min) {
+            0.0
+        } else {
+            0.5
+        }
+    };
+    let tx = fraction("xMin", "xMax") * (size.width - sx * view_box.width());
+    let ty = fraction("YMin", "YMax") * (size.height - sy * view_box.height());
+    Affine::new([
+        sx,
+        0.0,
+        0.0,
+        sy,
+        tx - sx * view_box.x0,
+        ty - sy * view_box.y0,
+    ])
+}
+
+/// Parse the value of a `transform` attribute.
+fn parse_transform(transform: &str) -> Option<Affine> {
+    let mut result = Affine::default();
+    let mut rest = transform;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() {
+            return Some(result);
+        }
+        let (open, close) = match (rest.find('('), rest.find(')')) {
+            (Some(open), Some(close)) if open < close => (open, close),
+            _ => return None,
+        };
+        let name = rest[..open].trim();
+        let args = parse_numbers(&rest[open + 1..close])?;
+        rest = &rest[close + 1..];
+        let skew = |angle: f64| angle.to_radians().tan();
+        result *= match (name, &args[..]) {
+            ("matrix", &[a, b, c, d, e, f]) => Affine::new([a, b, c, d, e, f]),
+            ("translate", &[x]) => Affine::translate((x, 0.0)),
+            ("translate", &[x, y]) => Affine::translate((x, y)),
+            ("scale", &[s]) => Affine::scale(s),
+            ("scale", &[x, y]) => Affine::new([x, 0.0, 0.0, y, 0.0, 0.0]),
+            ("rotate", &[a]) => Affine::rotate(a.to_radians()),
+            ("rotate", &[a, x, y]) => {
+                Affine::translate((x, y))
+                    * Affine::rotate(a.to_radians())
+                    * Affine::translate((-x, -y))
+            }
+            ("skewX", &[a]) => Affine::new([1.0, 0.0, skew(a), 1.0, 0.0, 0.0]),
+            ("skewY", &[a]) => Affine::new([1.0, skew(a), 0.0, 1.0, 0.0, 0.0]),
+            _ => return None,
+        };
+    }
+}
+
+/// Parse an optional transform attribute.
+fn transform_attr(node: Node, name: &str) -> Result<Option<Affine>, SvgError> {
+    match node.attribute(name) {
+        Some(value) => match parse_transform(value) {
+            Some(transform) => Ok(Some(transform)),
+            None => Err(invalid_attribute(name, value)),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Parse the declarations of a `style` attribute into properties and values.
+fn parse_declarations(style: &str) -> Vec<(&str, &str)> {
+    style
+        .split(';')
+        .filter_map(|declaration| declaration.split_once(':'))
+        .map(|(property, value)| {
+            let value = value.trim();
+            let value = value.strip_suffix("!important").unwrap_or(value);
+            (property.trim(), value.trim_end())
+        })
+        .collect()
+}
+
+/// Parse an optional length attribute of a shape, in user units.
+///
+/// Percentages are relative to the size of the viewport along the axis of
+/// the attribute.
+fn length_attr(node: Node, name: &str, viewport: Size) -> Result<Option<f64>, SvgError> {
+    let reference = match name {
+        "x" | "cx" | "rx" | "x1" | "x2" | "width" => viewport.width,
+        "y" | "cy" | "ry" | "y1" | "y2" | "height" => viewport.height,
+        _ => (0.5 * (viewport.width.powi(2) + viewport.height.powi(2))).sqrt(),
+    };
+    match node.attribute(name) {
+        Some(value) => match value.trim().strip_suffix('%') {
+            Some(percent) => match percent.parse::<f64>() {
+                Ok(percent) => Ok(Some(0.01 * percent * reference)),
+                Err(_) => Err(invalid_attribute(name, value)),
+            },
+            None => match parse_length(value) {
+                Some(length) => Ok(Some(length)),
+                None => Err(invalid_attribute(name, value)),
+            },
+        },
+        None => Ok(None),
+    }
+}
+
+fn rect_path(node: Node, viewport: Size) -> Result<Option<BezPath>, SvgError> {
+    let x = length_attr(node, "x", viewport)?.unwrap_or(0.0);
+    let y = length_attr(node, "y", viewport)?.unwrap_or(0.0);
+    let width = length_attr(node, "width", viewport)?.unwrap_or(0.0);
+    let height = length_attr(node, "height", viewport)?.unwrap_or(0.0);
+    if width <= 0.0 || height <= 0.0 {
+        return Ok(None);
+    }
+    let rx = length_attr(node, "rx", viewport)?;
+    let ry = length_attr(node, "ry", viewport)?;
+    // A missing radius is the same as the other one.
+    let (rx, ry) = match (rx, ry) {
+        (Some(rx), Some(ry)) => (rx, ry),
+        (Some(r), None) | (None, Some(r)) => (r, r),
+        (None, None) => (0.0, 0.0),
+    };
+    let rect = Rect::new(x, y, x + width, y + height);
+    Ok(Some(rounded_rect_path(
+        rect,
+        rx.clamp(0.0, 0.5 * width),
+        ry.clamp(0.0, 0.5 * height),
+    )))
+}
+
+fn ellipse_path(node: Node, viewport: Size, rx: f64, ry: f64) -> Result<Option<BezPath>, SvgError> {
+    if rx <= 0.0 || ry <= 0.0 {
+        return Ok(None);
+    }
+    let cx = length_attr(node, "cx", viewport)?.unwrap_or(0.0);
+    let cy = length_attr(node, "cy", viewport)?.unwrap_or(0.0);
+    let rect = Rect::new(cx - rx, cy - ry, cx + rx, cy + ry);
+    Ok(Some(rounded_rect_path(rect, rx, ry)))
+}
+
+/// Build the path of a rectangle with elliptical corners, which is an
+/// ellipse when the radii are half the size.
+fn rounded_rect_path(rect: Rect, rx: f64, ry: f64) -> BezPath {
+    let mut path = BezPath::new();
+    if rx <= 0.0 || ry <= 0.0 {
+        path.move_to((rect.x0, rect.y0));
+        path.line_to((rect.x1, rect.y0));
+        path.line_to((rect.x1, rect.y1));
+        path.line_to((rect.x0, rect.y1));
+        path.close_path();
+        return path;
+    }
+    // Distance of the control points of a quarter arc from its ends.
+    const KAPPA: f64 = 0.5522847498;
+    let (kx, ky) = (KAPPA * rx, KAPPA * ry);
+    let Rect { x0, y0, x1, y1 } = rect;
+    path.move_to((x0 + rx, y0));
+    path.line_to((x1 - rx, y0));
+    path.curve_to((x1 - rx + kx, y0), (x1, y0 + ry - ky), (x1, y0 + ry));
+    path.line_to((x1, y1 - ry));
+    path.curve_to((x1, y1 - ry + ky), (x1 - rx + kx, y1), (x1 - rx, y1));
+    path.line_to((x0 + rx, y1));
+    path.curve_to((x0 + rx - kx, y1), (x0, y1 - ry + ky), (x0, y1 - ry));
+    path.line_to((x0, y0 + ry));
+    path.curve_to((x0, y0 + ry - ky), (x0 + rx - kx, y0), (x0 + rx, y0));
+    path.close_path();
+    path
+}
+
+fn poly_path(node: Node, close: bool) -> Result<Option<BezPath>, SvgError> {
+    let points = node.attribute("points").unwrap_or("");
+    let points = parse_numbers(points).ok_or_else(|| invalid_attribute("points", points))?;
+    // An odd coordinate at the end is ignored.
+    let mut points = points.chunks_exact(2);
+    let mut path = BezPath::new();
+    match points.next() {
+        Some(start) => path.move_to((start[0], start[1])),
+        None => return Ok(None),
+    }
+    for point in points {
+        path.line_to((point[0], point[1]));
+    }
+    if close {
+        path.close_path();
+    }
+    Ok(Some(path))
+}
+
+/// Parse a coordinate, where percentages are relative to `reference`.
+fn parse_coordinate(value: &str, reference: f64) -> Option<f64> {
+    match value.trim().strip_suffix('%') {
+        Some(percent) => percent.parse::<f64>().ok().map(|p| 0.01 * p * reference),
+        None => parse_length(value),
+    }
+}
+
+/// Parse a list of numbers separated by whitespace or commas.
+fn parse_numbers(list: &str) -> Option<Vec<f64>> {
+    let mut numbers = Vec::new();
+    for number in list
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|number| !number.is_empty())
+    {
+        numbers.push(f64::from_str(number).ok()?);
+    }
+    Some(numbers)
+}
+
+/// Parse a dash array, returning `None` for a solid stroke.
+fn parse_dasharray(dasharray: &str) -> Option<Vec<f64>> {
+    if dasharray.trim() == "none" {
+        return None;
+    }
+    let dashes = dasharray
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|dash| !dash.is_empty())
+        .map(|dash| parse_length(dash).filter(|dash| *dash >= 0.0))
+        .collect::<Option<Vec<f64>>>()?;
+    // Like an invalid one, a pattern of zero length draws a solid stroke.
+    if dashes.iter().sum::<f64>() > 0.0 {
+        Some(dashes)
+    } else {
+        None
+    }
+}
+
+/// Parse a length in pixels. Percentages are not supported.
+fn parse_length(length: &str) -> Option<f64> {
+    let length = length.trim();
+    let units = [
+        ("px", 1.0),
+        ("pt", 4.0 / 3.0),
+        ("pc", 16.0),
+        ("in", 96.0),
+        ("cm", 96.0 / 2.54),
+        ("mm", 96.0 / 25.4),
+    ];
+    for (suffix, scale) in units {
+        if let Some(value) = length.strip_suffix(suffix) {
+            return value.trim().parse::<f64>().ok().map(|value| value * scale);
+        }
+    }
+    length.parse().ok()
+}
+
+/// Parse a CSS color, returning `None` if it is invalid.
+fn parse_color(color: &str) -> Option<Color> {
+    let color = color.trim();
+    if let Some(hex) = color.strip_prefix('#') {
+        // from_str_radix would accept a sign.
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let digits = u32::from_str_radix(hex, 16).ok()?;
+        // Expand each hex digit of a short color to a byte.
+        let expand = |n: u32| {
+            (0..n).fold(0, |rgba, i| {
+                rgba | (((digits >> (4 * i)) & 0xf) * (0x11 << (8 * i)))
+            })
+        };
+        let rgba = match hex.len() {
+            3 => (expand(3) << 8) | 0xff,
+            4 => expand(4),
+            6 => (digits << 8) | 0xff,
+            8 => digits,
+            _ => return None,
+        };
+        return Some(Color::from_rgba32_u32(rgba));
+    }
+    if let Some((function, args)) = color.strip_suffix(')').and_then(|c| c.split_once('(')) {
+        let args: Vec<&str> = args
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .collect();
+        let alpha = match args.len() {
+            3 => 1.0,
+            4 => parse_fraction(args[3], 1.0)?,
+            _ => return None,
         };
-        color.with_alpha(alpha)
+        return match function.trim().to_ascii_lowercase().as_str() {
+            "rgb" | "rgba" => {
+                let channel = |arg| parse_fraction(arg, 255.0);
+                Some(Color::rgba(
+                    channel(args[0])?,
+                    channel(args[1])?,
+                    channel(args[2])?,
+                    alpha,
+                ))
+            }
+            "hsl" | "hsla" => {
+                let hue: f64 = args[0]
+                    .strip_suffix("deg")
+                    .unwrap_or(args[0])
+                    .parse()
+                    .ok()?;
+                let saturation = parse_fraction(args[1].strip_suffix('%')?, 100.0)?;
+                let lightness = parse_fraction(args[2].strip_suffix('%')?, 100.0)?;
+                Some(hsl(hue, saturation, lightness, alpha))
+            }
+            _ => None,
+        };
+    }
+    let name = color.to_ascii_lowercase();
+    if name == "transparent" {
+        return Some(Color::from_rgba32_u32(0));
+    }
+    let ix = NAMED_COLORS
+        .binary_search_by_key(&name.as_str(), |&(name, _)| name)
+        .ok()?;
+    Some(Color::from_rgba32_u32((NAMED_COLORS[ix].1 << 8) | 0xff))
+}
+
+/// Parse a number or percentage as a fraction in [0, 1], where numbers are
+/// divided by `max`.
+fn parse_fraction(value: &str, max: f64) -> Option<f64> {
+    let fraction = match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<f64>().ok()? * 0.01,
+        None => value.parse::<f64>().ok()? / max,
+    };
+    Some(fraction.clamp(0.0, 1.0))
+}
+
+/// Convert a color from HSL, with the hue in degrees.
+fn hsl(hue: f64, saturation: f64, lightness: f64, alpha: f64) -> Color {
+    let h = hue.rem_euclid(360.0) / 30.0;
+    let a = saturation * lightness.min(1.0 - lightness);
+    let f = |n: f64| {
+        let k = (n + h) % 12.0;
+        lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    Color::rgba(f(0.0), f(8.0), f(4.0), alpha)
+}
+
+fn parse_opacity(opacity: &str) -> f64 {
+    let opacity = if let Some(pctg) = opacity.strip_suffix('%') {
+        let pctg: f64 = pctg.parse().unwrap_or(100.0);
+        pctg * 0.01
     } else {
-        color
+        opacity.parse().unwrap_or(1.0)
+    };
+    opacity.clamp(0.0, 1.0)
+}
+
+/// Scale the alpha of a color by an opacity.
+fn with_opacity(color: Color, opacity: f64) -> Color {
+    let alpha = (color.as_rgba_u32() & 0xff) as f64 / 255.0;
+    color.with_alpha(alpha * opacity)
+}
+
+/// CSS color keywords, sorted by name.
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod test {
+    use piet::kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape};
+    use piet::{LineCap, LineJoin};
+    use roxmltree::Document;
+
+    use super::{
+        parse_color, parse_transform, parse_viewport, view_box_transform, Item, LinearGradient,
+        Paint, PicoSvg, RadialGradient, SpreadMethod, SvgErrorKind, SvgWarning,
+    };
+
+    fn load(body: &str) -> PicoSvg {
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">{}</svg>"#,
+            body
+        );
+        PicoSvg::load(&svg, 1.0).unwrap()
+    }
+
+    fn paint(paint: &Paint) -> String {
+        match paint {
+            Paint::Color(color) => format!("#{:08x}", color.as_rgba_u32()),
+            Paint::LinearGradient(_) => "linear".into(),
+            Paint::RadialGradient(_) => "radial".into(),
+        }
+    }
+
+    /// Describe the items of a document, one per string.
+    fn items(svg: &PicoSvg) -> Vec<String> {
+        svg.items
+            .iter()
+            .map(|item| match item {
+                Item::Fill(fill) => format!("fill {}", paint(&fill.paint)),
+                Item::Stroke(stroke) => format!("stroke {} {}", paint(&stroke.paint), stroke.width),
+                Item::PushLayer(layer) => match layer.blend {
+                    Some(blend) => format!("layer {:?} {}", blend.mode, layer.alpha),
+                    None => format!("layer {}", layer.alpha),
+                },
+                Item::PopLayer => "pop layer".into(),
+                Item::PushMask(mask) => format!("mask {:?}", mask.mask),
+                Item::BeginMaskedContent => "masked content".into(),
+                Item::PopMask => "pop mask".into(),
+            })
+            .collect()
+    }
+
+    fn assert_affine(actual: Affine, expected: [f64; 6]) {
+        let coeffs = actual.as_coeffs();
+        assert!(
+            coeffs
+                .iter()
+                .zip(&expected)
+                .all(|(a, b)| (a - b).abs() < 1e-9),
+            "{:?} != {:?}",
+            coeffs,
+            expected
+        );
+    }
+
+    fn assert_point(actual: Point, expected: (f64, f64)) {
+        let distance = (actual - Point::from(expected)).hypot();
+        assert!(distance < 1e-9, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn transform_functions() {
+        let transform = |value| parse_transform(value).unwrap();
+        assert_affine(
+            transform("matrix(1 2 3 4 5 6)"),
+            [1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+        );
+        assert_affine(transform("translate(5)"), [1.0, 0.0, 0.0, 1.0, 5.0, 0.0]);
+        assert_affine(
+            transform("translate(5 -6)"),
+            [1.0, 0.0, 0.0, 1.0, 5.0, -6.0],
+        );
+        assert_affine(transform("scale(2)"), [2.0, 0.0, 0.0, 2.0, 0.0, 0.0]);
+        assert_affine(transform("scale(2 3)"), [2.0, 0.0, 0.0, 3.0, 0.0, 0.0]);
+        assert_affine(transform("rotate(90)"), [0.0, 1.0, -1.0, 0.0, 0.0, 0.0]);
+        assert_affine(transform("skewX(45)"), [1.0, 0.0, 1.0, 1.0, 0.0, 0.0]);
+        assert_affine(transform("skewY(45)"), [1.0, 1.0, 0.0, 1.0, 0.0, 0.0]);
+        assert_affine(transform(""), [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn rotate_about_center() {
+        let rotate = parse_transform("rotate(90 10 20)").unwrap();
+        assert_point(rotate * Point::new(10.0, 20.0), (10.0, 20.0));
+        assert_point(rotate * Point::new(11.0, 20.0), (10.0, 21.0));
+        assert_point(rotate * Point::new(10.0, 21.0), (9.0, 20.0));
+    }
+
+    #[test]
+    fn transform_lists() {
+        // The functions apply from right to left.
+        let expected = [2.0, 0.0, 0.0, 2.0, 1.0, 2.0];
+        for list in [
+            "translate(1 2) scale(2)",
+            "translate(1,2),scale(2)",
+            "translate(1, 2)scale(2)",
+            " translate( 1 , 2 ) , scale( 2 ) ",
+            "translate(1\n2)\tscale(2)",
+        ] {
+            assert_affine(parse_transform(list).unwrap(), expected);
+        }
+        assert_affine(
+            parse_transform("scale(2) translate(1 2)").unwrap(),
+            [2.0, 0.0, 0.0, 2.0, 2.0, 4.0],
+        );
+    }
+
+    #[test]
+    fn invalid_transforms() {
+        for value in [
+            "translate(1",
+            "translate 1 2",
+            "rotate(1 2)",
+            "scale(a)",
+            "shear(1)",
+            "matrix(1 2 3 4 5)",
+        ] {
+            assert!(parse_transform(value).is_none(), "{}", value);
+        }
+    }
+
+    /// The view box transform of a root element with the given attributes.
+    fn root_transform(attributes: &str) -> Affine {
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" {}/>"#,
+            attributes
+        );
+        let doc = Document::parse(&svg).unwrap();
+        let root = doc.root_element();
+        let (size, view_box) = parse_viewport(root).unwrap();
+        view_box_transform(root, view_box.unwrap(), size)
+    }
+
+    #[test]
+    fn view_box_meet() {
+        // The view box is scaled by 2 to fit the width, and centered
+        // vertically.
+        let transform = root_transform(r#"width="200" height="200" viewBox="10 20 100 50""#);
+        assert_affine(transform, [2.0, 0.0, 0.0, 2.0, -20.0, 10.0]);
+        let explicit = root_transform(
+            r#"width="200" height="200" viewBox="10 20 100 50" preserveAspectRatio="xMidYMid meet""#,
+        );
+        assert_affine(explicit, [2.0, 0.0, 0.0, 2.0, -20.0, 10.0]);
+    }
+
+    #[test]
+    fn view_box_slice() {
+        // The view box is scaled by 4 to cover the height, and centered
+        // horizontally.
+        let transform = root_transform(
+            r#"width="200" height="200" viewBox="10 20 100 50" preserveAspectRatio="xMidYMid slice""#,
+        );
+        assert_affine(transform, [4.0, 0.0, 0.0, 4.0, -140.0, -80.0]);
+    }
+
+    #[test]
+    fn view_box_none() {
+        let transform = root_transform(
+            r#"width="200" height="200" viewBox="10 20 100 50" preserveAspectRatio="none""#,
+        );
+        assert_affine(transform, [2.0, 0.0, 0.0, 4.0, -20.0, -80.0]);
+    }
+
+    #[test]
+    fn view_box_alignment() {
+        // Scaled by 2 to fit the height, on the right.
+        let transform = root_transform(
+            r#"width="200" height="200" viewBox="10 20 50 100" preserveAspectRatio="xMaxYMin""#,
+        );
+        assert_affine(transform, [2.0, 0.0, 0.0, 2.0, 80.0, -40.0]);
+        let transform = root_transform(
+            r#"width="200" height="200" viewBox="10 20 50 100" preserveAspectRatio="xMinYMax""#,
+        );
+        assert_affine(transform, [2.0, 0.0, 0.0, 2.0, -20.0, -40.0]);
+    }
+
+    #[test]
+    fn inherited_paint() {
+        let svg = load(
+            r#"<g fill="red" stroke="blue" stroke-width="3">
+                <g fill-opacity="0.5">
+                    <rect width="10" height="10"/>
+                    <rect width="10" height="10" fill="lime" stroke="none"/>
+                </g>
+                <rect width="10" height="10"/>
+            </g>
+            <rect width="10" height="10"/>"#,
+        );
+        assert_eq!(
+            items(&svg),
+            [
+                "fill #ff000080",
+                "stroke #0000ffff 3",
+                "fill #00ff0080",
+                "fill #ff0000ff",
+                "stroke #0000ffff 3",
+                "fill #000000ff",
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_group_opacity() {
+        // Group opacity isn't inherited, but each group is a layer.
+        let svg = load(
+            r#"<g opacity="0.5">
+                <g opacity="0.5"><rect width="10" height="10"/></g>
+                <rect width="10" height="10"/>
+            </g>"#,
+        );
+        assert_eq!(
+            items(&svg),
+            [
+                "layer 0.5",
+                "layer 0.5",
+                "fill #000000ff",
+                "pop layer",
+                "fill #000000ff",
+                "pop layer",
+            ]
+        );
+    }
+
+    #[test]
+    fn inherited_transform() {
+        let svg = load(
+            r#"<g transform="translate(10 20)">
+                <g transform="scale(2)"><rect x="1" y="1" width="2" height="2"/></g>
+            </g>"#,
+        );
+        match &svg.items[..] {
+            [Item::Fill(fill)] => {
+                let bbox = fill.path.bounding_box();
+                assert_eq!(
+                    (bbox.x0, bbox.y0, bbox.x1, bbox.y1),
+                    (12.0, 22.0, 16.0, 26.0)
+                );
+            }
+            _ => panic!("expected one fill"),
+        }
+    }
+
+    fn color(value: &str) -> Option<u32> {
+        parse_color(value).map(|color| color.as_rgba_u32())
+    }
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(color("#f80"), Some(0xff8800ff));
+        assert_eq!(color("#f80c"), Some(0xff8800cc));
+        assert_eq!(color("#FF8800"), Some(0xff8800ff));
+        assert_eq!(color("#ff880080"), Some(0xff880080));
+        assert_eq!(color(" #ff8800 "), Some(0xff8800ff));
+        for invalid in [
+            "#", "#ff", "#12345", "#1234567", "#ggg", "#+fff", "#+ff", "#-ff",
+        ] {
+            assert_eq!(color(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn functional_colors() {
+        assert_eq!(color("rgb(255, 128, 0)"), Some(0xff8000ff));
+        assert_eq!(color("rgb(100%, 50%, 0%)"), Some(0xff8000ff));
+        assert_eq!(color("rgba(255, 0, 0, 0.5)"), Some(0xff000080));
+        assert_eq!(color("rgba(255 0 0 / 50%)"), Some(0xff000080));
+        // Channels are clamped.
+        assert_eq!(color("rgb(300, -5, 0)"), Some(0xff0000ff));
+        assert_eq!(color("hsl(120, 100%, 50%)"), Some(0x00ff00ff));
+        assert_eq!(color("hsl(0, 0%, 100%)"), Some(0xffffffff));
+        assert_eq!(color("hsla(240deg 100% 50% / 0.25)"), Some(0x0000ff40));
+        for invalid in [
+            "rgb(1, 2)",
+            "rgb(1, 2, 3, 4, 5)",
+            "rgb(a, b, c)",
+            "hsl(0, 1, 1)",
+            "foo(1, 2, 3)",
+        ] {
+            assert_eq!(color(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn named_colors() {
+        assert_eq!(color("red"), Some(0xff0000ff));
+        assert_eq!(color("RebeccaPurple"), Some(0x663399ff));
+        assert_eq!(color("transparent"), Some(0));
+        assert_eq!(color("notacolor"), None);
+        // The table is searched by bisection.
+        assert!(super::NAMED_COLORS.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn current_color() {
+        let svg = load(
+            r#"<g color="lime">
+                <rect width="10" height="10" fill="currentColor" stroke="currentColor"/>
+                <rect width="10" height="10" color="blue" fill="currentColor"/>
+            </g>"#,
+        );
+        assert_eq!(
+            items(&svg),
+            ["fill #00ff00ff", "stroke #00ff00ff 1", "fill #0000ffff"]
+        );
+    }
+
+    #[test]
+    fn default_fill() {
+        let svg =
+            load(r#"<rect width="10" height="10"/><rect width="10" height="10" fill="none"/>"#);
+        assert_eq!(items(&svg), ["fill #000000ff"]);
+        let svg = PicoSvg::load(
+            r##"<svg xmlns="http://www.w3.org/2000/svg"><rect fill="#+fff"/></svg>"##,
+            1.0,
+        );
+        assert!(svg.is_err());
+    }
+
+    /// The path of the only item of a document.
+    fn shape(body: &str) -> BezPath {
+        match &load(body).items[..] {
+            [Item::Fill(fill)] => fill.path.clone(),
+            _ => panic!("expected one fill for {}", body),
+        }
+    }
+
+    fn bounds(path: &BezPath) -> (f64, f64, f64, f64) {
+        let Rect { x0, y0, x1, y1 } = path.bounding_box();
+        (x0, y0, x1, y1)
+    }
+
+    #[test]
+    fn rect() {
+        let path = shape(r#"<rect x="10" y="20" width="30" height="40"/>"#);
+        assert_eq!(bounds(&path), (10.0, 20.0, 40.0, 60.0));
+        assert_eq!(path.elements().len(), 5);
+        assert!(load(r#"<rect width="0" height="10"/>"#).items.is_empty());
+    }
+
+    #[test]
+    fn rounded_rect() {
+        let path = shape(r#"<rect x="10" y="20" width="30" height="40" rx="5" ry="8"/>"#);
+        assert_eq!(bounds(&path), (10.0, 20.0, 40.0, 60.0));
+        let elements = path.elements();
+        assert_eq!(elements[0], PathEl::MoveTo(Point::new(15.0, 20.0)));
+        assert!(matches!(elements[2], PathEl::CurveTo(_, _, p) if p == Point::new(40.0, 28.0)));
+        // A missing radius is the same as the other one.
+        let path = shape(r#"<rect x="10" y="20" width="30" height="40" ry="5"/>"#);
+        assert_eq!(path.elements()[0], PathEl::MoveTo(Point::new(15.0, 20.0)));
+        // Radii are clamped to half the size.
+        let path = shape(r#"<rect x="10" y="20" width="30" height="40" rx="100"/>"#);
+        assert_eq!(path.elements()[0], PathEl::MoveTo(Point::new(25.0, 20.0)));
+        assert_eq!(path.elements()[1], PathEl::LineTo(Point::new(25.0, 20.0)));
+    }
+
+    #[test]
+    fn circle_and_ellipse() {
+        let circle = shape(r#"<circle cx="50" cy="50" r="10"/>"#);
+        assert_eq!(bounds(&circle), (40.0, 40.0, 60.0, 60.0));
+        let ellipse = shape(r#"<ellipse cx="50" cy="50" rx="20" ry="10"/>"#);
+        assert_eq!(bounds(&ellipse), (30.0, 40.0, 70.0, 60.0));
+        let ellipse = shape(r#"<ellipse cx="50" cy="50" rx="20"/>"#);
+        assert_eq!(bounds(&ellipse), (30.0, 30.0, 70.0, 70.0));
+        assert!(load(r#"<circle cx="50" cy="50"/>"#).items.is_empty());
+    }
+
+    #[test]
+    fn lines() {
+        let line = shape(r#"<line x1="1" y1="2" x2="3" y2="4"/>"#);
+        assert_eq!(
+            line.elements(),
+            [
+                PathEl::MoveTo(Point::new(1.0, 2.0)),
+                PathEl::LineTo(Point::new(3.0, 4.0)),
+            ]
+        );
+        let polyline = shape(r#"<polyline points="0,0 10,0 10,10"/>"#);
+        assert_eq!(
+            polyline.elements(),
+            [
+                PathEl::MoveTo(Point::new(0.0, 0.0)),
+                PathEl::LineTo(Point::new(10.0, 0.0)),
+                PathEl::LineTo(Point::new(10.0, 10.0)),
+            ]
+        );
+        let polygon = shape(r#"<polygon points="0 0, 10 0, 10 10"/>"#);
+        assert_eq!(polygon.elements().len(), 4);
+        assert_eq!(polygon.elements()[3], PathEl::ClosePath);
+        // An odd coordinate at the end is ignored.
+        let polygon = shape(r#"<polygon points="0,0 10,0 5"/>"#);
+        assert_eq!(polygon.elements().len(), 3);
+    }
+
+    #[test]
+    fn style_attribute_precedence() {
+        let svg = load(
+            r#"<rect width="10" height="10" fill="red" style="fill: blue"/>
+            <rect width="10" height="10" style="fill:blue; fill: lime !important" fill="red"/>
+            <rect width="10" height="10" stroke="red" stroke-width="2" style="stroke-width: 4"/>
+            <rect width="10" height="10" fill="red" style="fill: inherit"/>"#,
+        );
+        assert_eq!(
+            items(&svg),
+            [
+                "fill #0000ffff",
+                "fill #00ff00ff",
+                "fill #000000ff",
+                "stroke #ff0000ff 4",
+                "fill #000000ff",
+            ]
+        );
+    }
+
+    const STOPS: &str =
+        r#"<stop offset="0" stop-color="red"/><stop offset="1" stop-color="blue"/>"#;
+
+    /// The paints of the fills of a document.
+    fn fill_paints(svg: &PicoSvg) -> Vec<Paint> {
+        svg.items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Fill(fill) => Some(fill.paint.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn linear(paint: &Paint) -> &LinearGradient {
+        match paint {
+            Paint::LinearGradient(gradient) => gradient,
+            _ => panic!("expected a linear gradient"),
+        }
+    }
+
+    fn radial(paint: &Paint) -> &RadialGradient {
+        match paint {
+            Paint::RadialGradient(gradient) => gradient,
+            _ => panic!("expected a radial gradient"),
+        }
+    }
+
+    #[test]
+    fn gradient_href_chain() {
+        let svg = load(&format!(
+            r##"<linearGradient id="base" x2="0" y2="1">{}</linearGradient>
+            <linearGradient id="middle" href="#base" spreadMethod="reflect"/>
+            <linearGradient id="top" xlink:href="#middle" y2="0.5"
+                xmlns:xlink="http://www.w3.org/1999/xlink"/>
+            <rect width="10" height="10" fill="url(#top)"/>"##,
+            STOPS
+        ));
+        let paints = fill_paints(&svg);
+        let gradient = linear(&paints[0]);
+        // Attributes come from the nearest gradient that has them.
+        assert_eq!(gradient.end, Point::new(0.0, 0.5));
+        assert!(matches!(gradient.spread, SpreadMethod::Reflect));
+        let stops = gradient.stops.iter().map(|stop| stop.color.as_rgba_u32());
+        assert_eq!(stops.collect::<Vec<_>>(), [0xff0000ff, 0x0000ffff]);
+    }
+
+    #[test]
+    fn gradient_href_cycle() {
+        let svg = load(&format!(
+            r##"<linearGradient id="a" href="#b">{}</linearGradient>
+            <linearGradient id="b" href="#a" x2="0.5"/>
+            <linearGradient id="c" href="#c"/>
+            <rect width="10" height="10" fill="url(#b)"/>
+            <rect width="10" height="10" fill="url(#c) green"/>"##,
+            STOPS
+        ));
+        let paints = fill_paints(&svg);
+        assert_eq!(linear(&paints[0]).end, Point::new(0.5, 0.0));
+        // A gradient without stops paints nothing, even with a fallback.
+        assert_eq!(paints.len(), 1);
+    }
+
+    #[test]
+    fn gradient_units() {
+        let svg = load(&format!(
+            r##"<linearGradient id="bbox" x1="0" x2="1">{0}</linearGradient>
+            <linearGradient id="user" gradientUnits="userSpaceOnUse" x1="10%" x2="50">{0}</linearGradient>
+            <g transform="translate(5 0)">
+                <rect x="10" y="20" width="30" height="40" fill="url(#bbox)"/>
+                <rect x="10" y="20" width="30" height="40" fill="url(#user)"/>
+            </g>"##,
+            STOPS
+        ));
+        let paints = fill_paints(&svg);
+        // Bounding box units map to the bounding box of the shape.
+        let bbox = linear(&paints[0]);
+        assert_point(bbox.transform * bbox.start, (15.0, 20.0));
+        assert_point(bbox.transform * bbox.end, (45.0, 20.0));
+        // User space percentages are relative to the viewport.
+        let user = linear(&paints[1]);
+        assert_eq!(
+            (user.start, user.end),
+            (Point::new(10.0, 0.0), Point::new(50.0, 0.0))
+        );
+        assert_affine(user.transform, [1.0, 0.0, 0.0, 1.0, 5.0, 0.0]);
+    }
+
+    #[test]
+    fn gradient_transform() {
+        let svg = load(&format!(
+            r##"<linearGradient id="g" gradientUnits="userSpaceOnUse" x2="10"
+                gradientTransform="rotate(90)">{0}</linearGradient>
+            <linearGradient id="h" gradientTransform="scale(2)">{0}</linearGradient>
+            <rect width="10" height="10" fill="url(#g)"/>
+            <rect x="10" width="10" height="10" fill="url(#h)"/>"##,
+            STOPS
+        ));
+        let paints = fill_paints(&svg);
+        let g = linear(&paints[0]);
+        assert_point(g.transform * g.end, (0.0, 10.0));
+        // The gradient transform applies within the bounding box.
+        let h = linear(&paints[1]);
+        assert_point(h.transform * h.end, (30.0, 0.0));
+    }
+
+    #[test]
+    fn gradient_spread_method() {
+        let spread = |method: &str| {
+            let svg = load(&format!(
+                r##"<linearGradient id="g" {}>{}</linearGradient>
+                <rect width="10" height="10" fill="url(#g)"/>"##,
+                method, STOPS
+            ));
+            linear(&fill_paints(&svg)[0]).spread
+        };
+        assert!(matches!(spread(""), SpreadMethod::Pad));
+        assert!(matches!(spread(r#"spreadMethod="pad""#), SpreadMethod::Pad));
+        assert!(matches!(
+            spread(r#"spreadMethod="reflect""#),
+            SpreadMethod::Reflect
+        ));
+        assert!(matches!(
+            spread(r#"spreadMethod="repeat""#),
+            SpreadMethod::Repeat
+        ));
+    }
+
+    #[test]
+    fn radial_gradient_defaults() {
+        let svg = load(&format!(
+            r##"<radialGradient id="r">{0}</radialGradient>
+            <radialGradient id="f" fx="0.25" fr="0.1">{0}</radialGradient>
+            <rect width="10" height="10" fill="url(#r)"/>
+            <rect width="10" height="10" fill="url(#f)"/>"##,
+            STOPS
+        ));
+        let paints = fill_paints(&svg);
+        let r = radial(&paints[0]);
+        assert_eq!((r.center, r.radius), (Point::new(0.5, 0.5), 0.5));
+        assert_eq!((r.focus, r.focus_radius), (r.center, 0.0));
+        let f = radial(&paints[1]);
+        assert_eq!((f.focus, f.focus_radius), (Point::new(0.25, 0.5), 0.1));
+    }
+
+    #[test]
+    fn use_instances() {
+        let svg = load(&format!(
+            r##"<defs>
+                <linearGradient id="g">{}</linearGradient>
+                <rect id="r" width="10" height="10" fill="url(#g)"/>
+                <symbol id="s" fill="lime"><rect width="5" height="5"/></symbol>
+            </defs>
+            <use href="#r" x="20"/>
+            <use href="#r" x="50" y="5" transform="scale(2)"/>
+            <use href="#s" fill="red"/>"##,
+            STOPS
+        ));
+        let paints = fill_paints(&svg);
+        assert_eq!(paints.len(), 3);
+        // Each instance has the gradient in its own bounding box.
+        let first = linear(&paints[0]);
+        assert_point(first.transform * first.start, (20.0, 0.0));
+        let second = linear(&paints[1]);
+        assert_point(second.transform * second.start, (100.0, 10.0));
+        assert_point(second.transform * second.end, (120.0, 10.0));
+        // The style of a symbol overrides that of the use element.
+        assert!(matches!(&paints[2], Paint::Color(color) if color.as_rgba_u32() == 0x00ff00ff));
+    }
+
+    #[test]
+    fn recursive_use() {
+        let svg = load(
+            r##"<g id="loop"><use href="#loop"/><rect width="10" height="10"/></g>
+            <use id="self" href="#self"/>"##,
+        );
+        // The group is drawn, and once more by its use, which skips itself.
+        assert_eq!(items(&svg), ["fill #000000ff", "fill #000000ff"]);
+    }
+
+    /// The bounds of the paths of the layers and masks of a document.
+    fn layer_bounds(svg: &PicoSvg) -> Vec<(f64, f64, f64, f64)> {
+        svg.items
+            .iter()
+            .filter_map(|item| match item {
+                Item::PushLayer(layer) => Some(bounds(&layer.path)),
+                Item::PushMask(mask) => Some(bounds(&mask.path)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn clip_path_layer() {
+        let svg = load(
+            r##"<clipPath id="c"><rect width="50" height="40"/></clipPath>
+            <g transform="translate(10 0)" clip-path="url(#c)">
+                <rect width="100" height="100"/>
+            </g>"##,
+        );
+        assert_eq!(items(&svg), ["layer 1", "fill #000000ff", "pop layer"]);
+        // The clip is in the user space of the element.
+        assert_eq!(layer_bounds(&svg), [(10.0, 0.0, 60.0, 40.0)]);
+    }
+
+    #[test]
+    fn clip_path_units() {
+        let svg = load(
+            r##"<clipPath id="c" clipPathUnits="objectBoundingBox">
+                <rect width="0.5" height="0.5"/>
+            </clipPath>
+            <rect x="10" y="10" width="40" height="40" clip-path="url(#c)"/>"##,
+        );
+        assert_eq!(layer_bounds(&svg), [(10.0, 10.0, 30.0, 30.0)]);
+    }
+
+    #[test]
+    fn empty_clip_path() {
+        let svg = load(
+            r##"<clipPath id="c"/>
+            <rect width="10" height="10" clip-path="url(#c)"/>
+            <rect width="10" height="10" clip-path="url(#missing)"/>"##,
+        );
+        // An empty clip hides the element, a missing one is ignored.
+        assert_eq!(items(&svg), ["fill #000000ff"]);
+    }
+
+    #[test]
+    fn opacity_layer() {
+        let svg = load(r#"<rect width="10" height="10" opacity="0.5"/>"#);
+        assert_eq!(items(&svg), ["layer 0.5", "fill #000000ff", "pop layer"]);
+        // Without a clip path, the layer covers the document.
+        assert_eq!(layer_bounds(&svg), [(0.0, 0.0, 100.0, 100.0)]);
+        let svg = load(r#"<rect width="10" height="10" opacity="1"/>"#);
+        assert_eq!(items(&svg), ["fill #000000ff"]);
+    }
+
+    #[test]
+    fn blend_mode_layer() {
+        let svg = load(
+            r#"<g style="mix-blend-mode: multiply"><rect width="10" height="10"/></g>
+            <g style="mix-blend-mode: screen" opacity="0.25"><rect width="10" height="10"/></g>
+            <g style="mix-blend-mode: normal"><rect width="10" height="10"/></g>"#,
+        );
+        assert_eq!(
+            items(&svg),
+            [
+                "layer Multiply 1",
+                "fill #000000ff",
+                "pop layer",
+                "layer Screen 0.25",
+                "fill #000000ff",
+                "pop layer",
+                "fill #000000ff",
+            ]
+        );
+    }
+
+    #[test]
+    fn mask_layer() {
+        let svg = load(
+            r##"<mask id="m"><rect width="100" height="100" fill="white"/></mask>
+            <mask id="a" style="mask-type: alpha"><rect width="5" height="5"/></mask>
+            <rect width="10" height="10" fill="red" mask="url(#m)"/>
+            <rect width="10" height="10" fill="red" mask="url(#a)"/>"##,
+        );
+        assert_eq!(
+            items(&svg),
+            [
+                "mask Luminance",
+                "fill #ffffffff",
+                "masked content",
+                "fill #ff0000ff",
+                "pop mask",
+                "mask Alpha",
+                "fill #000000ff",
+                "masked content",
+                "fill #ff0000ff",
+                "pop mask",
+            ]
+        );
+        // The default region extends the bounding box by 10%.
+        let (x0, y0, x1, y1) = layer_bounds(&svg)[0];
+        assert_point(Point::new(x0, y0), (-1.0, -1.0));
+        assert_point(Point::new(x1, y1), (11.0, 11.0));
+    }
+
+    #[test]
+    fn mask_within_layer() {
+        let svg = load(
+            r##"<clipPath id="c"><rect width="50" height="50"/></clipPath>
+            <mask id="m" maskUnits="userSpaceOnUse" x="0" y="0" width="20" height="20">
+                <rect width="10" height="10" fill="white" mask="url(#m)"/>
+            </mask>
+            <mask id="empty" maskUnits="userSpaceOnUse" width="0"/>
+            <g clip-path="url(#c)" opacity="0.5" mask="url(#m)">
+                <rect width="10" height="10"/>
+            </g>
+            <rect width="10" height="10" mask="url(#empty)"/>"##,
+        );
+        // The mask is inside the layer, and its use by its own content is
+        // ignored. An empty mask region hides the element.
+        assert_eq!(
+            items(&svg),
+            [
+                "layer 0.5",
+                "mask Luminance",
+                "fill #ffffffff",
+                "masked content",
+                "fill #000000ff",
+                "pop mask",
+                "pop layer",
+            ]
+        );
+        assert_eq!(
+            layer_bounds(&svg),
+            [(0.0, 0.0, 50.0, 50.0), (0.0, 0.0, 20.0, 20.0)]
+        );
+    }
+
+    /// A dash pattern and offset.
+    type Dashes = Option<(Vec<f64>, f64)>;
+
+    /// The width, join, cap, miter limit and dashes of the only stroke of a
+    /// document.
+    fn stroke(body: &str) -> (f64, LineJoin, LineCap, f64, Dashes) {
+        let svg = load(body);
+        let mut strokes = svg.items.iter().filter_map(|item| match item {
+            Item::Stroke(stroke) => Some(stroke),
+            _ => None,
+        });
+        let stroke = strokes.next().expect("no stroke");
+        assert!(strokes.next().is_none());
+        let style = &stroke.style;
+        (
+            stroke.width,
+            style.line_join.unwrap(),
+            style.line_cap.unwrap(),
+            style.miter_limit.unwrap(),
+            style.dash.clone(),
+        )
+    }
+
+    #[test]
+    fn stroke_defaults() {
+        let (width, join, cap, miter_limit, dash) = stroke(r#"<path d="M0 0L10 0" stroke="red"/>"#);
+        assert_eq!(
+            (width, join, cap, miter_limit),
+            (1.0, LineJoin::Miter, LineCap::Butt, 4.0)
+        );
+        assert_eq!(dash, None);
+        assert!(load(r#"<path d="M0 0L10 0" fill="none"/>"#)
+            .items
+            .is_empty());
+    }
+
+    #[test]
+    fn stroke_joins_and_caps() {
+        let style = |attributes: &str| {
+            let (_, join, cap, miter_limit, _) = stroke(&format!(
+                r#"<g stroke-linejoin="round" stroke-linecap="square" stroke-miterlimit="8">
+                    <path d="M0 0L10 0" stroke="red" {}/>
+                </g>"#,
+                attributes
+            ));
+            (join, cap, miter_limit)
+        };
+        // The properties are inherited.
+        assert_eq!(style(""), (LineJoin::Round, LineCap::Square, 8.0));
+        assert_eq!(
+            style(r#"stroke-linejoin="bevel" stroke-linecap="round" stroke-miterlimit="1""#),
+            (LineJoin::Bevel, LineCap::Round, 1.0)
+        );
+        assert_eq!(
+            style(r#"stroke-linejoin="miter-clip" stroke-linecap="butt""#),
+            (LineJoin::Miter, LineCap::Butt, 8.0)
+        );
+        assert_eq!(style(r#"stroke-linejoin="arcs""#).0, LineJoin::Miter);
+        // Invalid values are ignored, as are miter limits below 1.
+        assert_eq!(
+            style(r#"stroke-linejoin="sharp" stroke-linecap="flat" stroke-miterlimit="0.5""#),
+            (LineJoin::Round, LineCap::Square, 8.0)
+        );
+        assert_eq!(style(r#"stroke-miterlimit="many""#).2, 8.0);
+    }
+
+    #[test]
+    fn stroke_dashes() {
+        let dash = |attributes: &str| {
+            stroke(&format!(
+                r#"<path d="M0 0L10 0" stroke="red" {}/>"#,
+                attributes
+            ))
+            .4
+        };
+        assert_eq!(
+            dash(r#"stroke-dasharray="5 3""#),
+            Some((vec![5.0, 3.0], 0.0))
+        );
+        assert_eq!(
+            dash(r#"stroke-dasharray="5, 3" stroke-dashoffset="2""#),
+            Some((vec![5.0, 3.0], 2.0))
+        );
+        // Odd arrays are kept, and repeated when the stroke is expanded.
+        assert_eq!(
+            dash(r#"stroke-dasharray="5,3,2""#),
+            Some((vec![5.0, 3.0, 2.0], 0.0))
+        );
+        assert_eq!(dash(r#"stroke-dasharray="1in""#), Some((vec![96.0], 0.0)));
+        // These draw a solid stroke.
+        for dasharray in ["none", "0 0", "-1 2", "5 x"] {
+            let attribute = format!(r#"stroke-dasharray="{}""#, dasharray);
+            assert_eq!(dash(&attribute), None, "{}", dasharray);
+        }
+    }
+
+    #[test]
+    fn stroke_width() {
+        let width = |attributes: &str| {
+            stroke(&format!(
+                r#"<path d="M0 0L10 0" stroke="red" {}/>"#,
+                attributes
+            ))
+            .0
+        };
+        assert_eq!(width(r#"stroke-width="3""#), 3.0);
+        assert_eq!(width(r#"stroke-width="3px""#), 3.0);
+        assert_eq!(width(r#"style="stroke-width: 6pt""#), 8.0);
+        let svg = PicoSvg::load(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <path d="M0 0L10 0" stroke="red" stroke-width="wide"/>
+            </svg>"#,
+            1.0,
+        );
+        assert!(svg.is_err());
+    }
+
+    #[test]
+    fn non_scaling_stroke() {
+        let scaled = stroke(
+            r#"<g transform="scale(2)">
+                <path d="M0 0L10 0" stroke="red" stroke-width="3"
+                    stroke-dasharray="5 3" stroke-dashoffset="1"/>
+            </g>"#,
+        );
+        assert_eq!(scaled.0, 6.0);
+        assert_eq!(scaled.4, Some((vec![10.0, 6.0], 2.0)));
+        let non_scaling = stroke(
+            r#"<g transform="scale(2)">
+                <path d="M0 0L10 0" stroke="red" stroke-width="3"
+                    stroke-dasharray="5 3" stroke-dashoffset="1"
+                    vector-effect="non-scaling-stroke"/>
+            </g>"#,
+        );
+        assert_eq!(non_scaling.0, 3.0);
+        assert_eq!(non_scaling.4, Some((vec![5.0, 3.0], 1.0)));
+    }
+
+    const NESTED: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+  <g/>
+  <g>
+    <rect width="5" height="5"/>
+    <path id="leaf" d="M 0 0 L x"/>
+  </g>
+  <rect width="5" height="5" fill="nope"/>
+  <rect width="5" height="5" fill="red"/>
+</svg>"#;
+
+    #[test]
+    fn malformed_xml() {
+        let err = PicoSvg::load("<svg><g></svg>", 1.0).err().unwrap();
+        assert!(matches!(err.kind(), SvgErrorKind::Xml(_)));
+        assert!(err.location().is_none());
+        assert!(std::error::Error::source(&err).is_some());
+        assert!(PicoSvg::load_lenient("<svg", 1.0).is_err());
+    }
+
+    #[test]
+    fn error_location() {
+        let err = PicoSvg::load(NESTED, 1.0).err().unwrap();
+        assert!(matches!(err.kind(), SvgErrorKind::InvalidPath(_)));
+        let location = err.location().unwrap();
+        assert_eq!(location.element, "svg/g[2]/path#leaf");
+        assert_eq!((location.line, location.column), (5, 5));
+        assert!(err
+            .to_string()
+            .starts_with("5:5 in svg/g[2]/path#leaf: invalid path data"));
+    }
+
+    #[test]
+    fn error_kinds() {
+        let error = |body: &str| {
+            let svg = format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">{}</svg>"#,
+                body
+            );
+            let err = PicoSvg::load(&svg, 1.0).err().unwrap();
+            (err.to_string(), err.location().unwrap().element.clone())
+        };
+        let (message, element) = error(r#"<path/>"#);
+        assert!(message.ends_with("missing 'd' attribute"), "{}", message);
+        assert_eq!(element, "svg/path");
+        let (message, element) = error(r#"<rect/><rect width="x"/>"#);
+        assert!(message.ends_with("invalid width 'x'"), "{}", message);
+        assert_eq!(element, "svg/rect[2]");
+        let (message, element) = error(r#"<g><g transform="spin(1)"/></g>"#);
+        assert!(
+            message.ends_with("invalid transform 'spin(1)'"),
+            "{}",
+            message
+        );
+        assert_eq!(element, "svg/g/g");
+        let err = PicoSvg::load(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 0 10"/>"#,
+            1.0,
+        )
+        .err()
+        .unwrap();
+        match err.kind() {
+            SvgErrorKind::InvalidAttribute { name, value } => {
+                assert_eq!((name.as_str(), value.as_str()), ("viewBox", "0 0 0 10"));
+            }
+            _ => panic!("expected an invalid attribute"),
+        }
+        assert_eq!(err.location().unwrap().element, "svg");
+    }
+
+    #[test]
+    fn lenient_loading() {
+        let svg = PicoSvg::load_lenient(NESTED, 1.0).unwrap();
+        // The elements with errors are skipped, their siblings are not.
+        assert_eq!(items(&svg), ["fill #000000ff", "fill #ff0000ff"]);
+        let locations = svg
+            .warnings()
+            .iter()
+            .map(|warning| match warning {
+                SvgWarning::Error(err) => err.location().unwrap().element.clone(),
+                _ => panic!("unexpected warning {}", warning),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(locations, ["svg/g[2]/path#leaf", "svg/rect[1]"]);
+        assert!(svg.warnings()[1]
+            .to_string()
+            .starts_with("skipped element, 7:3"));
+    }
+
+    #[test]
+    fn lenient_partial_output() {
+        let svg = PicoSvg::load_lenient(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+                <g opacity="0.5"><rect width="5" height="5"/><circle r="x"/></g>
+                <g opacity="0.5"><rect width="5" height="5"/><g><path/></g></g>
+                <g transform="bad"><rect width="5" height="5"/></g>
+            </svg>"#,
+            1.0,
+        )
+        .unwrap();
+        // Layers stay balanced when their content has errors, and a group
+        // with an error is skipped entirely.
+        assert_eq!(
+            items(&svg),
+            [
+                "layer 0.5",
+                "fill #000000ff",
+                "pop layer",
+                "layer 0.5",
+                "fill #000000ff",
+                "pop layer",
+            ]
+        );
+        assert_eq!(svg.warnings().len(), 3);
+    }
+
+    #[test]
+    fn unsupported_features() {
+        let svg = PicoSvg::load(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:e="http://example.com/editor"
+                width="10" height="10">
+                <text>hello</text>
+                <rect width="5" height="5" filter="url(#f)" fill-rule="evenodd"/>
+                <rect width="5" height="5" style="filter: none"/>
+                <e:extension/>
+            </svg>"#,
+            1.0,
+        )
+        .unwrap();
+        // Unsupported features are warnings even when loading strictly.
+        let warnings = svg
+            .warnings()
+            .iter()
+            .map(|warning| match warning {
+                SvgWarning::Unsupported { feature, location } => {
+                    format!("{} {}", location.element, feature)
+                }
+                _ => panic!("unexpected warning {}", warning),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                "svg/text <text> element",
+                "svg/rect[1] 'filter' attribute",
+                "svg/rect[1] 'fill-rule' of 'evenodd'",
+            ]
+        );
+        assert_eq!(items(&svg), ["fill #000000ff", "fill #000000ff"]);
     }
 }
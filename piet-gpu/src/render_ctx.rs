@@ -1,162 +1,102 @@
-// This should match the value in kernel4.comp for correct rendering.
-const DO_SRGB_CONVERSION: bool = false;
-
 use std::borrow::Cow;
 
-#[cfg(feature = "swash")] use crate::encoder::GlyphEncoder;
-use crate::stages::{Config, Transform};
-use piet::kurbo::{Affine, PathEl, Point, Rect, Shape};
+use piet::kurbo::{Affine, PathEl, Point, Rect, RoundedRect, Shape};
 use piet::{
-    Color, Error, FixedGradient, ImageFormat, InterpolationMode, IntoBrush, RenderContext,
-    StrokeStyle,
+    Color, Error, FixedGradient, GradientStop, ImageFormat, InterpolationMode, IntoBrush, LineCap,
+    LineJoin, RenderContext, StrokeStyle,
 };
 
-use piet_gpu_hal::BufWrite;
-use piet_gpu_types::encoder::{Encode, Encoder};
-use piet_gpu_types::scene::Element;
+use piet_scene::brush::{self, Brush, Extend, Stop, StopVec};
+use piet_scene::geometry;
+use piet_scene::path::Element;
+use piet_scene::resource::ResourceContext;
+use piet_scene::scene::{build_scene, Builder, Cap, Fill, Join, Scene, Stroke};
 
-use crate::gradient::{Colrv1RadialGradient, LinearGradient, RadialGradient, RampCache};
+use crate::gradient::Colrv1RadialGradient;
 #[cfg(feature = "swash")] use crate::text::Font;
-#[cfg(feature = "swash")] pub use crate::text::{PietGpuText, PietGpuTextLayout, PietGpuTextLayoutBuilder};
+#[cfg(feature = "swash")] pub use crate::text::{PietGpuText, PietGpuTextLayout};
 
-use crate::Blend;
+use crate::{Blend, Mask, SceneError};
 
 pub struct PietGpuImage;
 
-pub struct PietGpuRenderContext {
-    encoder: Encoder,
-    elements: Vec<Element>,
+/// A piet render context that encodes into a piet-scene scene.
+///
+/// The scene is complete when the context is dropped, and can then be
+/// uploaded with `EncodedSceneRef::from_scene`.
+pub struct PietGpuRenderContext<'a> {
+    builder: Builder<'a>,
     // Will probably need direct accesss to hal Device to create images etc.
     #[cfg(feature = "swash")] inner_text: PietGpuText,
-    stroke_width: f32,
-    // We're tallying these cpu-side for expedience, but will probably
-    // move this to some kind of readback from element processing.
-    /// The count of elements that make it through to coarse rasterization.
-    path_count: usize,
-    /// The count of path segment elements.
-    pathseg_count: usize,
-    /// The count of transform elements.
-    trans_count: usize,
 
     cur_transform: Affine,
     state_stack: Vec<State>,
-    clip_stack: Vec<ClipElement>,
-
-    ramp_cache: RampCache,
-
-    // Fields for new element processing pipeline below
-    // TODO: delete old encoder, rename
-    new_encoder: crate::encoder::Encoder,
-}
-
-#[derive(Clone)]
-pub enum PietGpuBrush {
-    Solid(u32),
-    LinGradient(LinearGradient),
-    RadGradient(RadialGradient),
+    /// The number of layers pushed, including clips and masks.
+    n_layer: usize,
+    /// Depths of the layer stack holding masks whose content has not begun.
+    pending_masks: Vec<usize>,
+    // The first unbalanced layer operation, reported by `validate`.
+    structure_error: Option<SceneError>,
 }
 
 #[derive(Default)]
 struct State {
-    /// The transform relative to the parent state.
-    rel_transform: Affine,
     /// The transform at the parent state.
-    ///
-    /// This invariant should hold: transform * rel_transform = cur_transform
     transform: Affine,
+    n_transform: usize,
     n_clip: usize,
 }
 
-struct ClipElement {
-    blend: Option<Blend>,
-}
-
 const TOLERANCE: f64 = 0.25;
 
-impl PietGpuRenderContext {
-    pub fn new() -> PietGpuRenderContext {
-        let encoder = Encoder::new();
-        let elements = Vec::new();
+impl<'a> PietGpuRenderContext<'a> {
+    /// Create a context that replaces the content of `scene`.
+    ///
+    /// Gradient ramps are added to `rcx`, which should be retained across
+    /// frames so that they are reused.
+    pub fn new(scene: &'a mut Scene, rcx: &'a mut ResourceContext) -> PietGpuRenderContext<'a> {
+        rcx.advance();
+        let builder = build_scene(scene, rcx);
         #[cfg(feature = "swash")] let font = Font::new();
         #[cfg(feature = "swash")] let inner_text = PietGpuText::new(font);
 
-        let stroke_width = -1.0;
         PietGpuRenderContext {
-            encoder,
-            elements,
+            builder,
             #[cfg(feature = "swash")] inner_text,
-            stroke_width,
-            path_count: 0,
-            pathseg_count: 0,
-            trans_count: 0,
             cur_transform: Affine::default(),
             state_stack: Vec::new(),
-            clip_stack: Vec::new(),
-            ramp_cache: RampCache::default(),
-            new_encoder: crate::encoder::Encoder::new(),
+            n_layer: 0,
+            pending_masks: Vec::new(),
+            structure_error: None,
         }
     }
 
-    pub fn stage_config(&self) -> (Config, usize) {
-        self.new_encoder.stage_config()
-    }
-
-    /// Number of draw objects.
+    /// Check for unbalanced layer operations.
     ///
-    /// This is for the new element processing pipeline. It's not necessarily the
-    /// same as the number of paths (as in the old pipeline), but it might take a
-    /// while to sort that out.
-    pub fn n_drawobj(&self) -> usize {
-        self.new_encoder.n_drawobj()
-    }
-
-    /// Number of paths.
-    pub fn n_path(&self) -> u32 {
-        self.new_encoder.n_path()
-    }
-
-    pub fn n_pathseg(&self) -> u32 {
-        self.new_encoder.n_pathseg()
-    }
-
-    pub fn n_pathtag(&self) -> usize {
-        self.new_encoder.n_pathtag()
-    }
-
-    pub fn n_transform(&self) -> usize {
-        self.new_encoder.n_transform()
-    }
-
-    pub fn n_clip(&self) -> u32 {
-        self.new_encoder.n_clip()
-    }
-
-    pub fn write_scene(&self, buf: &mut BufWrite) {
-        self.new_encoder.write_scene(buf);
-    }
-
-    pub fn get_scene_buf(&mut self) -> &[u8] {
-        const ALIGN: usize = 128;
-        let padded_size = (self.elements.len() + (ALIGN - 1)) & ALIGN.wrapping_neg();
-        self.elements.resize(padded_size, Element::Nop());
-        self.elements.encode(&mut self.encoder);
-        self.encoder.buf()
-    }
-
-    pub fn path_count(&self) -> usize {
-        self.path_count
-    }
-
-    pub fn pathseg_count(&self) -> usize {
-        self.pathseg_count
+    /// Such operations are ignored rather than encoded, so the scene itself
+    /// stays well formed.
+    pub fn validate(&self) -> Result<(), SceneError> {
+        match &self.structure_error {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
     }
 
-    pub fn trans_count(&self) -> usize {
-        self.trans_count
+    /// The builder of the underlying scene.
+    ///
+    /// Transforms and layers pushed here are not tracked by `save` and
+    /// `restore`, so they should be balanced before returning to the piet
+    /// API.
+    pub fn builder(&mut self) -> &mut Builder<'a> {
+        &mut self.builder
     }
 
-    pub fn get_ramp_data(&self) -> Vec<u32> {
-        self.ramp_cache.get_ramp_data()
+    /// Replace the text state.
+    ///
+    /// Passing the text of a previous context (see `text()`) keeps its glyph
+    /// outline cache alive across frames.
+    #[cfg(feature = "swash")] pub fn set_text(&mut self, text: PietGpuText) {
+        self.inner_text = text;
     }
 }
 
@@ -191,8 +131,8 @@ impl piet::TextLayout for PietGpuTextLayout {
 }
 }}
 
-impl RenderContext for PietGpuRenderContext {
-    type Brush = PietGpuBrush;
+impl<'a> RenderContext for PietGpuRenderContext<'a> {
+    type Brush = Brush;
     type Image = PietGpuImage;
     type Text = PietGpuText;
     type TextLayout = PietGpuTextLayout;
@@ -202,72 +142,54 @@ impl RenderContext for PietGpuRenderContext {
     }
 
     fn solid_brush(&mut self, color: Color) -> Self::Brush {
-        // kernel4 expects colors encoded in alpha-premultiplied sRGB:
-        //
-        // [α,sRGB(α⋅R),sRGB(α⋅G),sRGB(α⋅B)]
-        //
-        // See also http://ssp.impulsetrain.com/gamma-premult.html.
-        let (r, g, b, a) = color.as_rgba();
-        let premul = Color::rgba(
-            to_srgb(from_srgb(r) * a),
-            to_srgb(from_srgb(g) * a),
-            to_srgb(from_srgb(b) * a),
-            a,
-        );
-        PietGpuBrush::Solid(premul.as_rgba_u32())
+        Brush::Solid(to_scene_color(&color))
     }
 
     fn gradient(&mut self, gradient: impl Into<FixedGradient>) -> Result<Self::Brush, Error> {
+        // Gradient geometry stays in user space and is transformed with the
+        // path it fills, so it follows the transform at draw time.
         match gradient.into() {
-            FixedGradient::Linear(lin) => {
-                let lin = self.ramp_cache.add_linear_gradient(&lin);
-                Ok(PietGpuBrush::LinGradient(lin))
-            }
-            FixedGradient::Radial(rad) => {
-                let rad = self.ramp_cache.add_radial_gradient(&rad);
-                Ok(PietGpuBrush::RadGradient(rad))
-            }
+            FixedGradient::Linear(lin) => Ok(Brush::LinearGradient(brush::LinearGradient {
+                start: to_scene_point(lin.start),
+                end: to_scene_point(lin.end),
+                stops: to_scene_stops(&lin.stops),
+                extend: Extend::Pad,
+            })),
+            FixedGradient::Radial(rad) => Ok(Brush::RadialGradient(brush::RadialGradient {
+                center0: to_scene_point(rad.center + rad.origin_offset),
+                radius0: 0.0,
+                center1: to_scene_point(rad.center),
+                radius1: rad.radius as f32,
+                stops: to_scene_stops(&rad.stops),
+                extend: Extend::Pad,
+            })),
         }
     }
 
     fn clear(&mut self, _color: Color) {}
 
     fn stroke(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>, width: f64) {
-        self.encode_linewidth(width.abs() as f32);
-        let brush = brush.make_brush(self, || shape.bounding_box()).into_owned();
-        let path = shape.path_elements(TOLERANCE);
-        self.encode_path(path, false);
-        self.encode_brush(&brush);
+        self.stroke_with_brush_transform(shape, brush, width, None, None);
     }
 
     fn stroke_styled(
         &mut self,
-        _shape: impl Shape,
-        _brush: &impl IntoBrush<Self>,
-        _width: f64,
-        _style: &StrokeStyle,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        width: f64,
+        style: &StrokeStyle,
     ) {
+        self.stroke_with_brush_transform(shape, brush, width, Some(style), None);
     }
 
     fn fill(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
-        let brush = brush.make_brush(self, || shape.bounding_box()).into_owned();
-        let path = shape.path_elements(TOLERANCE);
-        self.encode_linewidth(-1.0);
-        self.encode_path(path, true);
-        self.encode_brush(&brush);
+        self.fill_with_brush_transform(shape, brush, None);
     }
 
     fn fill_even_odd(&mut self, _shape: impl Shape, _brush: &impl IntoBrush<Self>) {}
 
     fn clip(&mut self, shape: impl Shape) {
-        self.encode_linewidth(-1.0);
-        let path = shape.path_elements(TOLERANCE);
-        self.encode_path(path, true);
-        self.new_encoder.begin_clip(None);
-        self.clip_stack.push(ClipElement { blend: None });
-        if let Some(tos) = self.state_stack.last_mut() {
-            tos.n_clip += 1;
-        }
+        self.push_layer(shape, None, 1.0);
     }
 
     fn text(&mut self) -> &mut Self::Text {
@@ -277,7 +199,6 @@ impl RenderContext for PietGpuRenderContext {
     fn draw_text(&mut self, layout: &Self::TextLayout, pos: impl Into<Point>) {
         cfg_if::cfg_if! {
                 if #[cfg(feature = "swash")] {
-                self.encode_linewidth(-1.0);
                 layout.draw_text(self, pos.into());
             } else {
                  let _ = (layout, pos); unimplemented!();
@@ -287,8 +208,8 @@ impl RenderContext for PietGpuRenderContext {
 
     fn save(&mut self) -> Result<(), Error> {
         self.state_stack.push(State {
-            rel_transform: Affine::default(),
             transform: self.cur_transform,
+            n_transform: 0,
             n_clip: 0,
         });
         Ok(())
@@ -296,12 +217,12 @@ impl RenderContext for PietGpuRenderContext {
 
     fn restore(&mut self) -> Result<(), Error> {
         if let Some(state) = self.state_stack.pop() {
-            if state.rel_transform != Affine::default() {
-                let a_inv = state.rel_transform.inverse();
-                self.encode_transform(Transform::from_kurbo(a_inv));
+            for _ in 0..state.n_transform {
+                self.builder.pop_transform();
             }
             self.cur_transform = state.transform;
-            for _ in 0..state.n_clip {
+            // Clips may already have been closed by `finish`.
+            for _ in 0..state.n_clip.min(self.n_layer) {
                 self.pop_clip();
             }
             Ok(())
@@ -311,16 +232,16 @@ impl RenderContext for PietGpuRenderContext {
     }
 
     fn finish(&mut self) -> Result<(), Error> {
-        for _ in 0..self.clip_stack.len() {
+        while self.n_layer > 0 {
             self.pop_clip();
         }
         Ok(())
     }
 
     fn transform(&mut self, transform: Affine) {
-        self.encode_transform(Transform::from_kurbo(transform));
+        self.builder.push_transform(to_scene_affine(transform));
         if let Some(tos) = self.state_stack.last_mut() {
-            tos.rel_transform *= transform;
+            tos.n_transform += 1;
         }
         self.cur_transform *= transform;
     }
@@ -352,7 +273,9 @@ impl RenderContext for PietGpuRenderContext {
     ) {
     }
 
-    fn blurred_rect(&mut self, _rect: Rect, _blur_radius: f64, _brush: &impl IntoBrush<Self>) {}
+    fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &impl IntoBrush<Self>) {
+        self.blurred_rounded_rect(rect, 0.0, blur_radius, brush);
+    }
 
     fn current_transform(&self) -> Affine {
         self.cur_transform
@@ -365,158 +288,454 @@ impl RenderContext for PietGpuRenderContext {
     }
 }
 
-impl PietGpuRenderContext {
+impl<'a> PietGpuRenderContext<'a> {
     pub fn blend(&mut self, shape: impl Shape, blend: Blend) {
-        self.encode_linewidth(-1.0);
-        let path = shape.path_elements(TOLERANCE);
-        self.encode_path(path, true);
-        self.new_encoder.begin_clip(Some(blend));
-        self.clip_stack.push(ClipElement { blend: Some(blend) });
+        self.push_layer(shape, Some(blend), 1.0);
+    }
+
+    /// Push a layer bound by `shape`.
+    ///
+    /// Drawing until the matching `pop_layer` is rendered in isolation, then
+    /// composited with the given blend (clip when `None`) and scaled by `alpha`.
+    /// Like clips, open layers are also popped by `restore` and `finish`.
+    pub fn push_layer(&mut self, shape: impl Shape, blend: Option<Blend>, alpha: f32) {
+        let blend = blend.unwrap_or_default().into();
+        self.builder
+            .push_layer_with_alpha(blend, alpha, to_scene_elements(shape));
+        self.n_layer += 1;
         if let Some(tos) = self.state_stack.last_mut() {
             tos.n_clip += 1;
         }
     }
 
-    pub fn radial_gradient_colrv1(&mut self, rad: &Colrv1RadialGradient) -> PietGpuBrush {
-        PietGpuBrush::RadGradient(self.ramp_cache.add_radial_gradient_colrv1(rad))
+    /// Pop the layer pushed by the most recent `push_layer`.
+    pub fn pop_layer(&mut self) {
+        // Popping a layer pushed before the last `save` would unbalance the
+        // clips popped by `restore`.
+        let has_layer = match self.state_stack.last() {
+            Some(tos) => tos.n_clip > 0,
+            None => self.n_layer > 0,
+        };
+        if !has_layer {
+            self.structure_error
+                .get_or_insert(SceneError::UnmatchedPopLayer);
+            return;
+        }
+        self.pop_clip();
+        if let Some(tos) = self.state_stack.last_mut() {
+            tos.n_clip -= 1;
+        }
     }
 
-    pub fn fill_transform(&mut self, shape: impl Shape, brush: &PietGpuBrush, transform: Affine) {
-        let path = shape.path_elements(TOLERANCE);
-        self.encode_linewidth(-1.0);
-        self.encode_path(path, true);
-        self.encode_transform(Transform::from_kurbo(transform));
-        self.new_encoder.swap_last_tags();
-        self.encode_brush(&brush);
-        self.encode_transform(Transform::from_kurbo(transform.inverse()));
+    /// Push a mask layer bound by `shape`.
+    ///
+    /// Drawing until `begin_masked_content` forms the mask, which is not
+    /// itself visible. Drawing from then until `pop_mask` is attenuated by
+    /// the alpha or luminance of the mask. The shape bounds both, so the
+    /// transform should not change before beginning the content.
+    pub fn push_mask(&mut self, shape: impl Shape, mask: Mask) {
+        self.builder
+            .push_mask(mask.into(), to_scene_elements(shape));
+        self.n_layer += 1;
+        self.pending_masks.push(self.n_layer);
+        if let Some(tos) = self.state_stack.last_mut() {
+            tos.n_clip += 1;
+        }
     }
 
-    fn encode_path(&mut self, path: impl Iterator<Item = PathEl>, is_fill: bool) {
-        if is_fill {
-            self.encode_path_inner(
-                path.flat_map(|el| {
-                    match el {
-                        PathEl::MoveTo(..) => Some(PathEl::ClosePath),
-                        _ => None,
-                    }
-                    .into_iter()
-                    .chain(Some(el))
-                })
-                .chain(Some(PathEl::ClosePath)),
-            )
-        } else {
-            self.encode_path_inner(path)
+    /// Finish the mask pushed by the most recent `push_mask` and start the
+    /// content it applies to.
+    pub fn begin_masked_content(&mut self) {
+        if !self.is_mask_pending() {
+            self.structure_error
+                .get_or_insert(SceneError::UnmatchedMaskContent);
+            return;
+        }
+        self.pending_masks.pop();
+        self.builder.begin_masked_content();
+        self.n_layer += 1;
+        if let Some(tos) = self.state_stack.last_mut() {
+            tos.n_clip += 1;
         }
     }
 
-    fn encode_path_inner(&mut self, path: impl Iterator<Item = PathEl>) {
-        let mut pe = self.new_encoder.path_encoder();
-        for el in path {
-            match el {
-                PathEl::MoveTo(p) => {
-                    let p = to_f32_2(p);
-                    pe.move_to(p[0], p[1]);
-                }
-                PathEl::LineTo(p) => {
-                    let p = to_f32_2(p);
-                    pe.line_to(p[0], p[1]);
-                }
-                PathEl::QuadTo(p1, p2) => {
-                    let p1 = to_f32_2(p1);
-                    let p2 = to_f32_2(p2);
-                    pe.quad_to(p1[0], p1[1], p2[0], p2[1]);
-                }
-                PathEl::CurveTo(p1, p2, p3) => {
-                    let p1 = to_f32_2(p1);
-                    let p2 = to_f32_2(p2);
-                    let p3 = to_f32_2(p3);
-                    pe.cubic_to(p1[0], p1[1], p2[0], p2[1], p3[0], p3[1]);
-                }
-                PathEl::ClosePath => pe.close_path(),
-            }
+    /// Pop the masked content and the mask pushed by the most recent
+    /// `push_mask`.
+    pub fn pop_mask(&mut self) {
+        if !self.is_mask_pending() {
+            self.pop_layer();
         }
-        pe.path();
-        let n_pathseg = pe.n_pathseg();
-        self.new_encoder.finish_path(n_pathseg);
+        self.pop_layer();
     }
 
-    fn pop_clip(&mut self) {
-        let tos = self.clip_stack.pop().unwrap();
-        self.new_encoder.end_clip(tos.blend);
+    fn is_mask_pending(&self) -> bool {
+        self.pending_masks.last() == Some(&self.n_layer)
     }
 
-    #[cfg(feature = "swash")] pub fn encode_glyph(&mut self, glyph: &GlyphEncoder) {
-        self.new_encoder.encode_glyph(glyph);
+    pub fn radial_gradient_colrv1(&mut self, rad: &Colrv1RadialGradient) -> Brush {
+        Brush::RadialGradient(brush::RadialGradient {
+            center0: to_scene_point(rad.center0),
+            radius0: rad.radius0 as f32,
+            center1: to_scene_point(rad.center1),
+            radius1: rad.radius1 as f32,
+            stops: to_scene_stops(&rad.stops),
+            extend: Extend::Pad,
+        })
     }
 
-    pub fn fill_glyph(&mut self, rgba_color: u32) {
-        self.new_encoder.fill_color(rgba_color);
+    /// Draw a rounded rectangle with Gaussian blur.
+    ///
+    /// The blur is evaluated analytically in fine rasterization, so only
+    /// solid brushes are supported; other brushes fill the rectangle without
+    /// blur.
+    pub fn blurred_rounded_rect(
+        &mut self,
+        rect: Rect,
+        radius: f64,
+        std_dev: f64,
+        brush: &impl IntoBrush<Self>,
+    ) {
+        let rect = rect.abs();
+        let std_dev = std_dev.abs();
+        let extent = rect.inflate(2.5 * std_dev, 2.5 * std_dev);
+        let brush = brush.make_brush(self, || extent).into_owned();
+        match brush {
+            Brush::Solid(color) => {
+                let rect = geometry::Rect {
+                    min: to_scene_point(Point::new(rect.x0, rect.y0)),
+                    max: to_scene_point(Point::new(rect.x1, rect.y1)),
+                };
+                self.builder
+                    .blurred_rect(color, rect, radius as f32, std_dev as f32);
+            }
+            _ => self.fill(RoundedRect::from_rect(rect, radius), &brush),
+        }
     }
 
-    pub(crate) fn encode_transform(&mut self, transform: Transform) {
-        self.new_encoder.transform(transform);
+    /// Fill a shape with a brush transformed by `transform`.
+    ///
+    /// Brush coordinates are in user space, so the brush also follows the
+    /// current transform; `transform` is applied before it, leaving the
+    /// shape itself unaffected.
+    pub fn fill_transform(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        transform: Affine,
+    ) {
+        self.fill_with_brush_transform(shape, brush, Some(transform));
     }
 
-    fn encode_linewidth(&mut self, linewidth: f32) {
-        if self.stroke_width != linewidth {
-            self.new_encoder.linewidth(linewidth);
-            self.stroke_width = linewidth;
-        }
+    /// Stroke a shape with a brush transformed by `transform`.
+    ///
+    /// See `fill_transform`. The stroke width is not affected by `transform`.
+    pub fn stroke_transform(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        width: f64,
+        transform: Affine,
+    ) {
+        self.stroke_with_brush_transform(shape, brush, width, None, Some(transform));
     }
 
-    pub fn encode_brush(&mut self, brush: &PietGpuBrush) {
-        match brush {
-            PietGpuBrush::Solid(rgba_color) => {
-                self.new_encoder.fill_color(*rgba_color);
-            }
-            PietGpuBrush::LinGradient(lin) => {
-                self.new_encoder
-                    .fill_lin_gradient(lin.ramp_id, lin.start, lin.end);
-            }
-            PietGpuBrush::RadGradient(rad) => {
-                self.new_encoder
-                    .fill_rad_gradient(rad.ramp_id, rad.start, rad.end, rad.r0, rad.r1);
-            }
+    fn fill_with_brush_transform(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        brush_transform: Option<Affine>,
+    ) {
+        let brush = brush.make_brush(self, || shape.bounding_box()).into_owned();
+        self.builder.fill(
+            Fill::NonZero,
+            &brush,
+            brush_transform.map(to_scene_affine),
+            to_scene_elements(shape),
+        );
+    }
+
+    fn stroke_with_brush_transform(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        width: f64,
+        style: Option<&StrokeStyle>,
+        brush_transform: Option<Affine>,
+    ) {
+        let brush = brush.make_brush(self, || shape.bounding_box()).into_owned();
+        let style = to_scene_stroke(width, style);
+        self.builder.stroke(
+            &style,
+            &brush,
+            brush_transform.map(to_scene_affine),
+            to_scene_elements(shape),
+        );
+    }
+
+    fn pop_clip(&mut self) {
+        // A mask without content is closed together with its layer.
+        if self.is_mask_pending() {
+            self.pending_masks.pop();
         }
+        self.builder.pop_layer();
+        self.n_layer -= 1;
     }
 }
 
-impl IntoBrush<PietGpuRenderContext> for PietGpuBrush {
+impl<'a> IntoBrush<PietGpuRenderContext<'a>> for Brush {
     fn make_brush<'b>(
         &'b self,
-        _piet: &mut PietGpuRenderContext,
+        _piet: &mut PietGpuRenderContext<'a>,
         _bbox: impl FnOnce() -> Rect,
-    ) -> std::borrow::Cow<'b, PietGpuBrush> {
+    ) -> std::borrow::Cow<'b, Brush> {
         Cow::Borrowed(self)
     }
 }
 
-pub(crate) fn to_f32_2(point: Point) -> [f32; 2] {
-    [point.x as f32, point.y as f32]
+pub(crate) fn to_scene_point(point: Point) -> geometry::Point {
+    geometry::Point::new(point.x as f32, point.y as f32)
 }
 
-fn to_srgb(f: f64) -> f64 {
-    if DO_SRGB_CONVERSION {
-        if f <= 0.0031308 {
-            f * 12.92
-        } else {
-            let a = 0.055;
-            (1. + a) * f64::powf(f, f64::recip(2.4)) - a
+pub(crate) fn to_scene_affine(affine: Affine) -> geometry::Affine {
+    let c = affine.as_coeffs();
+    geometry::Affine::new(&[
+        c[0] as f32,
+        c[1] as f32,
+        c[2] as f32,
+        c[3] as f32,
+        c[4] as f32,
+        c[5] as f32,
+    ])
+}
+
+pub(crate) fn to_scene_color(color: &Color) -> brush::Color {
+    let rgba = color.as_rgba_u32();
+    brush::Color::rgba8(
+        (rgba >> 24) as u8,
+        (rgba >> 16) as u8,
+        (rgba >> 8) as u8,
+        rgba as u8,
+    )
+}
+
+pub(crate) fn to_scene_stops(stops: &[GradientStop]) -> StopVec {
+    stops
+        .iter()
+        .map(|stop| Stop {
+            offset: stop.pos,
+            color: to_scene_color(&stop.color),
+        })
+        .collect()
+}
+
+/// Convert a piet stroke style, where unstyled strokes have round joins and
+/// caps, which are rendered without expanding them into fills.
+pub(crate) fn to_scene_stroke(width: f64, style: Option<&StrokeStyle>) -> Stroke<Vec<f32>> {
+    let style = match style {
+        Some(style) => style,
+        None => {
+            return Stroke {
+                width: width.abs() as f32,
+                join: Join::Round,
+                miter_limit: 4.0,
+                start_cap: Cap::Round,
+                end_cap: Cap::Round,
+                dash_pattern: vec![],
+                dash_offset: 0.0,
+                scale: true,
+            }
         }
-    } else {
-        f
+    };
+    // Missing properties have the defaults of piet.
+    let join = match style.line_join {
+        Some(LineJoin::Round) => Join::Round,
+        Some(LineJoin::Bevel) => Join::Bevel,
+        _ => Join::Miter,
+    };
+    let cap = match style.line_cap {
+        Some(LineCap::Round) => Cap::Round,
+        Some(LineCap::Square) => Cap::Square,
+        _ => Cap::Butt,
+    };
+    let (dash_pattern, dash_offset) = match &style.dash {
+        Some((dashes, offset)) => (dashes.iter().map(|&d| d as f32).collect(), *offset as f32),
+        None => (vec![], 0.0),
+    };
+    Stroke {
+        width: width.abs() as f32,
+        join,
+        miter_limit: style.miter_limit.unwrap_or(10.0) as f32,
+        start_cap: cap,
+        end_cap: cap,
+        dash_pattern,
+        dash_offset,
+        scale: true,
     }
 }
 
-fn from_srgb(f: f64) -> f64 {
-    if DO_SRGB_CONVERSION {
-        if f <= 0.04045 {
-            f / 12.92
-        } else {
-            let a = 0.055;
-            f64::powf((f + a) * f64::recip(1. + a), 2.4)
-        }
-    } else {
-        f
+pub(crate) fn to_scene_elements(shape: impl Shape) -> Vec<Element> {
+    shape
+        .path_elements(TOLERANCE)
+        .map(|el| match el {
+            PathEl::MoveTo(p) => Element::MoveTo(to_scene_point(p)),
+            PathEl::LineTo(p) => Element::LineTo(to_scene_point(p)),
+            PathEl::QuadTo(p1, p2) => Element::QuadTo(to_scene_point(p1), to_scene_point(p2)),
+            PathEl::CurveTo(p1, p2, p3) => {
+                Element::CurveTo(to_scene_point(p1), to_scene_point(p2), to_scene_point(p3))
+            }
+            PathEl::ClosePath => Element::Close,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use piet::kurbo::{Affine, Line, Point, Rect};
+    use piet::{Color, FixedLinearGradient, FixedRadialGradient, GradientStop, RenderContext};
+    use piet_scene::resource::ResourceContext;
+    use piet_scene::scene::drawtag::{DRAWTAG_FILLLINGRADIENT, DRAWTAG_FILLRADGRADIENT};
+    use piet_scene::scene::Scene;
+
+    use super::PietGpuRenderContext;
+
+    fn encode(f: impl FnOnce(&mut PietGpuRenderContext)) -> Scene {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut ctx = PietGpuRenderContext::new(&mut scene, &mut rcx);
+        f(&mut ctx);
+        ctx.finish().unwrap();
+        assert_eq!(ctx.validate(), Ok(()));
+        drop(ctx);
+        scene
+    }
+
+    fn stops() -> Vec<GradientStop> {
+        vec![
+            GradientStop {
+                pos: 0.0,
+                color: Color::WHITE,
+            },
+            GradientStop {
+                pos: 1.0,
+                color: Color::BLACK,
+            },
+        ]
+    }
+
+    /// The coefficients of the transforms in the transform stream.
+    fn transforms(scene: &Scene) -> Vec<[f32; 6]> {
+        let data = scene.data();
+        let t = |t: &piet_scene::geometry::Affine| [t.xx, t.yx, t.xy, t.yy, t.dx, t.dy];
+        data.transform_stream.iter().map(t).collect()
+    }
+
+    fn coeffs(affine: Affine) -> [f32; 6] {
+        affine.as_coeffs().map(|c| c as f32)
+    }
+
+    /// The geometry of the gradient drawn by a scene, following its ramp
+    /// index.
+    fn gradient(scene: &Scene, drawtag: u32) -> Vec<f32> {
+        let data = scene.data();
+        assert_eq!(data.drawtag_stream, [drawtag]);
+        data.drawdata_stream[4..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+
+    fn bounds(scene: &Scene) -> [f32; 4] {
+        let rect = scene.bounds().unwrap();
+        [rect.min.x, rect.min.y, rect.max.x, rect.max.y]
+    }
+
+    #[test]
+    fn fill_transform() {
+        let scene = encode(|ctx| {
+            ctx.transform(Affine::translate((10.0, 20.0)));
+            let brush = ctx
+                .gradient(FixedLinearGradient {
+                    start: Point::new(0.0, 0.0),
+                    end: Point::new(10.0, 0.0),
+                    stops: stops(),
+                })
+                .unwrap();
+            ctx.fill_transform(Rect::new(0.0, 0.0, 10.0, 10.0), &brush, Affine::scale(2.0));
+        });
+        // The brush transform is applied after the current transform, and
+        // undone after the draw.
+        assert_eq!(
+            transforms(&scene),
+            [
+                coeffs(Affine::IDENTITY),
+                coeffs(Affine::translate((10.0, 20.0))),
+                coeffs(Affine::scale(2.0)),
+                coeffs(Affine::scale(0.5)),
+            ]
+        );
+        assert_eq!(
+            gradient(&scene, DRAWTAG_FILLLINGRADIENT),
+            [0.0, 0.0, 10.0, 0.0]
+        );
+        // The shape only follows the current transform.
+        assert_eq!(bounds(&scene), [10.0, 20.0, 20.0, 30.0]);
+    }
+
+    #[test]
+    fn stroke_transform() {
+        let scene = encode(|ctx| {
+            let brush = ctx
+                .gradient(FixedLinearGradient {
+                    start: Point::new(0.0, 0.0),
+                    end: Point::new(0.0, 10.0),
+                    stops: stops(),
+                })
+                .unwrap();
+            let line = Line::new((0.0, 0.0), (10.0, 0.0));
+            ctx.stroke_transform(line, &brush, 4.0, Affine::scale(2.0));
+        });
+        assert_eq!(
+            transforms(&scene),
+            [
+                coeffs(Affine::IDENTITY),
+                coeffs(Affine::scale(2.0)),
+                coeffs(Affine::scale(0.5)),
+            ]
+        );
+        // The line width is encoded in brush space, so that it has the
+        // specified width in user space.
+        assert_eq!(scene.data().linewidth_stream.last(), Some(&2.0));
+        assert_eq!(
+            gradient(&scene, DRAWTAG_FILLLINGRADIENT),
+            [0.0, 0.0, 0.0, 10.0]
+        );
+        assert_eq!(bounds(&scene), [-2.0, -2.0, 12.0, 2.0]);
+    }
+
+    #[test]
+    fn user_space_gradients() {
+        let scene = encode(|ctx| {
+            ctx.transform(Affine::scale(2.0));
+            let brush = ctx
+                .gradient(FixedRadialGradient {
+                    center: Point::new(5.0, 5.0),
+                    origin_offset: (1.0, -1.0).into(),
+                    radius: 5.0,
+                    stops: stops(),
+                })
+                .unwrap();
+            ctx.fill(Rect::new(0.0, 0.0, 10.0, 10.0), &brush);
+        });
+        // The gradient is encoded as specified, and transformed with the
+        // path on the GPU, rather than with the transform at creation.
+        assert_eq!(
+            transforms(&scene),
+            [coeffs(Affine::IDENTITY), coeffs(Affine::scale(2.0))]
+        );
+        assert_eq!(
+            gradient(&scene, DRAWTAG_FILLRADGRADIENT),
+            [6.0, 4.0, 5.0, 5.0, 0.0, 5.0]
+        );
+        assert_eq!(bounds(&scene), [0.0, 0.0, 20.0, 20.0]);
     }
 }
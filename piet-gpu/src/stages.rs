@@ -18,6 +18,7 @@
 
 mod clip;
 mod draw;
+mod filter;
 mod path;
 mod transform;
 
@@ -25,6 +26,7 @@ use bytemuck::{Pod, Zeroable};
 
 pub use clip::{ClipBinding, ClipCode, CLIP_PART_SIZE};
 pub use draw::{DrawBinding, DrawCode, DrawMonoid, DrawStage, DRAW_PART_SIZE};
+pub use filter::{FilterBinding, FilterCode, FilterParams};
 pub use path::{PathBinding, PathCode, PathEncoder, PathStage, PATHSEG_PART_SIZE};
 use piet_gpu_hal::{Buffer, ComputePass, Session};
 pub use transform::{
@@ -64,6 +66,10 @@ pub struct Config {
     pub pathseg_offset: u32,
     pub drawtag_offset: u32,
     pub drawdata_offset: u32,
+    pub damage_x0: u32,
+    pub damage_y0: u32,
+    pub damage_x1: u32,
+    pub damage_y1: u32,
 }
 
 // The "element" stage combines a number of stages for parts of the pipeline.
@@ -138,6 +144,7 @@ impl ElementStage {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub unsafe fn record(
         &self,
         pass: &mut ComputePass,
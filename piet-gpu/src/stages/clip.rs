@@ -82,7 +82,7 @@ impl ClipBinding {
             );
             pass.memory_barrier();
         }
-        let n_wg = (n_clip + CLIP_PART_SIZE - 1) / CLIP_PART_SIZE;
+        let n_wg = n_clip.div_ceil(CLIP_PART_SIZE);
         if n_wg > 0 {
             pass.dispatch(
                 &code.leaf_pipeline,
@@ -138,7 +138,7 @@ impl DrawStage {
         if size > DRAW_PART_SIZE.pow(2) {
             panic!("very large scan not yet implemented");
         }
-        let n_workgroups = (size + DRAW_PART_SIZE - 1) / DRAW_PART_SIZE;
+        let n_workgroups = size.div_ceil(DRAW_PART_SIZE);
         if n_workgroups > 1 {
             pass.dispatch(
                 &code.reduce_pipeline,
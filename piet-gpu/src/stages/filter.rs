@@ -0,0 +1,190 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! The filter stage, applied to rendered layers before they are composited.
+
+use bytemuck::{Pod, Zeroable};
+
+use piet_gpu_hal::{
+    BackendType, BindType, Buffer, ComputePass, DescriptorSet, Error, Image, Pipeline, Session,
+};
+
+use crate::encoder::Filter;
+
+const FILTER_WG: u32 = 16;
+
+const FILTER_BLUR: u32 = 0;
+const FILTER_DROP_SHADOW: u32 = 1;
+const FILTER_COLOR_MATRIX: u32 = 2;
+
+/// Parameters of a filter pass.
+///
+/// Note: this should be kept in sync with the version in filter.comp.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, Zeroable, Pod)]
+pub struct FilterParams {
+    kind: u32,
+    std_dev: f32,
+    offset: [f32; 2],
+    rgba_color: u32,
+    /// Vertical offset of the destination in the image atlas.
+    atlas_y: u32,
+    _padding: [u32; 2],
+    /// Color matrix in column-major order, with the offset last.
+    matrix: [f32; 20],
+}
+
+impl FilterParams {
+    pub fn new(filter: &Filter, atlas_y: u32) -> FilterParams {
+        let mut params = FilterParams {
+            atlas_y,
+            ..Default::default()
+        };
+        match *filter {
+            Filter::Blur(std_dev) => {
+                params.kind = FILTER_BLUR;
+                params.std_dev = std_dev;
+            }
+            Filter::DropShadow {
+                dx,
+                dy,
+                std_dev,
+                rgba_color,
+            } => {
+                params.kind = FILTER_DROP_SHADOW;
+                params.std_dev = std_dev;
+                params.offset = [dx, dy];
+                params.rgba_color = rgba_color;
+            }
+            Filter::ColorMatrix(m) => {
+                params.kind = FILTER_COLOR_MATRIX;
+                for row in 0..4 {
+                    for col in 0..5 {
+                        params.matrix[col * 4 + row] = m[row * 5 + col];
+                    }
+                }
+            }
+        }
+        params
+    }
+}
+
+pub struct FilterCode {
+    h_pipeline: Pipeline,
+    v_pipeline: Pipeline,
+}
+
+pub struct FilterBinding {
+    h_ds: DescriptorSet,
+    v_ds: DescriptorSet,
+}
+
+impl FilterCode {
+    /// Create the filter pipelines.
+    ///
+    /// This fails on DX12: there are no DXIL builds of the filter shaders
+    /// yet, as they need dxc, and `include_shader!` needs all four outputs.
+    /// Once `ninja` in the shader directory has produced them, switch to
+    /// `include_shader!` like the other stages and remove them from
+    /// `STALE_DXIL`.
+    pub unsafe fn new(session: &Session) -> Result<FilterCode, Error> {
+        if session.backend_type() == BackendType::Dx12 {
+            return Err("filter layers are not supported on DX12".into());
+        }
+        let bind_types = [
+            BindType::BufReadOnly,
+            BindType::Image,
+            BindType::Image,
+            BindType::ImageRead,
+        ];
+        let h_code = session.choose_shader(
+            include_bytes!("../../shader/gen/filter_h.spv"),
+            include_str!("../../shader/gen/filter_h.hlsl"),
+            &[],
+            include_str!("../../shader/gen/filter_h.msl"),
+        );
+        let h_pipeline = session.create_compute_pipeline(h_code, &bind_types)?;
+        let v_code = session.choose_shader(
+            include_bytes!("../../shader/gen/filter_v.spv"),
+            include_str!("../../shader/gen/filter_v.hlsl"),
+            &[],
+            include_str!("../../shader/gen/filter_v.msl"),
+        );
+        let v_pipeline = session.create_compute_pipeline(v_code, &bind_types)?;
+        Ok(FilterCode {
+            h_pipeline,
+            v_pipeline,
+        })
+    }
+}
+
+impl FilterBinding {
+    /// Bind a filter pass.
+    ///
+    /// The source is the rendered layer, and the result is written to the
+    /// atlas at the offset given in the parameters.
+    pub unsafe fn new(
+        session: &Session,
+        code: &FilterCode,
+        params: &Buffer,
+        source: &Image,
+        scratch: &Image,
+        atlas: &Image,
+    ) -> FilterBinding {
+        let h_ds = session
+            .descriptor_set_builder()
+            .add_buffers(&[params])
+            .add_images(&[scratch, atlas])
+            .add_textures(&[source])
+            .build(session, &code.h_pipeline)
+            .unwrap();
+        let v_ds = session
+            .descriptor_set_builder()
+            .add_buffers(&[params])
+            .add_images(&[scratch, atlas])
+            .add_textures(&[source])
+            .build(session, &code.v_pipeline)
+            .unwrap();
+        FilterBinding { h_ds, v_ds }
+    }
+
+    /// Record the filter dispatches.
+    ///
+    /// Assumes memory barrier on entry. Provides memory barrier on exit.
+    pub unsafe fn record(
+        &self,
+        pass: &mut ComputePass,
+        code: &FilterCode,
+        width: u32,
+        height: u32,
+    ) {
+        let n_wg = (width.div_ceil(FILTER_WG), height.div_ceil(FILTER_WG), 1);
+        pass.dispatch(
+            &code.h_pipeline,
+            &self.h_ds,
+            n_wg,
+            (FILTER_WG, FILTER_WG, 1),
+        );
+        pass.memory_barrier();
+        pass.dispatch(
+            &code.v_pipeline,
+            &self.v_ds,
+            n_wg,
+            (FILTER_WG, FILTER_WG, 1),
+        );
+        pass.memory_barrier();
+    }
+}
@@ -164,7 +164,7 @@ impl PathStage {
 
         // Number of tags consumed in a tag reduce workgroup
         let reduce_part_tags = REDUCE_PART_SIZE * 4;
-        let n_wg_tag_reduce = (n_tags + reduce_part_tags - 1) / reduce_part_tags;
+        let n_wg_tag_reduce = n_tags.div_ceil(reduce_part_tags);
         if n_wg_tag_reduce > 1 {
             pass.dispatch(
                 &code.reduce_pipeline,
@@ -182,7 +182,7 @@ impl PathStage {
             );
             // No barrier needed here; clear doesn't depend on path tags
         }
-        let n_wg_clear = (n_paths + CLEAR_WG - 1) / CLEAR_WG;
+        let n_wg_clear = n_paths.div_ceil(CLEAR_WG);
         pass.dispatch(
             &code.clear_pipeline,
             &binding.clear_ds,
@@ -190,7 +190,7 @@ impl PathStage {
             (CLEAR_WG, 1, 1),
         );
         pass.memory_barrier();
-        let n_wg_pathseg = (n_tags + SCAN_PART_SIZE - 1) / SCAN_PART_SIZE;
+        let n_wg_pathseg = n_tags.div_ceil(SCAN_PART_SIZE);
         pass.dispatch(
             &code.pathseg_pipeline,
             &binding.path_ds,
@@ -140,7 +140,7 @@ impl TransformStage {
         if size > TRANSFORM_PART_SIZE.pow(2) {
             panic!("very large scan not yet implemented");
         }
-        let n_workgroups = (size + TRANSFORM_PART_SIZE - 1) / TRANSFORM_PART_SIZE;
+        let n_workgroups = size.div_ceil(TRANSFORM_PART_SIZE);
         if n_workgroups > 1 {
             pass.dispatch(
                 &code.reduce_pipeline,
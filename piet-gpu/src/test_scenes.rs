@@ -2,16 +2,40 @@
 
 use rand::{Rng, RngCore};
 
-use crate::{Blend, BlendMode, Colrv1RadialGradient, CompositionMode, PietGpuRenderContext};
-use piet::kurbo::{Affine, BezPath, Circle, Line, Point, Rect, Shape};
+use crate::{Blend, BlendMode, Colrv1RadialGradient, PietGpuRenderContext};
+use piet::kurbo::{Affine, BezPath, Circle, Line, Point, Rect, Shape, Vec2};
 use piet::{
-    Color, GradientStop, LinearGradient, Text, TextAttribute, TextLayoutBuilder, UnitPoint,
+    Color, GradientStop, LinearGradient, RenderContext, Text, TextAttribute, TextLayoutBuilder,
+    UnitPoint,
 };
 
-use crate::{PicoSvg, RenderContext, Vec2};
+use crate::PicoSvg;
 
 const N_CIRCLES: usize = 0;
 
+type RenderFn = fn(&mut PietGpuRenderContext);
+
+/// The scenes that can be selected by name, such as from the command line.
+pub const SCENES: &[(&str, RenderFn)] = &[
+    ("blend-grid", render_blend_grid),
+    ("scene", render_scene),
+    ("cardioid", |rc| render_cardioid(rc)),
+    ("clip", |rc| render_clip_test(rc)),
+    ("alpha", |rc| render_alpha_test(rc)),
+    ("gradient", render_gradient_test),
+    ("text", |rc| render_text_test(rc)),
+    ("tiger", |rc| render_tiger(rc)),
+    ("anim", |rc| render_anim_frame(rc, 0)),
+];
+
+/// Find a scene of `SCENES` by name.
+pub fn find_scene(name: &str) -> Option<RenderFn> {
+    SCENES
+        .iter()
+        .find(|(scene_name, _)| *scene_name == name)
+        .map(|(_, render)| *render)
+}
+
 pub fn render_blend_test(rc: &mut PietGpuRenderContext, i: usize, blend: Blend) {
     rc.fill(Rect::new(400., 400., 800., 800.), &Color::rgb8(0, 0, 200));
     rc.save().unwrap();
@@ -21,9 +45,9 @@ pub fn render_blend_test(rc: &mut PietGpuRenderContext, i: usize, blend: Blend)
     rc.restore().unwrap();
 }
 
-pub fn render_svg(rc: &mut impl RenderContext, svg: &PicoSvg) {
+pub fn render_svg(rc: &mut PietGpuRenderContext, svg: &PicoSvg) {
     let start = std::time::Instant::now();
-    svg.render(rc);
+    svg.render_layers(rc);
     println!("flattening and encoding time: {:?}", start.elapsed());
 }
 
@@ -31,6 +55,7 @@ pub fn render_scene(rc: &mut PietGpuRenderContext) {
     const WIDTH: usize = 2048;
     const HEIGHT: usize = 1536;
     let mut rng = rand::thread_rng();
+    #[allow(clippy::reversed_empty_ranges)]
     for _ in 0..N_CIRCLES {
         let color = Color::from_rgba32_u32(rng.next_u32());
         let center = Point::new(
@@ -69,7 +94,6 @@ pub fn render_scene(rc: &mut PietGpuRenderContext) {
     //render_tiger(rc);
 }
 
-#[allow(unused)]
 fn render_cardioid(rc: &mut impl RenderContext) {
     let n = 601;
     let dth = std::f64::consts::PI * 2.0 / (n as f64);
@@ -87,7 +111,6 @@ fn render_cardioid(rc: &mut impl RenderContext) {
     rc.stroke(&path, &Color::BLACK, 2.0);
 }
 
-#[allow(unused)]
 fn render_clip_test(rc: &mut impl RenderContext) {
     const N: usize = 16;
     const X0: f64 = 50.0;
@@ -99,7 +122,7 @@ fn render_clip_test(rc: &mut impl RenderContext) {
     let step = 1.0 / ((N + 1) as f64);
     for i in 0..N {
         let t = ((i + 1) as f64) * step;
-        rc.save();
+        rc.save().unwrap();
         let mut path = BezPath::new();
         path.move_to((X0, Y0));
         path.line_to((X1, Y0));
@@ -112,11 +135,10 @@ fn render_clip_test(rc: &mut impl RenderContext) {
     let rect = piet::kurbo::Rect::new(X0, Y0, X1, Y1);
     rc.fill(rect, &Color::BLACK);
     for _ in 0..N {
-        rc.restore();
+        rc.restore().unwrap();
     }
 }
 
-#[allow(unused)]
 fn render_alpha_test(rc: &mut impl RenderContext) {
     // Alpha compositing tests.
     rc.fill(
@@ -127,16 +149,15 @@ fn render_alpha_test(rc: &mut impl RenderContext) {
         diamond(Point::new(1024.0, 125.0)),
         &Color::Rgba32(0x00ff0080),
     );
-    rc.save();
+    rc.save().unwrap();
     rc.clip(diamond(Point::new(1024.0, 150.0)));
     rc.fill(
         diamond(Point::new(1024.0, 175.0)),
         &Color::Rgba32(0x0000ff80),
     );
-    rc.restore();
+    rc.restore().unwrap();
 }
 
-#[allow(unused)]
 fn render_gradient_test(rc: &mut PietGpuRenderContext) {
     let stops = vec![
         GradientStop {
@@ -170,12 +191,11 @@ fn diamond(origin: Point) -> impl Shape {
     path.line_to((origin.x, origin.y + SIZE));
     path.line_to((origin.x - SIZE, origin.y));
     path.close_path();
-    return path;
+    path
 }
 
-#[allow(unused)]
 fn render_text_test(rc: &mut impl RenderContext) {
-    rc.save();
+    rc.save().unwrap();
     //rc.transform(Affine::new([0.2, 0.0, 0.0, -0.2, 200.0, 800.0]));
     let layout = rc
         .text()
@@ -185,10 +205,9 @@ fn render_text_test(rc: &mut impl RenderContext) {
         .unwrap();
     rc.draw_text(&layout, Point::new(110.0, 600.0));
     rc.draw_text(&layout, Point::new(110.0, 700.0));
-    rc.restore();
+    rc.restore().unwrap();
 }
 
-#[allow(unused)]
 fn render_tiger(rc: &mut impl RenderContext) {
     let xml_str = std::str::from_utf8(include_bytes!("../Ghostscript_Tiger.svg")).unwrap();
     let start = std::time::Instant::now();
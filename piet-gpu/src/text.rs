@@ -1,18 +1,30 @@
+use std::cell::RefCell;
 use std::ops::RangeBounds;
+use std::rc::Rc;
 
+use swash::scale::outline::Outline;
 use swash::scale::{ScaleContext, Scaler};
 use swash::zeno::{Vector, Verb};
-use swash::{FontRef, GlyphId};
+use swash::{
+    tag_from_bytes, tag_from_str_lossy, ColorPalette, FontRef, GlyphId, NormalizedCoord, Setting,
+    Usability,
+};
 
 use piet::kurbo::{Point, Rect, Size};
 use piet::{
-    Error, FontFamily, HitTestPoint, HitTestPosition, LineMetric, Text, TextAttribute, TextLayout,
-    TextLayoutBuilder, TextStorage,
+    Color, Error, FontFamily, HitTestPoint, HitTestPosition, LineMetric, RenderContext, Text,
+    TextAttribute, TextLayout, TextLayoutBuilder, TextStorage,
+};
+
+use piet_scene::brush::{self, Brush};
+use piet_scene::geometry::{Affine, Point as ScenePoint};
+use piet_scene::path::Element;
+use piet_scene::scene::{
+    build_fragment, Fill, Fragment, GLYPH_FLAG_BGR, GLYPH_FLAG_GAMMA, GLYPH_FLAG_LCD,
 };
 
-use crate::encoder::GlyphEncoder;
+use crate::glyph_cache::{GlyphCache, GlyphCacheStats, GlyphKey, GlyphOutline};
 use crate::render_ctx;
-use crate::stages::Transform;
 use crate::PietGpuRenderContext;
 
 // This is very much a hack to get things working.
@@ -26,38 +38,199 @@ pub struct Font {
     font_ref: FontRef<'static>,
 }
 
+/// How to choose the color palette for fonts with color outlines.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaletteSelection {
+    /// The palette at the given index, falling back to the first palette.
+    Index(u16),
+    /// The first palette marked as usable with light backgrounds.
+    Light,
+    /// The first palette marked as usable with dark backgrounds.
+    Dark,
+}
+
+impl Default for PaletteSelection {
+    fn default() -> Self {
+        PaletteSelection::Index(0)
+    }
+}
+
+/// Order of the color subpixels of an LCD panel, from left to right.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SubpixelOrder {
+    Rgb,
+    Bgr,
+}
+
+/// Rasterization options tuned for small text.
+///
+/// All options are off by default.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct TextRasterOptions {
+    /// Place hinted glyphs at quarter pixel positions instead of whole
+    /// pixels. Unhinted glyphs are always positioned exactly.
+    pub subpixel_positioning: bool,
+    /// Embolden outlines at small sizes, to counter the thinning of stems
+    /// by antialiasing.
+    pub stem_darkening: bool,
+    /// Boost the coverage of glyph edges, approximating gamma-correct
+    /// blending of dark text on a light background.
+    pub gamma: bool,
+    /// Compute separate coverage for each color subpixel.
+    ///
+    /// This is only correct when rendering opaque text to an opaque target
+    /// that is displayed unscaled on a panel with the given subpixel order.
+    pub lcd: Option<SubpixelOrder>,
+}
+
+impl TextRasterOptions {
+    fn glyph_flags(&self) -> u32 {
+        let mut flags = 0;
+        match self.lcd {
+            Some(SubpixelOrder::Rgb) => flags |= GLYPH_FLAG_LCD,
+            Some(SubpixelOrder::Bgr) => flags |= GLYPH_FLAG_LCD | GLYPH_FLAG_BGR,
+            None => (),
+        }
+        if self.gamma {
+            flags |= GLYPH_FLAG_GAMMA;
+        }
+        flags
+    }
+}
+
+/// Scaling state and encoded outlines, shared by a `PietGpuText` and all
+/// layouts created from it.
+#[derive(Default)]
+struct TextCache {
+    scale_ctx: ScaleContext,
+    glyphs: GlyphCache,
+}
+
 #[derive(Clone)]
 pub struct PietGpuText {
     font: Font,
+    palette: PaletteSelection,
+    foreground: brush::Color,
+    raster: TextRasterOptions,
+    cache: Rc<RefCell<TextCache>>,
 }
 
 #[derive(Clone)]
 pub struct PietGpuTextLayout {
     font: Font,
     size: f64,
+    hint: bool,
+    coords: Vec<NormalizedCoord>,
+    palette: PaletteSelection,
+    foreground: brush::Color,
+    raster: TextRasterOptions,
     glyphs: Vec<Glyph>,
+    cache: Rc<RefCell<TextCache>>,
 }
 
 pub struct PietGpuTextLayoutBuilder {
     font: Font,
     text: String,
     size: f64,
+    hint: bool,
+    variations: Vec<Setting<f32>>,
+    palette: PaletteSelection,
+    foreground: brush::Color,
+    raster: TextRasterOptions,
+    cache: Rc<RefCell<TextCache>>,
 }
 
 #[derive(Clone, Debug)]
 struct Glyph {
     glyph_id: GlyphId,
+    /// Horizontal position in font units.
     x: f32,
     //y: f32,
 }
 
 struct TextRenderCtx<'a> {
     scaler: Scaler<'a>,
+    palette: PaletteSelection,
+    foreground: brush::Color,
+    /// Emboldening strength in outline units.
+    embolden: f32,
+}
+
+// Unhinted outlines are scaled to font units (for Roboto) and then transformed
+// to the requested size, so that one cached outline serves every size. Hinted
+// outlines are scaled at the actual pixel size.
+const DEFAULT_UPEM: u16 = 2048;
+
+/// Number of horizontal positions per pixel for subpixel positioning.
+const SUBPIXEL_BUCKETS: u8 = 4;
+
+/// Stem darkening fades out at this size, in pixels per em.
+const STEM_DARKENING_MAX_PPEM: f32 = 24.0;
+
+/// The amount stems are thickened at the smallest sizes, in pixels.
+const STEM_DARKENING_MAX_PX: f32 = 0.3;
+
+fn stem_darkening(size: f32) -> f32 {
+    STEM_DARKENING_MAX_PX * (1.0 - size / STEM_DARKENING_MAX_PPEM).max(0.0)
+}
+
+/// Snap the position of a hinted glyph, in pixels, to keep its stems on the
+/// pixel grid.
+///
+/// With subpixel positioning, the fractional part of the position is baked
+/// into the outline instead, in one of a few buckets, returned along with the
+/// whole pixel position.
+fn snap_hinted(x: f32, subpixel_positioning: bool) -> (f32, u8) {
+    if !subpixel_positioning {
+        return (x.round(), 0);
+    }
+    let buckets = SUBPIXEL_BUCKETS as f32;
+    let floor = x.floor();
+    let bucket = ((x - floor) * buckets).round();
+    if bucket == buckets {
+        (floor + 1.0, 0)
+    } else {
+        (floor, bucket as u8)
+    }
 }
 
 impl PietGpuText {
     pub(crate) fn new(font: Font) -> PietGpuText {
-        PietGpuText { font }
+        PietGpuText {
+            font,
+            palette: PaletteSelection::default(),
+            foreground: brush::Color::rgb8(255, 255, 255),
+            raster: TextRasterOptions::default(),
+            cache: Default::default(),
+        }
+    }
+
+    /// Set the default rasterization options.
+    pub fn set_raster_options(&mut self, raster: TextRasterOptions) {
+        self.raster = raster;
+    }
+
+    /// Set the default palette for fonts with color outlines.
+    pub fn set_palette(&mut self, palette: PaletteSelection) {
+        self.palette = palette;
+    }
+
+    /// Set the default text color.
+    ///
+    /// This is also substituted for the foreground entry (0xFFFF) of color
+    /// outlines.
+    pub fn set_foreground(&mut self, color: Color) {
+        self.foreground = render_ctx::to_scene_color(&color);
+    }
+
+    /// Statistics for the glyph outline cache.
+    pub fn glyph_cache_stats(&self) -> GlyphCacheStats {
+        self.cache.borrow().glyphs.stats()
+    }
+
+    /// Set the maximum number of encoded glyph outlines retained.
+    pub fn set_glyph_cache_size(&mut self, max_entries: usize) {
+        self.cache.borrow_mut().glyphs.set_max_entries(max_entries);
     }
 }
 
@@ -70,7 +243,11 @@ impl Text for PietGpuText {
     }
 
     fn new_text_layout(&mut self, text: impl TextStorage) -> Self::TextLayoutBuilder {
-        PietGpuTextLayoutBuilder::new(&self.font, &text.as_str())
+        let mut builder = PietGpuTextLayoutBuilder::new(&self.font, text.as_str(), &self.cache);
+        builder.palette = self.palette;
+        builder.foreground = self.foreground;
+        builder.raster = self.raster;
+        builder
     }
 
     fn font_family(&mut self, _family_name: &str) -> Option<FontFamily> {
@@ -114,115 +291,246 @@ impl TextLayout for PietGpuTextLayout {
 
 impl Font {
     pub fn new() -> Font {
-        let font_ref = FontRef::from_index(FONT_DATA, 0).expect("error parsing font");
+        Font::from_data(FONT_DATA)
+    }
+
+    fn from_data(data: &'static [u8]) -> Font {
+        let font_ref = FontRef::from_index(data, 0).expect("error parsing font");
         Font { font_ref }
     }
 
-    fn make_path<'a>(&self, glyph_id: GlyphId, tc: &mut TextRenderCtx<'a>) -> GlyphEncoder {
-        let mut encoder = GlyphEncoder::default();
+    /// Unique identifier used for keying cached outlines.
+    fn id(&self) -> u64 {
+        self.font_ref.key.value()
+    }
+
+    fn make_path<'a>(
+        &self,
+        glyph_id: GlyphId,
+        x_offset: f32,
+        tc: &mut TextRenderCtx<'a>,
+    ) -> GlyphOutline {
         if tc.scaler.has_color_outlines() {
             if let Some(outline) = tc.scaler.scale_color_outline(glyph_id) {
-                // TODO: be more sophisticated choosing a palette
-                let palette = self.font_ref.color_palettes().next().unwrap();
-                let mut i = 0;
-                while let Some(layer) = outline.get(i) {
-                    if let Some(color_ix) = layer.color_index() {
-                        let color = palette.get(color_ix);
-                        append_outline(&mut encoder, layer.verbs(), layer.points());
-                        encoder.fill_color(*bytemuck::from_bytes(&color));
-                    }
-                    i += 1;
-                }
-                return encoder;
+                let palette = select_palette(&self.font_ref, tc.palette);
+                let fragment = make_color_outline(&outline, palette, tc.foreground);
+                return GlyphOutline::Color(Box::new(fragment));
             }
         }
-        if let Some(outline) = tc.scaler.scale_outline(glyph_id) {
-            append_outline(&mut encoder, outline.verbs(), outline.points());
+        let mut path = Vec::new();
+        if let Some(mut outline) = tc.scaler.scale_outline(glyph_id) {
+            if tc.embolden != 0.0 {
+                outline.embolden(tc.embolden, tc.embolden);
+            }
+            if x_offset != 0.0 {
+                for point in outline.points_mut() {
+                    point.x += x_offset;
+                }
+            }
+            path = make_path_elements(outline.verbs(), outline.points());
         }
-        encoder
+        GlyphOutline::Mono(path)
     }
 }
 
 impl PietGpuTextLayout {
-    pub(crate) fn make_layout(font: &Font, text: &str, size: f64) -> PietGpuTextLayout {
+    fn make_layout(
+        builder: PietGpuTextLayoutBuilder,
+        coords: Vec<NormalizedCoord>,
+    ) -> PietGpuTextLayout {
+        let font = builder.font;
         let mut glyphs = Vec::new();
         let mut x = 0.0;
         //let y = 0.0;
-        for c in text.chars() {
+        let metrics = font.font_ref.glyph_metrics(&coords);
+        for c in builder.text.chars() {
             let glyph_id = font.font_ref.charmap().map(c);
             let glyph = Glyph { glyph_id, x /*, y*/ };
             glyphs.push(glyph);
-            let adv = font.font_ref.glyph_metrics(&[]).advance_width(glyph_id);
+            let adv = metrics.advance_width(glyph_id);
             x += adv;
         }
         PietGpuTextLayout {
             glyphs,
-            font: font.clone(),
-            size,
+            font,
+            size: builder.size,
+            hint: builder.hint,
+            coords,
+            palette: builder.palette,
+            foreground: builder.foreground,
+            raster: builder.raster,
+            cache: builder.cache,
         }
     }
 
     pub(crate) fn draw_text(&self, ctx: &mut PietGpuRenderContext, pos: Point) {
-        let mut scale_ctx = ScaleContext::new();
-        let scaler = scale_ctx.builder(self.font.font_ref).size(2048.).build();
-        let mut tc = TextRenderCtx { scaler };
-        // Should we use ppem from font, or let swash scale?
-        const DEFAULT_UPEM: u16 = 2048;
-        let scale = self.size as f32 / DEFAULT_UPEM as f32;
-        let mut inv_transform = None;
+        let mut cache = self.cache.borrow_mut();
+        let TextCache { scale_ctx, glyphs } = &mut *cache;
+        // The size of the text in the image, which differs from its size in
+        // user space under a scaling transform, such as for HiDPI displays.
+        let [xx, yx, xy, yy, dx, dy] = ctx.current_transform().as_coeffs();
+        let device_scale = (xx * yy - yx * xy).abs().sqrt() as f32;
+        let device_size = self.size as f32 * device_scale;
+        // A degenerate transform leaves nothing to draw.
+        if device_size.is_nan() || device_size <= 0.0 {
+            return;
+        }
+        // Hinting only makes sense at the size the outline is displayed, and
+        // only keeps stems on the pixel grid if they stay axis-aligned. Text
+        // that is rotated, skewed or scaled non-uniformly is not hinted.
+        let hint = self.hint && yx == 0.0 && xy == 0.0 && xx == yy && xx > 0.0;
+        let ppem = if hint {
+            device_size
+        } else {
+            DEFAULT_UPEM as f32
+        };
+        let embolden = if self.raster.stem_darkening {
+            stem_darkening(device_size) * ppem / device_size
+        } else {
+            0.0
+        };
+        // The scaler is only built once a glyph misses the cache, taking the
+        // scale context then.
+        let mut scale_ctx = Some(scale_ctx);
+        let mut tc = None;
+        let font_id = self.font.id();
+        // Only color outlines depend on the palette and foreground.
+        let has_color_outlines = self.font.font_ref.table(tag_from_bytes(b"COLR")).is_some();
+        let palette_index = if has_color_outlines {
+            select_palette(&self.font.font_ref, self.palette).map(|palette| palette.index())
+        } else {
+            None
+        };
+        let upem = self.font.font_ref.metrics(&self.coords).units_per_em;
+        let x_scale = ppem / upem as f32;
+        let scale = self.size as f32 / ppem;
+        let flags = self.raster.glyph_flags();
+        // Hinted glyphs are snapped at their position in the image, so the
+        // pen position is included before snapping. The current transform
+        // is then only a uniform scale and a translation.
+        let y = if hint {
+            ((pos.y * xx + dy).round() - dy) / xx
+        } else {
+            pos.y
+        } as f32;
         // TODO: handle y offsets also
-        let mut last_x = 0.0;
         for glyph in &self.glyphs {
-            let transform = match &mut inv_transform {
-                None => {
-                    let inv_scale = scale.recip();
-                    let translate = render_ctx::to_f32_2(pos);
-                    inv_transform = Some(Transform {
-                        mat: [inv_scale, 0.0, 0.0, -inv_scale],
-                        translate: [
-                            -translate[0] * inv_scale - glyph.x,
-                            translate[1] * inv_scale,
-                        ],
-                    });
-                    let tpos = render_ctx::to_f32_2(pos);
-                    let translate = [tpos[0] + scale * glyph.x, tpos[1]];
-                    Transform {
-                        mat: [scale, 0.0, 0.0, -scale],
-                        translate,
-                    }
+            let x = pos.x as f32 + scale * glyph.x * x_scale;
+            let (x, subpixel) = if hint {
+                let (x, subpixel) = snap_hinted(
+                    x * device_scale + dx as f32,
+                    self.raster.subpixel_positioning,
+                );
+                ((x - dx as f32) / device_scale, subpixel)
+            } else {
+                (x, 0)
+            };
+            let transform = Affine::new(&[scale, 0.0, 0.0, -scale, x, y]);
+            let mut key = GlyphKey::new(font_id, glyph.glyph_id, ppem, hint, &self.coords)
+                .with_subpixel(subpixel)
+                .with_embolden(embolden);
+            if let Some(palette_index) = palette_index {
+                key = key.with_colors(palette_index, self.foreground.to_premul_u32());
+            }
+            let x_offset = subpixel as f32 / SUBPIXEL_BUCKETS as f32;
+            let outline = glyphs.get_or_insert_with(key, || {
+                let tc = tc.get_or_insert_with(|| TextRenderCtx {
+                    scaler: scale_ctx
+                        .take()
+                        .unwrap()
+                        .builder(self.font.font_ref)
+                        .size(ppem)
+                        .hint(hint)
+                        .normalized_coords(&self.coords)
+                        .build(),
+                    palette: self.palette,
+                    foreground: self.foreground,
+                    embolden,
+                });
+                self.font.make_path(glyph.glyph_id, x_offset, tc)
+            });
+            let builder = ctx.builder();
+            match outline {
+                // Outlines that failed to scale draw nothing.
+                GlyphOutline::Mono(path) if path.is_empty() => continue,
+                GlyphOutline::Mono(path) => {
+                    builder.push_transform(transform);
+                    builder.fill_glyph(self.foreground, flags, path);
                 }
-                Some(inv) => {
-                    let delta_x = glyph.x - last_x;
-                    inv.translate[0] -= delta_x;
-                    Transform {
-                        mat: [1.0, 0.0, 0.0, 1.0],
-                        translate: [delta_x, 0.0],
-                    }
+                GlyphOutline::Color(fragment) => {
+                    builder.push_transform(transform);
+                    builder.append(fragment);
                 }
-            };
-            last_x = glyph.x;
-            //println!("{:?}, {:?}", transform.mat, transform.translate);
-            ctx.encode_transform(transform);
-            let glyph = self.font.make_path(glyph.glyph_id, &mut tc);
-            ctx.encode_glyph(&glyph);
-            if !glyph.is_color() {
-                ctx.fill_glyph(0xff_ff_ff_ff);
             }
-        }
-        if let Some(transform) = inv_transform {
-            ctx.encode_transform(transform);
+            builder.pop_transform();
         }
     }
 }
 
 impl PietGpuTextLayoutBuilder {
-    pub(crate) fn new(font: &Font, text: &str) -> PietGpuTextLayoutBuilder {
+    fn new(font: &Font, text: &str, cache: &Rc<RefCell<TextCache>>) -> PietGpuTextLayoutBuilder {
         PietGpuTextLayoutBuilder {
             font: font.clone(),
             text: text.to_owned(),
             size: 12.0,
+            hint: false,
+            variations: Vec::new(),
+            palette: PaletteSelection::default(),
+            foreground: brush::Color::rgb8(255, 255, 255),
+            raster: TextRasterOptions::default(),
+            cache: cache.clone(),
         }
     }
+
+    /// Set the rasterization options for this layout.
+    pub fn raster_options(mut self, raster: TextRasterOptions) -> Self {
+        self.raster = raster;
+        self
+    }
+
+    /// Choose the palette used for fonts with color outlines.
+    pub fn palette(mut self, palette: PaletteSelection) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Set the value of a font variation axis, such as `"wght"`.
+    ///
+    /// Axes not present in the font are ignored.
+    pub fn variation(mut self, tag: &str, value: f32) -> Self {
+        let tag = tag_from_str_lossy(tag);
+        self.variations.retain(|setting| setting.tag != tag);
+        self.variations.push(Setting { tag, value });
+        self
+    }
+
+    /// Set the weight (`wght` axis) of a variable font.
+    pub fn weight(self, weight: f32) -> Self {
+        self.variation("wght", weight)
+    }
+
+    /// Set the width (`wdth` axis) of a variable font, as a percentage of normal.
+    pub fn width(self, width: f32) -> Self {
+        self.variation("wdth", width)
+    }
+
+    /// Set the optical size (`opsz` axis) of a variable font.
+    ///
+    /// If this is not set, the optical size follows the font size.
+    pub fn optical_size(self, size: f32) -> Self {
+        self.variation("opsz", size)
+    }
+
+    /// Enable hinting of outlines at the layout's pixel size.
+    ///
+    /// This improves the legibility of small text, at the cost of scaling
+    /// outlines separately for each size. The size is that of the text in
+    /// the image, including the scale of the current transform. Text drawn
+    /// with a rotation, skew or non-uniform scale is not hinted.
+    pub fn hint(mut self, hint: bool) -> Self {
+        self.hint = hint;
+        self
+    }
 }
 
 impl TextLayoutBuilder for PietGpuTextLayoutBuilder {
@@ -240,6 +548,8 @@ impl TextLayoutBuilder for PietGpuTextLayoutBuilder {
         let attribute = attribute.into();
         match attribute {
             TextAttribute::FontSize(size) => self.size = size,
+            TextAttribute::Weight(weight) => return self.weight(weight.to_raw() as f32),
+            TextAttribute::TextColor(color) => self.foreground = render_ctx::to_scene_color(&color),
             _ => (),
         }
         self
@@ -254,44 +564,365 @@ impl TextLayoutBuilder for PietGpuTextLayoutBuilder {
     }
 
     fn build(self) -> Result<Self::Out, Error> {
-        Ok(PietGpuTextLayout::make_layout(
-            &self.font, &self.text, self.size,
-        ))
+        let opsz = tag_from_str_lossy("opsz");
+        let auto_opsz = Setting {
+            tag: opsz,
+            value: self.size as f32,
+        };
+        let auto_opsz = if self.variations.iter().any(|setting| setting.tag == opsz) {
+            None
+        } else {
+            Some(auto_opsz)
+        };
+        let coords = self
+            .font
+            .font_ref
+            .variations()
+            .normalized_coords(auto_opsz.into_iter().chain(self.variations.iter().copied()))
+            .collect();
+        Ok(PietGpuTextLayout::make_layout(self, coords))
+    }
+}
+
+/// Find the palette of a font matching `selection`.
+pub(crate) fn select_palette<'a>(
+    font_ref: &FontRef<'a>,
+    selection: PaletteSelection,
+) -> Option<ColorPalette<'a>> {
+    let selected = match selection {
+        PaletteSelection::Index(index) => font_ref.color_palettes().nth(index as usize),
+        PaletteSelection::Light => font_ref.color_palettes().find(|palette| {
+            matches!(
+                palette.usability(),
+                Some(Usability::Light) | Some(Usability::Both)
+            )
+        }),
+        PaletteSelection::Dark => font_ref.color_palettes().find(|palette| {
+            matches!(
+                palette.usability(),
+                Some(Usability::Dark) | Some(Usability::Both)
+            )
+        }),
+    };
+    selected.or_else(|| font_ref.color_palettes().next())
+}
+
+/// The palette entry index that COLR layers use for the text color.
+const FOREGROUND_COLOR_INDEX: u16 = 0xFFFF;
+
+/// The color of a color outline layer with the given palette entry.
+///
+/// Layers using the foreground entry or an entry missing from the palette,
+/// as well as all layers when the font has no palette, take the foreground
+/// color.
+fn layer_color(
+    color_index: Option<u16>,
+    palette: Option<&ColorPalette>,
+    foreground: brush::Color,
+) -> brush::Color {
+    match (color_index, palette) {
+        (Some(FOREGROUND_COLOR_INDEX), _) => foreground,
+        (Some(color_ix), Some(palette)) if color_ix < palette.len() => {
+            let [r, g, b, a] = palette.get(color_ix);
+            brush::Color::rgba8(r, g, b, a)
+        }
+        _ => foreground,
     }
 }
 
-pub(crate) fn append_outline(encoder: &mut GlyphEncoder, verbs: &[Verb], points: &[Vector]) {
-    let mut path_encoder = encoder.path_encoder();
+/// Encode each layer of a color outline followed by its fill.
+pub(crate) fn make_color_outline(
+    outline: &Outline,
+    palette: Option<ColorPalette>,
+    foreground: brush::Color,
+) -> Fragment {
+    let mut fragment = Fragment::default();
+    let mut builder = build_fragment(&mut fragment);
+    let mut i = 0;
+    while let Some(layer) = outline.get(i) {
+        let color = layer_color(layer.color_index(), palette.as_ref(), foreground);
+        let path = make_path_elements(layer.verbs(), layer.points());
+        builder.fill(Fill::NonZero, &Brush::Solid(color), None, &path);
+        i += 1;
+    }
+    // Only fills are added, so this cannot fail.
+    let _ = builder.finish();
+    fragment
+}
+
+pub(crate) fn make_path_elements(verbs: &[Verb], points: &[Vector]) -> Vec<Element> {
+    let point = |i: usize| ScenePoint::new(points[i].x, points[i].y);
+    let mut elements = Vec::with_capacity(verbs.len());
     let mut i = 0;
     for verb in verbs {
         match verb {
             Verb::MoveTo => {
-                let p = points[i];
-                path_encoder.move_to(p.x, p.y);
+                elements.push(Element::MoveTo(point(i)));
                 i += 1;
             }
             Verb::LineTo => {
-                let p = points[i];
-                path_encoder.line_to(p.x, p.y);
+                elements.push(Element::LineTo(point(i)));
                 i += 1;
             }
             Verb::QuadTo => {
-                let p1 = points[i];
-                let p2 = points[i + 1];
-                path_encoder.quad_to(p1.x, p1.y, p2.x, p2.y);
+                elements.push(Element::QuadTo(point(i), point(i + 1)));
                 i += 2;
             }
             Verb::CurveTo => {
-                let p1 = points[i];
-                let p2 = points[i + 1];
-                let p3 = points[i + 2];
-                path_encoder.cubic_to(p1.x, p1.y, p2.x, p2.y, p3.x, p3.y);
+                elements.push(Element::CurveTo(point(i), point(i + 1), point(i + 2)));
                 i += 3;
             }
-            Verb::Close => path_encoder.close_path(),
+            Verb::Close => elements.push(Element::Close),
         }
     }
-    path_encoder.path();
-    let n_pathseg = path_encoder.n_pathseg();
-    encoder.finish_path(n_pathseg);
+    elements
+}
+
+#[cfg(test)]
+mod test {
+    use piet::kurbo::Affine;
+    use piet::{RenderContext, Text, TextLayoutBuilder};
+    use piet_scene::brush::Color;
+    use piet_scene::resource::ResourceContext;
+    use piet_scene::scene::Scene;
+    use swash::{tag_from_str_lossy, FontRef};
+
+    use super::{
+        layer_color, select_palette, snap_hinted, Font, PaletteSelection, PietGpuText,
+        TextRasterOptions, FOREGROUND_COLOR_INDEX,
+    };
+    use crate::PietGpuRenderContext;
+
+    /// A font with only a CPAL table, holding a palette for each of the
+    /// given palette type flags. Palette `i` has a single gray entry of
+    /// level `10 * i`.
+    fn palette_font(types: &[u32]) -> Vec<u8> {
+        fn push16(data: &mut Vec<u8>, value: u16) {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        fn push32(data: &mut Vec<u8>, value: u32) {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        let n = types.len() as u16;
+        let records_offset = 24 + 2 * n as u32;
+        let types_offset = records_offset + 4 * n as u32;
+        let mut cpal = Vec::new();
+        // Version 1, with one entry per palette.
+        push16(&mut cpal, 1);
+        push16(&mut cpal, 1);
+        push16(&mut cpal, n);
+        push16(&mut cpal, n);
+        push32(&mut cpal, records_offset);
+        for i in 0..n {
+            push16(&mut cpal, i);
+        }
+        push32(&mut cpal, types_offset);
+        push32(&mut cpal, 0);
+        push32(&mut cpal, 0);
+        for i in 0..n {
+            let level = 10 * i as u8;
+            cpal.extend_from_slice(&[level, level, level, 255]);
+        }
+        for palette_type in types {
+            push32(&mut cpal, *palette_type);
+        }
+
+        let mut font = Vec::new();
+        push32(&mut font, 0x0001_0000);
+        push16(&mut font, 1);
+        push16(&mut font, 16);
+        push16(&mut font, 0);
+        push16(&mut font, 0);
+        font.extend_from_slice(b"CPAL");
+        push32(&mut font, 0);
+        push32(&mut font, 28);
+        push32(&mut font, cpal.len() as u32);
+        font.extend_from_slice(&cpal);
+        font
+    }
+
+    fn selected(data: &[u8], selection: PaletteSelection) -> Option<u16> {
+        let font_ref = FontRef::from_index(data, 0).unwrap();
+        select_palette(&font_ref, selection).map(|palette| palette.index())
+    }
+
+    #[test]
+    fn select_palette_by_index() {
+        let data = palette_font(&[0, 0, 0]);
+        assert_eq!(selected(&data, PaletteSelection::Index(1)), Some(1));
+        // Missing palettes fall back to the first.
+        assert_eq!(selected(&data, PaletteSelection::Index(7)), Some(0));
+        let empty = palette_font(&[]);
+        assert_eq!(selected(&empty, PaletteSelection::Index(0)), None);
+    }
+
+    #[test]
+    fn select_palette_by_background() {
+        // No flags, dark, light, both.
+        let data = palette_font(&[0, 2, 1, 3]);
+        assert_eq!(selected(&data, PaletteSelection::Light), Some(2));
+        assert_eq!(selected(&data, PaletteSelection::Dark), Some(1));
+        let data = palette_font(&[0, 3]);
+        assert_eq!(selected(&data, PaletteSelection::Light), Some(1));
+        assert_eq!(selected(&data, PaletteSelection::Dark), Some(1));
+        // Without a matching palette, the first is used.
+        let data = palette_font(&[0, 1]);
+        assert_eq!(selected(&data, PaletteSelection::Dark), Some(0));
+    }
+
+    #[test]
+    fn foreground_layers() {
+        let data = palette_font(&[0, 0, 0]);
+        let font_ref = FontRef::from_index(&data, 0).unwrap();
+        let palette = select_palette(&font_ref, PaletteSelection::Index(2)).unwrap();
+        let foreground = Color::rgb8(1, 2, 3);
+        let color = |index| layer_color(index, Some(&palette), foreground);
+        assert_eq!(color(Some(0)), Color::rgba8(20, 20, 20, 255));
+        assert_eq!(color(Some(FOREGROUND_COLOR_INDEX)), foreground);
+        assert_eq!(color(Some(1)), foreground);
+        assert_eq!(color(None), foreground);
+        assert_eq!(layer_color(Some(0), None, foreground), foreground);
+    }
+
+    #[test]
+    fn snap_whole_pixels() {
+        assert_eq!(snap_hinted(15.3, false), (15.0, 0));
+        assert_eq!(snap_hinted(15.7, false), (16.0, 0));
+        assert_eq!(snap_hinted(-0.7, false), (-1.0, 0));
+    }
+
+    #[test]
+    fn snap_subpixel_buckets() {
+        assert_eq!(snap_hinted(15.0, true), (15.0, 0));
+        assert_eq!(snap_hinted(15.3, true), (15.0, 1));
+        assert_eq!(snap_hinted(15.5, true), (15.0, 2));
+        assert_eq!(snap_hinted(15.9, true), (16.0, 0));
+        // Negative positions round towards the pixel on their left.
+        assert_eq!(snap_hinted(-0.75, true), (-1.0, 1));
+    }
+
+    #[test]
+    fn variations_replace_earlier_values() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut ctx = PietGpuRenderContext::new(&mut scene, &mut rcx);
+        let builder = ctx
+            .text()
+            .new_text_layout("a")
+            .weight(400.0)
+            .variation("wdth", 75.0)
+            .variation("wght", 700.0);
+        let wght = tag_from_str_lossy("wght");
+        let wght_values = builder
+            .variations
+            .iter()
+            .filter(|setting| setting.tag == wght)
+            .map(|setting| setting.value)
+            .collect::<Vec<_>>();
+        assert_eq!(wght_values, [700.0]);
+        assert_eq!(builder.variations.len(), 2);
+        // The bundled font has no variation axes, so they are ignored.
+        let layout = builder.build().unwrap();
+        assert!(layout.coords.iter().all(|coord| *coord == 0));
+    }
+
+    #[test]
+    fn hinted_pen_position_is_snapped() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut ctx = PietGpuRenderContext::new(&mut scene, &mut rcx);
+        ctx.text().set_raster_options(TextRasterOptions {
+            subpixel_positioning: true,
+            ..Default::default()
+        });
+        let layout = ctx
+            .text()
+            .new_text_layout("hinted")
+            .hint(true)
+            .build()
+            .unwrap();
+        ctx.draw_text(&layout, (10.0, 20.0));
+        let first = ctx.text().glyph_cache_stats();
+        // A whole pixel away, every glyph lands in the same bucket.
+        ctx.draw_text(&layout, (11.0, 20.4));
+        let second = ctx.text().glyph_cache_stats();
+        assert_eq!(second.misses, first.misses);
+        // A quarter pixel away, the pen position moves the glyphs to the
+        // next bucket.
+        ctx.draw_text(&layout, (10.25, 20.0));
+        let third = ctx.text().glyph_cache_stats();
+        assert!(third.misses > second.misses);
+    }
+
+    #[test]
+    fn hinted_at_device_size() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut ctx = PietGpuRenderContext::new(&mut scene, &mut rcx);
+        let mut layout = |size: f64| {
+            ctx.text()
+                .new_text_layout("hinted")
+                .font(piet::FontFamily::SYSTEM_UI, size)
+                .hint(true)
+                .build()
+                .unwrap()
+        };
+        let (small, large) = (layout(12.0), layout(24.0));
+        let mut draw_scaled = |layout, transform| {
+            ctx.with_save(|ctx| {
+                ctx.transform(transform);
+                ctx.draw_text(layout, (10.0, 20.0));
+                Ok(())
+            })
+            .unwrap();
+            ctx.text().glyph_cache_stats().misses
+        };
+        let first = draw_scaled(&small, Affine::scale(2.0));
+        // Scaled by 2, the small text is hinted at the size of the large
+        // text, so it shares its outlines.
+        assert_eq!(draw_scaled(&large, Affine::IDENTITY), first);
+        let second = draw_scaled(&small, Affine::IDENTITY);
+        assert!(second > first);
+        // Rotated text is not hinted, so it shares the outlines of unhinted
+        // text of the same size.
+        draw_scaled(&small, Affine::rotate(0.5));
+        let third = ctx.text().glyph_cache_stats().misses;
+        let unhinted = ctx
+            .text()
+            .new_text_layout("hinted")
+            .font(piet::FontFamily::SYSTEM_UI, 12.0)
+            .build()
+            .unwrap();
+        ctx.draw_text(&unhinted, (10.0, 20.0));
+        assert_eq!(ctx.text().glyph_cache_stats().misses, third);
+    }
+
+    #[test]
+    fn variable_font_axes() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut ctx = PietGpuRenderContext::new(&mut scene, &mut rcx);
+        // Inconsolata has weight and width axes, in that order.
+        let font = Font::from_data(include_bytes!("../third-party/Inconsolata-variable.ttf"));
+        ctx.set_text(PietGpuText::new(font));
+        let default = ctx.text().new_text_layout("mm").build().unwrap();
+        assert!(default.coords.iter().all(|coord| *coord == 0));
+        let condensed_bold = ctx
+            .text()
+            .new_text_layout("mm")
+            .weight(700.0)
+            .width(50.0)
+            .build()
+            .unwrap();
+        assert!(condensed_bold.coords[0] > 0);
+        assert!(condensed_bold.coords[1] < 0);
+        // Variations also apply to the metrics of the layout.
+        assert!(condensed_bold.glyphs[1].x < default.glyphs[1].x);
+
+        // And to the outlines, which are cached separately.
+        ctx.draw_text(&default, (0.0, 20.0));
+        let misses = ctx.text().glyph_cache_stats().misses;
+        ctx.draw_text(&condensed_bold, (0.0, 20.0));
+        assert!(ctx.text().glyph_cache_stats().misses > misses);
+    }
 }
@@ -0,0 +1,666 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! Structural validation of encoded scenes.
+//!
+//! The GPU pipeline trusts the encoding completely, so an inconsistent scene
+//! renders garbage tiles or reads out of bounds. These checks catch the
+//! inconsistencies before upload.
+
+use std::fmt;
+
+use piet_scene::scene::drawtag::{
+    DRAWTAG_BEGINCLIP, DRAWTAG_ENDCLIP, DRAWTAG_FILLBLURRECT, DRAWTAG_FILLCOLOR, DRAWTAG_FILLGLYPH,
+    DRAWTAG_FILLIMAGE, DRAWTAG_FILLLINGRADIENT, DRAWTAG_FILLRADGRADIENT, DRAWTAG_NOP,
+};
+
+// See shader/pathtag.h for the layout of path tags.
+const PATH_TAG_SEG_TYPE: u8 = 3;
+const PATH_TAG_SUBPATH_END: u8 = 4;
+const PATH_TAG_F32: u8 = 8;
+const PATH_TAG_PATH: u8 = 0x10;
+const PATH_TAG_TRANSFORM: u8 = 0x20;
+const PATH_TAG_LINEWIDTH: u8 = 0x40;
+
+/// A structural error in an encoded scene.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SceneError {
+    /// The draw tag at `index` is not a known draw object.
+    UnknownDrawTag { index: usize, tag: u32 },
+    /// The draw data does not have the size implied by the draw tags.
+    DrawDataSize { expected: usize, actual: usize },
+    /// The end clip at `index` has no matching begin clip.
+    UnmatchedEndClip { index: usize },
+    /// The end clip at `index` has a different blend than its begin clip.
+    MismatchedEndClip { index: usize },
+    /// Clips were begun and never ended.
+    UnclosedClips { count: usize },
+    /// The clip count does not match the begin and end clip tags.
+    ClipCount { expected: u32, actual: u32 },
+    /// The path count does not match the path tags.
+    PathCount { expected: u32, actual: u32 },
+    /// The number of draw objects does not match the number of paths.
+    DrawObjectCount { paths: u32, draw_objects: usize },
+    /// The path segment count does not match the path tags.
+    PathSegCount { expected: u32, actual: u32 },
+    /// The path segment data does not have the size implied by the path tags.
+    PathSegSize { expected: usize, actual: usize },
+    /// The transform stream does not have an entry for each transform tag
+    /// after the initial transform.
+    TransformCount { expected: usize, actual: usize },
+    /// The linewidth stream does not have an entry for each linewidth tag
+    /// after the initial linewidth.
+    LinewidthCount { expected: usize, actual: usize },
+    /// A filter layer is not composited by an image draw at its offset.
+    FilterLayerOffset { drawdata_offset: usize },
+    /// A filter layer has invalid content.
+    FilterLayer {
+        drawdata_offset: usize,
+        error: Box<SceneError>,
+    },
+    /// A layer was popped without a matching push in the same save state.
+    UnmatchedPopLayer,
+    /// Masked content was begun without a pending mask.
+    UnmatchedMaskContent,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::UnknownDrawTag { index, tag } => {
+                write!(f, "unknown draw tag {:#x} at draw object {}", tag, index)
+            }
+            SceneError::DrawDataSize { expected, actual } => write!(
+                f,
+                "draw data is {} bytes, but the draw tags require {}",
+                actual, expected
+            ),
+            SceneError::UnmatchedEndClip { index } => {
+                write!(f, "end clip at draw object {} has no begin clip", index)
+            }
+            SceneError::MismatchedEndClip { index } => write!(
+                f,
+                "end clip at draw object {} has a different blend than its begin clip",
+                index
+            ),
+            SceneError::UnclosedClips { count } => write!(f, "{} clips are never ended", count),
+            SceneError::ClipCount { expected, actual } => write!(
+                f,
+                "clip count is {}, but there are {} clip tags",
+                actual, expected
+            ),
+            SceneError::PathCount { expected, actual } => write!(
+                f,
+                "path count is {}, but there are {} path tags",
+                actual, expected
+            ),
+            SceneError::DrawObjectCount {
+                paths,
+                draw_objects,
+            } => write!(
+                f,
+                "there are {} draw objects for {} paths",
+                draw_objects, paths
+            ),
+            SceneError::PathSegCount { expected, actual } => write!(
+                f,
+                "path segment count is {}, but the path tags have {}",
+                actual, expected
+            ),
+            SceneError::PathSegSize { expected, actual } => write!(
+                f,
+                "path segment data is {} bytes, but the path tags require {}",
+                actual, expected
+            ),
+            SceneError::TransformCount { expected, actual } => write!(
+                f,
+                "there are {} transforms, but the path tags require {}",
+                actual, expected
+            ),
+            SceneError::LinewidthCount { expected, actual } => write!(
+                f,
+                "there are {} linewidths, but the path tags require {}",
+                actual, expected
+            ),
+            SceneError::FilterLayerOffset { drawdata_offset } => write!(
+                f,
+                "filter layer at draw data offset {} has no image draw",
+                drawdata_offset
+            ),
+            SceneError::FilterLayer {
+                drawdata_offset,
+                error,
+            } => write!(
+                f,
+                "in filter layer at draw data offset {}: {}",
+                drawdata_offset, error
+            ),
+            SceneError::UnmatchedPopLayer => {
+                write!(f, "layer popped without a matching push")
+            }
+            SceneError::UnmatchedMaskContent => {
+                write!(f, "masked content begun without a pending mask")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/// The streams of an encoded scene.
+pub(crate) struct SceneStreams<'a> {
+    pub n_transform: usize,
+    pub n_linewidth: usize,
+    pub tag_stream: &'a [u8],
+    pub pathseg_stream: &'a [u8],
+    pub drawtag_stream: &'a [u32],
+    pub drawdata_stream: &'a [u8],
+    pub n_path: u32,
+    pub n_pathseg: u32,
+    pub n_clip: u32,
+}
+
+impl<'a> SceneStreams<'a> {
+    pub fn validate(&self) -> Result<(), SceneError> {
+        self.validate_path_tags()?;
+        self.validate_draw_tags()?;
+        Ok(())
+    }
+
+    /// Return the draw data offset of each draw object with the given tag.
+    pub fn drawdata_offsets(&self, tag: u32) -> impl Iterator<Item = usize> + 'a {
+        self.drawtag_stream
+            .iter()
+            .scan(0, |offset, &drawtag| {
+                let start = *offset;
+                *offset += drawdata_size(drawtag);
+                Some((drawtag, start))
+            })
+            .filter(move |(drawtag, _)| *drawtag == tag)
+            .map(|(_, offset)| offset)
+    }
+
+    fn validate_path_tags(&self) -> Result<(), SceneError> {
+        let mut n_transform = 1;
+        let mut n_linewidth = 1;
+        let mut n_path = 0;
+        let mut n_pathseg = 0;
+        let mut pathseg_size = 0;
+        for &tag in self.tag_stream {
+            n_transform += (tag & PATH_TAG_TRANSFORM != 0) as usize;
+            n_linewidth += (tag & PATH_TAG_LINEWIDTH != 0) as usize;
+            n_path += (tag & PATH_TAG_PATH != 0) as u32;
+            // This follows reduce_tag in shader/pathtag.h.
+            let point_count = (tag & PATH_TAG_SEG_TYPE) as usize;
+            n_pathseg += (point_count != 0) as u32;
+            let n_points = point_count + (tag & PATH_TAG_SUBPATH_END != 0) as usize;
+            let point_size = if tag & PATH_TAG_F32 != 0 { 8 } else { 4 };
+            pathseg_size += n_points * point_size;
+        }
+        if n_transform != self.n_transform {
+            return Err(SceneError::TransformCount {
+                expected: n_transform,
+                actual: self.n_transform,
+            });
+        }
+        if n_linewidth != self.n_linewidth {
+            return Err(SceneError::LinewidthCount {
+                expected: n_linewidth,
+                actual: self.n_linewidth,
+            });
+        }
+        if n_path != self.n_path {
+            return Err(SceneError::PathCount {
+                expected: n_path,
+                actual: self.n_path,
+            });
+        }
+        if n_pathseg != self.n_pathseg {
+            return Err(SceneError::PathSegCount {
+                expected: n_pathseg,
+                actual: self.n_pathseg,
+            });
+        }
+        if pathseg_size != self.pathseg_stream.len() {
+            return Err(SceneError::PathSegSize {
+                expected: pathseg_size,
+                actual: self.pathseg_stream.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_draw_tags(&self) -> Result<(), SceneError> {
+        let mut drawdata_size_sum = 0;
+        let mut n_draw_path = 0;
+        let mut n_clip = 0;
+        // Blend words of the clips that have begun but not ended.
+        let mut clip_stack = Vec::new();
+        for (index, &tag) in self.drawtag_stream.iter().enumerate() {
+            match tag {
+                DRAWTAG_NOP => (),
+                DRAWTAG_FILLCOLOR
+                | DRAWTAG_FILLLINGRADIENT
+                | DRAWTAG_FILLRADGRADIENT
+                | DRAWTAG_FILLIMAGE
+                | DRAWTAG_FILLGLYPH
+                | DRAWTAG_FILLBLURRECT
+                | DRAWTAG_BEGINCLIP
+                | DRAWTAG_ENDCLIP => n_draw_path += 1,
+                _ => return Err(SceneError::UnknownDrawTag { index, tag }),
+            }
+            let offset = drawdata_size_sum;
+            drawdata_size_sum += drawdata_size(tag);
+            if tag != DRAWTAG_BEGINCLIP && tag != DRAWTAG_ENDCLIP {
+                continue;
+            }
+            n_clip += 1;
+            // The draw data of a clip is its blend word.
+            let blend = self
+                .drawdata_stream
+                .get(offset..offset + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+            if tag == DRAWTAG_BEGINCLIP {
+                clip_stack.push(blend);
+            } else {
+                match clip_stack.pop() {
+                    None => return Err(SceneError::UnmatchedEndClip { index }),
+                    Some(begin_blend) if begin_blend != blend => {
+                        return Err(SceneError::MismatchedEndClip { index })
+                    }
+                    _ => (),
+                }
+            }
+        }
+        if drawdata_size_sum != self.drawdata_stream.len() {
+            return Err(SceneError::DrawDataSize {
+                expected: drawdata_size_sum,
+                actual: self.drawdata_stream.len(),
+            });
+        }
+        if !clip_stack.is_empty() {
+            return Err(SceneError::UnclosedClips {
+                count: clip_stack.len(),
+            });
+        }
+        if n_clip != self.n_clip {
+            return Err(SceneError::ClipCount {
+                expected: n_clip,
+                actual: self.n_clip,
+            });
+        }
+        if n_draw_path != self.n_path as usize {
+            return Err(SceneError::DrawObjectCount {
+                paths: self.n_path,
+                draw_objects: n_draw_path,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The size in bytes of the draw data of a draw object.
+fn drawdata_size(tag: u32) -> usize {
+    (tag & 0x1c) as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{EncodedSceneRef, Filter, FilterLayerRef};
+    use crate::{shader_define, PietGpuRenderContext};
+    use piet_scene::geometry::Affine;
+    use piet_scene::resource::ResourceContext;
+    use piet_scene::scene::Scene;
+
+    /// A closed line, the single segment of a path.
+    const LINE: u8 = PATH_TAG_PATH | PATH_TAG_F32 | PATH_TAG_SUBPATH_END | 1;
+    const LINE_SEGS: [u8; 16] = [0; 16];
+
+    fn empty() -> SceneStreams<'static> {
+        SceneStreams {
+            n_transform: 1,
+            n_linewidth: 1,
+            tag_stream: &[],
+            pathseg_stream: &[],
+            drawtag_stream: &[],
+            drawdata_stream: &[],
+            n_path: 0,
+            n_pathseg: 0,
+            n_clip: 0,
+        }
+    }
+
+    /// A scene of one path per draw tag, with zeroed draw data.
+    fn paths<'a>(tags: &'a [u8], pathsegs: &'a [u8], drawtags: &'a [u32]) -> SceneStreams<'a> {
+        let n_path = drawtags.len() as u32;
+        SceneStreams {
+            tag_stream: &tags[..drawtags.len()],
+            pathseg_stream: &pathsegs[..16 * drawtags.len()],
+            drawtag_stream: drawtags,
+            n_path,
+            n_pathseg: n_path,
+            ..empty()
+        }
+    }
+
+    /// Clip draw data, one blend word per clip.
+    fn blends(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn drawtags_match_shader() {
+        let header = include_str!("../shader/drawtag.h");
+        let tags = [
+            (DRAWTAG_NOP, "Drawtag_Nop"),
+            (DRAWTAG_FILLCOLOR, "Drawtag_FillColor"),
+            (DRAWTAG_FILLLINGRADIENT, "Drawtag_FillLinGradient"),
+            (DRAWTAG_FILLRADGRADIENT, "Drawtag_FillRadGradient"),
+            (DRAWTAG_FILLIMAGE, "Drawtag_FillImage"),
+            (DRAWTAG_FILLGLYPH, "Drawtag_FillGlyph"),
+            (DRAWTAG_FILLBLURRECT, "Drawtag_FillBlurRect"),
+            (DRAWTAG_BEGINCLIP, "Drawtag_BeginClip"),
+            (DRAWTAG_ENDCLIP, "Drawtag_EndClip"),
+        ];
+        for (tag, name) in tags {
+            assert_eq!(tag, shader_define(header, name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn valid_scene() {
+        let drawdata = [0; 4];
+        let streams = SceneStreams {
+            drawdata_stream: &drawdata,
+            ..paths(&[LINE], &LINE_SEGS, &[DRAWTAG_FILLCOLOR])
+        };
+        assert_eq!(streams.validate(), Ok(()));
+        assert_eq!(empty().validate(), Ok(()));
+    }
+
+    #[test]
+    fn unknown_draw_tag() {
+        let streams = SceneStreams {
+            drawtag_stream: &[DRAWTAG_NOP, 0x1234],
+            ..empty()
+        };
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::UnknownDrawTag {
+                index: 1,
+                tag: 0x1234
+            })
+        );
+    }
+
+    #[test]
+    fn draw_data_size() {
+        let streams = paths(&[LINE], &LINE_SEGS, &[DRAWTAG_FILLCOLOR]);
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::DrawDataSize {
+                expected: 4,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn unmatched_end_clip() {
+        let drawdata = blends(&[0]);
+        let streams = SceneStreams {
+            drawdata_stream: &drawdata,
+            n_clip: 1,
+            ..paths(&[LINE], &LINE_SEGS, &[DRAWTAG_ENDCLIP])
+        };
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::UnmatchedEndClip { index: 0 })
+        );
+    }
+
+    #[test]
+    fn mismatched_end_clip() {
+        let drawdata = blends(&[3, 5]);
+        let streams = SceneStreams {
+            drawdata_stream: &drawdata,
+            n_clip: 2,
+            ..paths(&[LINE; 2], &[0; 32], &[DRAWTAG_BEGINCLIP, DRAWTAG_ENDCLIP])
+        };
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::MismatchedEndClip { index: 1 })
+        );
+    }
+
+    #[test]
+    fn unclosed_clips() {
+        let drawdata = blends(&[3, 5, 5]);
+        let streams = SceneStreams {
+            drawdata_stream: &drawdata,
+            n_clip: 3,
+            ..paths(
+                &[LINE; 3],
+                &[0; 48],
+                &[DRAWTAG_BEGINCLIP, DRAWTAG_BEGINCLIP, DRAWTAG_ENDCLIP],
+            )
+        };
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::UnclosedClips { count: 1 })
+        );
+    }
+
+    #[test]
+    fn clip_count() {
+        let drawdata = blends(&[3, 3]);
+        let streams = SceneStreams {
+            drawdata_stream: &drawdata,
+            ..paths(&[LINE; 2], &[0; 32], &[DRAWTAG_BEGINCLIP, DRAWTAG_ENDCLIP])
+        };
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::ClipCount {
+                expected: 2,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn path_count() {
+        let streams = SceneStreams {
+            n_path: 2,
+            ..paths(&[LINE], &LINE_SEGS, &[DRAWTAG_NOP])
+        };
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::PathCount {
+                expected: 1,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn draw_object_count() {
+        let streams = SceneStreams {
+            tag_stream: &[LINE],
+            pathseg_stream: &LINE_SEGS,
+            n_path: 1,
+            n_pathseg: 1,
+            ..empty()
+        };
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::DrawObjectCount {
+                paths: 1,
+                draw_objects: 0
+            })
+        );
+    }
+
+    #[test]
+    fn path_seg_count() {
+        let streams = SceneStreams {
+            n_pathseg: 0,
+            ..paths(&[LINE], &LINE_SEGS, &[DRAWTAG_NOP])
+        };
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::PathSegCount {
+                expected: 1,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn path_seg_size() {
+        // The segment is encoded with i16 coordinates, so it has two 4 byte
+        // points rather than 8 byte ones.
+        let streams = SceneStreams {
+            tag_stream: &[LINE & !PATH_TAG_F32],
+            ..paths(&[LINE], &LINE_SEGS, &[DRAWTAG_NOP])
+        };
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::PathSegSize {
+                expected: 8,
+                actual: 16
+            })
+        );
+    }
+
+    #[test]
+    fn transform_count() {
+        let streams = SceneStreams {
+            tag_stream: &[PATH_TAG_TRANSFORM],
+            ..empty()
+        };
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::TransformCount {
+                expected: 2,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn linewidth_count() {
+        let streams = SceneStreams {
+            tag_stream: &[PATH_TAG_LINEWIDTH],
+            n_linewidth: 3,
+            ..empty()
+        };
+        assert_eq!(
+            streams.validate(),
+            Err(SceneError::LinewidthCount {
+                expected: 2,
+                actual: 3
+            })
+        );
+    }
+
+    /// An encoded scene with a filter layer composited by its second draw,
+    /// an image.
+    fn filtered<'a>(
+        layer: EncodedSceneRef<'a, Affine>,
+        drawdata_offset: usize,
+    ) -> EncodedSceneRef<'a, Affine> {
+        const DRAWTAGS: [u32; 2] = [DRAWTAG_FILLCOLOR, DRAWTAG_FILLIMAGE];
+        const DRAWDATA: [u8; 12] = [0; 12];
+        EncodedSceneRef {
+            tag_stream: &[LINE; 2],
+            pathseg_stream: &[0; 32],
+            drawtag_stream: &DRAWTAGS,
+            drawdata_stream: &DRAWDATA,
+            n_path: 2,
+            n_pathseg: 2,
+            filter_layers: vec![FilterLayerRef {
+                scene: layer,
+                filter: Filter::Blur(1.0),
+                drawdata_offset,
+            }],
+            ..empty_scene()
+        }
+    }
+
+    fn empty_scene() -> EncodedSceneRef<'static, Affine> {
+        const TRANSFORMS: [Affine; 1] = [Affine::IDENTITY];
+        EncodedSceneRef {
+            transform_stream: &TRANSFORMS,
+            tag_stream: &[],
+            pathseg_stream: &[],
+            linewidth_stream: &[-1.0],
+            drawtag_stream: &[],
+            drawdata_stream: &[],
+            n_path: 0,
+            n_pathseg: 0,
+            n_clip: 0,
+            ramp_data: &[],
+            filter_layers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn filter_layer_offset() {
+        assert_eq!(filtered(empty_scene(), 4).validate(), Ok(()));
+        // The offset of the color draw, which cannot composite a layer.
+        assert_eq!(
+            filtered(empty_scene(), 0).validate(),
+            Err(SceneError::FilterLayerOffset { drawdata_offset: 0 })
+        );
+    }
+
+    #[test]
+    fn filter_layer() {
+        let layer = EncodedSceneRef {
+            n_path: 1,
+            ..empty_scene()
+        };
+        assert_eq!(
+            filtered(layer, 4).validate(),
+            Err(SceneError::FilterLayer {
+                drawdata_offset: 4,
+                error: Box::new(SceneError::PathCount {
+                    expected: 0,
+                    actual: 1
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn unmatched_pop_layer() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut ctx = PietGpuRenderContext::new(&mut scene, &mut rcx);
+        ctx.pop_layer();
+        assert_eq!(ctx.validate(), Err(SceneError::UnmatchedPopLayer));
+    }
+
+    #[test]
+    fn unmatched_mask_content() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut ctx = PietGpuRenderContext::new(&mut scene, &mut rcx);
+        ctx.begin_masked_content();
+        assert_eq!(ctx.validate(), Err(SceneError::UnmatchedMaskContent));
+    }
+}
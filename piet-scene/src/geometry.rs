@@ -42,8 +42,8 @@ impl Point {
 
     pub fn transform(&self, affine: &Affine) -> Self {
         Self {
-            x: self.x * affine.xx + self.y * affine.yx + affine.dx,
-            y: self.y * affine.yy + self.y * affine.xy + affine.dy,
+            x: self.x * affine.xx + self.y * affine.xy + affine.dx,
+            y: self.x * affine.yx + self.y * affine.yy + affine.dy,
         }
     }
 }
@@ -122,10 +122,7 @@ impl Affine {
 
     /// Transforms the specified point.
     pub fn transform_point(&self, point: Point) -> Point {
-        Point {
-            x: point.x * self.xx + point.y * self.yx + self.dx,
-            y: point.y * self.yy + point.y * self.xy + self.dy,
-        }
+        point.transform(self)
     }
 
     /// Compute the determinant of this transform.
@@ -219,9 +216,88 @@ impl Rect {
         self.max.y = self.max.y.max(point.y);
     }
 
-    /// Returns a new rectangle that encloses the minimum and maximum points
-    /// of this rectangle after applying the specified transform to each.
+    /// Returns the intersection of this rectangle with another.
+    ///
+    /// The result has zero area if the rectangles do not overlap.
+    pub fn intersect(&self, other: &Rect) -> Self {
+        let min = Point::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y));
+        let max = Point::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y));
+        Self {
+            min,
+            max: Point::new(max.x.max(min.x), max.y.max(min.y)),
+        }
+    }
+
+    /// Returns a new rectangle that encloses the corners of this rectangle
+    /// after applying the specified transform to each.
     pub fn transform(&self, affine: &Affine) -> Self {
-        Self::from_points([self.min.transform(affine), self.max.transform(affine)])
+        Self::from_points([
+            self.min.transform(affine),
+            Point::new(self.max.x, self.min.y).transform(affine),
+            self.max.transform(affine),
+            Point::new(self.min.x, self.max.y).transform(affine),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Affine, Point, Rect};
+
+    fn assert_near(a: Point, b: Point) {
+        assert!(
+            (a.x - b.x).abs() < 1e-5 && (a.y - b.y).abs() < 1e-5,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn point_transform() {
+        let affine = Affine::new(&[2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        let point = Point::new(1.0, 10.0).transform(&affine);
+        assert_eq!(point, Point::new(2.0 + 40.0 + 6.0, 3.0 + 50.0 + 7.0));
+        assert_eq!(affine.transform_point(Point::new(1.0, 10.0)), point);
+        let rotated = Point::new(1.0, 0.0).transform(&Affine::rotate(std::f32::consts::FRAC_PI_2));
+        assert_near(rotated, Point::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn rect_transform_rotated() {
+        // Transforming only min and max would give a degenerate rectangle
+        // along the diagonal.
+        let rect = Rect {
+            min: Point::new(0.0, 0.0),
+            max: Point::new(2.0, 1.0),
+        };
+        let bounds = rect.transform(&Affine::rotate(std::f32::consts::FRAC_PI_2));
+        assert_near(bounds.min, Point::new(-1.0, 0.0));
+        assert_near(bounds.max, Point::new(0.0, 2.0));
+        let bounds = rect.transform(&Affine::rotate(std::f32::consts::FRAC_PI_4));
+        let s = std::f32::consts::FRAC_1_SQRT_2;
+        assert_near(bounds.min, Point::new(-s, 0.0));
+        assert_near(bounds.max, Point::new(2.0 * s, 3.0 * s));
+    }
+
+    #[test]
+    fn rect_intersect_disjoint() {
+        let a = Rect {
+            min: Point::new(0.0, 0.0),
+            max: Point::new(1.0, 1.0),
+        };
+        let b = Rect {
+            min: Point::new(2.0, 3.0),
+            max: Point::new(4.0, 5.0),
+        };
+        let empty = a.intersect(&b);
+        assert_eq!(empty.width(), 0.0);
+        assert_eq!(empty.height(), 0.0);
+        let overlap = a.intersect(&Rect {
+            min: Point::new(0.5, -1.0),
+            max: Point::new(2.0, 0.25),
+        });
+        assert_eq!(overlap.min, Point::new(0.5, 0.0));
+        assert_eq!(overlap.max, Point::new(1.0, 0.25));
     }
 }
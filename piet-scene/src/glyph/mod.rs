@@ -17,9 +17,9 @@
 pub use moscato::pinot;
 
 use crate::brush::{Brush, Color};
-use crate::geometry::Affine;
+use crate::geometry::{Affine, Rect};
 use crate::path::Element;
-use crate::scene::{build_fragment, Fill, Fragment};
+use crate::scene::{build_fragment, Builder, Fill, Fragment};
 
 use moscato::{Context, Scaler};
 use pinot::{types::Tag, FontRef};
@@ -31,6 +31,12 @@ pub struct GlyphContext {
     ctx: Context,
 }
 
+impl Default for GlyphContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GlyphContext {
     /// Creates a new context.
     pub fn new() -> Self {
@@ -104,6 +110,9 @@ impl<'a> GlyphProvider<'a> {
         let mut fragment = Fragment::default();
         let mut builder = build_fragment(&mut fragment);
         let mut xform_stack: SmallVec<[Affine; 8]> = SmallVec::new();
+        // Bounds of the active clips and layers, each intersected with its
+        // parent. Used to give unbounded fills a finite extent.
+        let mut bounds_stack: SmallVec<[Rect; 8]> = SmallVec::new();
         for command in glyph.commands() {
             match command {
                 Command::PushTransform(xform) => {
@@ -120,15 +129,19 @@ impl<'a> GlyphProvider<'a> {
                 Command::PushClip(path_index) => {
                     let path = glyph.path(*path_index)?;
                     if let Some(xform) = xform_stack.last() {
-                        builder.push_layer(
-                            Default::default(),
-                            convert_transformed_path(path.elements(), xform),
-                        );
+                        let elements = convert_transformed_path(path.elements(), xform);
+                        push_bounds(&mut bounds_stack, path_bounds(elements.clone()));
+                        builder.push_layer(Default::default(), elements);
                     } else {
-                        builder.push_layer(Default::default(), convert_path(path.elements()));
+                        let elements = convert_path(path.elements());
+                        push_bounds(&mut bounds_stack, path_bounds(elements.clone()));
+                        builder.push_layer(Default::default(), elements);
                     }
                 }
-                Command::PopClip => builder.pop_layer(),
+                Command::PopClip => {
+                    bounds_stack.pop();
+                    builder.pop_layer();
+                }
                 Command::PushLayer(bounds) => {
                     let mut rect = Rect {
                         min: Point::new(bounds.min.x, bounds.min.y),
@@ -137,9 +150,13 @@ impl<'a> GlyphProvider<'a> {
                     if let Some(xform) = xform_stack.last() {
                         rect = rect.transform(xform);
                     }
+                    push_bounds(&mut bounds_stack, rect);
                     builder.push_layer(Default::default(), rect.elements());
                 }
-                Command::PopLayer => builder.pop_layer(),
+                Command::PopLayer => {
+                    bounds_stack.pop();
+                    builder.pop_layer();
+                }
                 Command::BeginBlend(bounds, mode) => {
                     let mut rect = Rect {
                         min: Point::new(bounds.min.x, bounds.min.y),
@@ -148,18 +165,24 @@ impl<'a> GlyphProvider<'a> {
                     if let Some(xform) = xform_stack.last() {
                         rect = rect.transform(xform);
                     }
+                    push_bounds(&mut bounds_stack, rect);
                     builder.push_layer(convert_blend(*mode), rect.elements())
                 }
-                Command::EndBlend => builder.pop_layer(),
+                Command::EndBlend => {
+                    bounds_stack.pop();
+                    builder.pop_layer();
+                }
                 Command::SimpleFill(path_index, brush, brush_xform) => {
                     let path = glyph.path(*path_index)?;
                     let brush = convert_brush(brush);
                     let brush_xform = brush_xform.map(|xform| convert_transform(&xform));
                     if let Some(xform) = xform_stack.last() {
+                        // The path is transformed to device space, so the
+                        // brush needs the glyph transform as well.
                         builder.fill(
                             Fill::NonZero,
                             &brush,
-                            brush_xform.map(|x| x * *xform),
+                            Some(brush_xform.map_or(*xform, |x| x * *xform)),
                             convert_transformed_path(path.elements(), xform),
                         );
                     } else {
@@ -171,9 +194,14 @@ impl<'a> GlyphProvider<'a> {
                         );
                     }
                 }
-                Command::Fill(_brush, _brush_xform) => {
-                    // TODO: this needs to compute a bounding box for
-                    // the parent clips
+                Command::Fill(brush, brush_xform) => {
+                    let brush = convert_brush(brush);
+                    let brush_xform = brush_xform.map(|xform| convert_transform(&xform));
+                    let brush_xform = match xform_stack.last() {
+                        Some(xform) => Some(brush_xform.map_or(*xform, |x| x * *xform)),
+                        None => brush_xform,
+                    };
+                    fill_bounds(&mut builder, &bounds_stack, &brush, brush_xform);
                 }
             }
         }
@@ -200,6 +228,40 @@ fn convert_path(
     })
 }
 
+fn push_bounds(stack: &mut SmallVec<[Rect; 8]>, rect: Rect) {
+    let rect = match stack.last() {
+        Some(parent) => rect.intersect(parent),
+        None => rect,
+    };
+    stack.push(rect);
+}
+
+/// Fills everything inside the enclosing clips and layers, whose bounds are
+/// on top of `bounds_stack`, so a rectangle over those bounds is sufficient.
+/// A fill outside of any clip has no meaningful extent, and is skipped.
+fn fill_bounds(
+    builder: &mut Builder,
+    bounds_stack: &[Rect],
+    brush: &Brush,
+    brush_xform: Option<Affine>,
+) {
+    if let Some(rect) = bounds_stack.last() {
+        builder.fill(Fill::NonZero, brush, brush_xform, rect.elements());
+    }
+}
+
+fn path_bounds(path: impl Iterator<Item = Element>) -> Rect {
+    Rect::from_points(path.flat_map(|el| {
+        let points: SmallVec<[crate::geometry::Point; 3]> = match el {
+            Element::MoveTo(p0) | Element::LineTo(p0) => [p0].into_iter().collect(),
+            Element::QuadTo(p0, p1) => [p0, p1].into_iter().collect(),
+            Element::CurveTo(p0, p1, p2) => [p0, p1, p2].into_iter().collect(),
+            Element::Close => SmallVec::new(),
+        };
+        points
+    }))
+}
+
 fn convert_transformed_path(
     path: impl Iterator<Item = moscato::Element> + Clone,
     xform: &Affine,
@@ -322,3 +384,53 @@ fn convert_extend(extend: moscato::ExtendMode) -> crate::brush::Extend {
         moscato::ExtendMode::Reflect => Reflect,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{fill_bounds, push_bounds};
+    use crate::brush::{Brush, Color};
+    use crate::geometry::{Affine, Point, Rect};
+    use crate::scene::{build_fragment, Fragment};
+    use smallvec::SmallVec;
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Rect {
+        Rect {
+            min: Point::new(x0, y0),
+            max: Point::new(x1, y1),
+        }
+    }
+
+    /// The bounds of a fragment with a COLRv1 fill inside the specified
+    /// clips.
+    fn fill_inside(clips: &[Rect]) -> Option<[f32; 4]> {
+        let mut fragment = Fragment::default();
+        let mut builder = build_fragment(&mut fragment);
+        let mut bounds_stack = SmallVec::new();
+        for clip in clips {
+            push_bounds(&mut bounds_stack, *clip);
+        }
+        let brush = Brush::Solid(Color::rgb8(255, 0, 0));
+        fill_bounds(&mut builder, &bounds_stack, &brush, None);
+        builder.finish().unwrap();
+        let bounds = fragment.bounds(&Affine::IDENTITY)?;
+        Some([bounds.min.x, bounds.min.y, bounds.max.x, bounds.max.y])
+    }
+
+    #[test]
+    fn fill_bounded_by_clips() {
+        assert_eq!(
+            fill_inside(&[rect(0.0, 0.0, 100.0, 100.0)]),
+            Some([0.0, 0.0, 100.0, 100.0])
+        );
+        // Nested clips bound the fill by their intersection.
+        assert_eq!(
+            fill_inside(&[rect(0.0, 0.0, 100.0, 100.0), rect(50.0, -20.0, 200.0, 60.0)]),
+            Some([50.0, 0.0, 100.0, 60.0])
+        );
+    }
+
+    #[test]
+    fn fill_outside_clips() {
+        assert_eq!(fill_inside(&[]), None);
+    }
+}
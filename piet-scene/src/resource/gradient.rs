@@ -1,7 +1,8 @@
 use crate::brush::{Color, Stop, StopVec};
 use std::collections::HashMap;
 
-const N_SAMPLES: usize = 512;
+/// Number of samples in each ramp of the ramp data.
+pub(crate) const N_SAMPLES: usize = 512;
 const RETAINED_COUNT: usize = 64;
 
 #[derive(Default)]
@@ -102,16 +103,19 @@ fn make_ramp<'a>(stops: &'a [Stop]) -> impl Iterator<Item = u32> + 'a {
     })
 }
 
+/// A color with premultiplied alpha, so that ramps interpolate the same way
+/// as the renderer composites.
 #[derive(Copy, Clone, Debug)]
 struct ColorF64([f64; 4]);
 
 impl ColorF64 {
     fn from_color(color: Color) -> Self {
+        let a = color.a as f64 / 255.0;
         Self([
-            color.r as f64 / 255.0,
-            color.g as f64 / 255.0,
-            color.b as f64 / 255.0,
-            color.a as f64 / 255.0,
+            color.r as f64 / 255.0 * a,
+            color.g as f64 / 255.0 * a,
+            color.b as f64 / 255.0 * a,
+            a,
         ])
     }
 
@@ -127,12 +131,35 @@ impl ColorF64 {
         ])
     }
 
-    fn to_premul_u32(&self) -> u32 {
-        let a = self.0[3].min(1.0).max(0.0);
-        let r = ((self.0[0] * a).min(1.0).max(0.0) * 255.0) as u32;
-        let g = ((self.0[1] * a).min(1.0).max(0.0) * 255.0) as u32;
-        let b = ((self.0[2] * a).min(1.0).max(0.0) * 255.0) as u32;
-        let a = (a * 255.0) as u32;
-        b | (g << 8) | (r << 16) | (a << 24)
+    // The gradient image is RGBA, with red in the low byte.
+    fn to_premul_u32(self) -> u32 {
+        let c = |x: f64| (x.clamp(0.0, 1.0) * 255.0).round() as u32;
+        c(self.0[0]) | (c(self.0[1]) << 8) | (c(self.0[2]) << 16) | (c(self.0[3]) << 24)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{make_ramp, N_SAMPLES};
+    use crate::brush::{Color, Stop};
+
+    #[test]
+    fn simple_ramp() {
+        let stops = [
+            Stop {
+                offset: 0.0,
+                color: Color::rgb8(255, 0, 0),
+            },
+            Stop {
+                offset: 1.0,
+                color: Color::rgba8(0, 0, 255, 0),
+            },
+        ];
+        let ramp: Vec<u32> = make_ramp(&stops).collect();
+        assert_eq!(ramp.len(), N_SAMPLES);
+        assert_eq!(ramp[0], 0xff0000ff);
+        assert_eq!(ramp[N_SAMPLES - 1], 0);
+        // Premultiplied interpolation never brightens the transparent end.
+        assert!(ramp.iter().all(|c| (c >> 16) & 0xff == 0));
     }
 }
@@ -2,6 +2,8 @@ mod gradient;
 
 use crate::brush::{Brush, Stop};
 use gradient::RampCache;
+#[cfg(feature = "cpu-render")]
+pub(crate) use gradient::N_SAMPLES;
 use std::collections::HashMap;
 
 /// Context for caching resources across rendering operations.
@@ -30,9 +32,8 @@ impl ResourceContext {
     }
 
     pub fn create_brush(&mut self, brush: &Brush) -> PersistentBrush {
-        match brush {
-            Brush::Persistent(dup) => return *dup,
-            _ => {}
+        if let Brush::Persistent(dup) = brush {
+            return *dup;
         }
         PersistentBrush { kind: 0, id: 0 }
     }
@@ -40,7 +41,7 @@ impl ResourceContext {
     pub fn destroy_brush(&mut self, _brush: PersistentBrush) {}
 
     pub fn ramp_data(&self) -> &[u32] {
-        &self.ramps.data()
+        self.ramps.data()
     }
 }
 
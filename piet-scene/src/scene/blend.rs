@@ -73,6 +73,15 @@ impl Blend {
     pub fn pack(&self) -> u32 {
         (self.mix as u32) << 8 | self.compose as u32
     }
+
+    /// Packs the blend together with the opacity of a layer.
+    ///
+    /// The upper 16 bits hold the transparency (1 - alpha) in 0.16 fixed
+    /// point, so that opaque layers pack the same as plain blends.
+    pub fn pack_with_alpha(&self, alpha: f32) -> u32 {
+        let transparency = ((1.0 - alpha.clamp(0.0, 1.0)) * 65535.0).round() as u32;
+        transparency << 16 | self.pack()
+    }
 }
 
 impl Default for Blend {
@@ -101,3 +110,51 @@ impl From<Compose> for Blend {
         }
     }
 }
+
+/// Defines how the content of a mask layer attenuates the content it masks.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Mask {
+    /// Use the alpha channel of the mask.
+    Alpha,
+    /// Use the luminance of the mask, multiplied by its alpha.
+    Luminance,
+}
+
+impl Mask {
+    pub fn pack(&self) -> u32 {
+        // These mix modes are only used internally by the fine rasterizer.
+        let mix = match self {
+            Mask::Alpha => 129,
+            Mask::Luminance => 130,
+        };
+        mix << 8 | Compose::SrcOver as u32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Blend, Compose, Mask, Mix};
+
+    #[test]
+    fn pack_with_alpha() {
+        let blend = Blend::new(Mix::Multiply, Compose::Xor);
+        assert_eq!(blend.pack(), 0x010b);
+        assert_eq!(blend.pack_with_alpha(1.0), blend.pack());
+        assert_eq!(blend.pack_with_alpha(0.0), 0xffff_010b);
+        assert_eq!(blend.pack_with_alpha(0.5), 0x8000_010b);
+        // Out of range opacities are clamped.
+        assert_eq!(blend.pack_with_alpha(2.0), blend.pack());
+        assert_eq!(blend.pack_with_alpha(-1.0), 0xffff_010b);
+        let clip = Blend::default().pack_with_alpha(0.25);
+        assert_eq!(clip & 0xffff, Blend::default().pack());
+        assert_eq!(clip >> 16, 49151);
+    }
+
+    #[test]
+    fn pack_masks() {
+        // Mix modes matching Blend_MaskAlpha and Blend_MaskLuminance in
+        // shader/blend.h.
+        assert_eq!(Mask::Alpha.pack(), 129 << 8 | Compose::SrcOver as u32);
+        assert_eq!(Mask::Luminance.pack(), 130 << 8 | Compose::SrcOver as u32);
+    }
+}
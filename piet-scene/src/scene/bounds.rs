@@ -0,0 +1,342 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! Bounds of encoded scenes computed on the CPU.
+
+use super::decode::{for_each_draw, Segment};
+use super::drawtag::{DRAWTAG_BEGINCLIP, DRAWTAG_ENDCLIP};
+use super::{Affine, Fragment, Point, Scene, SceneData};
+use crate::geometry::Rect;
+
+impl Scene {
+    /// Returns the bounds of all visible draw objects, or `None` if there
+    /// are none.
+    pub fn bounds(&self) -> Option<Rect> {
+        union_visible(&self.data, &self.draw_bounds())
+    }
+
+    /// Returns the bounds of each draw object, indexed like the draw tag
+    /// stream.
+    ///
+    /// These correspond to the bounding boxes computed by the GPU pipeline:
+    /// the tight bounds of the path, expanded by half the line width for
+    /// strokes and intersected with the enclosing clips. Begin and end clip
+    /// objects have the bounds of the clip. The bounds are `None` when empty.
+    pub fn draw_bounds(&self) -> Vec<Option<Rect>> {
+        draw_bounds(&self.data, None)
+    }
+}
+
+impl Fragment {
+    /// Returns the bounds of all visible draw objects when the fragment is
+    /// appended with the specified transform, or `None` if there are none.
+    pub fn bounds(&self, transform: &Affine) -> Option<Rect> {
+        union_visible(&self.data, &draw_bounds(&self.data, Some(*transform)))
+    }
+}
+
+fn draw_bounds(data: &SceneData, fragment_transform: Option<Affine>) -> Vec<Option<Rect>> {
+    let mut bounds = Vec::with_capacity(data.drawtag_stream.len());
+    let mut clips: Vec<Option<Rect>> = Vec::new();
+    for_each_draw(data, fragment_transform, |draw| {
+        let clip = clips.last().copied();
+        if draw.tag == DRAWTAG_ENDCLIP {
+            bounds.push(clips.pop().flatten());
+            return;
+        }
+        let mut path_bounds = segments_bounds(draw.segments);
+        if draw.linewidth >= 0.0 {
+            path_bounds = path_bounds.map(|rect| inflate(rect, 0.5 * draw.linewidth));
+        }
+        let draw_bounds = match clip {
+            Some(clip) => clip.and_then(|clip| path_bounds.and_then(|rect| intersect(rect, clip))),
+            None => path_bounds,
+        };
+        if draw.tag == DRAWTAG_BEGINCLIP {
+            clips.push(draw_bounds);
+        }
+        bounds.push(draw_bounds);
+    });
+    bounds
+}
+
+// Unions the bounds of all draw objects other than clips.
+fn union_visible(data: &SceneData, bounds: &[Option<Rect>]) -> Option<Rect> {
+    data.drawtag_stream
+        .iter()
+        .zip(bounds)
+        .filter(|(tag, _)| **tag != DRAWTAG_BEGINCLIP && **tag != DRAWTAG_ENDCLIP)
+        .filter_map(|(_, rect)| *rect)
+        .reduce(|mut a, b| {
+            a.add(b.min);
+            a.add(b.max);
+            a
+        })
+}
+
+fn segments_bounds(segments: &[Segment]) -> Option<Rect> {
+    let mut rect: Option<Rect> = None;
+    let mut add = |point: Point| match &mut rect {
+        Some(rect) => rect.add(point),
+        None => {
+            rect = Some(Rect {
+                min: point,
+                max: point,
+            })
+        }
+    };
+    for segment in segments {
+        match *segment {
+            Segment::Line(p0, p1) => {
+                add(p0);
+                add(p1);
+            }
+            Segment::Quad(p0, p1, p2) => {
+                add(p0);
+                add(p2);
+                // Extrema where the derivative vanishes on each axis.
+                for t in [
+                    quad_extremum(p0.x, p1.x, p2.x),
+                    quad_extremum(p0.y, p1.y, p2.y),
+                ]
+                .into_iter()
+                .flatten()
+                {
+                    let mt = 1.0 - t;
+                    add(Point::new(
+                        mt * mt * p0.x + 2.0 * mt * t * p1.x + t * t * p2.x,
+                        mt * mt * p0.y + 2.0 * mt * t * p1.y + t * t * p2.y,
+                    ));
+                }
+            }
+            Segment::Cubic(p0, p1, p2, p3) => {
+                add(p0);
+                add(p3);
+                let tx = cubic_extrema(p0.x, p1.x, p2.x, p3.x);
+                let ty = cubic_extrema(p0.y, p1.y, p2.y, p3.y);
+                for t in tx.into_iter().chain(ty).flatten() {
+                    let mt = 1.0 - t;
+                    let a = mt * mt * mt;
+                    let b = 3.0 * mt * mt * t;
+                    let c = 3.0 * mt * t * t;
+                    let d = t * t * t;
+                    add(Point::new(
+                        a * p0.x + b * p1.x + c * p2.x + d * p3.x,
+                        a * p0.y + b * p1.y + c * p2.y + d * p3.y,
+                    ));
+                }
+            }
+        }
+    }
+    rect
+}
+
+// Parameter in (0, 1) where a quadratic Bézier coordinate is extremal.
+fn quad_extremum(a: f32, b: f32, c: f32) -> Option<f32> {
+    let denom = a - 2.0 * b + c;
+    let t = (a - b) / denom;
+    (denom != 0.0 && t > 0.0 && t < 1.0).then_some(t)
+}
+
+// Parameters in (0, 1) where a cubic Bézier coordinate is extremal.
+fn cubic_extrema(a: f32, b: f32, c: f32, d: f32) -> [Option<f32>; 2] {
+    // The derivative is a quadratic in t with these coefficients, divided
+    // by 3.
+    let qa = -a + 3.0 * b - 3.0 * c + d;
+    let qb = 2.0 * (a - 2.0 * b + c);
+    let qc = b - a;
+    let in_range = |t: f32| (t > 0.0 && t < 1.0).then_some(t);
+    if qa == 0.0 {
+        if qb == 0.0 {
+            return [None, None];
+        }
+        return [in_range(-qc / qb), None];
+    }
+    let disc = qb * qb - 4.0 * qa * qc;
+    if disc < 0.0 {
+        return [None, None];
+    }
+    // Avoids cancellation when the cubic is close to a quadratic.
+    let q = -0.5 * (qb + disc.sqrt().copysign(qb));
+    let t1 = if q != 0.0 { in_range(qc / q) } else { None };
+    [in_range(q / qa), t1]
+}
+
+fn inflate(rect: Rect, amount: f32) -> Rect {
+    Rect {
+        min: Point::new(rect.min.x - amount, rect.min.y - amount),
+        max: Point::new(rect.max.x + amount, rect.max.y + amount),
+    }
+}
+
+fn intersect(a: Rect, b: Rect) -> Option<Rect> {
+    let rect = a.intersect(&b);
+    (rect.width() > 0.0 && rect.height() > 0.0).then_some(rect)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::brush::{Brush, Color};
+    use crate::geometry::{Affine, Point, Rect};
+    use crate::path::Element;
+    use crate::resource::ResourceContext;
+    use crate::scene::{
+        build_fragment, build_scene, Builder, Cap, Fill, Fragment, Join, Mix, Scene, Stroke,
+    };
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Rect {
+        Rect {
+            min: Point::new(x0, y0),
+            max: Point::new(x1, y1),
+        }
+    }
+
+    fn fill(builder: &mut Builder, shape: Rect) {
+        let brush = Brush::Solid(Color::rgb8(255, 0, 0));
+        builder.fill(Fill::NonZero, &brush, None, shape.elements());
+    }
+
+    fn build(f: impl FnOnce(&mut Builder)) -> Scene {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut builder = build_scene(&mut scene, &mut rcx);
+        f(&mut builder);
+        builder.finish().unwrap();
+        scene
+    }
+
+    #[track_caller]
+    fn assert_rect(actual: Option<Rect>, expected: Option<[f32; 4]>) {
+        let actual = actual.map(|r| [r.min.x, r.min.y, r.max.x, r.max.y]);
+        match (actual, expected) {
+            (Some(a), Some(e)) if a.iter().zip(e).all(|(a, e)| (a - e).abs() < 1e-4) => (),
+            (None, None) => (),
+            _ => panic!("bounds {:?}, expected {:?}", actual, expected),
+        }
+    }
+
+    #[test]
+    fn empty_scene() {
+        let scene = Scene::default();
+        assert!(scene.draw_bounds().is_empty());
+        assert_rect(scene.bounds(), None);
+        // A layer with nothing in it is not visible.
+        let scene = build(|builder| {
+            builder.push_layer(Mix::Normal.into(), rect(0.0, 0.0, 10.0, 10.0).elements());
+            builder.pop_layer();
+        });
+        assert_eq!(scene.draw_bounds().len(), 2);
+        assert_rect(scene.bounds(), None);
+    }
+
+    #[test]
+    fn transformed_paths() {
+        let scene = build(|builder| {
+            builder.push_transform(Affine::translate(10.0, 20.0) * Affine::scale(2.0, 3.0));
+            fill(builder, rect(1.0, 1.0, 2.0, 4.0));
+            builder.pop_transform();
+            // A quarter turn about the origin maps x to y and y to -x.
+            builder.push_transform(Affine::rotate(core::f32::consts::FRAC_PI_2));
+            fill(builder, rect(1.0, 2.0, 3.0, 5.0));
+            builder.pop_transform();
+        });
+        let bounds = scene.draw_bounds();
+        assert_rect(bounds[0], Some([12.0, 23.0, 14.0, 32.0]));
+        assert_rect(bounds[1], Some([-5.0, 1.0, -2.0, 3.0]));
+        assert_rect(scene.bounds(), Some([-5.0, 1.0, 14.0, 32.0]));
+    }
+
+    #[test]
+    fn curves_and_strokes() {
+        let scene = build(|builder| {
+            let brush = Brush::Solid(Color::rgb8(255, 0, 0));
+            // The curves reach their extrema at their midpoints, beyond their
+            // end points but short of their control points.
+            let curve = [
+                Element::MoveTo(Point::new(0.0, 0.0)),
+                Element::CurveTo(
+                    Point::new(0.0, 10.0),
+                    Point::new(10.0, 10.0),
+                    Point::new(10.0, 0.0),
+                ),
+                Element::QuadTo(Point::new(5.0, -10.0), Point::new(0.0, 0.0)),
+                Element::Close,
+            ];
+            builder.fill(Fill::NonZero, &brush, None, curve);
+            let style = Stroke {
+                width: 2.0,
+                join: Join::Round,
+                miter_limit: 4.0,
+                start_cap: Cap::Round,
+                end_cap: Cap::Round,
+                dash_pattern: [],
+                dash_offset: 0.0,
+                scale: true,
+            };
+            let line = [
+                Element::MoveTo(Point::new(20.0, 20.0)),
+                Element::LineTo(Point::new(30.0, 20.0)),
+            ];
+            builder.stroke(&style, &brush, None, line);
+        });
+        let bounds = scene.draw_bounds();
+        assert_rect(bounds[0], Some([0.0, -5.0, 10.0, 7.5]));
+        // Strokes are expanded by half their width.
+        assert_rect(bounds[1], Some([19.0, 19.0, 31.0, 21.0]));
+    }
+
+    #[test]
+    fn clip_intersection() {
+        let scene = build(|builder| {
+            builder.push_layer(Mix::Normal.into(), rect(0.0, 0.0, 10.0, 10.0).elements());
+            fill(builder, rect(5.0, -5.0, 20.0, 8.0));
+            builder.push_layer(Mix::Multiply.into(), rect(2.0, 2.0, 6.0, 20.0).elements());
+            fill(builder, rect(0.0, 0.0, 20.0, 20.0));
+            builder.pop_layer();
+            // Outside the clip, so not visible.
+            fill(builder, rect(20.0, 20.0, 30.0, 30.0));
+            builder.pop_layer();
+        });
+        let bounds = scene.draw_bounds();
+        assert_eq!(bounds.len(), 7);
+        assert_rect(bounds[0], Some([0.0, 0.0, 10.0, 10.0]));
+        assert_rect(bounds[1], Some([5.0, 0.0, 10.0, 8.0]));
+        // Nested clips intersect with the enclosing ones.
+        assert_rect(bounds[2], Some([2.0, 2.0, 6.0, 10.0]));
+        assert_rect(bounds[3], Some([2.0, 2.0, 6.0, 10.0]));
+        assert_rect(bounds[4], Some([2.0, 2.0, 6.0, 10.0]));
+        assert_rect(bounds[5], None);
+        assert_rect(bounds[6], Some([0.0, 0.0, 10.0, 10.0]));
+        // Clips only bound the draws inside them.
+        assert_rect(scene.bounds(), Some([2.0, 0.0, 10.0, 10.0]));
+    }
+
+    #[test]
+    fn fragment_bounds() {
+        let mut fragment = Fragment::default();
+        let mut builder = build_fragment(&mut fragment);
+        fill(&mut builder, rect(1.0, 2.0, 3.0, 4.0));
+        builder.finish().unwrap();
+        assert_rect(
+            fragment.bounds(&Affine::translate(10.0, 0.0)),
+            Some([11.0, 2.0, 13.0, 4.0]),
+        );
+        assert_rect(
+            fragment.bounds(&Affine::scale(2.0, 0.5)),
+            Some([2.0, 1.0, 6.0, 2.0]),
+        );
+    }
+}
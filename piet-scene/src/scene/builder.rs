@@ -14,15 +14,27 @@
 //
 // Also licensed under MIT license, at your choice.
 
+use super::drawtag::{
+    DRAWTAG_BEGINCLIP, DRAWTAG_ENDCLIP, DRAWTAG_FILLBLURRECT, DRAWTAG_FILLCOLOR, DRAWTAG_FILLGLYPH,
+    DRAWTAG_FILLIMAGE, DRAWTAG_FILLLINGRADIENT, DRAWTAG_FILLRADGRADIENT,
+};
+use super::stroke::{needs_expansion, stroke_outline};
 use super::style::{Fill, Stroke};
-use super::{Affine, Blend, Element, Fragment, FragmentResources, ResourcePatch, Scene, SceneData};
+use super::{
+    Affine, Blend, Element, Filter, FilterLayer, Fragment, FragmentResources, Mask, Mix,
+    ResourcePatch, Scene, SceneData,
+};
 use crate::brush::*;
+use crate::geometry::{Point, Rect};
 use crate::resource::ResourceContext;
 use bytemuck::{Pod, Zeroable};
 use core::borrow::Borrow;
 
 const MAX_BLEND_STACK: usize = 256;
 
+/// Maximum distance of expanded strokes from the true outlines, in pixels.
+const STROKE_TOLERANCE: f32 = 0.1;
+
 /// Creates a new builder for filling a scene. Any current content in the scene
 /// will be cleared.
 pub fn build_scene<'a>(scene: &'a mut Scene, rcx: &'a mut ResourceContext) -> Builder<'a> {
@@ -38,28 +50,121 @@ pub fn build_fragment<'a>(fragment: &'a mut Fragment) -> Builder<'a> {
     )
 }
 
+/// Creates a new builder for adding to the current content of a scene.
+///
+/// The scene must have been filled by [`build_scene`] before.
+pub fn extend_scene<'a>(scene: &'a mut Scene, rcx: &'a mut ResourceContext) -> Builder<'a> {
+    Builder::with_state(&mut scene.data, ResourceData::Scene(rcx))
+}
+
 /// Builder for constructing a scene or scene fragment.
 pub struct Builder<'a> {
     scene: &'a mut SceneData,
     resources: ResourceData<'a>,
-    layers: Vec<Blend>,
+    layers: Vec<u32>,
+    masks: Vec<PendingMask>,
+    filters: Vec<PendingFilter>,
     transforms: Vec<Affine>,
+    /// User id attached to subsequent draws.
+    id: Option<u64>,
+    /// The first unbalanced operation, reported by [`Self::finish`].
+    error: Option<BuildError>,
+}
+
+/// Error reported when the operations on a [`Builder`] are unbalanced.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BuildError {
+    /// A transform was popped without a matching push.
+    UnmatchedPopTransform,
+    /// A layer was popped without a matching push.
+    UnmatchedPopLayer,
+    /// Masked content was begun without a pending mask.
+    UnmatchedMaskContent,
+    /// The specified number of layers were never popped.
+    UnclosedLayers(usize),
+    /// The specified number of transforms were never popped.
+    UnclosedTransforms(usize),
+}
+
+impl core::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::UnmatchedPopTransform => write!(f, "transform popped without a matching push"),
+            Self::UnmatchedPopLayer => write!(f, "layer popped without a matching push"),
+            Self::UnmatchedMaskContent => {
+                write!(f, "masked content begun without a pending mask")
+            }
+            Self::UnclosedLayers(n) => write!(f, "{} layers were never popped", n),
+            Self::UnclosedTransforms(n) => write!(f, "{} transforms were never popped", n),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// A copy of an encoded path, for shapes that are encoded more than once.
+struct EncodedPath {
+    tags: Vec<u8>,
+    pathsegs: Vec<u8>,
+    n_pathseg: u32,
+}
+
+/// A mask whose content is being built.
+struct PendingMask {
+    mask: Mask,
+    /// Shape bounding the mask, which also bounds the masked content.
+    path: EncodedPath,
+    /// Depth of the layer stack including the layer holding the mask.
+    depth: usize,
+}
+
+/// A filtered layer whose content is being built.
+struct PendingFilter {
+    filter: Filter,
+    /// Shape bounding the layer, which also bounds the filtered image.
+    path: EncodedPath,
+    /// Transform in effect when the layer was pushed.
+    transform: Affine,
+    /// Sizes of the scene streams where the content begins.
+    start: StreamSizes,
+    /// Depth of the layer stack including the filtered layer.
+    depth: usize,
+}
+
+#[derive(Clone, Copy)]
+struct StreamSizes {
+    transforms: usize,
+    tags: usize,
+    pathsegs: usize,
+    linewidths: usize,
+    drawtags: usize,
+    drawdata: usize,
+    n_path: u32,
+    n_pathseg: u32,
+    n_clip: u32,
+    filter_layers: usize,
+    patches: usize,
 }
 
 impl<'a> Builder<'a> {
     /// Creates a new builder for constructing a scene.
     fn new(scene: &'a mut SceneData, mut resources: ResourceData<'a>) -> Self {
-        let is_fragment = match resources {
-            ResourceData::Fragment(_) => true,
-            _ => false,
-        };
+        let is_fragment = matches!(resources, ResourceData::Fragment(_));
         scene.reset(is_fragment);
         resources.clear();
+        Self::with_state(scene, resources)
+    }
+
+    fn with_state(scene: &'a mut SceneData, resources: ResourceData<'a>) -> Self {
         Self {
             scene,
             resources,
             layers: vec![],
+            masks: vec![],
+            filters: vec![],
             transforms: vec![],
+            id: None,
+            error: None,
         }
     }
 
@@ -71,14 +176,32 @@ impl<'a> Builder<'a> {
 
     /// Pops the current transform matrix.
     pub fn pop_transform(&mut self) {
-        if let Some(transform) = self.transforms.pop() {
-            self.transform(transform.inverse());
+        match self.transforms.pop() {
+            Some(transform) => self.transform(transform.inverse()),
+            None => self.record_error(BuildError::UnmatchedPopTransform),
         }
     }
 
+    /// Sets the user id attached to subsequent draws, which is reported by
+    /// [`Scene::hit_test`]. Layers and clips do not carry ids.
+    pub fn set_id(&mut self, id: Option<u64>) {
+        self.id = id;
+    }
+
     /// Pushes a new layer bound by the specifed shape and composed with
     /// previous layers using the specified blend mode.
-    pub fn push_layer<'s, E>(&mut self, blend: Blend, elements: E)
+    pub fn push_layer<E>(&mut self, blend: Blend, elements: E)
+    where
+        E: IntoIterator,
+        E::IntoIter: Clone,
+        E::Item: Borrow<Element>,
+    {
+        self.push_layer_with_alpha(blend, 1.0, elements);
+    }
+
+    /// Pushes a new layer like [`push_layer`](Self::push_layer), whose
+    /// content is also scaled by the specified opacity when it is composed.
+    pub fn push_layer_with_alpha<E>(&mut self, blend: Blend, alpha: f32, elements: E)
     where
         E: IntoIterator,
         E::IntoIter: Clone,
@@ -87,22 +210,98 @@ impl<'a> Builder<'a> {
         self.linewidth(-1.0);
         let elements = elements.into_iter();
         self.encode_path(elements, true);
-        self.begin_clip(Some(blend));
-        if self.layers.len() >= MAX_BLEND_STACK {
-            panic!("Maximum clip/blend stack size {} exceeded", MAX_BLEND_STACK);
-        }
-        self.layers.push(blend);
+        self.push_clip(blend.pack_with_alpha(alpha));
+    }
+
+    /// Pushes a new layer whose content is rendered to an intermediate
+    /// image and filtered before it is composed with previous layers.
+    ///
+    /// The specified shape bounds the filtered image, so it should include
+    /// the extent of blurs and shadows. Nothing under the layer is visible to
+    /// the filter.
+    pub fn push_filter_layer<E>(&mut self, blend: Blend, alpha: f32, filter: Filter, elements: E)
+    where
+        E: IntoIterator,
+        E::IntoIter: Clone,
+        E::Item: Borrow<Element>,
+    {
+        self.linewidth(-1.0);
+        let path = self.encode_path_copy(elements.into_iter());
+        self.push_clip(blend.pack_with_alpha(alpha));
+        let transform = self
+            .transforms
+            .iter()
+            .fold(Affine::IDENTITY, |acc, transform| acc * *transform);
+        self.filters.push(PendingFilter {
+            filter,
+            path,
+            transform,
+            start: self.stream_sizes(),
+            depth: self.layers.len(),
+        });
     }
 
     /// Pops the current layer.
     pub fn pop_layer(&mut self) {
-        if let Some(layer) = self.layers.pop() {
-            self.end_clip(Some(layer));
+        if self.layers.is_empty() {
+            self.record_error(BuildError::UnmatchedPopLayer);
+            return;
         }
+        // A mask without content draws nothing; close empty content so that
+        // the mask itself is not composited.
+        if self.start_masked_content() {
+            self.pop_clip();
+        }
+        if self.filters.last().map(|pending| pending.depth) == Some(self.layers.len()) {
+            self.finish_filter_layer();
+        }
+        self.pop_clip();
+    }
+
+    /// Pushes a mask layer bound by the specified shape.
+    ///
+    /// Content added until [`Self::begin_masked_content`] forms the mask,
+    /// which is not itself visible. Content added from then until
+    /// [`Self::pop_mask`] is attenuated by the alpha or luminance of the mask.
+    /// The shape bounds both, so the transform should not change between
+    /// pushing the mask and beginning its content.
+    pub fn push_mask<E>(&mut self, mask: Mask, elements: E)
+    where
+        E: IntoIterator,
+        E::IntoIter: Clone,
+        E::Item: Borrow<Element>,
+    {
+        self.linewidth(-1.0);
+        let pending = PendingMask {
+            mask,
+            path: self.encode_path_copy(elements.into_iter()),
+            depth: self.layers.len() + 1,
+        };
+        // The mask must be pushed on every tile, even where the shape
+        // covers it completely, so it uses a normal blend rather than clip.
+        self.push_clip(Blend::from(Mix::Normal).pack());
+        self.masks.push(pending);
+    }
+
+    /// Completes the mask pushed by the most recent [`Self::push_mask`] and
+    /// begins the content it applies to.
+    pub fn begin_masked_content(&mut self) {
+        if !self.start_masked_content() {
+            self.record_error(BuildError::UnmatchedMaskContent);
+        }
+    }
+
+    /// Pops the masked content and the mask pushed by the most recent
+    /// [`Self::push_mask`].
+    pub fn pop_mask(&mut self) {
+        if !self.is_mask_pending() {
+            self.pop_clip();
+        }
+        self.pop_layer();
     }
 
     /// Fills a shape using the specified style and brush.
-    pub fn fill<'s, E>(
+    pub fn fill<E>(
         &mut self,
         _style: Fill,
         brush: &Brush,
@@ -124,10 +323,14 @@ impl<'a> Builder<'a> {
         } else {
             self.encode_brush(brush);
         }
+        self.record_id();
     }
 
     /// Strokes a shape using the specified style and brush.
-    pub fn stroke<'s, D, E>(
+    ///
+    /// Strokes with round joins and caps and no dashes are rendered on the
+    /// GPU; others are expanded into a fill on the CPU.
+    pub fn stroke<D, E>(
         &mut self,
         style: &Stroke<D>,
         brush: &Brush,
@@ -139,7 +342,24 @@ impl<'a> Builder<'a> {
         E::IntoIter: Clone,
         E::Item: Borrow<Element>,
     {
-        self.linewidth(style.width);
+        if needs_expansion(style) {
+            // Flatten finely enough for the scale of the current transform.
+            let scale = self
+                .transforms
+                .iter()
+                .fold(1.0, |scale, t| scale * t.determinant().abs().sqrt());
+            let tolerance = STROKE_TOLERANCE / scale.max(f32::MIN_POSITIVE);
+            let outline = stroke_outline(style, elements.into_iter(), tolerance);
+            self.fill(Fill::NonZero, brush, brush_transform, &outline);
+            return;
+        }
+        // The line width is scaled by the transform of the path, which also
+        // includes the brush transform, so undo the scale of the latter.
+        let linewidth = match brush_transform {
+            Some(brush_transform) => style.width / brush_transform.determinant().abs().sqrt(),
+            None => style.width,
+        };
+        self.linewidth(linewidth);
         let elements = elements.into_iter();
         self.encode_path(elements, false);
         if let Some(brush_transform) = brush_transform {
@@ -150,11 +370,71 @@ impl<'a> Builder<'a> {
         } else {
             self.encode_brush(brush);
         }
+        self.record_id();
+    }
+
+    /// Fills a glyph outline with a solid color.
+    ///
+    /// The flags are a combination of `GLYPH_FLAG_*` values selecting
+    /// antialiasing tuned for text.
+    pub fn fill_glyph<E>(&mut self, color: Color, flags: u32, elements: E)
+    where
+        E: IntoIterator,
+        E::IntoIter: Clone,
+        E::Item: Borrow<Element>,
+    {
+        self.linewidth(-1.0);
+        self.encode_path(elements.into_iter(), true);
+        let rgba_color = color.to_premul_u32();
+        if flags == 0 {
+            self.scene.drawtag_stream.push(DRAWTAG_FILLCOLOR);
+            self.scene
+                .drawdata_stream
+                .extend(bytemuck::bytes_of(&FillColor { rgba_color }));
+        } else {
+            self.scene.drawtag_stream.push(DRAWTAG_FILLGLYPH);
+            self.scene
+                .drawdata_stream
+                .extend(bytemuck::bytes_of(&FillGlyph { rgba_color, flags }));
+        }
+        self.record_id();
+    }
+
+    /// Fills a rounded rectangle blurred by a Gaussian with the specified
+    /// standard deviation.
+    ///
+    /// The blur is evaluated analytically, so only solid colors are
+    /// supported.
+    pub fn blurred_rect(&mut self, color: Color, rect: Rect, radius: f32, std_dev: f32) {
+        let std_dev = std_dev.abs();
+        // The blur is negligible beyond 2.5 standard deviations.
+        let inflate = 2.5 * std_dev;
+        let extent = Rect {
+            min: Point::new(rect.min.x - inflate, rect.min.y - inflate),
+            max: Point::new(rect.max.x + inflate, rect.max.y + inflate),
+        };
+        self.linewidth(-1.0);
+        self.encode_path(extent.elements(), true);
+        self.scene.drawtag_stream.push(DRAWTAG_FILLBLURRECT);
+        self.scene
+            .drawdata_stream
+            .extend(bytemuck::bytes_of(&FillBlurRect {
+                rgba_color: color.to_premul_u32(),
+                rect: [rect.min.x, rect.min.y, rect.max.x, rect.max.y],
+                radius,
+                std_dev,
+            }));
+        self.record_id();
     }
 
     /// Appends a fragment to the scene.
     pub fn append(&mut self, fragment: &Fragment) {
         let drawdata_base = self.scene.drawdata_stream.len();
+        let filter_layer_base = self.scene.filter_layers.len();
+        let stops_base = match &self.resources {
+            ResourceData::Fragment(res) => res.stops.len(),
+            _ => 0,
+        };
         self.scene.append(&fragment.data);
         match &mut self.resources {
             ResourceData::Scene(res) => {
@@ -167,14 +447,13 @@ impl<'a> Builder<'a> {
                             let stops = &fragment.resources.stops[stops.clone()];
                             let ramp_id = res.add_ramp(stops);
                             let patch_base = *drawdata_offset + drawdata_base;
-                            (&mut self.scene.drawdata_stream[patch_base..patch_base + 4])
+                            self.scene.drawdata_stream[patch_base..patch_base + 4]
                                 .copy_from_slice(bytemuck::bytes_of(&ramp_id));
                         }
                     }
                 }
             }
             ResourceData::Fragment(res) => {
-                let stops_base = res.stops.len();
                 res.stops.extend_from_slice(&fragment.resources.stops);
                 res.patches.extend(fragment.resources.patches.iter().map(
                     |pending| match pending {
@@ -189,26 +468,54 @@ impl<'a> Builder<'a> {
                 ));
             }
         }
+        let transform = self
+            .transforms
+            .iter()
+            .fold(Affine::IDENTITY, |acc, transform| acc * *transform);
+        for layer in &mut self.scene.filter_layers[filter_layer_base..] {
+            patch_filter_layer(
+                layer,
+                &transform,
+                &fragment.resources.stops,
+                stops_base,
+                &mut self.resources,
+            );
+        }
     }
 
     /// Completes construction and finalizes the underlying scene.
-    pub fn finish(mut self) {
-        while let Some(layer) = self.layers.pop() {
-            self.end_clip(Some(layer));
-        }
-        match self.resources {
-            ResourceData::Fragment(_) => {
-                // Make sure the transform state is invariant for fragments
-                while !self.transforms.is_empty() {
-                    self.pop_transform();
-                }
+    ///
+    /// Layers that are still pushed are popped, as are transforms in a
+    /// fragment, so that the encoding is well formed. This is reported as an
+    /// error, as is any earlier unbalanced operation.
+    pub fn finish(mut self) -> Result<(), BuildError> {
+        if !self.layers.is_empty() {
+            self.record_error(BuildError::UnclosedLayers(self.layers.len()));
+        }
+        while !self.layers.is_empty() {
+            self.pop_layer();
+        }
+        if !self.transforms.is_empty() {
+            self.record_error(BuildError::UnclosedTransforms(self.transforms.len()));
+        }
+        if let ResourceData::Fragment(_) = self.resources {
+            // Make sure the transform state is invariant for fragments
+            while !self.transforms.is_empty() {
+                self.pop_transform();
             }
-            _ => {}
+        }
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(()),
         }
     }
 }
 
 impl<'a> Builder<'a> {
+    fn record_error(&mut self, error: BuildError) {
+        self.error.get_or_insert(error);
+    }
+
     fn encode_path<E>(&mut self, elements: E, is_fill: bool)
     where
         E: Iterator,
@@ -262,6 +569,14 @@ impl<'a> Builder<'a> {
         self.scene.tag_stream.swap(len - 1, len - 2);
     }
 
+    // Attaches the current id, if any, to the last draw object.
+    fn record_id(&mut self) {
+        if let Some(id) = self.id {
+            let ix = self.scene.drawtag_stream.len() as u32 - 1;
+            self.scene.draw_ids.push((ix, id));
+        }
+    }
+
     // -1.0 means "fill"
     fn linewidth(&mut self, linewidth: f32) {
         self.scene.tag_stream.push(0x40);
@@ -323,23 +638,161 @@ impl<'a> Builder<'a> {
         }
     }
 
+    /// Whether the innermost layer is a mask whose content has not begun,
+    /// so that draws still go to the mask itself.
+    fn is_mask_pending(&self) -> bool {
+        self.masks.last().map(|pending| pending.depth) == Some(self.layers.len())
+    }
+
+    fn start_masked_content(&mut self) -> bool {
+        if !self.is_mask_pending() {
+            return false;
+        }
+        let pending = self.masks.pop().unwrap();
+        self.linewidth(-1.0);
+        self.append_encoded_path(&pending.path);
+        self.push_clip(pending.mask.pack());
+        true
+    }
+
+    // Moves the content of the current filtered layer to a separate scene
+    // and replaces it with a draw of the filtered image.
+    fn finish_filter_layer(&mut self) {
+        let pending = self.filters.pop().unwrap();
+        let start = pending.start;
+        let scene = &mut *self.scene;
+        let mut data = SceneData::default();
+        data.transform_stream.push(pending.transform);
+        data.transform_stream
+            .extend(scene.transform_stream.drain(start.transforms..));
+        data.linewidth_stream.push(-1.0);
+        data.linewidth_stream
+            .extend(scene.linewidth_stream.drain(start.linewidths..));
+        data.tag_stream = scene.tag_stream.split_off(start.tags);
+        data.pathseg_stream = scene.pathseg_stream.split_off(start.pathsegs);
+        data.drawtag_stream = scene.drawtag_stream.split_off(start.drawtags);
+        data.drawdata_stream = scene.drawdata_stream.split_off(start.drawdata);
+        data.n_path = scene.n_path - start.n_path;
+        data.n_pathseg = scene.n_pathseg - start.n_pathseg;
+        data.n_clip = scene.n_clip - start.n_clip;
+        scene.n_path = start.n_path;
+        scene.n_pathseg = start.n_pathseg;
+        scene.n_clip = start.n_clip;
+        let ids_start = scene
+            .draw_ids
+            .partition_point(|(ix, _)| (*ix as usize) < start.drawtags);
+        data.draw_ids = scene.draw_ids.split_off(ids_start);
+        for (ix, _) in &mut data.draw_ids {
+            *ix -= start.drawtags as u32;
+        }
+        data.filter_layers = scene.filter_layers.split_off(start.filter_layers);
+        for layer in &mut data.filter_layers {
+            layer.drawdata_offset -= start.drawdata;
+        }
+        let patches = match &mut self.resources {
+            ResourceData::Fragment(res) => res
+                .patches
+                .split_off(start.patches)
+                .into_iter()
+                .map(|patch| match patch {
+                    ResourcePatch::Ramp {
+                        drawdata_offset,
+                        stops,
+                    } => ResourcePatch::Ramp {
+                        drawdata_offset: drawdata_offset - start.drawdata,
+                        stops,
+                    },
+                })
+                .collect(),
+            _ => vec![],
+        };
+        self.linewidth(-1.0);
+        self.append_encoded_path(&pending.path);
+        let drawdata_offset = self.scene.drawdata_stream.len();
+        // The renderer fills in the location of the filtered image.
+        self.scene.drawtag_stream.push(DRAWTAG_FILLIMAGE);
+        self.scene
+            .drawdata_stream
+            .extend(bytemuck::bytes_of(&FillImage::default()));
+        self.record_id();
+        self.scene.filter_layers.push(FilterLayer {
+            data,
+            filter: pending.filter,
+            drawdata_offset,
+            patches,
+        });
+    }
+
+    fn stream_sizes(&self) -> StreamSizes {
+        StreamSizes {
+            transforms: self.scene.transform_stream.len(),
+            tags: self.scene.tag_stream.len(),
+            pathsegs: self.scene.pathseg_stream.len(),
+            linewidths: self.scene.linewidth_stream.len(),
+            drawtags: self.scene.drawtag_stream.len(),
+            drawdata: self.scene.drawdata_stream.len(),
+            n_path: self.scene.n_path,
+            n_pathseg: self.scene.n_pathseg,
+            n_clip: self.scene.n_clip,
+            filter_layers: self.scene.filter_layers.len(),
+            patches: match &self.resources {
+                ResourceData::Fragment(res) => res.patches.len(),
+                _ => 0,
+            },
+        }
+    }
+
+    // Encodes a fill path, returning a copy for encoding it again.
+    fn encode_path_copy<E>(&mut self, elements: E) -> EncodedPath
+    where
+        E: Iterator,
+        E::Item: Borrow<Element>,
+    {
+        let tag_start = self.scene.tag_stream.len();
+        let pathseg_start = self.scene.pathseg_stream.len();
+        let n_pathseg = self.scene.n_pathseg;
+        self.encode_path(elements, true);
+        EncodedPath {
+            tags: self.scene.tag_stream[tag_start..].to_vec(),
+            pathsegs: self.scene.pathseg_stream[pathseg_start..].to_vec(),
+            n_pathseg: self.scene.n_pathseg - n_pathseg,
+        }
+    }
+
+    fn append_encoded_path(&mut self, path: &EncodedPath) {
+        self.scene.tag_stream.extend_from_slice(&path.tags);
+        self.scene.pathseg_stream.extend_from_slice(&path.pathsegs);
+        self.scene.n_path += 1;
+        self.scene.n_pathseg += path.n_pathseg;
+    }
+
+    fn push_clip(&mut self, blend: u32) {
+        self.begin_clip(blend);
+        if self.layers.len() >= MAX_BLEND_STACK {
+            panic!("Maximum clip/blend stack size {} exceeded", MAX_BLEND_STACK);
+        }
+        self.layers.push(blend);
+    }
+
+    fn pop_clip(&mut self) {
+        if let Some(blend) = self.layers.pop() {
+            self.end_clip(blend);
+        }
+    }
+
     /// Start a clip.
-    fn begin_clip(&mut self, blend: Option<Blend>) {
+    fn begin_clip(&mut self, blend: u32) {
         self.scene.drawtag_stream.push(DRAWTAG_BEGINCLIP);
-        let element = Clip {
-            blend: blend.unwrap_or(Blend::default()).pack(),
-        };
+        let element = Clip { blend };
         self.scene
             .drawdata_stream
             .extend(bytemuck::bytes_of(&element));
         self.scene.n_clip += 1;
     }
 
-    fn end_clip(&mut self, blend: Option<Blend>) {
+    fn end_clip(&mut self, blend: u32) {
         self.scene.drawtag_stream.push(DRAWTAG_ENDCLIP);
-        let element = Clip {
-            blend: blend.unwrap_or(Blend::default()).pack(),
-        };
+        let element = Clip { blend };
         self.scene
             .drawdata_stream
             .extend(bytemuck::bytes_of(&element));
@@ -350,6 +803,48 @@ impl<'a> Builder<'a> {
     }
 }
 
+// Adjusts a filtered layer appended from a fragment, and its descendants, to
+// the transform and resources of the builder.
+fn patch_filter_layer(
+    layer: &mut FilterLayer,
+    transform: &Affine,
+    stops: &[Stop],
+    stops_base: usize,
+    resources: &mut ResourceData,
+) {
+    if let Some(first) = layer.data.transform_stream.first_mut() {
+        *first = *transform * *first;
+    }
+    match resources {
+        ResourceData::Scene(res) => {
+            for patch in layer.patches.drain(..) {
+                match patch {
+                    ResourcePatch::Ramp {
+                        drawdata_offset,
+                        stops: range,
+                    } => {
+                        let ramp_id = res.add_ramp(&stops[range]);
+                        layer.data.drawdata_stream[drawdata_offset..drawdata_offset + 4]
+                            .copy_from_slice(bytemuck::bytes_of(&ramp_id));
+                    }
+                }
+            }
+        }
+        ResourceData::Fragment(_) => {
+            for patch in &mut layer.patches {
+                match patch {
+                    ResourcePatch::Ramp { stops: range, .. } => {
+                        *range = range.start + stops_base..range.end + stops_base;
+                    }
+                }
+            }
+        }
+    }
+    for child in &mut layer.data.filter_layers {
+        patch_filter_layer(child, transform, stops, stops_base, resources);
+    }
+}
+
 enum ResourceData<'a> {
     Fragment(&'a mut FragmentResources),
     Scene(&'a mut ResourceContext),
@@ -357,23 +852,13 @@ enum ResourceData<'a> {
 
 impl ResourceData<'_> {
     fn clear(&mut self) {
-        match self {
-            Self::Fragment(res) => {
-                res.patches.clear();
-                res.stops.clear();
-            }
-            _ => {}
+        if let Self::Fragment(res) = self {
+            res.patches.clear();
+            res.stops.clear();
         }
     }
 }
 
-// Tags for draw objects. See shader/drawtag.h for the authoritative source.
-const DRAWTAG_FILLCOLOR: u32 = 0x44;
-const DRAWTAG_FILLLINGRADIENT: u32 = 0x114;
-const DRAWTAG_FILLRADGRADIENT: u32 = 0x2dc;
-const DRAWTAG_BEGINCLIP: u32 = 0x05;
-const DRAWTAG_ENDCLIP: u32 = 0x25;
-
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
 pub struct FillColor {
@@ -398,7 +883,31 @@ pub struct FillRadGradient {
     r1: f32,
 }
 
-#[allow(unused)]
+// Flags for glyph fills. See shader/drawtag.h.
+
+/// Computes separate coverage for each color subpixel of an LCD panel.
+pub const GLYPH_FLAG_LCD: u32 = 1;
+/// Orders the subpixels of [`GLYPH_FLAG_LCD`] as blue, green, red.
+pub const GLYPH_FLAG_BGR: u32 = 2;
+/// Boosts the coverage of glyph edges, approximating gamma-correct blending.
+pub const GLYPH_FLAG_GAMMA: u32 = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct FillGlyph {
+    rgba_color: u32,
+    flags: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct FillBlurRect {
+    rgba_color: u32,
+    rect: [f32; 4],
+    radius: f32,
+    std_dev: f32,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
 pub struct FillImage {
@@ -554,3 +1063,136 @@ impl<'a> PathBuilder<'a> {
         self.n_pathseg
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::brush::{Brush, Color};
+    use crate::geometry::{Point, Rect};
+    use crate::resource::ResourceContext;
+    use crate::scene::decode::{for_each_draw, read_u32};
+    use crate::scene::drawtag::{DRAWTAG_BEGINCLIP, DRAWTAG_ENDCLIP, DRAWTAG_FILLCOLOR};
+    use crate::scene::{build_scene, Blend, BuildError, Fill, Mask, Mix, Scene};
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Rect {
+        Rect {
+            min: Point::new(x0, y0),
+            max: Point::new(x1, y1),
+        }
+    }
+
+    /// The draw tags of a scene, with the blend words of clips.
+    fn draws(scene: &Scene) -> Vec<(u32, Option<u32>)> {
+        let data = scene.data();
+        let mut draws = Vec::new();
+        for_each_draw(data, None, |draw| {
+            let blend = match draw.tag {
+                DRAWTAG_BEGINCLIP | DRAWTAG_ENDCLIP => {
+                    Some(read_u32(&data.drawdata_stream, draw.drawdata_offset))
+                }
+                _ => None,
+            };
+            draws.push((draw.tag, blend));
+        });
+        draws
+    }
+
+    #[test]
+    fn mask_layers() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut builder = build_scene(&mut scene, &mut rcx);
+        let brush = Brush::Solid(Color::rgb8(255, 0, 0));
+        let shape = rect(0.0, 0.0, 10.0, 10.0);
+        builder.push_mask(Mask::Luminance, shape.elements());
+        builder.fill(Fill::NonZero, &brush, None, shape.elements());
+        builder.begin_masked_content();
+        builder.fill(Fill::NonZero, &brush, None, shape.elements());
+        builder.pop_mask();
+        builder.finish().unwrap();
+
+        // The mask is drawn into a normal layer, and the content into a
+        // layer that is composited with the mask as its backdrop.
+        let normal = Some(Blend::from(Mix::Normal).pack());
+        let luminance = Some(Mask::Luminance.pack());
+        assert_eq!(
+            draws(&scene),
+            [
+                (DRAWTAG_BEGINCLIP, normal),
+                (DRAWTAG_FILLCOLOR, None),
+                (DRAWTAG_BEGINCLIP, luminance),
+                (DRAWTAG_FILLCOLOR, None),
+                (DRAWTAG_ENDCLIP, luminance),
+                (DRAWTAG_ENDCLIP, normal),
+            ]
+        );
+    }
+
+    #[test]
+    fn mask_without_content() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut builder = build_scene(&mut scene, &mut rcx);
+        let brush = Brush::Solid(Color::rgb8(255, 0, 0));
+        let shape = rect(0.0, 0.0, 10.0, 10.0);
+        builder.push_mask(Mask::Alpha, shape.elements());
+        builder.fill(Fill::NonZero, &brush, None, shape.elements());
+        builder.pop_mask();
+        builder.finish().unwrap();
+
+        // The empty content is still closed, so the mask is not composited.
+        let normal = Some(Blend::from(Mix::Normal).pack());
+        let alpha = Some(Mask::Alpha.pack());
+        assert_eq!(
+            draws(&scene),
+            [
+                (DRAWTAG_BEGINCLIP, normal),
+                (DRAWTAG_FILLCOLOR, None),
+                (DRAWTAG_BEGINCLIP, alpha),
+                (DRAWTAG_ENDCLIP, alpha),
+                (DRAWTAG_ENDCLIP, normal),
+            ]
+        );
+    }
+
+    #[test]
+    fn unmatched_masked_content() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut builder = build_scene(&mut scene, &mut rcx);
+        builder.begin_masked_content();
+        assert_eq!(builder.finish(), Err(BuildError::UnmatchedMaskContent));
+    }
+
+    #[test]
+    fn unmatched_pop_transform() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut builder = build_scene(&mut scene, &mut rcx);
+        builder.pop_transform();
+        assert_eq!(builder.finish(), Err(BuildError::UnmatchedPopTransform));
+    }
+
+    #[test]
+    fn unclosed_layers() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut builder = build_scene(&mut scene, &mut rcx);
+        let shape = rect(0.0, 0.0, 10.0, 10.0);
+        builder.push_layer(Mix::Multiply.into(), shape.elements());
+        builder.push_layer(Mix::Normal.into(), shape.elements());
+        assert_eq!(builder.finish(), Err(BuildError::UnclosedLayers(2)));
+
+        // The layers are still popped, innermost first.
+        let normal = Some(Blend::from(Mix::Normal).pack());
+        let multiply = Some(Blend::from(Mix::Multiply).pack());
+        assert_eq!(
+            draws(&scene),
+            [
+                (DRAWTAG_BEGINCLIP, multiply),
+                (DRAWTAG_BEGINCLIP, normal),
+                (DRAWTAG_ENDCLIP, normal),
+                (DRAWTAG_ENDCLIP, multiply),
+            ]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! Decoding of the scene streams on the CPU, for queries about the content.
+
+use super::{Affine, Point, SceneData};
+
+/// Path segment in the coordinate space of the rendered image.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Segment {
+    Line(Point, Point),
+    Quad(Point, Point, Point),
+    Cubic(Point, Point, Point, Point),
+}
+
+/// Draw object with its decoded path.
+pub(crate) struct Draw<'a> {
+    /// Index of the draw object in the draw tag stream.
+    pub index: usize,
+    pub tag: u32,
+    /// Byte offset of the draw object in the draw data stream.
+    pub drawdata_offset: usize,
+    /// Line width scaled by the transform of the path, or negative for fills.
+    pub linewidth: f32,
+    /// Transform of the path, which also applies to its brush.
+    #[cfg(feature = "cpu-render")]
+    pub transform: Affine,
+    pub segments: &'a [Segment],
+}
+
+/// Calls `f` with each draw object in the streams, in order.
+///
+/// Scenes and filtered layers begin with a root transform and line width,
+/// which is indicated by passing `None`. Fragments do not, and are instead
+/// decoded as if appended with the specified transform.
+pub(crate) fn for_each_draw(
+    data: &SceneData,
+    fragment_transform: Option<Affine>,
+    mut f: impl FnMut(&Draw),
+) {
+    let (mut transform, mut transform_ix, mut linewidth, mut linewidth_ix) =
+        match fragment_transform {
+            Some(transform) => (transform, 0, -1.0, 0),
+            None => (
+                data.transform_stream.first().copied().unwrap_or_default(),
+                1,
+                data.linewidth_stream.first().copied().unwrap_or(-1.0),
+                1,
+            ),
+        };
+    let mut pathseg_ix = 0;
+    let mut drawdata_offset = 0;
+    let mut draw_ix = 0;
+    let mut segments = Vec::new();
+    for &tag in &data.tag_stream {
+        if tag & 0x20 != 0 {
+            transform = transform * data.transform_stream[transform_ix];
+            transform_ix += 1;
+        }
+        if tag & 0x40 != 0 {
+            linewidth = data.linewidth_stream[linewidth_ix];
+            linewidth_ix += 1;
+        }
+        let n_points = (tag & 3) as usize;
+        if n_points != 0 {
+            let p =
+                |i: usize| read_point(&data.pathseg_stream, pathseg_ix + i).transform(&transform);
+            segments.push(match n_points {
+                1 => Segment::Line(p(0), p(1)),
+                2 => Segment::Quad(p(0), p(1), p(2)),
+                _ => Segment::Cubic(p(0), p(1), p(2), p(3)),
+            });
+            // The end of a subpath is followed by the start of the next.
+            pathseg_ix += n_points + ((tag >> 2) & 1) as usize;
+        }
+        if tag & 0x10 != 0 {
+            let tag = data.drawtag_stream[draw_ix];
+            // Like strokes on the GPU, the width is scaled by the transform
+            // of the path.
+            let linewidth = if linewidth < 0.0 {
+                linewidth
+            } else {
+                linewidth * transform.determinant().abs().sqrt()
+            };
+            f(&Draw {
+                index: draw_ix,
+                tag,
+                drawdata_offset,
+                linewidth,
+                #[cfg(feature = "cpu-render")]
+                transform,
+                segments: &segments,
+            });
+            drawdata_offset += (tag & 0x1c) as usize;
+            draw_ix += 1;
+            segments.clear();
+        }
+    }
+}
+
+fn read_point(pathsegs: &[u8], ix: usize) -> Point {
+    let bytes = &pathsegs[ix * 8..ix * 8 + 8];
+    Point::new(
+        f32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+        f32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+    )
+}
+
+pub(crate) fn read_u32(drawdata: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(drawdata[offset..offset + 4].try_into().unwrap())
+}
@@ -0,0 +1,31 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! Tags for draw objects, shared by the scene encoding and the renderer.
+//!
+//! See shader/drawtag.h in piet-gpu for the authoritative source. The low
+//! bits of each tag encode the size of its draw data.
+
+/// Padding, with no path or draw data.
+pub const DRAWTAG_NOP: u32 = 0;
+pub const DRAWTAG_FILLCOLOR: u32 = 0x44;
+pub const DRAWTAG_FILLLINGRADIENT: u32 = 0x114;
+pub const DRAWTAG_FILLRADGRADIENT: u32 = 0x2dc;
+pub const DRAWTAG_FILLIMAGE: u32 = 0x48;
+pub const DRAWTAG_FILLGLYPH: u32 = 0x4a;
+pub const DRAWTAG_FILLBLURRECT: u32 = 0x19c;
+pub const DRAWTAG_BEGINCLIP: u32 = 0x05;
+pub const DRAWTAG_ENDCLIP: u32 = 0x25;
@@ -0,0 +1,96 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+use super::{ResourcePatch, SceneData};
+use crate::brush::Color;
+
+/// Image filter applied to the content of a layer before it is composited.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Filter {
+    /// Gaussian blur with the specified standard deviation in pixels.
+    Blur(f32),
+    /// Shadow of the content, blurred and offset, drawn beneath it.
+    DropShadow {
+        dx: f32,
+        dy: f32,
+        std_dev: f32,
+        color: Color,
+    },
+    /// Row-major 4x5 matrix applied to unpremultiplied RGBA components in
+    /// the range 0 to 1. The last column is added as an offset.
+    ColorMatrix([f32; 20]),
+}
+
+impl Filter {
+    /// Returns a color matrix that scales the saturation of colors, where 0
+    /// produces grayscale and 1 is the identity.
+    pub fn saturate(s: f32) -> Self {
+        Self::ColorMatrix([
+            0.213 + 0.787 * s,
+            0.715 - 0.715 * s,
+            0.072 - 0.072 * s,
+            0.0,
+            0.0,
+            0.213 - 0.213 * s,
+            0.715 + 0.285 * s,
+            0.072 - 0.072 * s,
+            0.0,
+            0.0,
+            0.213 - 0.213 * s,
+            0.715 - 0.715 * s,
+            0.072 + 0.928 * s,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+            0.0,
+        ])
+    }
+}
+
+/// Content of a filtered layer.
+///
+/// The content is rendered to an intermediate image, filtered, and then
+/// composited into the parent scene by an image draw.
+#[derive(Clone)]
+pub struct FilterLayer {
+    pub(crate) data: SceneData,
+    pub(crate) filter: Filter,
+    pub(crate) drawdata_offset: usize,
+    // Unresolved gradient ramps in the content of layers in fragments.
+    pub(crate) patches: Vec<ResourcePatch>,
+}
+
+impl FilterLayer {
+    /// Returns the encoded content of the layer. Its transform stream starts
+    /// with the transform in effect when the layer was pushed.
+    pub fn data(&self) -> &SceneData {
+        &self.data
+    }
+
+    /// Returns the filter applied to the content.
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    /// Returns the byte offset in the parent draw data stream of the image
+    /// draw that composites the filtered content.
+    pub fn drawdata_offset(&self) -> usize {
+        self.drawdata_offset
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! Approximation of curves by line segments.
+
+use super::Point;
+
+pub(crate) fn flatten_quad(
+    p0: Point,
+    p1: Point,
+    p2: Point,
+    tolerance: f32,
+    lines: &mut Vec<[Point; 2]>,
+) {
+    let dd = length(p0.x - 2.0 * p1.x + p2.x, p0.y - 2.0 * p1.y + p2.y);
+    let n = subdivisions(dd * 0.25, tolerance);
+    let mut last = p0;
+    for i in 1..=n {
+        let t = i as f32 / n as f32;
+        let mt = 1.0 - t;
+        let p = Point::new(
+            mt * mt * p0.x + 2.0 * mt * t * p1.x + t * t * p2.x,
+            mt * mt * p0.y + 2.0 * mt * t * p1.y + t * t * p2.y,
+        );
+        lines.push([last, p]);
+        last = p;
+    }
+}
+
+pub(crate) fn flatten_cubic(
+    p0: Point,
+    p1: Point,
+    p2: Point,
+    p3: Point,
+    tolerance: f32,
+    lines: &mut Vec<[Point; 2]>,
+) {
+    let dd = length(p0.x - 2.0 * p1.x + p2.x, p0.y - 2.0 * p1.y + p2.y)
+        .max(length(p1.x - 2.0 * p2.x + p3.x, p1.y - 2.0 * p2.y + p3.y));
+    let n = subdivisions(dd * 0.75, tolerance);
+    let mut last = p0;
+    for i in 1..=n {
+        let t = i as f32 / n as f32;
+        let mt = 1.0 - t;
+        let a = mt * mt * mt;
+        let b = 3.0 * mt * mt * t;
+        let c = 3.0 * mt * t * t;
+        let d = t * t * t;
+        let p = Point::new(
+            a * p0.x + b * p1.x + c * p2.x + d * p3.x,
+            a * p0.y + b * p1.y + c * p2.y + d * p3.y,
+        );
+        lines.push([last, p]);
+        last = p;
+    }
+}
+
+// Number of line segments keeping a curve within tolerance, given a bound on
+// the error of a single segment.
+fn subdivisions(error: f32, tolerance: f32) -> usize {
+    ((error / tolerance).sqrt().ceil() as usize).clamp(1, 256)
+}
+
+pub(crate) fn length(x: f32, y: f32) -> f32 {
+    (x * x + y * y).sqrt()
+}
@@ -0,0 +1,305 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! Hit testing of encoded scenes on the CPU.
+
+use super::decode::{for_each_draw, read_u32, Segment};
+use super::drawtag::{DRAWTAG_BEGINCLIP, DRAWTAG_ENDCLIP, DRAWTAG_FILLIMAGE};
+use super::flatten::{flatten_cubic, flatten_quad, length};
+use super::{Mask, Point, Scene, SceneData};
+
+/// Maximum distance of flattened curves from the true curves, in pixels.
+const TOLERANCE: f32 = 0.1;
+
+/// Draw object found by [`Scene::hit_test`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Hit {
+    /// Index of the draw object in the draw tag stream.
+    pub draw_index: usize,
+    /// User id attached to the draw object by
+    /// [`Builder::set_id`](super::Builder::set_id).
+    pub id: Option<u64>,
+}
+
+impl Scene {
+    /// Returns the topmost draw object covering the specified point, which is
+    /// in the coordinate space of the rendered image.
+    ///
+    /// Fills follow the non-zero winding rule and strokes are hit within half
+    /// their width of the outline. Draws are only hit inside all enclosing
+    /// clips and layers; masks are hit within their bounding shape, and the
+    /// draws forming a mask are never hit. A filtered layer is hit as its
+    /// image draw, where its content is hit.
+    pub fn hit_test(&self, point: Point) -> Option<Hit> {
+        let draw_index = hit_test_data(&self.data, point)?;
+        let id = self
+            .data
+            .draw_ids
+            .binary_search_by_key(&(draw_index as u32), |(ix, _)| *ix)
+            .ok()
+            .map(|i| self.data.draw_ids[i].1);
+        Some(Hit { draw_index, id })
+    }
+}
+
+/// A layer pushed by a begin clip draw object.
+struct Layer {
+    /// Whether the point is inside this and all enclosing layers.
+    inside: bool,
+    /// The topmost hit before the layer was pushed.
+    hit_before: Option<usize>,
+}
+
+fn hit_test_data(data: &SceneData, point: Point) -> Option<usize> {
+    let mut lines = Vec::new();
+    let mut layers: Vec<Layer> = Vec::new();
+    let mut hit = None;
+    for_each_draw(data, None, |draw| {
+        let inside_layers = layers.last().is_none_or(|layer| layer.inside);
+        let mut hit_path = || {
+            lines.clear();
+            for segment in draw.segments {
+                match *segment {
+                    Segment::Line(p0, p1) => lines.push([p0, p1]),
+                    Segment::Quad(p0, p1, p2) => flatten_quad(p0, p1, p2, TOLERANCE, &mut lines),
+                    Segment::Cubic(p0, p1, p2, p3) => {
+                        flatten_cubic(p0, p1, p2, p3, TOLERANCE, &mut lines)
+                    }
+                }
+            }
+            if draw.linewidth < 0.0 {
+                winding(&lines, point) != 0
+            } else {
+                let half_width = 0.5 * draw.linewidth;
+                lines
+                    .iter()
+                    .any(|line| distance_to_line(point, line) <= half_width)
+            }
+        };
+        match draw.tag {
+            DRAWTAG_BEGINCLIP => {
+                let blend = read_u32(&data.drawdata_stream, draw.drawdata_offset);
+                // Content drawn before the masked content forms the mask and
+                // is not itself visible.
+                let is_mask = blend & 0xffff == Mask::Alpha.pack()
+                    || blend & 0xffff == Mask::Luminance.pack();
+                if is_mask {
+                    if let Some(parent) = layers.last() {
+                        hit = parent.hit_before;
+                    }
+                }
+                let inside = inside_layers && hit_path();
+                layers.push(Layer {
+                    inside,
+                    hit_before: hit,
+                });
+            }
+            DRAWTAG_ENDCLIP => {
+                layers.pop();
+            }
+            DRAWTAG_FILLIMAGE => {
+                let content_hit = data
+                    .filter_layers
+                    .iter()
+                    .find(|layer| layer.drawdata_offset == draw.drawdata_offset)
+                    .is_none_or(|layer| hit_test_data(&layer.data, point).is_some());
+                if inside_layers && content_hit && hit_path() {
+                    hit = Some(draw.index);
+                }
+            }
+            _ => {
+                if inside_layers && hit_path() {
+                    hit = Some(draw.index);
+                }
+            }
+        }
+    });
+    hit
+}
+
+fn winding(lines: &[[Point; 2]], point: Point) -> i32 {
+    let mut winding = 0;
+    for [p0, p1] in lines {
+        if (p0.y <= point.y) != (p1.y <= point.y) {
+            let t = (point.y - p0.y) / (p1.y - p0.y);
+            if p0.x + t * (p1.x - p0.x) > point.x {
+                winding += if p1.y > p0.y { 1 } else { -1 };
+            }
+        }
+    }
+    winding
+}
+
+pub(crate) fn distance_to_line(point: Point, [p0, p1]: &[Point; 2]) -> f32 {
+    let (dx, dy) = (p1.x - p0.x, p1.y - p0.y);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 {
+        (((point.x - p0.x) * dx + (point.y - p0.y) * dy) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    length(point.x - (p0.x + t * dx), point.y - (p0.y + t * dy))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::brush::{Brush, Color};
+    use crate::geometry::{Affine, Point, Rect};
+    use crate::resource::ResourceContext;
+    use crate::scene::{build_scene, Blend, Builder, Fill, Mask, Mix, Scene};
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Rect {
+        Rect {
+            min: Point::new(x0, y0),
+            max: Point::new(x1, y1),
+        }
+    }
+
+    fn fill(builder: &mut Builder, id: u64, shape: Rect) {
+        let brush = Brush::Solid(Color::rgb8(255, 0, 0));
+        builder.set_id(Some(id));
+        builder.fill(Fill::NonZero, &brush, None, shape.elements());
+    }
+
+    fn build(f: impl FnOnce(&mut Builder)) -> Scene {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut builder = build_scene(&mut scene, &mut rcx);
+        f(&mut builder);
+        builder.finish().unwrap();
+        scene
+    }
+
+    fn hit_id(scene: &Scene, x: f32, y: f32) -> Option<u64> {
+        scene.hit_test(Point::new(x, y)).and_then(|hit| hit.id)
+    }
+
+    #[test]
+    fn topmost_draw() {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut builder = build_scene(&mut scene, &mut rcx);
+        let brush = Brush::Solid(Color::rgb8(255, 0, 0));
+        let rect = |x0, y0, x1, y1| Rect {
+            min: Point::new(x0, y0),
+            max: Point::new(x1, y1),
+        };
+        builder.set_id(Some(1));
+        builder.fill(
+            Fill::NonZero,
+            &brush,
+            None,
+            rect(0.0, 0.0, 100.0, 100.0).elements(),
+        );
+        builder.set_id(Some(2));
+        builder.push_transform(Affine::translate(50.0, 0.0));
+        builder.push_layer(Blend::default(), rect(0.0, 0.0, 20.0, 20.0).elements());
+        builder.fill(
+            Fill::NonZero,
+            &brush,
+            None,
+            rect(0.0, 0.0, 40.0, 40.0).elements(),
+        );
+        builder.pop_layer();
+        builder.pop_transform();
+        builder.finish().unwrap();
+
+        let id = |x, y| scene.hit_test(Point::new(x, y)).and_then(|hit| hit.id);
+        assert_eq!(id(60.0, 10.0), Some(2));
+        // Outside the clip.
+        assert_eq!(id(60.0, 30.0), Some(1));
+        assert_eq!(id(200.0, 10.0), None);
+    }
+
+    #[test]
+    fn clipped_out() {
+        let scene = build(|builder| {
+            fill(builder, 1, rect(0.0, 0.0, 10.0, 10.0));
+            builder.push_layer(Mix::Normal.into(), rect(20.0, 0.0, 30.0, 10.0).elements());
+            // Covers the first fill, but is clipped out there.
+            fill(builder, 2, rect(0.0, 0.0, 30.0, 10.0));
+            builder.pop_layer();
+        });
+        assert_eq!(hit_id(&scene, 5.0, 5.0), Some(1));
+        assert_eq!(hit_id(&scene, 25.0, 5.0), Some(2));
+        assert_eq!(hit_id(&scene, 15.0, 5.0), None);
+        // Layers themselves are never hit.
+        let scene = build(|builder| {
+            builder.push_layer(Mix::Normal.into(), rect(0.0, 0.0, 10.0, 10.0).elements());
+            builder.pop_layer();
+        });
+        assert_eq!(scene.hit_test(Point::new(5.0, 5.0)), None);
+    }
+
+    #[test]
+    fn transformed_paths() {
+        let scene = build(|builder| {
+            builder.push_transform(Affine::translate(100.0, 0.0) * Affine::scale(4.0, 2.0));
+            fill(builder, 1, rect(0.0, 0.0, 10.0, 10.0));
+            builder.pop_transform();
+            // A quarter turn maps the rectangle to x in [-10, 0].
+            builder.push_transform(Affine::rotate(core::f32::consts::FRAC_PI_2));
+            fill(builder, 2, rect(0.0, 0.0, 10.0, 10.0));
+            builder.pop_transform();
+        });
+        assert_eq!(hit_id(&scene, 135.0, 15.0), Some(1));
+        assert_eq!(hit_id(&scene, 135.0, 25.0), None);
+        assert_eq!(hit_id(&scene, 95.0, 5.0), None);
+        assert_eq!(hit_id(&scene, -5.0, 5.0), Some(2));
+        assert_eq!(hit_id(&scene, 5.0, 5.0), None);
+    }
+
+    #[test]
+    fn nested_layers() {
+        let scene = build(|builder| {
+            builder.push_layer(Mix::Normal.into(), rect(0.0, 0.0, 20.0, 20.0).elements());
+            builder.push_layer(
+                Mix::Multiply.into(),
+                rect(10.0, 10.0, 30.0, 30.0).elements(),
+            );
+            fill(builder, 1, rect(0.0, 0.0, 30.0, 30.0));
+            builder.pop_layer();
+            // Only clipped by the outer layer.
+            fill(builder, 2, rect(0.0, 0.0, 5.0, 30.0));
+            builder.pop_layer();
+        });
+        // Inside both layers.
+        assert_eq!(hit_id(&scene, 15.0, 15.0), Some(1));
+        // Inside the inner layer only.
+        assert_eq!(hit_id(&scene, 25.0, 25.0), None);
+        // Inside the outer layer only.
+        assert_eq!(hit_id(&scene, 8.0, 8.0), None);
+        assert_eq!(hit_id(&scene, 2.0, 15.0), Some(2));
+        assert_eq!(hit_id(&scene, 2.0, 25.0), None);
+    }
+
+    #[test]
+    fn masks() {
+        let scene = build(|builder| {
+            fill(builder, 1, rect(0.0, 0.0, 40.0, 40.0));
+            builder.push_mask(Mask::Alpha, rect(0.0, 0.0, 20.0, 20.0).elements());
+            fill(builder, 2, rect(0.0, 0.0, 40.0, 40.0));
+            builder.begin_masked_content();
+            fill(builder, 3, rect(10.0, 10.0, 40.0, 40.0));
+            builder.pop_mask();
+        });
+        // The draws forming the mask are never hit, and the content is only
+        // hit within the bounding shape of the mask.
+        assert_eq!(hit_id(&scene, 5.0, 5.0), Some(1));
+        assert_eq!(hit_id(&scene, 15.0, 15.0), Some(3));
+        assert_eq!(hit_id(&scene, 30.0, 30.0), Some(1));
+    }
+}
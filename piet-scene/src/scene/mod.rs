@@ -15,11 +15,25 @@
 // Also licensed under MIT license, at your choice.
 
 mod blend;
+mod bounds;
 mod builder;
+mod decode;
+pub mod drawtag;
+mod filter;
+mod flatten;
+mod hit_test;
+#[cfg(feature = "cpu-render")]
+mod render;
+mod stroke;
 mod style;
 
-pub use blend::{Blend, Compose, Mix};
-pub use builder::{build_fragment, build_scene, Builder};
+pub use blend::{Blend, Compose, Mask, Mix};
+pub use builder::{
+    build_fragment, build_scene, extend_scene, BuildError, Builder, GLYPH_FLAG_BGR,
+    GLYPH_FLAG_GAMMA, GLYPH_FLAG_LCD,
+};
+pub use filter::{Filter, FilterLayer};
+pub use hit_test::Hit;
 pub use style::*;
 
 use super::brush::*;
@@ -29,7 +43,7 @@ use super::path::Element;
 use core::ops::Range;
 
 /// Raw data streams describing an encoded scene.
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct SceneData {
     pub transform_stream: Vec<Affine>,
     pub tag_stream: Vec<u8>,
@@ -40,6 +54,12 @@ pub struct SceneData {
     pub n_path: u32,
     pub n_pathseg: u32,
     pub n_clip: u32,
+    /// Filtered layers composited by image draws in this scene, in the order
+    /// of those draws.
+    pub filter_layers: Vec<FilterLayer>,
+    /// User ids of draw objects as pairs of draw index and id, ordered by
+    /// draw index.
+    pub draw_ids: Vec<(u32, u64)>,
 }
 
 impl SceneData {
@@ -53,6 +73,8 @@ impl SceneData {
         self.n_path = 0;
         self.n_pathseg = 0;
         self.n_clip = 0;
+        self.filter_layers.clear();
+        self.draw_ids.clear();
         if !is_fragment {
             self.transform_stream
                 .push(Affine::new(&[1.0, 0.0, 0.0, 1.0, 0.0, 0.0]));
@@ -61,6 +83,19 @@ impl SceneData {
     }
 
     fn append(&mut self, other: &SceneData) {
+        let drawdata_base = self.drawdata_stream.len();
+        let drawtag_base = self.drawtag_stream.len() as u32;
+        self.draw_ids.extend(
+            other
+                .draw_ids
+                .iter()
+                .map(|(ix, id)| (ix + drawtag_base, *id)),
+        );
+        self.filter_layers
+            .extend(other.filter_layers.iter().map(|layer| FilterLayer {
+                drawdata_offset: layer.drawdata_offset + drawdata_base,
+                ..layer.clone()
+            }));
         self.transform_stream
             .extend_from_slice(&other.transform_stream);
         self.tag_stream.extend_from_slice(&other.tag_stream);
@@ -111,7 +146,8 @@ struct FragmentResources {
     stops: Vec<Stop>,
 }
 
-enum ResourcePatch {
+#[derive(Clone)]
+pub(crate) enum ResourcePatch {
     Ramp {
         drawdata_offset: usize,
         stops: Range<usize>,
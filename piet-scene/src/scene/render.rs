@@ -0,0 +1,1111 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! Rendering of encoded scenes on the CPU.
+//!
+//! This is a reference for the GPU renderer, and stands in for it where no
+//! GPU is present. It follows the fine rasterizer (kernel4.comp) and the
+//! filter stage (filter.comp) of piet-gpu: fills have the same exact area
+//! coverage, strokes the same distance field, and colors, gradients, blends
+//! and filters the same arithmetic, including the rounding to 8 bits of the
+//! blend stack and intermediate images.
+
+use super::decode::{for_each_draw, read_u32, Segment};
+use super::drawtag::{
+    DRAWTAG_BEGINCLIP, DRAWTAG_ENDCLIP, DRAWTAG_FILLBLURRECT, DRAWTAG_FILLCOLOR, DRAWTAG_FILLGLYPH,
+    DRAWTAG_FILLIMAGE, DRAWTAG_FILLLINGRADIENT, DRAWTAG_FILLRADGRADIENT,
+};
+use super::flatten::{flatten_cubic, flatten_quad};
+use super::hit_test::distance_to_line;
+use super::{
+    Blend, Compose, Filter, Mix, Point, Scene, SceneData, GLYPH_FLAG_BGR, GLYPH_FLAG_GAMMA,
+    GLYPH_FLAG_LCD,
+};
+use crate::resource::{ResourceContext, N_SAMPLES};
+
+/// Maximum distance of flattened curves from the true curves, in pixels.
+const TOLERANCE: f32 = 0.25;
+
+/// Exponent applied to glyph coverage with [`GLYPH_FLAG_GAMMA`].
+const TEXT_GAMMA_INV: f32 = 1.0 / 1.45;
+
+/// Blur kernels are truncated at this distance in pixels.
+const MAX_BLUR_RADIUS: i32 = 96;
+
+// Mix modes of mask layers, which are only used by the renderer.
+const MIX_MASK_ALPHA: u32 = 129;
+const MIX_MASK_LUMINANCE: u32 = 130;
+
+/// Premultiplied color with components in the range 0 to 1.
+type Rgba = [f32; 4];
+
+impl Scene {
+    /// Renders the scene on the CPU.
+    ///
+    /// Returns premultiplied RGBA pixels with 8 bits per component, in rows
+    /// from the top, as read back from the image of the GPU renderer. The
+    /// result matches the GPU closely but not exactly: curves are flattened
+    /// differently, and the GPU bounds some effects by tiles rather than
+    /// pixels.
+    ///
+    /// This is only available with the `cpu-render` feature.
+    pub fn render_cpu(&self, rcx: &ResourceContext, width: usize, height: usize) -> Vec<u8> {
+        render_data(&self.data, rcx.ramp_data(), width, height)
+            .into_iter()
+            .flat_map(|rgba| rgba.map(to_unorm))
+            .collect()
+    }
+}
+
+/// Rectangle of pixels, with exclusive maximum.
+#[derive(Copy, Clone)]
+struct PixelRect {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+}
+
+impl PixelRect {
+    /// The pixels touched by the lines, inflated by `margin`.
+    fn covering(lines: &[[Point; 2]], margin: f32) -> Self {
+        let (mut x0, mut y0, mut x1, mut y1) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for point in lines.iter().flatten() {
+            x0 = x0.min(point.x);
+            y0 = y0.min(point.y);
+            x1 = x1.max(point.x);
+            y1 = y1.max(point.y);
+        }
+        if x0 > x1 {
+            return Self::empty();
+        }
+        // Negative coordinates saturate to zero.
+        Self {
+            x0: (x0 - margin).floor() as usize,
+            y0: (y0 - margin).floor() as usize,
+            x1: (x1 + margin).ceil() as usize,
+            y1: (y1 + margin).ceil() as usize,
+        }
+    }
+
+    fn empty() -> Self {
+        Self {
+            x0: 0,
+            y0: 0,
+            x1: 0,
+            y1: 0,
+        }
+    }
+
+    fn intersect(&self, other: &Self) -> Self {
+        Self {
+            x0: self.x0.max(other.x0),
+            y0: self.y0.max(other.y0),
+            x1: self.x1.min(other.x1),
+            y1: self.y1.min(other.y1),
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.x1.saturating_sub(self.x0)
+    }
+
+    fn height(&self) -> usize {
+        self.y1.saturating_sub(self.y0)
+    }
+
+    /// The coordinates of the pixels, in rows from the top.
+    fn pixels(&self) -> impl Iterator<Item = (usize, usize)> {
+        let (x0, x1) = (self.x0, self.x0 + self.width());
+        (self.y0..self.y0 + self.height()).flat_map(move |y| (x0..x1).map(move |x| (x, y)))
+    }
+}
+
+/// Coverage of the pixels of a rectangle, in rows from the top.
+struct Coverage {
+    rect: PixelRect,
+    area: Vec<f32>,
+}
+
+/// A layer pushed by a begin clip draw object.
+struct Layer {
+    /// Coverage of the shape of the layer, which bounds its content.
+    coverage: Coverage,
+    /// The pixels under the layer before it was pushed.
+    backdrop: Vec<Rgba>,
+}
+
+fn render_data(data: &SceneData, ramps: &[u32], width: usize, height: usize) -> Vec<Rgba> {
+    let image_rect = PixelRect {
+        x0: 0,
+        y0: 0,
+        x1: width,
+        y1: height,
+    };
+    let mut pixels = vec![[0.0; 4]; width * height];
+    let mut layers: Vec<Layer> = Vec::new();
+    let mut lines = Vec::new();
+    for_each_draw(data, None, |draw| {
+        let bounds = layers
+            .last()
+            .map_or(image_rect, |layer| layer.coverage.rect);
+        lines.clear();
+        flatten(draw.segments, &mut lines);
+        let word = |i: usize| read_u32(&data.drawdata_stream, draw.drawdata_offset + 4 * i);
+        let float = |i: usize| f32::from_bits(word(i));
+        match draw.tag {
+            DRAWTAG_BEGINCLIP => {
+                let rect = PixelRect::covering(&lines, 0.0).intersect(&bounds);
+                let coverage = fill_coverage(&lines, rect, 0.0);
+                // The blend stack holds 8-bit colors, as on the GPU.
+                let backdrop = rect
+                    .pixels()
+                    .map(|(x, y)| quantize(std::mem::take(&mut pixels[y * width + x])))
+                    .collect();
+                layers.push(Layer { coverage, backdrop });
+            }
+            DRAWTAG_ENDCLIP => {
+                let layer = match layers.pop() {
+                    Some(layer) => layer,
+                    None => return,
+                };
+                let blend = word(0);
+                let alpha = 1.0 - (blend >> 16) as f32 * (1.0 / 65535.0);
+                let pixel_coverage = layer.coverage.rect.pixels().zip(&layer.coverage.area);
+                for (((x, y), &area), &backdrop) in pixel_coverage.zip(&layer.backdrop) {
+                    let pixel = &mut pixels[y * width + x];
+                    // Outside its shape, the layer leaves the backdrop as it
+                    // was, which the GPU only does for whole tiles.
+                    *pixel = if area == 0.0 {
+                        backdrop
+                    } else {
+                        mix_blend_compose(backdrop, scale(*pixel, area * alpha), blend & 0xffff)
+                    };
+                }
+            }
+            DRAWTAG_FILLBLURRECT => {
+                let transform = draw.transform;
+                let corners = [
+                    Point::new(float(1), float(2)),
+                    Point::new(float(3), float(2)),
+                    Point::new(float(1), float(4)),
+                    Point::new(float(3), float(4)),
+                ]
+                .map(|corner| corner.transform(&transform));
+                // Rotation and skew are approximated by the bounding box of
+                // the transformed rectangle.
+                let min = corners
+                    .iter()
+                    .fold(Point::new(f32::MAX, f32::MAX), |min, p| {
+                        Point::new(min.x.min(p.x), min.y.min(p.y))
+                    });
+                let max = corners
+                    .iter()
+                    .fold(Point::new(f32::MIN, f32::MIN), |max, p| {
+                        Point::new(max.x.max(p.x), max.y.max(p.y))
+                    });
+                let blur_scale = transform.determinant().abs().sqrt();
+                let blur = BlurRect {
+                    min,
+                    max,
+                    radius: float(5) * blur_scale,
+                    std_dev: float(6) * blur_scale,
+                };
+                let color = unpack_color(word(0));
+                // The blur computes its own coverage within the extent of
+                // the path.
+                for (x, y) in PixelRect::covering(&lines, 0.0).intersect(&bounds).pixels() {
+                    let p = Point::new(x as f32 + 0.5, y as f32 + 0.5);
+                    let pixel = &mut pixels[y * width + x];
+                    *pixel = src_over(*pixel, scale(color, blur.coverage(p)));
+                }
+            }
+            DRAWTAG_FILLCOLOR
+            | DRAWTAG_FILLGLYPH
+            | DRAWTAG_FILLLINGRADIENT
+            | DRAWTAG_FILLRADGRADIENT
+            | DRAWTAG_FILLIMAGE => {
+                let flags = match draw.tag {
+                    DRAWTAG_FILLGLYPH if draw.linewidth < 0.0 => word(1),
+                    _ => 0,
+                };
+                let lcd = flags & GLYPH_FLAG_LCD != 0;
+                let mut coverage = if draw.linewidth >= 0.0 {
+                    let half_width = 0.5 * draw.linewidth;
+                    let rect = PixelRect::covering(&lines, half_width + 0.5).intersect(&bounds);
+                    stroke_coverage(&lines, half_width, rect)
+                } else {
+                    // Subpixels sample up to a third of a pixel to the side.
+                    let margin = if lcd { 1.0 } else { 0.0 };
+                    let rect = PixelRect::covering(&lines, margin).intersect(&bounds);
+                    fill_coverage(&lines, rect, 0.0)
+                };
+                let rect = coverage.rect;
+                // Coverage of the red and blue subpixels of LCD glyphs.
+                let mut subpixels = lcd.then(|| {
+                    // Sample each subpixel at the center of its third of the
+                    // pixel.
+                    let r_offset = if flags & GLYPH_FLAG_BGR != 0 {
+                        1.0 / 3.0
+                    } else {
+                        -1.0 / 3.0
+                    };
+                    (
+                        fill_coverage(&lines, rect, r_offset),
+                        fill_coverage(&lines, rect, -r_offset),
+                    )
+                });
+                if flags & GLYPH_FLAG_GAMMA != 0 {
+                    let gamma = |coverage: &mut Coverage| {
+                        for area in &mut coverage.area {
+                            *area = area.powf(TEXT_GAMMA_INV);
+                        }
+                    };
+                    gamma(&mut coverage);
+                    if let Some((r, b)) = &mut subpixels {
+                        gamma(r);
+                        gamma(b);
+                    }
+                }
+                let paint = match draw.tag {
+                    DRAWTAG_FILLLINGRADIENT => {
+                        let transform = draw.transform;
+                        let p0 = Point::new(float(1), float(2)).transform(&transform);
+                        let p1 = Point::new(float(3), float(4)).transform(&transform);
+                        let (dx, dy) = (p1.x - p0.x, p1.y - p0.y);
+                        let scale = 1.0 / (dx * dx + dy * dy);
+                        let line_x = dx * scale;
+                        let line_y = dy * scale;
+                        let line_c = -(p0.x * line_x + p0.y * line_y);
+                        Paint::LinGradient {
+                            ramp: ramp(ramps, word(0)),
+                            line_x,
+                            line_y,
+                            line_c,
+                        }
+                    }
+                    DRAWTAG_FILLRADGRADIENT => {
+                        let t = draw.transform;
+                        let p0 = Point::new(float(1), float(2));
+                        let p1 = Point::new(float(3), float(4));
+                        let (r0, r1) = (float(5), float(6));
+                        let inv_det = 1.0 / (t.xx * t.yy - t.yx * t.xy);
+                        let inv_mat = [
+                            inv_det * t.yy,
+                            -inv_det * t.yx,
+                            -inv_det * t.xy,
+                            inv_det * t.xx,
+                        ];
+                        let inv_tr = Point::new(
+                            inv_mat[0] * t.dx + inv_mat[2] * t.dy + p0.x,
+                            inv_mat[1] * t.dx + inv_mat[3] * t.dy + p0.y,
+                        );
+                        let center1 = Point::new(p1.x - p0.x, p1.y - p0.y);
+                        let rr = r1 / (r1 - r0);
+                        let rainv =
+                            rr / (r1 * r1 - (center1.x * center1.x + center1.y * center1.y));
+                        Paint::RadGradient {
+                            ramp: ramp(ramps, word(0)),
+                            inv_mat,
+                            inv_tr,
+                            c1: Point::new(center1.x * rainv, center1.y * rainv),
+                            ra: rr * rainv,
+                            roff: rr - 1.0,
+                        }
+                    }
+                    DRAWTAG_FILLIMAGE => {
+                        // Image draws composite filtered layers, which have
+                        // the size of the scene.
+                        let image = data
+                            .filter_layers
+                            .iter()
+                            .find(|layer| layer.drawdata_offset == draw.drawdata_offset)
+                            .map(|layer| {
+                                let content = render_data(&layer.data, ramps, width, height);
+                                apply_filter(content, &layer.filter, width, height)
+                            });
+                        match image {
+                            Some(image) => Paint::Image(image),
+                            None => Paint::Color([0.0; 4]),
+                        }
+                    }
+                    _ => Paint::Color(unpack_color(word(0))),
+                };
+                for (i, (x, y)) in rect.pixels().enumerate() {
+                    let fg = paint.color(x, y, width);
+                    let area = coverage.area[i];
+                    let pixel = &mut pixels[y * width + x];
+                    *pixel = match &subpixels {
+                        // Blend each channel with the coverage of its
+                        // subpixel.
+                        Some((r, b)) => {
+                            let cov = [r.area[i], area, b.area[i]];
+                            let mut rgba = [0.0; 4];
+                            for c in 0..3 {
+                                rgba[c] = pixel[c] * (1.0 - fg[3] * cov[c]) + fg[c] * cov[c];
+                            }
+                            rgba[3] = pixel[3] * (1.0 - fg[3] * area) + fg[3] * area;
+                            rgba
+                        }
+                        None => src_over(*pixel, scale(fg, area)),
+                    };
+                }
+            }
+            _ => {}
+        }
+    });
+    pixels
+}
+
+/// Source of the colors of a draw object, in the coordinate space of the
+/// rendered image.
+enum Paint<'a> {
+    Color(Rgba),
+    LinGradient {
+        ramp: &'a [u32],
+        line_x: f32,
+        line_y: f32,
+        line_c: f32,
+    },
+    RadGradient {
+        ramp: &'a [u32],
+        inv_mat: [f32; 4],
+        inv_tr: Point,
+        c1: Point,
+        ra: f32,
+        roff: f32,
+    },
+    Image(Vec<Rgba>),
+}
+
+impl Paint<'_> {
+    /// The color at a pixel. Like the GPU, gradients are evaluated at the
+    /// corner of the pixel.
+    fn color(&self, x: usize, y: usize, width: usize) -> Rgba {
+        let (fx, fy) = (x as f32, y as f32);
+        match self {
+            Self::Color(rgba) => *rgba,
+            Self::LinGradient {
+                ramp,
+                line_x,
+                line_y,
+                line_c,
+            } => ramp_color(ramp, line_x * fx + line_y * fy + line_c),
+            Self::RadGradient {
+                ramp,
+                inv_mat,
+                inv_tr,
+                c1,
+                ra,
+                roff,
+            } => {
+                let px = inv_mat[0] * fx + inv_mat[2] * fy - inv_tr.x;
+                let py = inv_mat[1] * fx + inv_mat[3] * fy - inv_tr.y;
+                let ba = px * c1.x + py * c1.y;
+                let ca = ra * (px * px + py * py);
+                ramp_color(ramp, (ba * ba + ca).sqrt() - ba - roff)
+            }
+            Self::Image(image) => image[y * width + x],
+        }
+    }
+}
+
+fn flatten(segments: &[Segment], lines: &mut Vec<[Point; 2]>) {
+    for segment in segments {
+        match *segment {
+            Segment::Line(p0, p1) => lines.push([p0, p1]),
+            Segment::Quad(p0, p1, p2) => flatten_quad(p0, p1, p2, TOLERANCE, lines),
+            Segment::Cubic(p0, p1, p2, p3) => flatten_cubic(p0, p1, p2, p3, TOLERANCE, lines),
+        }
+    }
+}
+
+/// Computes the area of each pixel covered by the lines, following the
+/// non-zero winding rule.
+///
+/// The sample area of each pixel is shifted horizontally by `x_offset`.
+fn fill_coverage(lines: &[[Point; 2]], rect: PixelRect, x_offset: f32) -> Coverage {
+    let (width, height) = (rect.width(), rect.height());
+    // Each line adds the signed area it covers to its pixels, and its height
+    // to the pixels on its right through the running sum of each row. The
+    // cells past the end of the row receive the contributions of lines on
+    // the right edge.
+    let stride = width + 2;
+    let mut acc = vec![0.0f32; stride * height];
+    let origin = Point::new(rect.x0 as f32 + x_offset, rect.y0 as f32);
+    for [p0, p1] in lines {
+        let p0 = Point::new(p0.x - origin.x, p0.y - origin.y);
+        let p1 = Point::new(p1.x - origin.x, p1.y - origin.y);
+        // Parts of the line left of the rectangle cover the whole width of
+        // their rows, so they are moved to its left edge, and parts on the
+        // right cover nothing, so they are moved past its right edge.
+        let w = width as f32;
+        let mut ts = [0.0; 4];
+        let mut n = 1;
+        for edge in [0.0, w] {
+            let t = (edge - p0.x) / (p1.x - p0.x);
+            if t > 0.0 && t < 1.0 {
+                ts[n] = t;
+                n += 1;
+            }
+        }
+        ts[1..n].sort_by(|a, b| a.partial_cmp(b).unwrap());
+        ts[n] = 1.0;
+        let at = |t: f32| {
+            Point::new(
+                (p0.x + t * (p1.x - p0.x)).clamp(0.0, w),
+                p0.y + t * (p1.y - p0.y),
+            )
+        };
+        for i in 0..n {
+            accumulate_line(&mut acc, stride, height, at(ts[i]), at(ts[i + 1]));
+        }
+    }
+    let mut area = Vec::with_capacity(width * height);
+    for row in acc.chunks_exact(stride) {
+        let mut sum = 0.0;
+        for cell in &row[..width] {
+            sum += cell;
+            area.push(f32::min(sum.abs(), 1.0));
+        }
+    }
+    Coverage { rect, area }
+}
+
+// Accumulates a line within the horizontal extent of the cells into the rows
+// it crosses.
+fn accumulate_line(acc: &mut [f32], stride: usize, height: usize, p0: Point, p1: Point) {
+    if p0.y == p1.y {
+        return;
+    }
+    let (dir, p0, p1) = if p0.y < p1.y {
+        (1.0, p0, p1)
+    } else {
+        (-1.0, p1, p0)
+    };
+    let dxdy = (p1.x - p0.x) / (p1.y - p0.y);
+    let y_start = p0.y.max(0.0);
+    let y_end = p1.y.min(height as f32);
+    if y_start >= y_end {
+        return;
+    }
+    let mut x = p0.x + (y_start - p0.y) * dxdy;
+    let mut y = y_start;
+    let max_x = (stride - 2) as f32;
+    for row in y_start as usize..y_end.ceil() as usize {
+        let row_end = ((row + 1) as f32).min(y_end);
+        let x_next = x + dxdy * (row_end - y);
+        accumulate_span(
+            &mut acc[row * stride..(row + 1) * stride],
+            x.clamp(0.0, max_x),
+            x_next.clamp(0.0, max_x),
+            (row_end - y) * dir,
+        );
+        x = x_next;
+        y = row_end;
+    }
+}
+
+// Accumulates the part of a line within one row, which spans `d` of its
+// height (signed by direction) between `xa` and `xb`.
+fn accumulate_span(row: &mut [f32], xa: f32, xb: f32, d: f32) {
+    let (x0, x1) = if xa < xb { (xa, xb) } else { (xb, xa) };
+    let x0_floor = x0.floor();
+    let x0i = x0_floor as usize;
+    let x1_ceil = x1.ceil();
+    let x1i = x1_ceil as usize;
+    if x1i <= x0i + 1 {
+        let xmf = 0.5 * (x0 + x1) - x0_floor;
+        row[x0i] += d - d * xmf;
+        row[x0i + 1] += d * xmf;
+    } else {
+        let s = 1.0 / (x1 - x0);
+        let x0f = x0 - x0_floor;
+        let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+        let x1f = x1 - x1_ceil + 1.0;
+        let am = 0.5 * s * x1f * x1f;
+        row[x0i] += d * a0;
+        if x1i == x0i + 2 {
+            row[x0i + 1] += d * (1.0 - a0 - am);
+        } else {
+            let a1 = s * (1.5 - x0f);
+            row[x0i + 1] += d * (a1 - a0);
+            for cell in &mut row[x0i + 2..x1i - 1] {
+                *cell += d * s;
+            }
+            let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+            row[x1i - 1] += d * (1.0 - a2 - am);
+        }
+        row[x1i] += d * am;
+    }
+}
+
+/// Computes the coverage of a stroke from the distance of the pixel centers
+/// to the lines.
+fn stroke_coverage(lines: &[[Point; 2]], half_width: f32, rect: PixelRect) -> Coverage {
+    let width = rect.width();
+    let mut distance = vec![f32::MAX; width * rect.height()];
+    for line in lines {
+        let line_rect = PixelRect::covering(&[*line], half_width + 0.5).intersect(&rect);
+        for (x, y) in line_rect.pixels() {
+            let center = Point::new(x as f32 + 0.5, y as f32 + 0.5);
+            let d = &mut distance[(y - rect.y0) * width + x - rect.x0];
+            *d = d.min(distance_to_line(center, line));
+        }
+    }
+    let area = distance
+        .into_iter()
+        .map(|d| (half_width + 0.5 - d).clamp(0.0, 1.0))
+        .collect();
+    Coverage { rect, area }
+}
+
+/// A rounded rectangle blurred by a Gaussian, in the coordinate space of the
+/// rendered image.
+struct BlurRect {
+    min: Point,
+    max: Point,
+    radius: f32,
+    std_dev: f32,
+}
+
+impl BlurRect {
+    /// The approximation described in
+    /// <https://raphlinus.github.io/graphics/2020/04/21/blurred-rounded-rects.html>.
+    fn coverage(&self, p: Point) -> f32 {
+        let size = Point::new(self.max.x - self.min.x, self.max.y - self.min.y);
+        let center = Point::new(
+            0.5 * (self.min.x + self.max.x),
+            0.5 * (self.min.y + self.max.y),
+        );
+        let std_dev = self.std_dev.max(1e-3);
+        let s_inv = 1.0 / (std_dev * 2f32.sqrt());
+        let min_edge = size.x.min(size.y);
+        let rmax = 0.5 * min_edge;
+        let radius = self.radius.min(rmax);
+        let r0 = (radius * radius + 1.15 * 1.15 * std_dev * std_dev)
+            .sqrt()
+            .min(rmax);
+        let r1 = (radius * radius + 4.0 * std_dev * std_dev).sqrt().min(rmax);
+        let exponent = 2.0 * r1 / r0.max(1e-6);
+        let delta = 1.25
+            * std_dev
+            * ((-(0.5 * s_inv * size.x).powi(2)).exp() - (-(0.5 * s_inv * size.y).powi(2)).exp());
+        let w = size.x + delta.min(0.0);
+        let h = size.y - delta.max(0.0);
+        let scale = 0.5 * erf7(s_inv * 0.5 * (w.max(h) - 0.5 * radius));
+        let pos_x = (p.x - center.x).abs() - (0.5 * w - r0);
+        let pos_y = (p.y - center.y).abs() - (0.5 * h - r0);
+        let d_pos =
+            (pos_x.max(0.0).powf(exponent) + pos_y.max(0.0).powf(exponent)).powf(1.0 / exponent);
+        let d_neg = pos_x.max(pos_y).min(0.0);
+        let d = d_pos + d_neg - r0;
+        scale * (erf7(s_inv * (min_edge + d)) - erf7(s_inv * d))
+    }
+}
+
+/// Approximation of the error function, accurate to about 1e-3.
+fn erf7(x: f32) -> f32 {
+    let x = x * core::f32::consts::FRAC_2_SQRT_PI;
+    let xx = x * x;
+    let x = x + (0.24295 + (0.03395 + 0.0104 * xx) * xx) * (x * xx);
+    x / (1.0 + x * x).sqrt()
+}
+
+/// The row of the ramp data holding the gradient with the specified index.
+fn ramp(ramps: &[u32], index: u32) -> &[u32] {
+    let start = index as usize * N_SAMPLES;
+    ramps.get(start..start + N_SAMPLES).unwrap_or(&[])
+}
+
+fn ramp_color(ramp: &[u32], t: f32) -> Rgba {
+    let x = (t.clamp(0.0, 1.0) * (N_SAMPLES - 1) as f32).round() as usize;
+    // The ramp data is RGBA, with red in the low byte.
+    let rgba = ramp.get(x).copied().unwrap_or(0);
+    [0, 8, 16, 24].map(|shift| (rgba >> shift & 0xff) as f32 * (1.0 / 255.0))
+}
+
+/// Applies the filter of a filtered layer to its rendered content.
+fn apply_filter(content: Vec<Rgba>, filter: &Filter, width: usize, height: usize) -> Vec<Rgba> {
+    // The content, the result of the horizontal pass and the filtered image
+    // are all 8-bit images on the GPU.
+    let source: Vec<Rgba> = content.into_iter().map(quantize).collect();
+    let image: Vec<Rgba> = match *filter {
+        Filter::Blur(std_dev) => {
+            let scratch = blur(&source, width, height, std_dev, (1, 0), (0, 0));
+            blur(&scratch, width, height, std_dev, (0, 1), (0, 0))
+        }
+        Filter::DropShadow {
+            dx,
+            dy,
+            std_dev,
+            color,
+        } => {
+            let offset = (dx.round() as isize, dy.round() as isize);
+            let scratch = blur(&source, width, height, std_dev, (1, 0), offset);
+            let shadow = blur(&scratch, width, height, std_dev, (0, 1), (0, 0));
+            let color = unpack_color(color.to_premul_u32());
+            // Colors are premultiplied, so the shadow goes under the
+            // content.
+            source
+                .iter()
+                .zip(shadow)
+                .map(|(&fg, shadow)| src_over(scale(color, shadow[3]), fg))
+                .collect()
+        }
+        Filter::ColorMatrix(m) => source
+            .iter()
+            .map(|rgba| {
+                let inv_a = 1.0 / rgba[3].max(1e-6);
+                let c = [rgba[0] * inv_a, rgba[1] * inv_a, rgba[2] * inv_a, rgba[3]];
+                let row = |i: usize| {
+                    let m = &m[i * 5..i * 5 + 5];
+                    (m[0] * c[0] + m[1] * c[1] + m[2] * c[2] + m[3] * c[3] + m[4]).clamp(0.0, 1.0)
+                };
+                let a = row(3);
+                [row(0) * a, row(1) * a, row(2) * a, a]
+            })
+            .collect(),
+    };
+    image.into_iter().map(quantize).collect()
+}
+
+/// One dimension of a separable Gaussian blur, of the image moved by
+/// `offset`. Pixels outside the image are transparent.
+fn blur(
+    image: &[Rgba],
+    width: usize,
+    height: usize,
+    std_dev: f32,
+    dir: (isize, isize),
+    offset: (isize, isize),
+) -> Vec<Rgba> {
+    let load = |x: isize, y: isize| {
+        let (x, y) = (x - offset.0, y - offset.1);
+        if x < 0 || y < 0 || x >= width as isize || y >= height as isize {
+            [0.0; 4]
+        } else {
+            image[y as usize * width + x as usize]
+        }
+    };
+    let radius = ((3.0 * std_dev).ceil() as i32).min(MAX_BLUR_RADIUS) as isize;
+    let k = -0.5 / (std_dev * std_dev);
+    let mut weight_sum = 1.0;
+    let weights: Vec<f32> = (1..=radius)
+        .map(|i| {
+            let w = (k * (i * i) as f32).exp();
+            weight_sum += 2.0 * w;
+            w
+        })
+        .collect();
+    let mut result = Vec::with_capacity(width * height);
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let mut sum = load(x, y);
+            for (i, w) in (1..).zip(&weights) {
+                let a = load(x + i * dir.0, y + i * dir.1);
+                let b = load(x - i * dir.0, y - i * dir.1);
+                for c in 0..4 {
+                    sum[c] += w * (a[c] + b[c]);
+                }
+            }
+            result.push(sum.map(|c| quantize_unit(c / weight_sum)));
+        }
+    }
+    result
+}
+
+/// Unpacks a premultiplied color packed with red in the high byte.
+fn unpack_color(rgba: u32) -> Rgba {
+    [24, 16, 8, 0].map(|shift| (rgba >> shift & 0xff) as f32 * (1.0 / 255.0))
+}
+
+fn to_unorm(c: f32) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn quantize_unit(c: f32) -> f32 {
+    to_unorm(c) as f32 * (1.0 / 255.0)
+}
+
+fn quantize(rgba: Rgba) -> Rgba {
+    rgba.map(quantize_unit)
+}
+
+fn scale(rgba: Rgba, s: f32) -> Rgba {
+    rgba.map(|c| c * s)
+}
+
+fn src_over(backdrop: Rgba, src: Rgba) -> Rgba {
+    let inv_a = 1.0 - src[3];
+    [0, 1, 2, 3].map(|i| backdrop[i] * inv_a + src[i])
+}
+
+/// Composites the content of a layer onto its backdrop, both premultiplied,
+/// with a blend mode packed by [`Blend::pack`] or
+/// [`Mask::pack`](super::Mask::pack).
+fn mix_blend_compose(backdrop: Rgba, src: Rgba, mode: u32) -> Rgba {
+    let (mix_mode, compose_mode) = (mode >> 8, mode & 0xff);
+    // Both the normal blend and clips are plain source over.
+    if mode & 0x7fff == Blend::from(Mix::Normal).pack() {
+        return src_over(backdrop, src);
+    }
+    match mix_mode {
+        MIX_MASK_ALPHA => return scale(src, backdrop[3]),
+        MIX_MASK_LUMINANCE => {
+            // Luminance of the premultiplied mask, which includes its alpha.
+            let lum = 0.2125 * backdrop[0] + 0.7154 * backdrop[1] + 0.0721 * backdrop[2];
+            return scale(src, lum);
+        }
+        _ => {}
+    }
+    // Un-premultiply colors for blending.
+    const EPSILON: f32 = 1e-15;
+    let unpremul = |rgba: Rgba| {
+        let inv_a = 1.0 / (rgba[3] + EPSILON);
+        [rgba[0] * inv_a, rgba[1] * inv_a, rgba[2] * inv_a]
+    };
+    let cs = unpremul(src);
+    let cb = unpremul(backdrop);
+    let blended = mix_blend(cb, cs, mix_mode);
+    let (ab, as_) = (backdrop[3], src[3]);
+    let cs = [0, 1, 2].map(|i| cs[i] + (blended[i] - cs[i]) * ab);
+    if compose_mode == Compose::SrcOver as u32 {
+        let [r, g, b] = [0, 1, 2].map(|i| backdrop[i] + (cs[i] - backdrop[i]) * as_);
+        [r, g, b, as_ + ab * (1.0 - as_)]
+    } else {
+        mix_compose(cb, cs, ab, as_, compose_mode)
+    }
+}
+
+type Rgb = [f32; 3];
+
+fn mix_blend(cb: Rgb, cs: Rgb, mode: u32) -> Rgb {
+    let each = |f: fn(f32, f32) -> f32| [0, 1, 2].map(|i| f(cb[i], cs[i]));
+    match mode {
+        m if m == Mix::Multiply as u32 => each(|b, s| b * s),
+        m if m == Mix::Screen as u32 => each(screen),
+        m if m == Mix::Overlay as u32 => each(|b, s| hard_light(s, b)),
+        m if m == Mix::Darken as u32 => each(f32::min),
+        m if m == Mix::Lighten as u32 => each(f32::max),
+        m if m == Mix::ColorDodge as u32 => each(color_dodge),
+        m if m == Mix::ColorBurn as u32 => each(color_burn),
+        m if m == Mix::HardLight as u32 => each(hard_light),
+        m if m == Mix::SoftLight as u32 => each(soft_light),
+        m if m == Mix::Difference as u32 => each(|b, s| (b - s).abs()),
+        m if m == Mix::Exclusion as u32 => each(|b, s| b + s - 2.0 * b * s),
+        m if m == Mix::Hue as u32 => set_lum(set_sat(cs, sat(cb)), lum(cb)),
+        m if m == Mix::Saturation as u32 => set_lum(set_sat(cb, sat(cs)), lum(cb)),
+        m if m == Mix::Color as u32 => set_lum(cs, lum(cb)),
+        m if m == Mix::Luminosity as u32 => set_lum(cb, lum(cs)),
+        _ => cs,
+    }
+}
+
+fn screen(cb: f32, cs: f32) -> f32 {
+    cb + cs - cb * cs
+}
+
+fn color_dodge(cb: f32, cs: f32) -> f32 {
+    if cb == 0.0 {
+        0.0
+    } else if cs == 1.0 {
+        1.0
+    } else {
+        f32::min(1.0, cb / (1.0 - cs))
+    }
+}
+
+fn color_burn(cb: f32, cs: f32) -> f32 {
+    if cb == 1.0 {
+        1.0
+    } else if cs == 0.0 {
+        0.0
+    } else {
+        1.0 - f32::min(1.0, (1.0 - cb) / cs)
+    }
+}
+
+fn hard_light(cb: f32, cs: f32) -> f32 {
+    if cs <= 0.5 {
+        cb * 2.0 * cs
+    } else {
+        screen(cb, 2.0 * cs - 1.0)
+    }
+}
+
+fn soft_light(cb: f32, cs: f32) -> f32 {
+    let d = if cb <= 0.25 {
+        ((16.0 * cb - 12.0) * cb + 4.0) * cb
+    } else {
+        cb.sqrt()
+    };
+    if cs <= 0.5 {
+        cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+    } else {
+        cb + (2.0 * cs - 1.0) * (d - cb)
+    }
+}
+
+fn sat(c: Rgb) -> f32 {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+fn lum(c: Rgb) -> f32 {
+    0.3 * c[0] + 0.59 * c[1] + 0.11 * c[2]
+}
+
+fn clip_color(c: Rgb) -> Rgb {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+    let mut c = c;
+    if n < 0.0 {
+        c = c.map(|c| l + (c - l) * l / (l - n));
+    }
+    if x > 1.0 {
+        c = c.map(|c| l + (c - l) * (1.0 - l) / (x - l));
+    }
+    c
+}
+
+fn set_lum(c: Rgb, l: f32) -> Rgb {
+    let d = l - lum(c);
+    clip_color(c.map(|c| c + d))
+}
+
+fn set_sat(c: Rgb, s: f32) -> Rgb {
+    // Indices of the smallest, middle and largest components.
+    let (min, mid, max) = if c[0] <= c[1] {
+        if c[1] <= c[2] {
+            (0, 1, 2)
+        } else if c[0] <= c[2] {
+            (0, 2, 1)
+        } else {
+            (2, 0, 1)
+        }
+    } else if c[0] <= c[2] {
+        (1, 0, 2)
+    } else if c[1] <= c[2] {
+        (1, 2, 0)
+    } else {
+        (2, 1, 0)
+    };
+    let mut result = [0.0; 3];
+    if c[max] > c[min] {
+        result[mid] = (c[mid] - c[min]) * s / (c[max] - c[min]);
+        result[max] = s;
+    }
+    result
+}
+
+fn mix_compose(cb: Rgb, cs: Rgb, ab: f32, as_: f32, mode: u32) -> Rgba {
+    let (fa, fb) = match mode {
+        m if m == Compose::Copy as u32 => (1.0, 0.0),
+        m if m == Compose::Dest as u32 => (0.0, 1.0),
+        m if m == Compose::SrcOver as u32 => (1.0, 1.0 - as_),
+        m if m == Compose::DestOver as u32 => (1.0 - ab, 1.0),
+        m if m == Compose::SrcIn as u32 => (ab, 0.0),
+        m if m == Compose::DestIn as u32 => (0.0, as_),
+        m if m == Compose::SrcOut as u32 => (1.0 - ab, 0.0),
+        m if m == Compose::DestOut as u32 => (0.0, 1.0 - as_),
+        m if m == Compose::SrcAtop as u32 => (ab, 1.0 - as_),
+        m if m == Compose::DestAtop as u32 => (1.0 - ab, as_),
+        m if m == Compose::Xor as u32 => (1.0 - ab, 1.0 - as_),
+        m if m == Compose::Plus as u32 => (1.0, 1.0),
+        m if m == Compose::PlusLighter as u32 => {
+            let [r, g, b] = [0, 1, 2].map(|i| as_ * cs[i] + ab * cb[i]);
+            return [r, g, b, as_ + ab].map(|c| c.min(1.0));
+        }
+        _ => (0.0, 0.0),
+    };
+    let as_fa = as_ * fa;
+    let ab_fb = ab * fb;
+    let [r, g, b] = [0, 1, 2].map(|i| as_fa * cs[i] + ab_fb * cb[i]);
+    [r, g, b, as_fa + ab_fb]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::brush::{Brush, Color, Extend, LinearGradient, Stop};
+    use crate::geometry::{Point, Rect};
+    use crate::path::Element;
+    use crate::resource::ResourceContext;
+    use crate::scene::{build_scene, Blend, Builder, Cap, Fill, Filter, Join, Mask, Scene, Stroke};
+
+    const WIDTH: usize = 8;
+    const HEIGHT: usize = 4;
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Rect {
+        Rect {
+            min: Point::new(x0, y0),
+            max: Point::new(x1, y1),
+        }
+    }
+
+    fn fill(builder: &mut Builder, color: Color, rect: Rect) {
+        builder.fill(Fill::NonZero, &Brush::Solid(color), None, rect.elements());
+    }
+
+    /// Renders a scene, returning a function for its pixels.
+    fn render(build: impl FnOnce(&mut Builder)) -> impl Fn(usize, usize) -> [u8; 4] {
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut builder = build_scene(&mut scene, &mut rcx);
+        build(&mut builder);
+        builder.finish().unwrap();
+        let image = scene.render_cpu(&rcx, WIDTH, HEIGHT);
+        move |x, y| {
+            let ix = (y * WIDTH + x) * 4;
+            [image[ix], image[ix + 1], image[ix + 2], image[ix + 3]]
+        }
+    }
+
+    #[test]
+    fn fill_area() {
+        let pixel = render(|builder| {
+            fill(builder, Color::rgb8(255, 0, 0), rect(1.5, 1.0, 4.0, 3.0));
+        });
+        assert_eq!(pixel(0, 1), [0, 0, 0, 0]);
+        assert_eq!(pixel(1, 1), [128, 0, 0, 128]);
+        assert_eq!(pixel(2, 2), [255, 0, 0, 255]);
+        assert_eq!(pixel(3, 2), [255, 0, 0, 255]);
+        assert_eq!(pixel(4, 2), [0, 0, 0, 0]);
+        assert_eq!(pixel(2, 3), [0, 0, 0, 0]);
+        // Shapes outside the image are cut off.
+        let pixel = render(|builder| {
+            fill(
+                builder,
+                Color::rgb8(0, 0, 255),
+                rect(-10.0, -10.0, 0.5, 100.0),
+            );
+        });
+        assert_eq!(pixel(0, 0), [0, 0, 128, 128]);
+        assert_eq!(pixel(1, 3), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn stroke_distance() {
+        let style = Stroke {
+            width: 2.0,
+            join: Join::Round,
+            miter_limit: 4.0,
+            start_cap: Cap::Round,
+            end_cap: Cap::Round,
+            dash_pattern: [],
+            dash_offset: 0.0,
+            scale: true,
+        };
+        let pixel = render(|builder| {
+            let line = [
+                Element::MoveTo(Point::new(2.0, 2.0)),
+                Element::LineTo(Point::new(6.0, 2.0)),
+            ];
+            builder.stroke(&style, &Brush::Solid(Color::rgb8(0, 255, 0)), None, line);
+        });
+        assert_eq!(pixel(3, 1), [0, 255, 0, 255]);
+        assert_eq!(pixel(3, 2), [0, 255, 0, 255]);
+        assert_eq!(pixel(3, 0), [0, 0, 0, 0]);
+        assert_eq!(pixel(3, 3), [0, 0, 0, 0]);
+        // The round cap covers 1.5 - sqrt(0.5) of the pixel past the end.
+        assert_eq!(pixel(6, 1)[1], 202);
+        assert_eq!(pixel(7, 1), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn layers() {
+        let pixel = render(|builder| {
+            fill(builder, Color::rgb8(0, 0, 255), rect(0.0, 0.0, 8.0, 4.0));
+            builder.push_layer_with_alpha(
+                Blend::default(),
+                0.5,
+                rect(2.0, 0.0, 6.0, 4.0).elements(),
+            );
+            fill(builder, Color::rgb8(255, 0, 0), rect(0.0, 0.0, 8.0, 4.0));
+            builder.pop_layer();
+        });
+        assert_eq!(pixel(1, 0), [0, 0, 255, 255]);
+        // The opacity is packed in 16 bits, so it is slightly below 0.5.
+        assert_eq!(pixel(2, 0), [127, 0, 128, 255]);
+        assert_eq!(pixel(6, 0), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn masks() {
+        let pixel = render(|builder| {
+            builder.push_mask(Mask::Alpha, rect(0.0, 0.0, 8.0, 4.0).elements());
+            fill(builder, Color::rgba8(0, 0, 0, 64), rect(0.0, 0.0, 4.0, 4.0));
+            builder.begin_masked_content();
+            fill(
+                builder,
+                Color::rgb8(255, 255, 255),
+                rect(0.0, 0.0, 8.0, 4.0),
+            );
+            builder.pop_mask();
+        });
+        assert_eq!(pixel(0, 0), [64, 64, 64, 64]);
+        assert_eq!(pixel(4, 0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn linear_gradient() {
+        let pixel = render(|builder| {
+            let gradient = LinearGradient {
+                start: Point::new(0.0, 0.0),
+                end: Point::new(7.0, 0.0),
+                stops: [
+                    Stop {
+                        offset: 0.0,
+                        color: Color::rgb8(255, 0, 0),
+                    },
+                    Stop {
+                        offset: 1.0,
+                        color: Color::rgb8(0, 0, 255),
+                    },
+                ]
+                .into_iter()
+                .collect(),
+                extend: Extend::Pad,
+            };
+            let brush = Brush::LinearGradient(gradient);
+            let shape = rect(0.0, 0.0, 8.0, 4.0);
+            builder.fill(Fill::NonZero, &brush, None, shape.elements());
+        });
+        // Gradients are evaluated at the corners of pixels.
+        assert_eq!(pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(7, 0), [0, 0, 255, 255]);
+        let [r, _, b, _] = pixel(3, 0);
+        assert!(r > b && b > 64, "{} {}", r, b);
+    }
+
+    #[test]
+    fn filter_layers() {
+        let pixel = render(|builder| {
+            let shape = rect(0.0, 0.0, 8.0, 4.0);
+            builder.push_filter_layer(
+                Blend::default(),
+                1.0,
+                Filter::saturate(0.0),
+                shape.elements(),
+            );
+            fill(builder, Color::rgb8(255, 0, 0), rect(0.0, 0.0, 4.0, 4.0));
+            builder.pop_layer();
+        });
+        // The luminance of red in the saturation matrix.
+        assert_eq!(pixel(0, 0), [54, 54, 54, 255]);
+        assert_eq!(pixel(4, 0), [0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,458 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! Expansion of strokes into filled outlines on the CPU.
+//!
+//! The fine rasterizer only renders strokes with round joins and caps, so
+//! other styles and dashes are expanded into polygons for the segments, joins
+//! and caps. These overlap but are all wound the same way, so filling them
+//! with the non-zero winding rule covers their union.
+
+use super::flatten::{flatten_cubic, flatten_quad, length};
+use super::{Cap, Join, Point, Stroke};
+use crate::path::Element;
+use core::borrow::Borrow;
+use core::f32::consts::{FRAC_PI_2, PI};
+
+/// Returns true if a stroke style must be expanded into a fill.
+pub(crate) fn needs_expansion<D: Borrow<[f32]>>(style: &Stroke<D>) -> bool {
+    style.join != Join::Round
+        || style.start_cap != Cap::Round
+        || style.end_cap != Cap::Round
+        || dash_pattern(style.dash_pattern.borrow()).is_some()
+}
+
+/// Expands a stroke into closed polygons covering it, flattening curves to
+/// within `tolerance`.
+pub(crate) fn stroke_outline<D, E>(style: &Stroke<D>, elements: E, tolerance: f32) -> Vec<Element>
+where
+    D: Borrow<[f32]>,
+    E: Iterator,
+    E::Item: Borrow<Element>,
+{
+    let mut stroker = Stroker {
+        join: style.join,
+        miter_limit: style.miter_limit,
+        start_cap: style.start_cap,
+        end_cap: style.end_cap,
+        half_width: 0.5 * style.width.abs(),
+        tolerance,
+        outline: vec![],
+    };
+    if stroker.half_width == 0.0 {
+        return vec![];
+    }
+    let pattern = dash_pattern(style.dash_pattern.borrow());
+    let mut dashes = vec![];
+    for (points, closed) in polylines(elements, tolerance) {
+        match &pattern {
+            Some(pattern) => {
+                dashes.clear();
+                dash(&points, closed, pattern, style.dash_offset, &mut dashes);
+                for dash in &dashes {
+                    stroker.polyline(dash, false);
+                }
+            }
+            None => stroker.polyline(&points, closed),
+        }
+    }
+    stroker.outline
+}
+
+/// Returns the dash pattern with an even number of entries, or `None` if
+/// the stroke is solid.
+fn dash_pattern(pattern: &[f32]) -> Option<Vec<f32>> {
+    // Like SVG, an invalid pattern renders a solid stroke.
+    if pattern.iter().any(|len| !len.is_finite() || *len < 0.0)
+        || pattern.iter().sum::<f32>() <= 0.0
+    {
+        return None;
+    }
+    // A pattern of odd length is repeated to alternate on and off.
+    let repeat = if pattern.len() % 2 == 1 { 2 } else { 1 };
+    Some(
+        pattern
+            .iter()
+            .copied()
+            .cycle()
+            .take(pattern.len() * repeat)
+            .collect(),
+    )
+}
+
+/// Flattens a path into polylines, with a flag for closed subpaths.
+///
+/// Subpaths without segments are omitted, while those of zero length are
+/// kept as a single point.
+fn polylines<E>(elements: E, tolerance: f32) -> Vec<(Vec<Point>, bool)>
+where
+    E: Iterator,
+    E::Item: Borrow<Element>,
+{
+    let mut polylines = vec![];
+    let mut points: Vec<Point> = vec![];
+    let mut has_segments = false;
+    let mut lines = vec![];
+    let mut start = Point::default();
+    let mut last = start;
+    let mut finish = |points: &mut Vec<Point>, has_segments: &mut bool, closed: bool| {
+        if *has_segments {
+            if closed && points.len() > 1 && points.first() == points.last() {
+                points.pop();
+            }
+            let closed = closed && points.len() > 1;
+            polylines.push((core::mem::take(points), closed));
+        }
+        points.clear();
+        *has_segments = false;
+    };
+    for el in elements {
+        lines.clear();
+        match *el.borrow() {
+            Element::MoveTo(p) => {
+                finish(&mut points, &mut has_segments, false);
+                start = p;
+                last = p;
+                continue;
+            }
+            Element::LineTo(p) => lines.push([last, p]),
+            Element::QuadTo(p1, p2) => flatten_quad(last, p1, p2, tolerance, &mut lines),
+            Element::CurveTo(p1, p2, p3) => flatten_cubic(last, p1, p2, p3, tolerance, &mut lines),
+            Element::Close => lines.push([last, start]),
+        }
+        if points.is_empty() {
+            points.push(last);
+        }
+        for [_, p] in &lines {
+            if points.last() != Some(p) {
+                points.push(*p);
+            }
+        }
+        has_segments = true;
+        last = lines.last().map_or(last, |line| line[1]);
+        if let Element::Close = *el.borrow() {
+            finish(&mut points, &mut has_segments, true);
+            last = start;
+        }
+    }
+    finish(&mut points, &mut has_segments, false);
+    polylines
+}
+
+/// Splits a polyline into the dashes of a pattern, which starts over at each
+/// subpath.
+fn dash(
+    points: &[Point],
+    closed: bool,
+    pattern: &[f32],
+    offset: f32,
+    dashes: &mut Vec<Vec<Point>>,
+) {
+    let total: f32 = pattern.iter().sum();
+    let mut i = 0;
+    let mut remaining = pattern[0];
+    let mut phase = offset.rem_euclid(total);
+    while phase > 0.0 {
+        if phase >= remaining {
+            phase -= remaining;
+            i = (i + 1) % pattern.len();
+            remaining = pattern[i];
+        } else {
+            remaining -= phase;
+            phase = 0.0;
+        }
+    }
+    let mut current = vec![];
+    if i % 2 == 0 {
+        current.push(points[0]);
+    }
+    let n_segments = if closed {
+        points.len()
+    } else {
+        points.len() - 1
+    };
+    for k in 0..n_segments {
+        let (p0, p1) = (points[k], points[(k + 1) % points.len()]);
+        let segment_length = length(p1.x - p0.x, p1.y - p0.y);
+        let mut t = 0.0;
+        while segment_length - t > remaining {
+            t += remaining;
+            let s = t / segment_length;
+            let p = Point::new(p0.x + s * (p1.x - p0.x), p0.y + s * (p1.y - p0.y));
+            if i % 2 == 0 {
+                current.push(p);
+                dashes.push(core::mem::take(&mut current));
+            } else {
+                current.push(p);
+            }
+            i = (i + 1) % pattern.len();
+            remaining = pattern[i];
+        }
+        remaining -= segment_length - t;
+        if i % 2 == 0 {
+            current.push(p1);
+        }
+    }
+    if i % 2 == 0 && current.len() > 1 {
+        dashes.push(current);
+    }
+}
+
+struct Stroker {
+    join: Join,
+    miter_limit: f32,
+    start_cap: Cap,
+    end_cap: Cap,
+    half_width: f32,
+    tolerance: f32,
+    outline: Vec<Element>,
+}
+
+impl Stroker {
+    fn polyline(&mut self, points: &[Point], closed: bool) {
+        let mut points = points.to_vec();
+        points.dedup();
+        let n = points.len();
+        if n == 1 {
+            // Zero length subpaths only show their caps.
+            self.dot(points[0], self.start_cap);
+            return;
+        }
+        let n_segments = if closed { n } else { n - 1 };
+        for k in 0..n_segments {
+            let (p0, p1) = (points[k], points[(k + 1) % n]);
+            let [nx, ny] = self.normal(p0, p1);
+            self.polygon(&[
+                Point::new(p0.x + nx, p0.y + ny),
+                Point::new(p1.x + nx, p1.y + ny),
+                Point::new(p1.x - nx, p1.y - ny),
+                Point::new(p0.x - nx, p0.y - ny),
+            ]);
+        }
+        if closed {
+            for k in 0..n {
+                self.join(points[(k + n - 1) % n], points[k], points[(k + 1) % n]);
+            }
+        } else {
+            for k in 1..n - 1 {
+                self.join(points[k - 1], points[k], points[k + 1]);
+            }
+            self.cap(points[0], points[1], self.start_cap);
+            self.cap(points[n - 1], points[n - 2], self.end_cap);
+        }
+    }
+
+    /// Adds a join at `p` between the segments from `p0` and to `p1`.
+    fn join(&mut self, p0: Point, p: Point, p1: Point) {
+        let [nx0, ny0] = self.normal(p0, p);
+        let [nx1, ny1] = self.normal(p, p1);
+        // The join covers the outer side of the turn.
+        let cross = (p.x - p0.x) * (p1.y - p.y) - (p.y - p0.y) * (p1.x - p.x);
+        let s = if cross > 0.0 { -1.0 } else { 1.0 };
+        let a = Point::new(p.x + s * nx0, p.y + s * ny0);
+        let b = Point::new(p.x + s * nx1, p.y + s * ny1);
+        match self.join {
+            Join::Bevel => self.polygon(&[p, a, b]),
+            Join::Miter => {
+                // The ratio of the miter length to the width is the inverse
+                // of the cosine of half the angle between the normals.
+                let (sx, sy) = (nx0 + nx1, ny0 + ny1);
+                let len = length(sx, sy);
+                let ratio = 2.0 * self.half_width / len;
+                if len > 0.0 && ratio <= self.miter_limit {
+                    let d = s * ratio * self.half_width / len;
+                    let tip = Point::new(p.x + d * sx, p.y + d * sy);
+                    self.polygon(&[p, a, tip, b]);
+                } else {
+                    self.polygon(&[p, a, b]);
+                }
+            }
+            Join::Round => {
+                let (ax, ay) = (a.x - p.x, a.y - p.y);
+                let (bx, by) = (b.x - p.x, b.y - p.y);
+                let sweep = (ax * by - ay * bx).atan2(ax * bx + ay * by);
+                self.arc(p, a, sweep);
+            }
+        }
+    }
+
+    /// Adds a cap at the end `p` of a segment from `p0`.
+    fn cap(&mut self, p: Point, p0: Point, cap: Cap) {
+        let [nx, ny] = self.normal(p0, p);
+        // The direction of the segment, scaled to half the width.
+        let (dx, dy) = (ny, -nx);
+        match cap {
+            Cap::Butt => {}
+            Cap::Square => self.polygon(&[
+                Point::new(p.x + nx, p.y + ny),
+                Point::new(p.x + nx + dx, p.y + ny + dy),
+                Point::new(p.x - nx + dx, p.y - ny + dy),
+                Point::new(p.x - nx, p.y - ny),
+            ]),
+            Cap::Round => self.arc(p, Point::new(p.x + nx, p.y + ny), 2.0 * PI),
+        }
+    }
+
+    /// Adds the caps of a subpath of zero length at `p`.
+    fn dot(&mut self, p: Point, cap: Cap) {
+        let hw = self.half_width;
+        match cap {
+            Cap::Butt => {}
+            Cap::Square => self.polygon(&[
+                Point::new(p.x - hw, p.y - hw),
+                Point::new(p.x + hw, p.y - hw),
+                Point::new(p.x + hw, p.y + hw),
+                Point::new(p.x - hw, p.y + hw),
+            ]),
+            Cap::Round => self.arc(p, Point::new(p.x + hw, p.y), 2.0 * PI),
+        }
+    }
+
+    /// Adds a circular sector around `center`, from `start` by the angle
+    /// `sweep`.
+    fn arc(&mut self, center: Point, start: Point, sweep: f32) {
+        let hw = self.half_width;
+        let step = if self.tolerance < hw {
+            2.0 * (1.0 - self.tolerance / hw).acos()
+        } else {
+            FRAC_PI_2
+        };
+        let n = ((sweep.abs() / step).ceil() as usize).clamp(1, 1024);
+        let (x, y) = (start.x - center.x, start.y - center.y);
+        let mut points = Vec::with_capacity(n + 2);
+        points.push(center);
+        for i in 0..=n {
+            let (sin, cos) = (sweep * i as f32 / n as f32).sin_cos();
+            points.push(Point::new(
+                center.x + x * cos - y * sin,
+                center.y + x * sin + y * cos,
+            ));
+        }
+        self.polygon(&points);
+    }
+
+    /// The normal of the segment from `p0` to `p1`, scaled to half the width.
+    fn normal(&self, p0: Point, p1: Point) -> [f32; 2] {
+        let (dx, dy) = (p1.x - p0.x, p1.y - p0.y);
+        let scale = self.half_width / length(dx, dy);
+        [-dy * scale, dx * scale]
+    }
+
+    /// Adds a closed polygon, wound in the same direction as the others.
+    fn polygon(&mut self, points: &[Point]) {
+        let mut area = 0.0;
+        for (i, p0) in points.iter().enumerate() {
+            let p1 = points[(i + 1) % points.len()];
+            area += p0.x * p1.y - p1.x * p0.y;
+        }
+        if area == 0.0 {
+            return;
+        }
+        let mut points = points.to_vec();
+        if area < 0.0 {
+            points.reverse();
+        }
+        self.outline.push(Element::MoveTo(points[0]));
+        self.outline
+            .extend(points[1..].iter().map(|&p| Element::LineTo(p)));
+        self.outline.push(Element::Close);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::brush::{Brush, Color};
+    use crate::geometry::Point;
+    use crate::path::Element;
+    use crate::resource::ResourceContext;
+    use crate::scene::{build_scene, Cap, Join, Scene, Stroke};
+
+    use super::{dash, dash_pattern};
+
+    #[test]
+    fn expanded_styles() {
+        let corner = [
+            Element::MoveTo(Point::new(0.0, 0.0)),
+            Element::LineTo(Point::new(100.0, 0.0)),
+            Element::LineTo(Point::new(100.0, 100.0)),
+        ];
+        let stroke = |join, cap, dash_pattern: &[f32]| {
+            let mut scene = Scene::default();
+            let mut rcx = ResourceContext::new();
+            let mut builder = build_scene(&mut scene, &mut rcx);
+            let style = Stroke {
+                width: 10.0,
+                join,
+                miter_limit: 4.0,
+                start_cap: cap,
+                end_cap: cap,
+                dash_pattern,
+                dash_offset: 0.0,
+                scale: true,
+            };
+            let brush = Brush::Solid(Color::rgb8(255, 0, 0));
+            builder.stroke(&style, &brush, None, corner);
+            builder.finish().unwrap();
+            move |x, y| scene.hit_test(Point::new(x, y)).is_some()
+        };
+
+        let hit = stroke(Join::Miter, Cap::Butt, &[]);
+        assert!(hit(50.0, 4.0) && !hit(50.0, 6.0));
+        assert!(hit(104.0, -4.0));
+        assert!(!hit(-2.0, 0.0));
+
+        let hit = stroke(Join::Bevel, Cap::Square, &[]);
+        assert!(!hit(104.5, -4.5));
+        assert!(hit(-4.0, 4.0));
+
+        let hit = stroke(Join::Round, Cap::Round, &[20.0, 10.0]);
+        assert!(hit(10.0, 0.0) && !hit(25.0, 0.0) && hit(35.0, 0.0));
+        assert!(hit(-4.0, 0.0) && !hit(-4.0, 4.0));
+    }
+
+    #[test]
+    fn dash_patterns() {
+        // Odd patterns are repeated, so that their entries alternate
+        // between dashes and gaps.
+        assert_eq!(
+            dash_pattern(&[5.0, 3.0, 2.0]),
+            Some(vec![5.0, 3.0, 2.0, 5.0, 3.0, 2.0])
+        );
+        assert_eq!(dash_pattern(&[5.0, 3.0]), Some(vec![5.0, 3.0]));
+        assert_eq!(dash_pattern(&[]), None);
+        assert_eq!(dash_pattern(&[0.0, 0.0]), None);
+        assert_eq!(dash_pattern(&[-1.0, 2.0]), None);
+        assert_eq!(dash_pattern(&[f32::NAN]), None);
+    }
+
+    #[test]
+    fn odd_dashes() {
+        let line = [Point::new(0.0, 0.0), Point::new(20.0, 0.0)];
+        let pattern = dash_pattern(&[5.0, 3.0, 2.0]).unwrap();
+        let spans = |offset| {
+            let mut dashes = vec![];
+            dash(&line, false, &pattern, offset, &mut dashes);
+            dashes
+                .iter()
+                .map(|dash| (dash[0].x, dash[dash.len() - 1].x))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(spans(0.0), [(0.0, 5.0), (8.0, 10.0), (15.0, 18.0)]);
+        assert_eq!(spans(6.0), [(2.0, 4.0), (9.0, 12.0), (14.0, 19.0)]);
+        // The offset wraps around the whole pattern.
+        assert_eq!(spans(26.0), spans(6.0));
+    }
+}
@@ -109,7 +109,7 @@ impl ClearStage {
     }
 
     pub unsafe fn record(&self, pass: &mut ComputePass, code: &ClearCode, bindings: &ClearBinding) {
-        let n_workgroups = (self.n_elements + WG_SIZE - 1) / WG_SIZE;
+        let n_workgroups = self.n_elements.div_ceil(WG_SIZE);
         // An issue: for clearing large buffers (>16M), we need to check the
         // number of workgroups against the (dynamically detected) limit, and
         // potentially issue multiple dispatches.
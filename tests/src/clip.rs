@@ -127,7 +127,7 @@ impl ClipData {
                     false
                 } else {
                     z = z.wrapping_mul(742938285) % ((1 << 31) - 1);
-                    (z % 2) != 0
+                    !z.is_multiple_of(2)
                 };
                 if is_push {
                     depth += 1;
@@ -201,7 +201,7 @@ impl ClipData {
             let mut expected_path = None;
             if *path_ix >= 0x8000_0000 {
                 let parent = parent_stack.pop().unwrap();
-                expected_path = Some(self.clip_stream[parent as usize]);
+                expected_path = Some(self.clip_stream[parent]);
                 bbox_stack.pop().unwrap();
             } else {
                 parent_stack.push(i);
@@ -16,6 +16,8 @@
 
 //! Test config parameters.
 
+use std::path::PathBuf;
+
 use clap::ArgMatches;
 
 pub struct Config {
@@ -23,6 +25,14 @@ pub struct Config {
     pub size: Size,
     pub n_iter: u64,
     pub verify_all: bool,
+    /// The directory of the reference images of golden tests.
+    pub golden_dir: PathBuf,
+    /// The directory of SVG documents to render in golden tests.
+    pub svg_dir: PathBuf,
+    /// Store the rendered images as references instead of comparing them.
+    pub bless: bool,
+    /// The largest difference of a channel that passes a golden test.
+    pub tolerance: u8,
 }
 
 pub struct Groups(String);
@@ -42,11 +52,25 @@ impl Config {
             .and_then(|s| s.parse().ok())
             .unwrap_or(1000);
         let verify_all = matches.is_present("verify_all");
+        let golden_dir = PathBuf::from(matches.value_of("golden_dir").unwrap_or("golden"));
+        let svg_dir = matches
+            .value_of("svg_dir")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| golden_dir.join("svg"));
+        let bless = matches.is_present("bless");
+        let tolerance = matches
+            .value_of("tolerance")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(2);
         Config {
             groups,
             size,
             n_iter,
             verify_all,
+            golden_dir,
+            svg_dir,
+            bless,
+            tolerance,
         }
     }
 }
@@ -112,15 +112,15 @@ impl DrawTestData {
         let drawmonoid_alloc = 0;
         let anno_alloc = drawmonoid_alloc + 16 * n_tags;
         let clip_alloc = anno_alloc + ANNOTATED_SIZE * n_tags;
-        let stage_config = stages::Config {
+
+        stages::Config {
             n_elements: n_tags as u32,
             anno_alloc: anno_alloc as u32,
             drawmonoid_alloc: drawmonoid_alloc as u32,
             clip_alloc: clip_alloc as u32,
             drawtag_offset: 0,
             ..Default::default()
-        };
-        stage_config
+        }
     }
 
     fn memory_size(&self) -> u64 {
@@ -0,0 +1,289 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! Golden image tests, which render scenes and compare them with reference
+//! images.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use piet_gpu::{test_scenes, PicoSvg, PietGpuRenderContext};
+use piet_gpu_hal::Error;
+use piet_scene::resource::ResourceContext;
+use piet_scene::scene::Scene;
+
+use crate::render::{Image, SceneRenderer};
+use crate::{Config, Runner, TestResult};
+
+// Multiples of the tile size, so the renderer's image needs no cropping.
+const WIDTH: usize = 1024;
+const HEIGHT: usize = 1024;
+
+/// The subdirectory of the golden directory for the references of the CPU
+/// renderer, which differ slightly from those of GPUs.
+const CPU_DIR: &str = "cpu";
+
+/// The subdirectory of the reference directory for the images of failed
+/// tests.
+const FAILURES_DIR: &str = "failures";
+
+/// What to render in a test.
+enum Case {
+    Scene(fn(&mut PietGpuRenderContext)),
+    Svg(PathBuf),
+}
+
+/// The differences between an image and its reference.
+struct Comparison {
+    /// The number of pixels with a channel differing by more than the
+    /// tolerance.
+    n_bad: usize,
+    /// The largest difference of any channel.
+    max_error: u8,
+    /// The peak signal-to-noise ratio in dB, infinite for equal images.
+    psnr: f64,
+    /// An image highlighting the differing pixels.
+    diff: Image,
+}
+
+/// Run a test for each test scene and each SVG document of the SVG directory.
+///
+/// Without a runner, the scenes are rendered on the CPU and compared with the
+/// references of the CPU renderer. A test without a reference image fails.
+/// With `bless`, the rendered images are stored as the new references
+/// instead.
+pub unsafe fn golden_tests(runner: Option<&Runner>, config: &Config) -> Vec<TestResult> {
+    let mut results = Vec::new();
+    let mut cases = test_scenes::SCENES
+        .iter()
+        .map(|(name, render)| (format!("scene-{}", name), Case::Scene(*render)))
+        .collect::<Vec<_>>();
+    match svg_files(&config.svg_dir) {
+        Ok(files) => cases.extend(files.into_iter().map(|file| {
+            let stem = file.file_stem().unwrap_or_default();
+            let name = format!("svg-{}", stem.to_string_lossy());
+            (name, Case::Svg(file))
+        })),
+        Err(e) => {
+            let mut result = TestResult::new("golden svg");
+            result.fail(format!(
+                "no SVG documents in {}: {}",
+                config.svg_dir.display(),
+                e
+            ));
+            results.push(result);
+        }
+    }
+
+    let (mut renderer, reference_dir) = match runner {
+        Some(runner) => match SceneRenderer::new(runner, WIDTH, HEIGHT) {
+            Ok(renderer) => (Some((runner, renderer)), config.golden_dir.clone()),
+            Err(e) => {
+                let mut result = TestResult::new("golden");
+                result.fail(format!("can't create renderer: {}", e));
+                results.push(result);
+                return results;
+            }
+        },
+        None => (None, config.golden_dir.join(CPU_DIR)),
+    };
+    let failures_dir = reference_dir.join(FAILURES_DIR);
+    for (name, case) in &cases {
+        let mut result = TestResult::new(format!("golden {}", name));
+        let reference_path = reference_dir.join(format!("{}.png", name));
+        let image = match render(renderer.as_mut(), case) {
+            Ok(image) => image,
+            Err(e) => {
+                result.fail(format!("can't render: {}", e));
+                results.push(result);
+                continue;
+            }
+        };
+        if config.bless {
+            let written = std::fs::create_dir_all(&reference_dir)
+                .map_err(Error::from)
+                .and_then(|_| write_png(&reference_path, &image));
+            if let Err(e) = written {
+                result.fail(format!("can't write reference: {}", e));
+            }
+            results.push(result);
+            continue;
+        }
+        if !reference_path.exists() {
+            result.fail("no reference image, run with --bless to create it");
+            results.push(result);
+            continue;
+        }
+        let reference = match read_png(&reference_path) {
+            Ok(reference) => reference,
+            Err(e) => {
+                result.fail(format!("can't read reference: {}", e));
+                results.push(result);
+                continue;
+            }
+        };
+        match compare(&image, &reference, config.tolerance) {
+            Err(failure) => result.fail(failure),
+            Ok(comparison) if comparison.n_bad > 0 => {
+                let mut failure = format!(
+                    "{} pixels differ, max error {}, PSNR {:.1} dB",
+                    comparison.n_bad, comparison.max_error, comparison.psnr
+                );
+                let actual_path = failures_dir.join(format!("{}.png", name));
+                let diff_path = failures_dir.join(format!("{}.diff.png", name));
+                let written = std::fs::create_dir_all(&failures_dir)
+                    .map_err(Error::from)
+                    .and_then(|_| write_png(&actual_path, &image))
+                    .and_then(|_| write_png(&diff_path, &comparison.diff));
+                if let Err(e) = written {
+                    failure += &format!(", can't write diff: {}", e);
+                }
+                result.fail(failure);
+            }
+            Ok(_) => (),
+        }
+        results.push(result);
+    }
+    results
+}
+
+/// Render a case with the GPU renderer, or on the CPU without one.
+unsafe fn render(
+    renderer: Option<&mut (&Runner, SceneRenderer)>,
+    case: &Case,
+) -> Result<Image, Error> {
+    let mut scene = Scene::default();
+    let mut rcx = ResourceContext::new();
+    {
+        let mut ctx = PietGpuRenderContext::new(&mut scene, &mut rcx);
+        match case {
+            Case::Scene(render) => render(&mut ctx),
+            Case::Svg(path) => {
+                let xml_str = std::fs::read_to_string(path)?;
+                let svg = PicoSvg::load(&xml_str, fit_scale(&xml_str)?)?;
+                svg.render_layers(&mut ctx);
+            }
+        }
+        ctx.validate()?;
+    }
+    match renderer {
+        Some((runner, renderer)) => renderer.render(runner, &scene, &rcx),
+        None => Ok(Image {
+            width: WIDTH,
+            height: HEIGHT,
+            data: scene.render_cpu(&rcx, WIDTH, HEIGHT),
+        }),
+    }
+}
+
+/// The scale that fits an SVG document to the image.
+fn fit_scale(xml_str: &str) -> Result<f64, Error> {
+    let size = PicoSvg::load(xml_str, 1.0)?.size();
+    let scale = (WIDTH as f64 / size.width).min(HEIGHT as f64 / size.height);
+    Ok(if scale.is_finite() && scale > 0.0 {
+        scale
+    } else {
+        1.0
+    })
+}
+
+fn compare(image: &Image, reference: &Image, tolerance: u8) -> Result<Comparison, String> {
+    if (image.width, image.height) != (reference.width, reference.height) {
+        return Err(format!(
+            "size {}x{} differs from reference {}x{}",
+            image.width, image.height, reference.width, reference.height
+        ));
+    }
+    let mut n_bad = 0;
+    let mut max_error = 0;
+    let mut sum_squares = 0.0;
+    let mut diff = Vec::with_capacity(image.data.len());
+    for (pixel, ref_pixel) in image
+        .data
+        .chunks_exact(4)
+        .zip(reference.data.chunks_exact(4))
+    {
+        let mut error = 0;
+        for (a, b) in pixel.iter().zip(ref_pixel) {
+            let d = a.max(b) - a.min(b);
+            error = error.max(d);
+            sum_squares += (d as f64).powi(2);
+        }
+        max_error = max_error.max(error);
+        if error > tolerance {
+            n_bad += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            // A dimmed copy of the reference, for context.
+            let gray = (ref_pixel[..3].iter().map(|c| *c as u32).sum::<u32>() / 12) as u8;
+            diff.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+    let mse = sum_squares / image.data.len() as f64;
+    let psnr = 10.0 * (255.0 * 255.0 / mse).log10();
+    Ok(Comparison {
+        n_bad,
+        max_error,
+        psnr,
+        diff: Image {
+            width: image.width,
+            height: image.height,
+            data: diff,
+        },
+    })
+}
+
+/// The SVG files of a directory, in order.
+fn svg_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_svg = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+        if is_svg && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn read_png(path: &Path) -> Result<Image, Error> {
+    let decoder = png::Decoder::new(File::open(path)?);
+    let (info, mut reader) = decoder.read_info()?;
+    if info.color_type != png::ColorType::RGBA || info.bit_depth != png::BitDepth::Eight {
+        return Err("reference is not 8-bit RGBA".into());
+    }
+    let mut data = vec![0; info.buffer_size()];
+    reader.next_frame(&mut data)?;
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        data,
+    })
+}
+
+fn write_png(path: &Path, image: &Image) -> Result<(), Error> {
+    let w = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(w, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.data)?;
+    Ok(())
+}
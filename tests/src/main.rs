@@ -16,6 +16,8 @@
 
 //! Tests for piet-gpu shaders and GPU capabilities.
 
+#![allow(clippy::missing_safety_doc)]
+
 mod clear;
 mod clip;
 mod config;
@@ -27,9 +29,13 @@ mod prefix_tree;
 mod runner;
 mod test_result;
 
+#[cfg(feature = "piet-gpu")]
+mod golden;
 #[cfg(feature = "piet-gpu")]
 mod path;
 #[cfg(feature = "piet-gpu")]
+mod render;
+#[cfg(feature = "piet-gpu")]
 mod transform;
 
 use clap::{App, Arg};
@@ -79,6 +85,29 @@ fn main() {
                 .long("dx12")
                 .help("Prefer DX12 backend"),
         )
+        .arg(
+            Arg::with_name("golden_dir")
+                .long("golden_dir")
+                .help("Directory of golden reference images")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("svg_dir")
+                .long("svg_dir")
+                .help("Directory of SVG documents for golden tests")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bless")
+                .long("bless")
+                .help("Store rendered images as golden references"),
+        )
+        .arg(
+            Arg::with_name("tolerance")
+                .long("tolerance")
+                .help("Largest channel difference passing golden tests")
+                .takes_value(true),
+        )
         .get_matches();
     let style = if matches.is_present("verbose") {
         ReportStyle::Verbose
@@ -94,7 +123,20 @@ fn main() {
         if matches.is_present("dx12") {
             flags |= InstanceFlags::DX12;
         }
-        let mut runner = Runner::new(flags);
+        let mut runner = match Runner::new(flags) {
+            Ok(runner) => runner,
+            Err(e) => {
+                // Only the golden tests can run without a GPU.
+                println!("No GPU ({}), rendering golden tests on the CPU", e);
+                #[cfg(feature = "piet-gpu")]
+                if config.groups.matches("golden") {
+                    for result in golden::golden_tests(None, &config) {
+                        report(&result);
+                    }
+                }
+                return;
+            }
+        };
         if style == ReportStyle::Verbose {
             // TODO: get adapter name in here too
             println!("Backend: {:?}", runner.backend_type());
@@ -142,5 +184,17 @@ fn main() {
             report(&draw::draw_test(&mut runner, &config));
             report(&clip::clip_test(&mut runner, &config));
         }
+        #[cfg(feature = "piet-gpu")]
+        if config.groups.matches("render") {
+            for result in render::render_tests(&mut runner, &config) {
+                report(&result);
+            }
+        }
+        #[cfg(feature = "piet-gpu")]
+        if config.groups.matches("golden") {
+            for result in golden::golden_tests(Some(&runner), &config) {
+                report(&result);
+            }
+        }
     }
 }
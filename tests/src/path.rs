@@ -208,7 +208,8 @@ impl PathData {
         let trans_alloc = 0;
         let pathseg_alloc = trans_alloc + n_trans * 24;
         let path_bbox_alloc = pathseg_alloc + self.n_pathseg * PATHSEG_SIZE;
-        let stage_config = stages::Config {
+
+        stages::Config {
             pathseg_alloc,
             trans_alloc,
             path_bbox_alloc,
@@ -218,8 +219,7 @@ impl PathData {
             linewidth_offset,
             pathseg_offset,
             ..Default::default()
-        };
-        stage_config
+        }
     }
 
     fn fill_scene(&self, buf: &mut BufWrite) {
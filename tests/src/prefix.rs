@@ -124,7 +124,7 @@ impl PrefixCode {
 
 impl PrefixStage {
     unsafe fn new(runner: &mut Runner, n_elements: u64) -> PrefixStage {
-        let n_workgroups = (n_elements + ELEMENTS_PER_WG - 1) / ELEMENTS_PER_WG;
+        let n_workgroups = n_elements.div_ceil(ELEMENTS_PER_WG);
         let state_buf_size = 4 + 12 * n_workgroups;
         let state_buf = runner
             .session
@@ -154,7 +154,7 @@ impl PrefixStage {
     }
 
     unsafe fn record(&self, commands: &mut Commands, code: &PrefixCode, bindings: &PrefixBinding) {
-        let n_workgroups = (self.n_elements + ELEMENTS_PER_WG - 1) / ELEMENTS_PER_WG;
+        let n_workgroups = self.n_elements.div_ceil(ELEMENTS_PER_WG);
         commands.cmd_buf.clear_buffer(&self.state_buf, None);
         commands.cmd_buf.memory_barrier();
         let mut pass = commands.compute_pass(0, 1);
@@ -114,7 +114,7 @@ impl PrefixTreeStage {
         let mut sizes = vec![size];
         let mut tmp_bufs = Vec::new();
         while size > ELEMENTS_PER_WG {
-            size = (size + ELEMENTS_PER_WG - 1) / ELEMENTS_PER_WG;
+            size = size.div_ceil(ELEMENTS_PER_WG);
             sizes.push(size);
             let buf = runner
                 .session
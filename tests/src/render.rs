@@ -0,0 +1,393 @@
+// Copyright 2022 The piet-gpu authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// Also licensed under MIT license, at your choice.
+
+//! Tests of the full renderer, comparing small scenes with images computed on
+//! the CPU.
+
+use piet_gpu::{EncodedSceneRef, Renderer};
+use piet_gpu_hal::{Buffer, BufferUsage, Error, QueryPool};
+use piet_scene::brush::{Brush, Color};
+use piet_scene::geometry::{Point, Rect};
+use piet_scene::path::Element;
+use piet_scene::resource::ResourceContext;
+use piet_scene::scene::{build_scene, Blend, Builder, Fill, Mask, Scene, GLYPH_FLAG_LCD};
+
+use crate::{Config, Runner, TestResult};
+
+// Four tiles on each side.
+const WIDTH: usize = 64;
+const HEIGHT: usize = 64;
+
+/// An RGBA image.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+/// A renderer whose image is read back after each frame.
+pub struct SceneRenderer {
+    renderer: Renderer,
+    query_pool: QueryPool,
+    image_buf: Buffer,
+    width: usize,
+    height: usize,
+}
+
+/// A scene and the image it should render to.
+struct Case {
+    name: &'static str,
+    draw: fn(&mut Builder),
+    expected: fn(usize, usize) -> Expected,
+}
+
+/// The expected color of a pixel.
+struct Expected {
+    /// The premultiplied RGBA color. The fine rasterizer blends the sRGB
+    /// encoded values directly, so this is also what it stores.
+    rgba: [f32; 4],
+    /// Tolerance added to the configured one, for pixels where the renderer
+    /// approximates.
+    slack: u8,
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "lcd tile edges",
+        draw: draw_lcd_rect,
+        expected: lcd_rect,
+    },
+    Case {
+        name: "blurred rect",
+        draw: draw_blurred_rect,
+        expected: blurred_rect,
+    },
+    Case {
+        name: "layer alpha",
+        draw: draw_layer_alpha,
+        expected: layer_alpha,
+    },
+    Case {
+        name: "alpha mask",
+        draw: draw_alpha_mask,
+        expected: alpha_mask,
+    },
+    Case {
+        name: "luminance mask",
+        draw: draw_luminance_mask,
+        expected: luminance_mask,
+    },
+];
+
+pub unsafe fn render_tests(runner: &mut Runner, config: &Config) -> Vec<TestResult> {
+    let mut renderer = match SceneRenderer::new(runner, WIDTH, HEIGHT) {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            let mut result = TestResult::new("render");
+            result.fail(format!("can't create renderer: {}", e));
+            return vec![result];
+        }
+    };
+    let mut results = Vec::new();
+    for case in CASES {
+        let mut result = TestResult::new(format!("render {}", case.name));
+        let mut scene = Scene::default();
+        let mut rcx = ResourceContext::new();
+        let mut builder = build_scene(&mut scene, &mut rcx);
+        (case.draw)(&mut builder);
+        builder.finish().expect("test scenes are balanced");
+        match renderer.render(runner, &scene, &rcx) {
+            Ok(image) => {
+                if let Err(failure) = compare(&image, case.expected, config.tolerance) {
+                    result.fail(failure);
+                }
+            }
+            Err(e) => result.fail(format!("can't render: {}", e)),
+        }
+        results.push(result);
+    }
+    results
+}
+
+impl SceneRenderer {
+    pub unsafe fn new(runner: &Runner, width: usize, height: usize) -> Result<Self, Error> {
+        let session = &runner.session;
+        let renderer = Renderer::new(session, width, height, 1)?;
+        let query_pool = session.create_query_pool(Renderer::QUERY_POOL_SIZE)?;
+        let image_usage = BufferUsage::MAP_READ | BufferUsage::COPY_DST;
+        let image_buf = session.create_buffer((width * height * 4) as u64, image_usage)?;
+        Ok(SceneRenderer {
+            renderer,
+            query_pool,
+            image_buf,
+            width,
+            height,
+        })
+    }
+
+    pub unsafe fn render(
+        &mut self,
+        runner: &Runner,
+        scene: &Scene,
+        rcx: &ResourceContext,
+    ) -> Result<Image, Error> {
+        self.renderer
+            .upload_scene(&EncodedSceneRef::from_scene(scene, rcx), 0)?;
+        let mut cmd_buf = runner.session.cmd_buf()?;
+        cmd_buf.begin();
+        self.renderer.record(&mut cmd_buf, &self.query_pool, 0);
+        cmd_buf.copy_image_to_buffer(&self.renderer.image_dev, &self.image_buf);
+        cmd_buf.finish_timestamps(&self.query_pool);
+        cmd_buf.host_barrier();
+        cmd_buf.finish();
+        let submitted = runner.session.run_cmd_buf(cmd_buf, &[], &[])?;
+        submitted.wait()?;
+        let mut data = Vec::new();
+        self.image_buf.read(&mut data)?;
+        Ok(Image {
+            width: self.width,
+            height: self.height,
+            data,
+        })
+    }
+}
+
+/// Compare an image with the expected colors.
+fn compare(
+    image: &Image,
+    expected: fn(usize, usize) -> Expected,
+    tolerance: u8,
+) -> Result<(), String> {
+    let mut n_bad = 0;
+    let mut first_bad = None;
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let Expected { rgba, slack } = expected(x, y);
+            let expected = rgba.map(to_unorm);
+            let tolerance = tolerance.saturating_add(slack);
+            let ix = (y * image.width + x) * 4;
+            let actual = &image.data[ix..ix + 4];
+            let bad = actual
+                .iter()
+                .zip(&expected)
+                .any(|(a, b)| a.max(b) - a.min(b) > tolerance);
+            if bad {
+                n_bad += 1;
+                first_bad.get_or_insert((
+                    x,
+                    y,
+                    expected,
+                    [actual[0], actual[1], actual[2], actual[3]],
+                ));
+            }
+        }
+    }
+    match first_bad {
+        Some((x, y, expected, actual)) => Err(format!(
+            "{} pixels differ, first at ({}, {}): expected {:?}, got {:?}",
+            n_bad, x, y, expected, actual
+        )),
+        None => Ok(()),
+    }
+}
+
+impl From<[f32; 4]> for Expected {
+    fn from(rgba: [f32; 4]) -> Self {
+        Expected { rgba, slack: 0 }
+    }
+}
+
+fn to_unorm(c: f32) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn rect_elements(x0: f32, y0: f32, x1: f32, y1: f32) -> [Element; 5] {
+    [
+        Element::MoveTo(Point::new(x0, y0)),
+        Element::LineTo(Point::new(x1, y0)),
+        Element::LineTo(Point::new(x1, y1)),
+        Element::LineTo(Point::new(x0, y1)),
+        Element::Close,
+    ]
+}
+
+/// The coverage of the pixel column at `x`, with its sample area shifted by
+/// `offset`, by the span from `x0` to `x1`.
+fn span_coverage(x: usize, offset: f32, x0: f32, x1: f32) -> f32 {
+    let left = x as f32 + offset;
+    (x1.min(left + 1.0) - x0.max(left)).max(0.0)
+}
+
+// A rectangle whose vertical edges lie next to tile edges, so the shifted
+// subpixel samples would reach into the neighboring tiles.
+const LCD_RECT: [f32; 4] = [15.9, 4.0, 48.1, 12.0];
+
+fn draw_lcd_rect(builder: &mut Builder) {
+    let [x0, y0, x1, y1] = LCD_RECT;
+    let white = Color::rgb8(255, 255, 255);
+    builder.fill_glyph(white, GLYPH_FLAG_LCD, rect_elements(x0, y0, x1, y1));
+}
+
+fn lcd_rect(x: usize, y: usize) -> Expected {
+    let [x0, y0, x1, y1] = LCD_RECT;
+    if (y as f32) < y0 || (y as f32) >= y1 {
+        return [0.0; 4].into();
+    }
+    // The red and blue subpixels are sampled a third of a pixel to the left
+    // and right, except where that would leave the tile.
+    let tile_x = (x % 16) as f32;
+    let shift = |offset: f32| offset.clamp(-tile_x, 15.0 - tile_x);
+    let r = span_coverage(x, shift(-1.0 / 3.0), x0, x1);
+    let g = span_coverage(x, 0.0, x0, x1);
+    let b = span_coverage(x, shift(1.0 / 3.0), x0, x1);
+    [r, g, b, g].into()
+}
+
+const BLUR_RECT: [f32; 4] = [8.0, 16.0, 56.0, 48.0];
+const BLUR_RADIUS: f32 = 4.0;
+const BLUR_STD_DEV: f32 = 3.0;
+
+fn draw_blurred_rect(builder: &mut Builder) {
+    let [x0, y0, x1, y1] = BLUR_RECT;
+    let rect = Rect {
+        min: Point::new(x0, y0),
+        max: Point::new(x1, y1),
+    };
+    let black = Color::rgb8(0, 0, 0);
+    builder.blurred_rect(black, rect, BLUR_RADIUS, BLUR_STD_DEV);
+}
+
+/// The blurred rectangle, integrated numerically.
+///
+/// The renderer evaluates the blur analytically, with an approximation that
+/// is exact along straight edges but within about 0.15 of the reference near
+/// the corners.
+fn blurred_rect(x: usize, y: usize) -> Expected {
+    let [x0, y0, x1, y1] = BLUR_RECT.map(f64::from);
+    let radius = BLUR_RADIUS as f64;
+    let s_inv = 1.0 / (BLUR_STD_DEV as f64 * std::f64::consts::SQRT_2);
+    // Blurs are sampled at pixel centers.
+    let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+    // Each row of the rectangle is integrated exactly along x.
+    let n_rows = 512;
+    let dy = (y1 - y0) / n_rows as f64;
+    let mut coverage = 0.0;
+    for i in 0..n_rows {
+        let row_y = y0 + (i as f64 + 0.5) * dy;
+        let corner_dy = (y0 + radius - row_y).max(row_y - (y1 - radius)).max(0.0);
+        let inset = radius - (radius * radius - corner_dy * corner_dy).max(0.0).sqrt();
+        let gx = 0.5 * (erf((x1 - inset - px) * s_inv) - erf((x0 + inset - px) * s_inv));
+        let gy = (-((row_y - py) * s_inv).powi(2)).exp() * s_inv / std::f64::consts::PI.sqrt();
+        coverage += gx * gy * dy;
+    }
+    let reach = radius + 3.0 * BLUR_STD_DEV as f64;
+    let near = |p: f64, a: f64, b: f64| (p - a).abs().min((p - b).abs()) < reach;
+    let slack = if near(px, x0, x1) && near(py, y0, y1) {
+        40
+    } else {
+        0
+    };
+    Expected {
+        rgba: [0.0, 0.0, 0.0, coverage as f32],
+        slack,
+    }
+}
+
+// Two overlapping opaque squares in a half transparent layer.
+const RED_SQUARE: [f32; 4] = [8.0, 8.0, 40.0, 40.0];
+const BLUE_SQUARE: [f32; 4] = [24.0, 24.0, 56.0, 56.0];
+
+fn draw_layer_alpha(builder: &mut Builder) {
+    let layer = rect_elements(0.0, 0.0, WIDTH as f32, HEIGHT as f32);
+    builder.push_layer_with_alpha(Blend::default(), 0.5, layer);
+    for ([x0, y0, x1, y1], color) in [
+        (RED_SQUARE, Color::rgb8(255, 0, 0)),
+        (BLUE_SQUARE, Color::rgb8(0, 0, 255)),
+    ] {
+        let brush = Brush::Solid(color);
+        builder.fill(Fill::NonZero, &brush, None, rect_elements(x0, y0, x1, y1));
+    }
+    builder.pop_layer();
+}
+
+/// The layer is composited as a whole, so the red square does not show
+/// through the blue one.
+fn layer_alpha(x: usize, y: usize) -> Expected {
+    let inside = |[x0, y0, x1, y1]: [f32; 4]| {
+        let (x, y) = (x as f32, y as f32);
+        x >= x0 && x < x1 && y >= y0 && y < y1
+    };
+    if inside(BLUE_SQUARE) {
+        [0.0, 0.0, 0.5, 0.5].into()
+    } else if inside(RED_SQUARE) {
+        [0.5, 0.0, 0.0, 0.5].into()
+    } else {
+        [0.0; 4].into()
+    }
+}
+
+/// Draw opaque red through a mask whose left and right halves are filled
+/// with the given colors.
+fn draw_masked(builder: &mut Builder, mask: Mask, left: Color, right: Color) {
+    let (w, h) = (WIDTH as f32, HEIGHT as f32);
+    builder.push_mask(mask, rect_elements(0.0, 0.0, w, h));
+    let halves = [(0.0, left), (0.5 * w, right)];
+    for (x0, color) in halves {
+        let brush = Brush::Solid(color);
+        builder.fill(
+            Fill::NonZero,
+            &brush,
+            None,
+            rect_elements(x0, 0.0, x0 + 0.5 * w, h),
+        );
+    }
+    builder.begin_masked_content();
+    let red = Brush::Solid(Color::rgb8(255, 0, 0));
+    builder.fill(Fill::NonZero, &red, None, rect_elements(0.0, 0.0, w, h));
+    builder.pop_mask();
+}
+
+fn draw_alpha_mask(builder: &mut Builder) {
+    let half_white = Color::rgba8(255, 255, 255, 128);
+    draw_masked(builder, Mask::Alpha, half_white, Color::rgba8(0, 0, 0, 0));
+}
+
+fn alpha_mask(x: usize, _y: usize) -> Expected {
+    let alpha = if x < WIDTH / 2 { 128.0 / 255.0 } else { 0.0 };
+    [alpha, 0.0, 0.0, alpha].into()
+}
+
+fn draw_luminance_mask(builder: &mut Builder) {
+    let green = Color::rgb8(0, 255, 0);
+    let blue = Color::rgb8(0, 0, 255);
+    draw_masked(builder, Mask::Luminance, green, blue);
+}
+
+/// The mask is the luminance of each half, with the weights of the fine
+/// rasterizer.
+fn luminance_mask(x: usize, _y: usize) -> Expected {
+    let luminance = if x < WIDTH / 2 { 0.7154 } else { 0.0721 };
+    [luminance, 0.0, 0.0, luminance].into()
+}
+
+/// The error function, accurate to about 1e-7 (Abramowitz and Stegun 7.1.26).
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = ((((1.061405429 * t - 1.453152027) * t + 1.421413741) * t - 0.284496736) * t
+        + 0.254829592)
+        * t;
+    (1.0 - poly * (-x * x).exp()).copysign(x)
+}
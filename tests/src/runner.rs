@@ -21,7 +21,7 @@ use std::ops::RangeBounds;
 use bytemuck::Pod;
 use piet_gpu_hal::{
     BackendType, BufReadGuard, BufWriteGuard, Buffer, BufferUsage, CmdBuf, ComputePass,
-    ComputePassDescriptor, Instance, InstanceFlags, QueryPool, Session,
+    ComputePassDescriptor, Error, Instance, InstanceFlags, QueryPool, Session,
 };
 
 pub struct Runner {
@@ -44,16 +44,17 @@ pub struct BufStage {
 }
 
 impl Runner {
-    pub unsafe fn new(flags: InstanceFlags) -> Runner {
-        let (instance, _) = Instance::new(None, flags).unwrap();
-        let device = instance.device(None).unwrap();
+    /// Create a runner, failing when no GPU is available.
+    pub unsafe fn new(flags: InstanceFlags) -> Result<Runner, Error> {
+        let (instance, _) = Instance::new(None, flags)?;
+        let device = instance.device(None)?;
         let session = Session::new(device);
         let cmd_buf_pool = Vec::new();
-        Runner {
+        Ok(Runner {
             instance,
             session,
             cmd_buf_pool,
-        }
+        })
     }
 
     pub unsafe fn commands(&mut self) -> Commands {
@@ -81,7 +82,7 @@ impl Runner {
         let submitted = self.session.run_cmd_buf(cmd_buf, &[], &[]).unwrap();
         self.cmd_buf_pool.extend(submitted.wait().unwrap());
         let timestamps = self.session.fetch_query_pool(&query_pool).unwrap();
-        timestamps.get(0).copied().unwrap_or_default()
+        timestamps.first().copied().unwrap_or_default()
     }
 
     #[allow(unused)]
@@ -119,7 +120,7 @@ impl Runner {
 
 impl Commands {
     /// Start a compute pass with timer queries.
-    pub unsafe fn compute_pass(&mut self, start_query: u32, end_query: u32) -> ComputePass {
+    pub unsafe fn compute_pass(&mut self, start_query: u32, end_query: u32) -> ComputePass<'_> {
         self.cmd_buf
             .begin_compute_pass(&ComputePassDescriptor::timer(
                 &self.query_pool,
@@ -146,7 +147,7 @@ impl BufStage {
         self.stage_buf.map_read(range).unwrap()
     }
 
-    pub unsafe fn map_write<'a>(&'a mut self, range: impl RangeBounds<usize>) -> BufWriteGuard {
+    pub unsafe fn map_write<'a>(&'a mut self, range: impl RangeBounds<usize>) -> BufWriteGuard<'a> {
         self.stage_buf.map_write(range).unwrap()
     }
 }
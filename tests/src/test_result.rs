@@ -98,7 +98,7 @@ impl TestResult {
 
 fn format_nice(x: f64, precision: usize) -> String {
     // Precision should probably scale; later
-    let (scale, suffix) = if x >= 1e12 && x < 1e15 {
+    let (scale, suffix) = if (1e12..1e15).contains(&x) {
         (1e-12, "T")
     } else if x >= 1e9 {
         (1e-9, "G")